@@ -0,0 +1,389 @@
+use crate::config::Config;
+use crate::diff::algorithm::{ diff_lines_with, split_lines, DiffAlgorithm, Edit };
+use crate::diff::{ diff_trees, ChangeStatus, DiffOptions };
+use crate::history::pretty::subject;
+use crate::object::{ decode, read_blob, read_commit, Commit, Signature };
+use crate::revision::resolve_revision;
+use crate::utility::date_util::{ format_date, DateFormat };
+
+use anyhow::{ anyhow, bail, Result };
+use std::collections::{ BinaryHeap, HashMap, HashSet };
+
+/// Output format of `blame`
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum BlameOutput {
+    #[default]
+    Default,
+    /// Commit details are shown the first time the commit appears
+    Porcelain,
+    /// Commit details are shown for every line
+    LinePorcelain,
+}
+
+/// Options of `blame`
+#[derive(Clone, Debug, Default)]
+pub struct BlameOptions {
+    /// Commit the file is taken from (HEAD by default)
+    pub revision: Option<String>,
+    pub path: String,
+    /// Lines to blame: `<start>,<end>`, `<start>,+<count>` or `<start>,` (1-based)
+    pub line_range: Option<String>,
+    pub output: BlameOutput,
+    /// Hide author and date (`-s`)
+    pub suppress_author: bool,
+    /// Show author emails instead of names (`-e`)
+    pub show_email: bool,
+    /// Lines differing only in whitespace are not changes (`-w`)
+    pub ignore_whitespace: bool,
+    /// Commits whose changes are attributed to the lines they replaced
+    pub ignore_revs: Vec<String>,
+    /// File listing commits to ignore (`blame.ignoreRevsFile` by default)
+    pub ignore_revs_file: Option<String>,
+}
+
+/// Version of the file in a commit
+#[derive(Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+struct Origin {
+    commit: String,
+    path: String,
+}
+
+/// Line of the blamed file, `line` is its index in the version of the suspect
+#[derive(Clone, Copy)]
+struct Line {
+    final_line: usize,
+    line: usize,
+}
+
+/// Commit and line of its version of the file a line was introduced in
+#[derive(Clone)]
+struct Blamed {
+    origin: Origin,
+    line: usize,
+}
+
+/// Blob of the path in the commit
+fn file_blob(origin: &Origin) -> Option<String> {
+    resolve_revision(&format!("{}:{}", origin.commit, origin.path)).ok()
+}
+
+/// Version of the file in the parent, following renames
+fn parent_origin(parent: &str, commit: &Commit, path: &str) -> Result<Option<(Origin, String)>> {
+    let origin = Origin { commit: parent.to_string(), path: path.to_string() };
+    if let Some(blob) = file_blob(&origin) {
+        return Ok(Some((origin, blob)));
+    }
+    let parent_tree = read_commit(parent)?.tree;
+    let tree_options = DiffOptions::default().tree_options(true);
+    let changes = diff_trees(Some(&parent_tree), Some(&commit.tree), &tree_options)?;
+    let renamed = changes
+        .iter()
+        .filter(|change| matches!(change.status, ChangeStatus::Renamed(_)))
+        .find(|change| change.path() == path)
+        .and_then(|change| change.old.as_ref());
+    Ok(
+        renamed.map(|old| {
+            (Origin { commit: parent.to_string(), path: old.path.clone() }, old.sha.clone())
+        })
+    )
+}
+
+/// Commits to ignore, from the options or from `blame.ignoreRevsFile`
+fn ignored_commits(options: &BlameOptions) -> Result<HashSet<String>> {
+    let mut revs = options.ignore_revs.clone();
+    let file = match &options.ignore_revs_file {
+        Some(file) => Some(file.clone()),
+        None => Config::load()?.get("blame.ignorerevsfile")?,
+    };
+    if let Some(file) = file {
+        let text = std::fs::read_to_string(&file)
+            .map_err(|_| anyhow!("could not open object name list: {file}"))?;
+        for line in text.lines() {
+            let rev = line.split('#').next().unwrap_or_default().trim();
+            if !rev.is_empty() {
+                revs.push(rev.to_string());
+            }
+        }
+    }
+    revs.iter()
+        .map(|rev| {
+            resolve_revision(&format!("{rev}^{{commit}}"))
+                .map_err(|_| anyhow!("cannot find revision {rev} to ignore"))
+        })
+        .collect()
+}
+
+/// Lines to blame (0-based, end excluded) from `-L`
+fn line_range(range: Option<&str>, path: &str, count: usize) -> Result<(usize, usize)> {
+    let Some(range) = range else {
+        return Ok((0, count));
+    };
+    let number = |text: &str| {
+        text.parse::<usize>().map_err(|_| anyhow!("invalid -L argument '{range}'"))
+    };
+    let (start, end) = range.split_once(',').unwrap_or((range, ""));
+    let start = number(start)?.max(1);
+    let end = match end {
+        "" => count,
+        end if end.starts_with('+') => start + number(&end[1..])?.max(1) - 1,
+        end => number(end)?,
+    };
+    if start > count {
+        bail!("file {path} has only {count} line{}", if count == 1 { "" } else { "s" });
+    }
+    let (start, end) = if end < start { (end.max(1), start) } else { (start, end) };
+    Ok((start - 1, end.min(count)))
+}
+
+/// Lines of the parent version equal to lines of the suspect version, lines inserted by ignored
+/// commits are matched with the lines the change deleted
+fn line_mapping(edits: &[Edit], count: usize, ignored: bool) -> Vec<Option<usize>> {
+    let mut mapping = vec![None; count];
+    let (mut deleted, mut inserted) = (Vec::new(), Vec::new());
+    for edit in edits.iter().chain([Edit::Equal(usize::MAX, usize::MAX)].iter()) {
+        match *edit {
+            Edit::Equal(old, new) => {
+                if ignored {
+                    for (&new, &old) in inserted.iter().zip(deleted.iter()) {
+                        mapping[new] = Some(old);
+                    }
+                }
+                deleted.clear();
+                inserted.clear();
+                if new < count {
+                    mapping[new] = Some(old);
+                }
+            }
+            Edit::Delete(old) => deleted.push(old),
+            Edit::Insert(new) => inserted.push(new),
+        }
+    }
+    mapping
+}
+
+/// Versions of the file still holding lines to blame, visited newest first
+#[derive(Default)]
+struct Suspects {
+    queue: BinaryHeap<(i64, Origin)>,
+    lines: HashMap<Origin, Vec<Line>>,
+    commits: HashMap<String, Commit>,
+}
+
+impl Suspects {
+    /// Pass lines to the version, merged with lines already passed to it
+    fn add(&mut self, origin: Origin, lines: Vec<Line>) -> Result<()> {
+        if lines.is_empty() {
+            return Ok(());
+        }
+        if !self.commits.contains_key(&origin.commit) {
+            self.commits.insert(origin.commit.clone(), read_commit(&origin.commit)?);
+        }
+        if let Some(waiting) = self.lines.get_mut(&origin) {
+            waiting.extend(lines);
+        } else {
+            self.queue.push((self.commits[&origin.commit].committer.time, origin.clone()));
+            self.lines.insert(origin, lines);
+        }
+        Ok(())
+    }
+
+    fn next(&mut self) -> Option<(Origin, Vec<Line>)> {
+        while let Some((_, origin)) = self.queue.pop() {
+            if let Some(lines) = self.lines.remove(&origin) {
+                return Some((origin, lines));
+            }
+        }
+        None
+    }
+}
+
+/// Walk history from the commit, attributing each line to the commit which introduced it
+fn blame_lines(
+    suspects: &mut Suspects,
+    options: &BlameOptions,
+    ignored: &HashSet<String>,
+    previous: &mut HashMap<Origin, Origin>
+) -> Result<HashMap<usize, Blamed>> {
+    let mut blamed = HashMap::new();
+    while let Some((origin, mut lines)) = suspects.next() {
+        let commit = suspects.commits[&origin.commit].clone();
+        let blob = file_blob(&origin).ok_or_else(|| anyhow!("no such path {}", origin.path))?;
+        let mut parents = Vec::new();
+        for parent in &commit.parents {
+            if let Some(found) = parent_origin(parent, &commit, &origin.path)? {
+                parents.push(found);
+            }
+        }
+        if let Some((first, _)) = parents.first() {
+            previous.insert(origin.clone(), first.clone());
+        }
+
+        // A parent with the same contents takes all the lines
+        if let Some((parent, _)) = parents.iter().find(|(_, sha)| *sha == blob) {
+            suspects.add(parent.clone(), lines)?;
+            continue;
+        }
+        let data = read_blob(&blob)?.data;
+        let own_lines = split_lines(&data);
+        let is_ignored = ignored.contains(&origin.commit);
+        for (number, (parent, sha)) in parents.iter().enumerate() {
+            let parent_data = read_blob(sha)?.data;
+            let parent_lines = split_lines(&parent_data);
+            let edits = diff_lines_with(
+                &parent_lines,
+                &own_lines,
+                DiffAlgorithm::Myers,
+                options.ignore_whitespace
+            );
+            let mapping = line_mapping(&edits, own_lines.len(), is_ignored && number == 0);
+            let (passed, kept): (Vec<Line>, Vec<Line>) = lines
+                .into_iter()
+                .partition(|line| mapping[line.line].is_some());
+            let passed = passed
+                .into_iter()
+                .filter_map(|line| {
+                    mapping[line.line].map(|old| Line { final_line: line.final_line, line: old })
+                })
+                .collect();
+            suspects.add(parent.clone(), passed)?;
+            lines = kept;
+        }
+        for line in lines {
+            blamed.insert(line.final_line, Blamed { origin: origin.clone(), line: line.line });
+        }
+    }
+    Ok(blamed)
+}
+
+/// Commit details of the porcelain formats
+fn porcelain_headers(commit: &Commit, previous: Option<&Origin>) -> String {
+    let person = |role: &str, signature: &Signature| {
+        format!(
+            "{role} {}\n{role}-mail <{}>\n{role}-time {}\n{role}-tz {}\n",
+            decode(&signature.name, commit.encoding()),
+            decode(&signature.email, commit.encoding()),
+            signature.time,
+            signature.tz
+        )
+    };
+    let mut out = person("author", &commit.author);
+    out.push_str(&person("committer", &commit.committer));
+    out.push_str(&format!("summary {}\n", subject(&commit.message_text())));
+    if commit.parents.is_empty() {
+        out.push_str("boundary\n");
+    }
+    if let Some(previous) = previous {
+        out.push_str(&format!("previous {} {}\n", previous.commit, previous.path));
+    }
+    out
+}
+
+/// Line text with a single trailing newline
+fn line_text(line: &[u8]) -> String {
+    let text = String::from_utf8_lossy(line);
+    format!("{}\n", text.strip_suffix('\n').unwrap_or(&text))
+}
+
+/// Show the commit which last changed each line of the file
+pub fn blame(options: &BlameOptions) -> Result<String> {
+    let revision = options.revision.as_deref().unwrap_or("HEAD");
+    let head = resolve_revision(&format!("{revision}^{{commit}}"))?;
+    let path = options.path.trim_start_matches("./").to_string();
+    let start = Origin { commit: head.clone(), path: path.clone() };
+    let blob = file_blob(&start).ok_or_else(|| anyhow!("no such path '{path}' in {revision}"))?;
+    let data = read_blob(&blob)?.data;
+    let final_lines = split_lines(&data);
+    let (first, last) = line_range(options.line_range.as_deref(), &path, final_lines.len())?;
+
+    let ignored = ignored_commits(options)?;
+    let mut suspects = Suspects::default();
+    suspects.add(start, (first..last).map(|line| Line { final_line: line, line }).collect())?;
+    let mut previous = HashMap::new();
+    let mut blamed: Vec<(usize, Blamed)> = blame_lines(
+        &mut suspects,
+        options,
+        &ignored,
+        &mut previous
+    )?
+        .into_iter()
+        .collect();
+    blamed.sort_by_key(|(number, _)| *number);
+    let commits = suspects.commits;
+
+    let mut out = String::new();
+    if options.output != BlameOutput::Default {
+        let mut seen = HashSet::new();
+        for (index, (number, line)) in blamed.iter().enumerate() {
+            // Group of consecutive lines coming from consecutive lines of the same version
+            let starts_group = index == 0 || {
+                let (prev_number, prev) = &blamed[index - 1];
+                prev.origin != line.origin ||
+                    prev.line + 1 != line.line ||
+                    prev_number + 1 != *number
+            };
+            let sha = &line.origin.commit;
+            out.push_str(&format!("{sha} {} {}", line.line + 1, number + 1));
+            if starts_group {
+                let size = blamed[index..]
+                    .iter()
+                    .zip(0..)
+                    .take_while(|((n, next), offset)| {
+                        next.origin == line.origin &&
+                            next.line == line.line + offset &&
+                            *n == number + offset
+                    })
+                    .count();
+                out.push_str(&format!(" {size}"));
+            }
+            out.push('\n');
+            if options.output == BlameOutput::LinePorcelain || seen.insert(sha.clone()) {
+                out.push_str(&porcelain_headers(&commits[sha], previous.get(&line.origin)));
+                out.push_str(&format!("filename {}\n", line.origin.path));
+            }
+            out.push_str(&format!("\t{}", line_text(final_lines[*number])));
+        }
+        return Ok(out);
+    }
+
+    let show_path = blamed.iter().any(|(_, line)| line.origin.path != path);
+    let person = |commit: &Commit| {
+        if options.show_email {
+            format!("<{}>", decode(&commit.author.email, commit.encoding()))
+        } else {
+            decode(&commit.author.name, commit.encoding()).into_owned()
+        }
+    };
+    let width = blamed
+        .iter()
+        .map(|(_, line)| person(&commits[&line.origin.commit]).chars().count())
+        .max()
+        .unwrap_or(0);
+    let path_width = blamed
+        .iter()
+        .map(|(_, line)| line.origin.path.chars().count())
+        .max()
+        .unwrap_or(0);
+    let number_width = last.to_string().len();
+    for (number, line) in &blamed {
+        let commit = &commits[&line.origin.commit];
+        let sha = &line.origin.commit;
+        let sha = if commit.parents.is_empty() {
+            format!("^{}", &sha[..7])
+        } else {
+            sha[..8].to_string()
+        };
+        out.push_str(&sha);
+        if show_path {
+            out.push_str(&format!(" {:<path_width$}", line.origin.path));
+        }
+        if options.suppress_author {
+            out.push_str(&format!(" {:>number_width$})", number + 1));
+        } else {
+            let date = format_date(commit.author.time, &commit.author.tz, DateFormat::Iso);
+            let name = person(commit);
+            out.push_str(&format!(" ({name:<width$} {date} {:>number_width$})", number + 1));
+        }
+        out.push_str(&format!(" {}", line_text(final_lines[*number])));
+    }
+    Ok(out)
+}
@@ -0,0 +1,349 @@
+use crate::config::{ self, Config, Scope };
+use crate::diff::abbrev;
+use crate::history::{ is_ancestor, pretty::subject, walk, LogOptions };
+use crate::object::read_commit;
+use crate::refs::{ self, current_branch, list_refs, read_ref };
+use crate::revision::{ read_head_commit, resolve_revision };
+use crate::utility::fs_utility::find_root_folder;
+use crate::utility::other_util::signature;
+
+use anyhow::{ anyhow, bail, Result };
+use std::fs;
+
+/// Operation of the `branch` command
+#[derive(Clone, Debug)]
+pub enum BranchAction {
+    /// List branches (`-v` count, `-a`, `-r`)
+    List {
+        verbose: u8,
+        all: bool,
+        remotes: bool,
+    },
+    Create {
+        name: String,
+        start: Option<String>,
+        force: bool,
+    },
+    Delete {
+        names: Vec<String>,
+        force: bool,
+        remotes: bool,
+    },
+    /// Rename the branch (current one when `old` is not given)
+    Rename {
+        old: Option<String>,
+        new: String,
+        force: bool,
+    },
+    SetUpstream {
+        upstream: String,
+        branch: Option<String>,
+    },
+    UnsetUpstream {
+        branch: Option<String>,
+    },
+}
+
+/// Line of the branch list
+struct ListedBranch {
+    current: bool,
+    name: String,
+    /// Local branch name used for upstream tracking info
+    local: Option<String>,
+    sha: String,
+    /// Target of symbolic remote ref (`origin/HEAD -> origin/master`)
+    symbolic: Option<String>,
+}
+
+/// Branch name rules on top of ref name rules
+fn check_branch_name(name: &str) -> Result<()> {
+    let valid = refs::is_valid_ref_name(&format!("refs/heads/{name}"));
+    if !valid || name.starts_with('-') || name == "HEAD" {
+        bail!("'{name}' is not a valid branch name");
+    }
+    Ok(())
+}
+
+/// Current branch, or the given one
+fn branch_or_current(branch: Option<&str>, error: &str) -> Result<String> {
+    match branch {
+        Some(branch) => Ok(branch.to_string()),
+        None => current_branch()?.ok_or_else(|| anyhow!("{error}")),
+    }
+}
+
+/// Upstream of the branch (`branch.<name>.remote` and `.merge`) as ref and short name
+fn upstream(config: &Config, branch: &str) -> Result<Option<(String, String)>> {
+    let remote = config.get(&format!("branch.{branch}.remote"))?;
+    let merge = config.get(&format!("branch.{branch}.merge"))?;
+    let (Some(remote), Some(merge)) = (remote, merge) else {
+        return Ok(None);
+    };
+    let name = merge.strip_prefix("refs/heads/").unwrap_or(&merge);
+    Ok(
+        Some(if remote == "." {
+            (format!("refs/heads/{name}"), name.to_string())
+        } else {
+            (format!("refs/remotes/{remote}/{name}"), format!("{remote}/{name}"))
+        })
+    )
+}
+
+/// Tracking info shown by `-v` (`[ahead 1, behind 2] `) and `-vv` (with the upstream name)
+fn tracking_info(config: &Config, branch: &str, sha: &str, verbose: u8) -> Result<String> {
+    let Some((upstream_ref, short)) = upstream(config, branch)? else {
+        return Ok(String::new());
+    };
+    let text = match read_ref(&upstream_ref)? {
+        Some(upstream_sha) => {
+            // Commits of the symmetric difference, ours are on the left side
+            let entries = walk(&[format!("{sha}...{upstream_sha}")], &LogOptions::default())?;
+            let ahead = entries.iter().filter(|entry| entry.left).count();
+            let behind = entries.len() - ahead;
+            let mut parts: Vec<String> = Vec::new();
+            if ahead > 0 {
+                parts.push(format!("ahead {ahead}"));
+            }
+            if behind > 0 {
+                parts.push(format!("behind {behind}"));
+            }
+            parts.join(", ")
+        }
+        None => "gone".to_string(),
+    };
+
+    Ok(match (verbose >= 2, text.is_empty()) {
+        (true, true) => format!("[{short}] "),
+        (true, false) => format!("[{short}: {text}] "),
+        (false, true) => String::new(),
+        (false, false) => format!("[{text}] "),
+    })
+}
+
+/// List local (and remote-tracking) branches, current one is marked with `*`
+pub fn list(verbose: u8, all: bool, remotes: bool) -> Result<String> {
+    let config = Config::load()?;
+    let current = current_branch()?;
+    let mut branches: Vec<ListedBranch> = Vec::new();
+
+    if let (None, Some(sha)) = (&current, read_head_commit()?) {
+        branches.push(ListedBranch {
+            current: true,
+            name: format!("(HEAD detached at {})", abbrev(&sha)),
+            local: None,
+            sha,
+            symbolic: None,
+        });
+    }
+    if !remotes {
+        for (name, sha) in list_refs("refs/heads/")? {
+            let short = name.trim_start_matches("refs/heads/").to_string();
+            branches.push(ListedBranch {
+                current: current.as_ref() == Some(&short),
+                name: short.clone(),
+                local: Some(short),
+                sha,
+                symbolic: None,
+            });
+        }
+    }
+    if all || remotes {
+        for (name, sha) in list_refs("refs/remotes/")? {
+            let short = name.trim_start_matches("refs/remotes/");
+            let symbolic = refs::symbolic_target(&name)?.map(|target| {
+                target.trim_start_matches("refs/remotes/").to_string()
+            });
+            branches.push(ListedBranch {
+                current: false,
+                name: if all { format!("remotes/{short}") } else { short.to_string() },
+                local: None,
+                sha,
+                symbolic,
+            });
+        }
+    }
+
+    let width = branches
+        .iter()
+        .map(|branch| branch.name.chars().count())
+        .max()
+        .unwrap_or_default();
+    let mut output = String::new();
+    for branch in &branches {
+        let marker = if branch.current { '*' } else { ' ' };
+        let line = match (&branch.symbolic, verbose) {
+            (Some(target), 0) => format!("{} -> {target}", branch.name),
+            (Some(target), _) => format!("{:<width$} -> {target}", branch.name),
+            (None, 0) => branch.name.clone(),
+            (None, _) => {
+                let tracking = match &branch.local {
+                    Some(local) => tracking_info(&config, local, &branch.sha, verbose)?,
+                    None => String::new(),
+                };
+                let subject = subject(&read_commit(&branch.sha)?.message_text());
+                format!("{:<width$} {} {tracking}{subject}", branch.name, abbrev(&branch.sha))
+            }
+        };
+        output += &format!("{marker} {line}\n");
+    }
+    Ok(output)
+}
+
+/// Check that the branch can be created (or reset with `force`), returns whether it exists
+pub fn check_new_branch(name: &str, force: bool) -> Result<bool> {
+    check_branch_name(name)?;
+    let exists = read_ref(&format!("refs/heads/{name}"))?.is_some();
+    if exists && !force {
+        bail!("a branch named '{name}' already exists");
+    }
+    Ok(exists)
+}
+
+/// Create branch at the start commit (HEAD by default)
+pub fn create(name: &str, start: Option<&str>, force: bool) -> Result<String> {
+    let exists = check_new_branch(name, force)?;
+    if exists && current_branch()?.as_deref() == Some(name) {
+        bail!("cannot force update the current branch");
+    }
+    let ref_name = format!("refs/heads/{name}");
+
+    let start = start.unwrap_or("HEAD");
+    let sha = resolve_revision(&format!("{start}^{{commit}}")).map_err(|_| {
+        anyhow!("not a valid object name: '{start}'")
+    })?;
+    let message = if exists {
+        format!("branch: Reset to {start}")
+    } else {
+        format!("branch: Created from {start}")
+    };
+    refs::update_ref(&ref_name, &sha, &signature("committer")?, &message)?;
+    Ok(String::new())
+}
+
+/// Delete branches, unless forced only those merged into their upstream (or HEAD)
+pub fn delete(names: &[String], force: bool, remotes: bool) -> Result<String> {
+    let config = Config::load()?;
+    let current = current_branch()?;
+    let head = read_head_commit()?;
+
+    // All branches are checked before anything is deleted
+    let mut deleted: Vec<(&String, String, String)> = Vec::new();
+    for name in names {
+        let ref_name = if remotes {
+            format!("refs/remotes/{name}")
+        } else {
+            format!("refs/heads/{name}")
+        };
+        let Some(sha) = read_ref(&ref_name)? else {
+            if remotes {
+                bail!("remote-tracking branch '{name}' not found.");
+            }
+            bail!("branch '{name}' not found.");
+        };
+        if !remotes && current.as_ref() == Some(name) {
+            let worktree = fs::canonicalize(find_root_folder()?)?;
+            bail!("Cannot delete branch '{name}' checked out at '{}'", worktree.display());
+        }
+        if !remotes && !force {
+            let base = match upstream(&config, name)? {
+                Some((upstream_ref, _)) => read_ref(&upstream_ref)?.or(head.clone()),
+                None => head.clone(),
+            };
+            let merged = match base {
+                Some(base) => is_ancestor(&sha, &base)?,
+                None => true,
+            };
+            if !merged {
+                bail!(
+                    "The branch '{name}' is not fully merged.\n\
+                     If you are sure you want to delete it, run 'git branch -D {name}'."
+                );
+            }
+        }
+        deleted.push((name, ref_name, sha));
+    }
+
+    let mut output = String::new();
+    for (name, ref_name, sha) in deleted {
+        refs::delete_ref(&ref_name)?;
+        if remotes {
+            output += &format!("Deleted remote-tracking branch {name} (was {}).\n", abbrev(&sha));
+        } else {
+            config::remove_section(&config::scope_file(Scope::Local)?, &format!("branch.{name}"))?;
+            output += &format!("Deleted branch {name} (was {}).\n", abbrev(&sha));
+        }
+    }
+    Ok(output)
+}
+
+/// Rename branch with its reflog and config section
+pub fn rename(old: Option<&str>, new: &str, force: bool) -> Result<String> {
+    let current = current_branch()?;
+    let old = branch_or_current(old, "cannot rename the current branch while not on any")?;
+    check_branch_name(new)?;
+    let old_ref = format!("refs/heads/{old}");
+    let new_ref = format!("refs/heads/{new}");
+
+    // Unborn current branch has no ref yet, only HEAD is moved
+    if read_ref(&old_ref)?.is_none() && current.as_ref() != Some(&old) {
+        bail!("no branch named '{old}'");
+    }
+    if old != new && read_ref(&new_ref)?.is_some() {
+        if !force {
+            bail!("a branch named '{new}' already exists");
+        }
+        refs::delete_ref(&new_ref)?;
+    }
+
+    let message = format!("Branch: renamed {old_ref} to {new_ref}");
+    refs::rename_ref(&old_ref, &new_ref, &signature("committer")?, &message)?;
+    let local = config::scope_file(Scope::Local)?;
+    config::rename_section(&local, &format!("branch.{old}"), &format!("branch.{new}"))?;
+    Ok(String::new())
+}
+
+/// Make the branch track a local or remote-tracking branch
+pub fn set_upstream(upstream: &str, branch: Option<&str>) -> Result<String> {
+    let error = format!(
+        "could not set upstream of HEAD to {upstream} when it does not point to any branch."
+    );
+    let branch = branch_or_current(branch, &error)?;
+    if read_ref(&format!("refs/heads/{branch}"))?.is_none() {
+        bail!("branch '{branch}' does not exist");
+    }
+
+    // Remote-tracking branches need a configured remote
+    let config = Config::load()?;
+    let (remote, merge) = if read_ref(&format!("refs/heads/{upstream}"))?.is_some() {
+        (".".to_string(), format!("refs/heads/{upstream}"))
+    } else if read_ref(&format!("refs/remotes/{upstream}"))?.is_some() {
+        let configured = upstream.split_once('/').filter(|(remote, _)| {
+            config.get(&format!("remote.{remote}.url")).is_ok_and(|url| url.is_some())
+        });
+        let Some((remote, name)) = configured else {
+            bail!(
+                "cannot set up tracking information; starting point '{upstream}' is not a branch"
+            );
+        };
+        (remote.to_string(), format!("refs/heads/{name}"))
+    } else {
+        bail!("the requested upstream branch '{upstream}' does not exist");
+    };
+
+    let local = config::scope_file(Scope::Local)?;
+    config::set_value(&local, &format!("branch.{branch}.remote"), &remote)?;
+    config::set_value(&local, &format!("branch.{branch}.merge"), &merge)?;
+    Ok(format!("branch '{branch}' set up to track '{upstream}'.\n"))
+}
+
+/// Remove upstream configuration of the branch
+pub fn unset_upstream(branch: Option<&str>) -> Result<String> {
+    let branch = branch_or_current(branch, "HEAD does not point to a branch")?;
+    if upstream(&Config::load()?, &branch)?.is_none() {
+        bail!("branch '{branch}' has no upstream information");
+    }
+    let local = config::scope_file(Scope::Local)?;
+    config::unset_value(&local, &format!("branch.{branch}.remote"), true)?;
+    config::unset_value(&local, &format!("branch.{branch}.merge"), true)?;
+    Ok(String::new())
+}
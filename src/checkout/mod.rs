@@ -1,20 +1,33 @@
-use crate::utility::fs_utility::*;
-use crate::utility::other_util::*;
+use crate::branch::check_new_branch;
+use crate::config;
+use crate::diff::{ self, abbrev, flatten_tree, index_files, worktree_files, FileMap };
+use crate::diff::{ WorktreeModes, BLOB_MODE, EXEC_MODE, GITLINK_MODE, SYMLINK_MODE, TREE_MODE };
+use crate::history::pretty::subject;
+use crate::index::{ read_index, write_index, Index, IndexEntry };
+use crate::object::{ read_blob, read_commit, read_tree };
+use crate::refs::{ self, current_branch, read_ref };
+use crate::revision::{ read_head_commit, resolve_revision };
+use crate::utility::fs_utility::{ find_root_folder, write_locked };
+use crate::utility::other_util::{ hash_blob, signature };
 
 use anyhow::{ anyhow, bail, Result };
-use std::{ fs, path::Path };
+use std::collections::{ BTreeMap, BTreeSet };
+use std::{ ffi::OsStr, fs, path::Path };
 use std::io::prelude::*;
-
-const HEAD_PATH: &str = ".git/HEAD";
+use std::os::unix::{ ffi::OsStrExt, fs::{ symlink, PermissionsExt } };
 
 /// Function to write all refs from commit
 /// To call we must be right in the working directory!
-pub fn write_refs(refs: &Vec<(String, String)>) -> Result<()> {
-    let head_hash = &refs.get(0).ok_or_else(|| anyhow!("Cannot get HEAD ref"))?.0;
+pub fn write_refs(refs: &[(String, String)]) -> Result<()> {
+    let head_hash = &refs.first().ok_or_else(|| anyhow!("Cannot get HEAD ref"))?.0;
     fs::create_dir_all(".git/")?;
 
     for (hash, path) in &refs[1..] {
-        if hash == head_hash {
+        // Peeled tags (`refs/tags/v1^{}`) only tell which commit the tag points to
+        if path.ends_with("^{}") {
+            continue;
+        }
+        if hash == head_hash && path.starts_with("refs/heads/") {
             // Write ref into head
             // Save ref
             let mut obj: fs::File = fs::File::create(".git/HEAD")?;
@@ -34,7 +47,7 @@ pub fn write_refs(refs: &Vec<(String, String)>) -> Result<()> {
     }
 
     // Detached head
-    if !fs::metadata(".git/HEAD").is_ok() {
+    if fs::metadata(".git/HEAD").is_err() {
         let mut obj: fs::File = fs::File::create(".git/HEAD")?;
         obj.write_all(format!("{head_hash}\n").as_bytes())?;
     }
@@ -42,141 +55,475 @@ pub fn write_refs(refs: &Vec<(String, String)>) -> Result<()> {
     Ok(())
 }
 
-enum HeadRef {
-    Sha(String),
-    Ref(String),
+/// Function to checkout to HEAD, the checked out files are staged
+/// To call we must be right in the working directory
+pub fn checkout_head() -> Result<()> {
+    // Get commit referenced by HEAD
+    let commit_hash = read_head_commit()?.ok_or_else(|| anyhow!("Cannot resolve HEAD"))?;
+    let commit = read_commit(&commit_hash)?;
+
+    let mut index = Index::default();
+    checkout_tree(&commit.tree, "", &mut index)?;
+    write_index(&index)
 }
 
-/// Returns reference saved in HEAD
-/// To call we must be right in the working directory
-fn get_head_ref() -> Result<HeadRef> {
-    let bytes: Vec<u8> = match fs::read(HEAD_PATH) {
-        Ok(data) => data,
-        Err(_) => {
-            bail!("Cannot open '{}'", HEAD_PATH);
+/// Checkout to full tree object under the prefix, adding index entries of the written files
+/// with their tree modes
+fn checkout_tree(tree_hash: &str, prefix: &str, index: &mut Index) -> Result<()> {
+    // Create folder if it's missing
+    let root = find_root_folder()?;
+    fs::create_dir_all(format!("{root}{prefix}"))?;
+
+    // Read tree object
+    let tree = read_tree(tree_hash)?;
+    let modes = WorktreeModes::load()?;
+
+    for entry in tree.entries {
+        let path = format!("{prefix}{}", entry.name_text());
+        match entry.mode.as_str() {
+            // Go to inner tree
+            TREE_MODE => checkout_tree(&entry.sha, &format!("{path}/"), index)?,
+            // Create file (or symlink) and save data
+            BLOB_MODE | EXEC_MODE | SYMLINK_MODE | GITLINK_MODE => {
+                let written = write_worktree_file(&root, &path, &entry.mode, &entry.sha, modes)?;
+                index.entries.push(written);
+            }
+            mode => bail!("Checkout wasn't successfull, unsupported mode {mode}!"),
         }
-    };
-    let head_contentents = String::from_utf8(bytes)?;
-    // println!("HEAD: {head_contentents}");
+    }
 
-    if head_contentents.starts_with("ref: ") {
-        Ok(HeadRef::Ref(head_contentents[5..head_contentents.len() - 1].to_string()))
-    } else if head_contentents.len() == 41 {
-        Ok(HeadRef::Sha(head_contentents[..40].to_string()))
-    } else {
-        bail!("Incorrect head contents: {head_contentents}!")
+    Ok(())
+}
+
+/// Replace whatever is at the path with the blob, exec bit is set for `100755`,
+/// symlinks are written as plain files with the target when `core.symlinks` is off,
+/// submodules (`160000`) get an empty folder until `submodule update` fills it
+fn write_entry(full_path: &str, mode: &str, sha: &str, modes: WorktreeModes) -> Result<()> {
+    let existing = fs::symlink_metadata(full_path);
+    if mode == GITLINK_MODE {
+        if existing.is_ok_and(|meta| !meta.is_dir()) {
+            fs::remove_file(full_path)?;
+        }
+        fs::create_dir_all(full_path)?;
+        return Ok(());
+    }
+    if let Ok(meta) = existing {
+        if meta.is_dir() {
+            fs::remove_dir_all(full_path)?;
+        } else {
+            fs::remove_file(full_path)?;
+        }
+    }
+
+    let data = read_blob(sha)?.data;
+    if mode == SYMLINK_MODE && modes.symlinks {
+        symlink(OsStr::from_bytes(&data), full_path)?;
+        return Ok(());
     }
+    fs::write(full_path, data)?;
+    if mode == EXEC_MODE {
+        // Executable for everyone who can read it
+        let mut permissions = fs::metadata(full_path)?.permissions();
+        permissions.set_mode(permissions.mode() | ((permissions.mode() & 0o444) >> 2));
+        fs::set_permissions(full_path, permissions)?;
+    }
+    Ok(())
 }
 
-/// Returns referenced commit from HEAD
+/// Write config file after clone
 /// To call we must be right in the working directory
-fn get_head_commit_sha() -> Result<String> {
-    match get_head_ref()? {
-        HeadRef::Sha(hash) => Ok(hash),
-        HeadRef::Ref(referenced_file) => {
-            let bytes: Vec<u8> = match fs::read(format!(".git/{referenced_file}")) {
-                Ok(data) => data,
-                Err(_) => {
-                    bail!("Cannot open '{}'", referenced_file);
-                }
-            };
-            Ok(String::from_utf8(bytes)?[..40].to_owned())
+pub fn write_config(repo_url: &str) -> Result<()> {
+    if let Some(head_ref) = refs::symbolic_target("HEAD")? {
+        let branch = head_ref
+            .rsplit_once('/')
+            .ok_or_else(|| anyhow!("Cannot separate branch name!"))?.1;
+        let values = [
+            ("core.repositoryformatversion".to_string(), "0"),
+            ("core.filemode".to_string(), "true"),
+            ("core.bare".to_string(), "false"),
+            ("core.logallrefupdates".to_string(), "true"),
+            ("remote.origin.url".to_string(), repo_url),
+            ("remote.origin.fetch".to_string(), "+refs/heads/*:refs/remotes/origin/*"),
+            (format!("branch.{branch}.remote"), "origin"),
+            (format!("branch.{branch}.merge"), &head_ref),
+        ];
+        for (name, value) in values {
+            config::set_value(".git/config", &name, value)?;
         }
+    } else {
+        bail!("Not a ref inside HEAD after clone!");
     }
+    Ok(())
 }
 
-/// Function to checkout to HEAD
-/// To call we must be right in the working directory
-pub fn checkout_head() -> Result<()> {
-    // Get commit referenced by HEAD
-    let commit_hash = get_head_commit_sha()?;
-    // println!("commit: {commit_hash:?}");
+/// Options of `switch` and `checkout <branch>`
+#[derive(Clone, Debug, Default)]
+pub struct SwitchOptions {
+    /// Branch or commit to switch to (start point of the new branch)
+    pub target: Option<String>,
+    /// Branch to create (`-b`, `-c`)
+    pub create: Option<String>,
+    /// Reset the branch if it already exists (`-B`, `-C`)
+    pub force_create: bool,
+    pub detach: bool,
+    /// Throw away local changes (`-f`, `--discard-changes`)
+    pub force: bool,
+    /// Target must be a branch unless detaching (`switch`)
+    pub branch_only: bool,
+}
 
-    let commit = String::from_utf8(read_data_decompressed(&commit_hash)?)?;
-    // println!("commit data:{commit}");
+/// Change of a single working tree path: new (<mode>, <SHA-1>) or None for removal
+pub(crate) type Update = (String, Option<(String, String)>);
 
-    let (_, commit_contents) = commit
-        .split_once('\0')
-        .ok_or_else(|| anyhow!("Cannot parse commit!"))?;
-    let (tree_data, _) = commit_contents
-        .split_once('\n')
-        .ok_or_else(|| anyhow!("Cannot parse commit!"))?;
-    let (obj_type, tree_hash) = tree_data
-        .split_once(' ')
-        .ok_or_else(|| anyhow!("Cannot parse commit!"))?;
-    if obj_type != "tree" {
-        bail!("Incorrect commit object structure!");
-    }
-    // println!("tree: {tree_hash}");
+/// Command updating the working tree, named in errors about local changes
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum UpdateKind {
+    Checkout,
+    Merge,
+}
 
-    let basic_path: String = String::from(".");
-    checkout_tree(tree_hash, basic_path)
+impl UpdateKind {
+    /// Command name and what the user should do the cleanup before
+    fn wording(self) -> (&'static str, &'static str) {
+        match self {
+            UpdateKind::Checkout => ("checkout", "switch branches"),
+            UpdateKind::Merge => ("merge", "merge"),
+        }
+    }
 }
 
-/// Checkout to full tree object
-fn checkout_tree(tree_hash: &str, path: String) -> Result<()> {
-    // Create folder if it's missing
-    fs::create_dir_all(&path)?;
+/// Kind of non-branch revision for the `switch` error message
+fn revision_kind(target: &str) -> Result<&'static str> {
+    Ok(if read_ref(&format!("refs/tags/{target}"))?.is_some() {
+        "tag"
+    } else if read_ref(&format!("refs/remotes/{target}"))?.is_some() {
+        "remote branch"
+    } else {
+        "commit"
+    })
+}
 
-    // Read data from tree object
-    let bytes_decoded: Vec<u8> = read_data_decompressed(tree_hash)?;
+/// One line description of the commit (`<sha7> <subject>`)
+fn commit_line(sha: &str) -> Result<String> {
+    Ok(format!("{} {}", abbrev(sha), subject(&read_commit(sha)?.message_text())))
+}
 
-    // Parse tree
-    for (filename, _mode, sha) in parse_tree(&bytes_decoded)? {
-        // println!("{filename}, {_mode}, {sha}");
-        let object_contents: Vec<u8> = read_data_decompressed(&sha)?;
-        let mut slices_itr = object_contents.split_inclusive(|c| *c == b'\0');
+/// Files of the commit, empty for unborn branch
+pub(crate) fn commit_files(sha: Option<&str>) -> Result<FileMap> {
+    match sha {
+        Some(sha) => flatten_tree(&read_commit(sha)?.tree),
+        None => Ok(FileMap::new()),
+    }
+}
 
-        let header = slices_itr.next().ok_or_else(|| anyhow!("Cannot divide header!"))?;
+/// Find working tree updates moving from `head` to `target` files (two-way merge),
+/// local changes are kept when the path does not differ between the commits
+pub(crate) fn plan_updates(
+    head: &FileMap,
+    target: &FileMap,
+    index: &FileMap,
+    worktree: &FileMap,
+    force: bool,
+    kind: UpdateKind
+) -> Result<Vec<Update>> {
+    let root = find_root_folder()?;
+    let paths: BTreeSet<&String> = head.keys().chain(target.keys()).chain(index.keys()).collect();
 
-        if header.starts_with(b"tree") {
-            // Go to inner tree
-            checkout_tree(&sha, format!("{path}/{filename}"))?;
-        } else if header.starts_with(b"blob") {
-            // Create file and save data
-            let mut obj: fs::File = fs::File::create(format!("{path}/{filename}"))?;
+    let mut updates: Vec<Update> = Vec::new();
+    let mut dirty: Vec<&str> = Vec::new();
+    let mut untracked: Vec<&str> = Vec::new();
+    for path in paths {
+        let (h, t, i, w) = (head.get(path), target.get(path), index.get(path), worktree.get(path));
+        if force {
+            if t != i || (t.is_some() && t != w) || (t.is_none() && w.is_some()) {
+                updates.push((path.clone(), t.cloned()));
+            }
+            continue;
+        }
+        if h == t || i == t {
+            continue;
+        }
 
-            if let Some(binary) = slices_itr.next() {
-                obj.write_all(binary)?;
+        // Missing working tree file counts as unchanged
+        let clean = i == h && (w.is_none() || w == i);
+        if !clean {
+            dirty.push(path);
+            continue;
+        }
+        // New file must not overwrite a different untracked file
+        if i.is_none() && fs::symlink_metadata(format!("{root}{path}")).is_ok() {
+            let blob = t.map(|(_, sha)| sha.as_str());
+            let sha = hash_blob(&diff::read_worktree_file(path)?);
+            if blob != Some(sha.as_str()) {
+                untracked.push(path);
+                continue;
             }
-        } else {
-            bail!("Checkout wasn't successfull, wrong header!");
         }
+        updates.push((path.clone(), t.cloned()));
     }
 
+    let (command, action) = kind.wording();
+    if !dirty.is_empty() {
+        bail!(
+            "Your local changes to the following files would be overwritten by {command}:\n\t{}\n\
+             Please commit your changes or stash them before you {action}.\nAborting",
+            dirty.join("\n\t")
+        );
+    }
+    if !untracked.is_empty() {
+        bail!(
+            "The following untracked working tree files would be overwritten by {command}:\n\t{}\n\
+             Please move or remove them before you {action}.\nAborting",
+            untracked.join("\n\t")
+        );
+    }
+    Ok(updates)
+}
+
+/// Remove file and its parent folders left empty (up to the repository root)
+fn remove_worktree_file(root: &str, path: &str) -> Result<()> {
+    let full_path = format!("{root}{path}");
+    if let Ok(meta) = fs::symlink_metadata(&full_path) {
+        // Populated submodules are left in place
+        if meta.is_dir() {
+            let _ = fs::remove_dir(&full_path);
+        } else {
+            fs::remove_file(&full_path)?;
+        }
+    }
+    let mut parent = Path::new(path).parent();
+    while let Some(dir) = parent.filter(|dir| !dir.as_os_str().is_empty()) {
+        if fs::remove_dir(format!("{root}{}", dir.display())).is_err() {
+            break;
+        }
+        parent = dir.parent();
+    }
     Ok(())
 }
 
-fn format_config(repo_url: &str, branch: &str, head_ref: &str) -> String {
-    format!(
-        "[core]
-\trepositoryformatversion = 0
-\tfilemode = true
-\tbare = false
-\tlogallrefupdates = true
-[remote \"origin\"]
-\turl = {repo_url}
-\tfetch = +refs/heads/*:refs/remotes/origin/*
-[branch \"{branch}\"]
-\tremote = origin
-\tmerge = {head_ref}\n"
-    )
+/// Write blob into the working tree, returns its index entry
+fn write_worktree_file(
+    root: &str,
+    path: &str,
+    mode: &str,
+    sha: &str,
+    modes: WorktreeModes
+) -> Result<IndexEntry> {
+    let full_path = format!("{root}{path}");
+    if let Some(parent) = Path::new(&full_path).parent() {
+        fs::create_dir_all(parent)?;
+    }
+    write_entry(&full_path, mode, sha, modes)?;
+    IndexEntry::from_metadata(path, mode, sha, &fs::symlink_metadata(&full_path)?)
 }
 
-/// Write config file after clone
-/// To call we must be right in the working directory
-pub fn write_config(repo_url: &str) -> Result<()> {
-    if let HeadRef::Ref(head_ref) = get_head_ref()? {
-        let mut cfg_file: fs::File = fs::File::create(format!(".git/config"))?;
-        let branch = head_ref
-            .rsplit_once('/')
-            .ok_or_else(|| anyhow!("Cannot separate branch name!"))?.1;
-        let write_data = format_config(repo_url, branch, &head_ref);
-        cfg_file.write_all(write_data.as_bytes())?;
+/// Apply updates to the working tree only, returns index entries of the written files
+pub(crate) fn update_worktree(updates: &[Update]) -> Result<Vec<IndexEntry>> {
+    let root = find_root_folder()?;
+    let modes = WorktreeModes::load()?;
+
+    // Removals go first, so folders can replace files and vice versa
+    for (path, _) in updates.iter().filter(|(_, new)| new.is_none()) {
+        remove_worktree_file(&root, path)?;
+    }
+    let mut written: Vec<IndexEntry> = Vec::new();
+    for (path, new) in updates {
+        if let Some((mode, sha)) = new {
+            written.push(write_worktree_file(&root, path, mode, sha, modes)?);
+        }
+    }
+    Ok(written)
+}
+
+/// Apply updates to the working tree and the index
+pub(crate) fn apply_updates(updates: &[Update]) -> Result<()> {
+    let written = update_worktree(updates)?;
+    let mut index = read_index()?;
+    let mut entries: BTreeMap<String, IndexEntry> = index.entries
+        .drain(..)
+        .map(|entry| (entry.path.clone(), entry))
+        .collect();
+    for (path, _) in updates.iter().filter(|(_, new)| new.is_none()) {
+        entries.remove(path);
+    }
+    for entry in written {
+        entries.insert(entry.path.clone(), entry);
+    }
+
+    index.entries = entries.into_values().collect();
+    write_index(&index)
+}
+
+/// Index entry for a file staged without writing it, with the stat data of the working tree
+/// file when it has the same contents (so it is not seen as changed)
+pub(crate) fn staged_entry(path: &str, mode: &str, sha: &str) -> Result<IndexEntry> {
+    let full_path = find_root_folder()? + path;
+    if let Ok(meta) = fs::symlink_metadata(&full_path) {
+        if !meta.is_dir() && hash_blob(&diff::read_worktree_file(path)?) == sha {
+            return IndexEntry::from_metadata(path, mode, sha, &meta);
+        }
+    }
+    IndexEntry::new(path, mode, sha)
+}
+
+/// Replace index entries under the pathspec by the files (entries not in `files` are removed),
+/// unchanged entries keep their stat data
+pub(crate) fn stage_files(files: &FileMap, pathspec: &[String]) -> Result<()> {
+    let mut index = read_index()?;
+    let mut entries: BTreeMap<(String, u8), IndexEntry> = index.entries
+        .drain(..)
+        .map(|entry| ((entry.path.clone(), entry.stage()), entry))
+        .collect();
+
+    let mut staged: Vec<IndexEntry> = Vec::new();
+    for (path, (mode, sha)) in files {
+        if !diff::matches_pathspec(path, pathspec) {
+            continue;
+        }
+        match entries.remove(&(path.clone(), 0)) {
+            Some(entry) if entry.sha == *sha && entry.mode_string() == *mode => staged.push(entry),
+            _ => staged.push(staged_entry(path, mode, sha)?),
+        }
+    }
+    entries.retain(|(path, _), _| !diff::matches_pathspec(path, pathspec));
+
+    index.entries = entries.into_values().chain(staged).collect();
+    write_index(&index)
+}
+
+/// Bring the index and the working tree from `head` to the commit (nothing for an unborn
+/// branch), throwing away changes of tracked files and unmerged entries
+pub(crate) fn reset_hard(head: Option<&str>, target: Option<&str>) -> Result<()> {
+    let index = read_index()?;
+    let head_files = commit_files(head)?;
+    let target_files = commit_files(target)?;
+    let index_map = index_files(&index);
+    let worktree = worktree_files(&index, &index_map)?;
+    let kind = UpdateKind::Checkout;
+    let mut updates =
+        plan_updates(&head_files, &target_files, &index_map, &worktree, true, kind)?;
+    for entry in index.entries.iter().filter(|entry| entry.stage() != 0) {
+        if !updates.iter().any(|(path, _)| *path == entry.path) {
+            updates.push((entry.path.clone(), target_files.get(&entry.path).cloned()));
+        }
+    }
+    apply_updates(&updates)
+}
+
+/// Switch to the branch (or detach HEAD at the commit) updating the index and the working tree
+pub fn switch(options: &SwitchOptions) -> Result<String> {
+    let current = current_branch()?;
+    let head = read_head_commit()?;
+    let target_name = options.target.clone().unwrap_or_else(|| "HEAD".to_string());
+
+    // Branch HEAD will point to after the switch (None for detached HEAD)
+    let resolve = |name: &str| resolve_revision(&format!("{name}^{{commit}}"));
+    let (branch, target): (Option<String>, Option<String>) = match &options.create {
+        Some(name) => {
+            check_new_branch(name, options.force_create)?;
+            let target = match (&options.target, &head) {
+                (None, None) => None,
+                _ => {
+                    let error = format!(
+                        "'{target_name}' is not a commit and a branch '{name}' \
+                         cannot be created from it"
+                    );
+                    Some(resolve(&target_name).map_err(|_| anyhow!(error))?)
+                }
+            };
+            (Some(name.clone()), target)
+        }
+        None => {
+            let Some(name) = &options.target else {
+                bail!("missing branch or commit argument");
+            };
+            let local = read_ref(&format!("refs/heads/{name}"))?;
+            if local.is_some() && !options.detach {
+                (Some(name.clone()), local)
+            } else {
+                let sha = resolve(name).map_err(|_| anyhow!("invalid reference: {name}"))?;
+                if options.branch_only && !options.detach {
+                    bail!("a branch is expected, got {} '{name}'", revision_kind(name)?);
+                }
+                (None, Some(sha))
+            }
+        }
+    };
+
+    // Bring the working tree and the index to the target commit
+    let index = read_index()?;
+    if index.entries.iter().any(|entry| entry.stage() != 0) {
+        bail!("you need to resolve your current index first");
+    }
+    let head_files = commit_files(head.as_deref())?;
+    let target_files = commit_files(target.as_deref())?;
+    let index_map = index_files(&index);
+    let worktree = worktree_files(&index, &index_map)?;
+    let updates = plan_updates(
+        &head_files,
+        &target_files,
+        &index_map,
+        &worktree,
+        options.force,
+        UpdateKind::Checkout
+    )?;
+    apply_updates(&updates)?;
+
+    // Local changes carried over to the new commit
+    let index = read_index()?;
+    let index_map = index_files(&index);
+    let worktree = worktree_files(&index, &index_map)?;
+    let changes = diff::compare_file_maps(&target_files, &worktree, true, &[]);
+    // New branch at HEAD does not touch the working tree, so nothing is reported
+    let mut output = if options.create.is_some() && options.target.is_none() {
+        String::new()
     } else {
-        bail!("Not a ref inside HEAD after clone!");
+        diff::format::name_status(&changes)
+    };
+
+    // Move branch and HEAD with reflog entries
+    let committer = signature("committer")?;
+    let Some(sha) = target else {
+        // Unborn branch only gets a new name
+        let name = branch.unwrap_or_default();
+        write_locked(&(find_root_folder()? + ".git/HEAD"), format!("ref: refs/heads/{name}\n"))?;
+        return Ok(output + &format!("Switched to a new branch '{name}'\n"));
+    };
+    let existed = match &options.create {
+        Some(name) => {
+            let ref_name = format!("refs/heads/{name}");
+            let existed = read_ref(&ref_name)?.is_some();
+            let message = if existed {
+                format!("branch: Reset to {target_name}")
+            } else {
+                format!("branch: Created from {target_name}")
+            };
+            refs::update_ref(&ref_name, &sha, &committer, &message)?;
+            existed
+        }
+        None => false,
+    };
+    let from = current.clone().or(head.clone()).unwrap_or_default();
+    let to = options.create.as_deref().unwrap_or(&target_name);
+    let message = format!("checkout: moving from {from} to {to}");
+    refs::set_head(branch.as_deref(), &sha, &committer, &message)?;
+
+    if let (None, Some(old)) = (&current, &head) {
+        if *old != sha {
+            output += &format!("Previous HEAD position was {}\n", commit_line(old)?);
+        }
     }
-    Ok(())
+    output += &match (&branch, &options.create) {
+        (Some(name), Some(_)) if current.as_ref() == Some(name) => {
+            format!("Reset branch '{name}'\n")
+        }
+        (Some(name), None) if current.as_ref() == Some(name) => format!("Already on '{name}'\n"),
+        (Some(name), Some(_)) if existed => format!("Switched to and reset branch '{name}'\n"),
+        (Some(name), Some(_)) => format!("Switched to a new branch '{name}'\n"),
+        (Some(name), None) => format!("Switched to branch '{name}'\n"),
+        (None, _) => format!("HEAD is now at {}\n", commit_line(&sha)?),
+    };
+    Ok(output)
 }
 
 /// Generate index file (stage files)
@@ -0,0 +1,134 @@
+use crate::diff::{ diff_trees, TreeDiffOptions };
+use crate::object::Commit;
+
+use anyhow::Result;
+use std::collections::BTreeSet;
+
+/// Settings of the filters, the only ones written (and read) by git
+pub const HASH_VERSION: u32 = 1;
+pub const NUM_HASHES: u32 = 7;
+pub const BITS_PER_ENTRY: u32 = 10;
+/// Commits changing more paths get a filter matching everything
+const MAX_CHANGED_PATHS: usize = 512;
+
+const SEED_FIRST: u32 = 0x293ae76f;
+const SEED_SECOND: u32 = 0x7e646e2c;
+
+/// Murmur3 hash as computed by git (bytes are sign extended like `char` in version 1 filters)
+fn murmur3(seed: u32, data: &[u8]) -> u32 {
+    let scramble = |k: u32| k.wrapping_mul(0xcc9e2d51).rotate_left(15).wrapping_mul(0x1b873593);
+    let byte = |b: u8| b as i8 as u32;
+
+    let mut hash = seed;
+    let mut blocks = data.chunks_exact(4);
+    for block in &mut blocks {
+        let k = byte(block[0]) | byte(block[1]) << 8 | byte(block[2]) << 16 | byte(block[3]) << 24;
+        hash ^= scramble(k);
+        hash = hash.rotate_left(13).wrapping_mul(5).wrapping_add(0xe6546b64);
+    }
+    let tail = blocks.remainder();
+    if !tail.is_empty() {
+        let mut k: u32 = 0;
+        for (index, b) in tail.iter().enumerate() {
+            k ^= byte(*b) << (8 * index);
+        }
+        hash ^= scramble(k);
+    }
+
+    hash ^= data.len() as u32;
+    hash ^= hash >> 16;
+    hash = hash.wrapping_mul(0x85ebca6b);
+    hash ^= hash >> 13;
+    hash = hash.wrapping_mul(0xc2b2ae35);
+    hash ^ (hash >> 16)
+}
+
+/// Bit positions of a path (double hashing of two murmur3 seeds)
+#[derive(Clone, Debug)]
+pub struct BloomKey {
+    hashes: [u32; NUM_HASHES as usize],
+}
+
+impl BloomKey {
+    pub fn new(path: &str) -> BloomKey {
+        let first = murmur3(SEED_FIRST, path.as_bytes());
+        let second = murmur3(SEED_SECOND, path.as_bytes());
+        let mut hashes = [0; NUM_HASHES as usize];
+        for (index, hash) in hashes.iter_mut().enumerate() {
+            *hash = first.wrapping_add((index as u32).wrapping_mul(second));
+        }
+        BloomKey { hashes }
+    }
+
+    /// Byte and bit of each hash in a filter of `len` bytes
+    fn bits(&self, len: usize) -> impl Iterator<Item = (usize, u8)> + '_ {
+        let size = len as u64 * 8;
+        self.hashes.iter().map(move |hash| {
+            let bit = *hash as u64 % size;
+            ((bit / 8) as usize, 1 << (bit % 8))
+        })
+    }
+
+    /// False when the path is certainly not in the filter
+    pub fn maybe_in(&self, filter: &[u8]) -> bool {
+        !filter.is_empty() && self.bits(filter.len()).all(|(index, bit)| filter[index] & bit != 0)
+    }
+}
+
+/// Keys checked for a pathspec: the path and its leading directories, None when the pathspec
+/// matches every path
+pub fn pathspec_keys(spec: &str) -> Option<Vec<BloomKey>> {
+    let mut path = spec.trim_end_matches('/');
+    if path.is_empty() || path == "." {
+        return None;
+    }
+    let mut keys: Vec<BloomKey> = vec![BloomKey::new(path)];
+    while let Some((parent, _)) = path.rsplit_once('/') {
+        keys.push(BloomKey::new(parent));
+        path = parent;
+    }
+    Some(keys)
+}
+
+/// Filter of paths (and their leading directories) changed against the first parent
+pub fn changed_paths_filter(commit: &Commit, parent_tree: Option<&str>) -> Result<Vec<u8>> {
+    let options = TreeDiffOptions { recursive: true, ..Default::default() };
+    let changes = diff_trees(parent_tree, Some(&commit.tree), &options)?;
+    let mut paths: BTreeSet<&str> = BTreeSet::new();
+    if changes.len() <= MAX_CHANGED_PATHS {
+        for change in &changes {
+            let mut path = change.path();
+            paths.insert(path);
+            while let Some((parent, _)) = path.rsplit_once('/') {
+                paths.insert(parent);
+                path = parent;
+            }
+        }
+    }
+    if changes.len() > MAX_CHANGED_PATHS || paths.len() > MAX_CHANGED_PATHS {
+        return Ok(vec![0xff]);
+    }
+
+    // Empty filters still take a byte
+    let len = (paths.len() * BITS_PER_ENTRY as usize).div_ceil(8).max(1);
+    let mut filter = vec![0; len];
+    for path in paths {
+        for (index, bit) in BloomKey::new(path).bits(len) {
+            filter[index] |= bit;
+        }
+    }
+    Ok(filter)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn murmur3_values() {
+        // Test vectors of git's t/helper/test-bloom.c
+        assert_eq!(murmur3(0, b""), 0x00000000);
+        assert_eq!(murmur3(0, b"Hello world!"), 0x627b0c2c);
+        assert_eq!(murmur3(0, b"The quick brown fox jumps over the lazy dog"), 0x2e4ff723);
+    }
+}
@@ -0,0 +1,549 @@
+pub mod bloom;
+
+use crate::config::Config;
+use crate::history::CommitWalker;
+use crate::object::{ read_commit, read_object, Commit, Object };
+use crate::refs::list_refs;
+use crate::utility::fs_utility::{ find_root_folder, write_locked };
+use crate::utility::other_util::get_hash_from_data;
+use bloom::{ pathspec_keys, BloomKey };
+
+use anyhow::{ anyhow, bail, Result };
+use std::collections::hash_map::Entry;
+use std::collections::{ HashMap, HashSet };
+use std::fs;
+
+const SIGNATURE: &[u8] = b"CGPH";
+const HASH_LEN: usize = 20;
+const HEADER_LEN: usize = 8;
+const CHUNK_ENTRY_LEN: usize = 12;
+/// Tree, two parent positions and the generation with the commit time
+const COMMIT_DATA_LEN: usize = HASH_LEN + 16;
+const NO_PARENT: u32 = 0x7000_0000;
+/// Second parent value pointing into the extra edges, also marks the last extra edge
+const EXTRA_EDGES: u32 = 0x8000_0000;
+const GENERATION_MAX: u32 = 0x3fff_ffff;
+/// Settings header of the Bloom filter data chunk
+const BLOOM_HEADER_LEN: usize = 12;
+
+/// Operation of the `commit-graph` command
+#[derive(Clone, Debug)]
+pub enum CommitGraphAction {
+    /// Write the graph of commits reachable from refs, as a new layer of the chain with `split`
+    Write {
+        split: bool,
+        changed_paths: bool,
+    },
+}
+
+/// Commit data stored in the graph
+#[derive(Clone, Debug)]
+pub struct GraphCommit {
+    pub tree: String,
+    pub parents: Vec<String>,
+    pub time: i64,
+    /// Topological level: 1 for root commits, otherwise one more than the highest parent level
+    /// (0 when the writer did not compute it)
+    pub generation: u32,
+}
+
+fn read_u32(data: &[u8], offset: usize) -> u32 {
+    u32::from_be_bytes(data[offset..offset + 4].try_into().unwrap())
+}
+
+fn read_u64(data: &[u8], offset: usize) -> u64 {
+    u64::from_be_bytes(data[offset..offset + 8].try_into().unwrap())
+}
+
+/// Single commit-graph file, the base layer comes first in a split chain
+#[derive(Debug)]
+struct Layer {
+    data: Vec<u8>,
+    /// Checksum of the file, which names the files of a chain
+    hash: String,
+    count: usize,
+    /// Commits in the layers below, positions of this layer start there
+    base_count: usize,
+    fanout: usize,
+    lookup: usize,
+    commit_data: usize,
+    extra_edges: Option<(usize, usize)>,
+    /// Filter end offsets and the filter data (after the settings header)
+    bloom: Option<(usize, usize, usize)>,
+}
+
+impl Layer {
+    fn parse(data: Vec<u8>, base_count: usize, bases: usize) -> Result<Layer> {
+        if data.len() < HEADER_LEN + HASH_LEN || !data.starts_with(SIGNATURE) {
+            bail!("commit-graph signature mismatch");
+        }
+        if data[4] != 1 {
+            bail!("commit-graph version {} does not match version 1", data[4]);
+        }
+        if data[5] != 1 {
+            bail!("commit-graph hash version {} does not match version 1", data[5]);
+        }
+        if data[7] as usize != bases {
+            bail!("commit-graph has {} base graphs instead of {bases}", data[7]);
+        }
+
+        let end = data.len() - HASH_LEN;
+        let mut chunks: HashMap<&[u8], (usize, usize)> = HashMap::new();
+        for index in 0..data[6] as usize {
+            let entry = HEADER_LEN + index * CHUNK_ENTRY_LEN;
+            if entry + 2 * CHUNK_ENTRY_LEN > end {
+                bail!("commit-graph chunk lookup table is truncated");
+            }
+            let start = read_u64(&data, entry + 4) as usize;
+            let next = read_u64(&data, entry + CHUNK_ENTRY_LEN + 4) as usize;
+            if start > next || next > end {
+                bail!("commit-graph chunk offsets are out of range");
+            }
+            chunks.insert(&data[entry..entry + 4], (start, next));
+        }
+        let chunk = |id: &str, len: Option<usize>| -> Result<Option<usize>> {
+            match chunks.get(id.as_bytes()) {
+                Some((start, end)) if len.is_some_and(|len| end - start != len) => {
+                    bail!("commit-graph {id} chunk has wrong size")
+                }
+                Some((start, _)) => Ok(Some(*start)),
+                None => Ok(None),
+            }
+        };
+
+        let fanout = chunk("OIDF", Some(256 * 4))?
+            .ok_or_else(|| anyhow!("commit-graph is missing the OID fanout chunk"))?;
+        let count = read_u32(&data, fanout + 255 * 4) as usize;
+        let lookup = chunk("OIDL", Some(count * HASH_LEN))?
+            .ok_or_else(|| anyhow!("commit-graph is missing the OID lookup chunk"))?;
+        let commit_data = chunk("CDAT", Some(count * COMMIT_DATA_LEN))?
+            .ok_or_else(|| anyhow!("commit-graph is missing the commit data chunk"))?;
+        let extra_edges = chunks.get("EDGE".as_bytes()).copied();
+
+        // Filters with other settings cannot be queried with our keys
+        let bloom = match (chunk("BIDX", Some(count * 4))?, chunks.get("BDAT".as_bytes())) {
+            (Some(index), Some(&(start, end))) if
+                end - start >= BLOOM_HEADER_LEN &&
+                read_u32(&data, start) == bloom::HASH_VERSION &&
+                read_u32(&data, start + 4) == bloom::NUM_HASHES &&
+                read_u32(&data, start + 8) == bloom::BITS_PER_ENTRY
+            => Some((index, start + BLOOM_HEADER_LEN, end)),
+            _ => None,
+        };
+
+        let hash = hex::encode(&data[end..]);
+        Ok(Layer {
+            data,
+            hash,
+            count,
+            base_count,
+            fanout,
+            lookup,
+            commit_data,
+            extra_edges,
+            bloom,
+        })
+    }
+
+    fn oid(&self, index: usize) -> &[u8] {
+        let start = self.lookup + index * HASH_LEN;
+        &self.data[start..start + HASH_LEN]
+    }
+
+    /// Index of the commit in this layer
+    fn find(&self, oid: &[u8]) -> Option<usize> {
+        let first = oid[0] as usize;
+        let mut low = match first {
+            0 => 0,
+            _ => read_u32(&self.data, self.fanout + (first - 1) * 4) as usize,
+        };
+        let mut high = (read_u32(&self.data, self.fanout + first * 4) as usize).min(self.count);
+        while low < high {
+            let middle = (low + high) / 2;
+            match self.oid(middle).cmp(oid) {
+                std::cmp::Ordering::Less => low = middle + 1,
+                std::cmp::Ordering::Greater => high = middle,
+                std::cmp::Ordering::Equal => return Some(middle),
+            }
+        }
+        None
+    }
+}
+
+/// Commits stored in `.git/objects/info/commit-graph` or in the split chain of
+/// `.git/objects/info/commit-graphs`
+#[derive(Debug)]
+pub struct CommitGraph {
+    layers: Vec<Layer>,
+}
+
+fn info_folder() -> Result<String> {
+    Ok(find_root_folder()? + ".git/objects/info/")
+}
+
+/// Layers listed in `commit-graph-chain`, base first
+fn load_chain() -> Result<Vec<Layer>> {
+    let folder = info_folder()? + "commit-graphs/";
+    let Ok(chain) = fs::read_to_string(format!("{folder}commit-graph-chain")) else {
+        return Ok(Vec::new());
+    };
+    let mut layers: Vec<Layer> = Vec::new();
+    for hash in chain.lines().map(str::trim).filter(|line| !line.is_empty()) {
+        let data = fs::read(format!("{folder}graph-{hash}.graph"))
+            .map_err(|_| anyhow!("unable to find all commit-graph files"))?;
+        let base_count = layers.last().map_or(0, |layer| layer.base_count + layer.count);
+        let layer = Layer::parse(data, base_count, layers.len())?;
+        if layer.hash != hash {
+            bail!("commit-graph chain does not match");
+        }
+        layers.push(layer);
+    }
+    Ok(layers)
+}
+
+impl CommitGraph {
+    /// Graph of the repository, None when there is none or `core.commitGraph` is false
+    pub fn load() -> Result<Option<CommitGraph>> {
+        if !Config::load()?.get_bool("core.commitgraph")?.unwrap_or(true) {
+            return Ok(None);
+        }
+        // A single file takes precedence over a chain
+        let layers = match fs::read(info_folder()? + "commit-graph") {
+            Ok(data) => vec![Layer::parse(data, 0, 0)?],
+            Err(_) => load_chain()?,
+        };
+        Ok(if layers.is_empty() { None } else { Some(CommitGraph { layers }) })
+    }
+
+    /// Position of the commit in the graph, counted across layers
+    fn position(&self, sha: &str) -> Option<usize> {
+        let oid = hex::decode(sha).ok().filter(|oid| oid.len() == HASH_LEN)?;
+        self.layers
+            .iter()
+            .rev()
+            .find_map(|layer| layer.find(&oid).map(|index| layer.base_count + index))
+    }
+
+    fn locate(&self, position: usize) -> Result<(&Layer, usize)> {
+        self.layers
+            .iter()
+            .find(|layer| position < layer.base_count + layer.count)
+            .filter(|layer| position >= layer.base_count)
+            .map(|layer| (layer, position - layer.base_count))
+            .ok_or_else(|| anyhow!("commit-graph position {position} is out of range"))
+    }
+
+    fn sha_at(&self, position: u32) -> Result<String> {
+        let (layer, index) = self.locate(position as usize)?;
+        Ok(hex::encode(layer.oid(index)))
+    }
+
+    pub fn commit(&self, sha: &str) -> Result<Option<GraphCommit>> {
+        let Some(position) = self.position(sha) else {
+            return Ok(None);
+        };
+        let (layer, index) = self.locate(position)?;
+        let data = &layer.data;
+        let entry = layer.commit_data + index * COMMIT_DATA_LEN;
+
+        let mut parents: Vec<String> = Vec::new();
+        let first = read_u32(data, entry + HASH_LEN);
+        let second = read_u32(data, entry + HASH_LEN + 4);
+        if first != NO_PARENT {
+            parents.push(self.sha_at(first)?);
+        }
+        if second & EXTRA_EDGES != 0 {
+            let (start, end) = layer.extra_edges
+                .ok_or_else(|| anyhow!("commit-graph is missing the extra edges chunk"))?;
+            let mut edge = start + (second & !EXTRA_EDGES) as usize * 4;
+            loop {
+                if edge + 4 > end {
+                    bail!("commit-graph extra edges are out of range");
+                }
+                let value = read_u32(data, edge);
+                parents.push(self.sha_at(value & !EXTRA_EDGES)?);
+                if value & EXTRA_EDGES != 0 {
+                    break;
+                }
+                edge += 4;
+            }
+        } else if second != NO_PARENT {
+            parents.push(self.sha_at(second)?);
+        }
+
+        let high = read_u32(data, entry + HASH_LEN + 8);
+        let low = read_u32(data, entry + HASH_LEN + 12);
+        Ok(Some(GraphCommit {
+            tree: hex::encode(&data[entry..entry + HASH_LEN]),
+            parents,
+            time: (((high & 3) as i64) << 32) | low as i64,
+            generation: high >> 2,
+        }))
+    }
+
+    /// Changed-path filter of the commit, None when it was not computed
+    fn bloom_filter(&self, sha: &str) -> Option<&[u8]> {
+        let (layer, index) = self.locate(self.position(sha)?).ok()?;
+        let (offsets, start, end) = layer.bloom?;
+        let filter_end = read_u32(&layer.data, offsets + index * 4) as usize;
+        let filter_start = match index {
+            0 => 0,
+            _ => read_u32(&layer.data, offsets + (index - 1) * 4) as usize,
+        };
+        if filter_start >= filter_end || start + filter_end > end {
+            return None;
+        }
+        Some(&layer.data[start + filter_start..start + filter_end])
+    }
+
+    pub fn has_bloom_filters(&self) -> bool {
+        self.layers.iter().any(|layer| layer.bloom.is_some())
+    }
+
+    /// Whether the changed-path filter of the commit rules out changes of the paths against
+    /// its first parent
+    pub fn rules_out(&self, sha: &str, query: &BloomQuery) -> bool {
+        self.bloom_filter(sha).is_some_and(|filter| {
+            query.keys.iter().all(|keys| keys.iter().any(|key| !key.maybe_in(filter)))
+        })
+    }
+}
+
+/// Pathspecs prepared for changed-path filter lookups
+#[derive(Clone, Debug)]
+pub struct BloomQuery {
+    keys: Vec<Vec<BloomKey>>,
+}
+
+impl BloomQuery {
+    /// None without pathspecs or when one of them matches every path
+    pub fn new(paths: &[String]) -> Option<BloomQuery> {
+        if paths.is_empty() {
+            return None;
+        }
+        let keys = paths.iter().map(|path| pathspec_keys(path)).collect::<Option<Vec<_>>>()?;
+        Some(BloomQuery { keys })
+    }
+}
+
+/// Commits the refs point to, tags are peeled and other objects skipped
+fn ref_commits() -> Result<Vec<String>> {
+    let mut commits: Vec<String> = Vec::new();
+    for (_, mut sha) in list_refs("refs/")? {
+        loop {
+            match read_object(&sha)? {
+                Object::Tag(tag) => sha = tag.object,
+                Object::Commit(_) => {
+                    if !commits.contains(&sha) {
+                        commits.push(sha);
+                    }
+                    break;
+                }
+                _ => break,
+            }
+        }
+    }
+    Ok(commits)
+}
+
+/// Serialize a graph file of the commits (sorted by sha) on top of the base layers
+fn serialize(
+    commits: &[(String, Commit)],
+    bases: &[Layer],
+    generations: &[u32],
+    filters: Option<&[Vec<u8>]>
+) -> Result<Vec<u8>> {
+    let base_count = bases.last().map_or(0, |layer| layer.base_count + layer.count);
+    let positions: HashMap<&str, usize> = commits
+        .iter()
+        .enumerate()
+        .map(|(index, (sha, _))| (sha.as_str(), base_count + index))
+        .collect();
+    let position = |sha: &str| -> Result<u32> {
+        let found = positions.get(sha).copied().or_else(|| {
+            let oid = hex::decode(sha).ok()?;
+            bases
+                .iter()
+                .find_map(|layer| layer.find(&oid).map(|index| layer.base_count + index))
+        });
+        found
+            .map(|position| position as u32)
+            .ok_or_else(|| anyhow!("commit-graph is missing the parent {sha}"))
+    };
+
+    let mut fanout: Vec<u8> = Vec::new();
+    for byte in 0..=255u8 {
+        let below = commits.partition_point(|(sha, _)| {
+            u8::from_str_radix(&sha[..2], 16).is_ok_and(|first| first <= byte)
+        });
+        fanout.extend((below as u32).to_be_bytes());
+    }
+    let mut lookup: Vec<u8> = Vec::new();
+    let mut commit_data: Vec<u8> = Vec::new();
+    let mut extra_edges: Vec<u8> = Vec::new();
+    for ((sha, commit), generation) in commits.iter().zip(generations) {
+        lookup.extend(hex::decode(sha)?);
+        commit_data.extend(hex::decode(&commit.tree)?);
+        let parents = commit.parents
+            .iter()
+            .map(|parent| position(parent))
+            .collect::<Result<Vec<u32>>>()?;
+        let first = parents.first().copied().unwrap_or(NO_PARENT);
+        let second = match parents.len() {
+            0 | 1 => NO_PARENT,
+            2 => parents[1],
+            _ => {
+                let edge = (extra_edges.len() / 4) as u32 | EXTRA_EDGES;
+                for (index, parent) in parents[1..].iter().enumerate() {
+                    let last = if index == parents.len() - 2 { EXTRA_EDGES } else { 0 };
+                    extra_edges.extend((parent | last).to_be_bytes());
+                }
+                edge
+            }
+        };
+        commit_data.extend(first.to_be_bytes());
+        commit_data.extend(second.to_be_bytes());
+        let time = commit.committer.time as u64;
+        let high = (generation.min(&GENERATION_MAX) << 2) | ((time >> 32) as u32 & 3);
+        commit_data.extend(high.to_be_bytes());
+        commit_data.extend((time as u32).to_be_bytes());
+    }
+
+    let mut chunks: Vec<(&str, Vec<u8>)> = vec![
+        ("OIDF", fanout),
+        ("OIDL", lookup),
+        ("CDAT", commit_data),
+    ];
+    if !extra_edges.is_empty() {
+        chunks.push(("EDGE", extra_edges));
+    }
+    if let Some(filters) = filters {
+        let mut index: Vec<u8> = Vec::new();
+        let mut data: Vec<u8> = Vec::new();
+        for setting in [bloom::HASH_VERSION, bloom::NUM_HASHES, bloom::BITS_PER_ENTRY] {
+            data.extend(setting.to_be_bytes());
+        }
+        for filter in filters {
+            data.extend(filter);
+            index.extend(((data.len() - BLOOM_HEADER_LEN) as u32).to_be_bytes());
+        }
+        chunks.push(("BIDX", index));
+        chunks.push(("BDAT", data));
+    }
+    if !bases.is_empty() {
+        let mut hashes: Vec<u8> = Vec::new();
+        for layer in bases {
+            hashes.extend(hex::decode(&layer.hash)?);
+        }
+        chunks.push(("BASE", hashes));
+    }
+
+    let mut file: Vec<u8> = SIGNATURE.to_vec();
+    file.extend([1, 1, chunks.len() as u8, bases.len() as u8]);
+    let mut offset = HEADER_LEN + (chunks.len() + 1) * CHUNK_ENTRY_LEN;
+    for (id, contents) in &chunks {
+        file.extend(id.as_bytes());
+        file.extend((offset as u64).to_be_bytes());
+        offset += contents.len();
+    }
+    file.extend([0; 4]);
+    file.extend((offset as u64).to_be_bytes());
+    for (_, contents) in chunks {
+        file.extend(contents);
+    }
+    file.extend(hex::decode(get_hash_from_data(&file))?);
+    Ok(file)
+}
+
+/// Write the graph of all commits reachable from refs, with `split` only commits missing from
+/// the existing chain go to a new layer (merged with the layers above a twice bigger one)
+pub fn write(split: bool, changed_paths: bool) -> Result<String> {
+    let existing = CommitGraph::load()?;
+    let changed_paths = changed_paths || existing.as_ref().is_some_and(|g| g.has_bloom_filters());
+    let mut chain = if split { load_chain()? } else { Vec::new() };
+    let in_chain = |sha: &str| -> bool {
+        let Ok(oid) = hex::decode(sha) else {
+            return false;
+        };
+        chain.iter().any(|layer| layer.find(&oid).is_some())
+    };
+
+    // Commits missing from the chain, their ancestors in the chain are not needed
+    let mut commits: HashMap<String, Commit> = HashMap::new();
+    let mut stack: Vec<String> = ref_commits()?;
+    while let Some(sha) = stack.pop() {
+        if commits.contains_key(&sha) || in_chain(&sha) {
+            continue;
+        }
+        let commit = read_commit(&sha)?;
+        stack.extend(commit.parents.iter().cloned());
+        commits.insert(sha, commit);
+    }
+    if split && commits.is_empty() && !chain.is_empty() {
+        return Ok(String::new());
+    }
+
+    let mut kept = chain.len();
+    let mut count = commits.len();
+    while kept > 0 && chain[kept - 1].count <= 2 * count {
+        kept -= 1;
+        count += chain[kept].count;
+    }
+    for layer in chain.drain(kept..) {
+        for index in 0..layer.count {
+            if let Entry::Vacant(entry) = commits.entry(hex::encode(layer.oid(index))) {
+                let commit = read_commit(entry.key())?;
+                entry.insert(commit);
+            }
+        }
+    }
+
+    let mut commits: Vec<(String, Commit)> = commits.into_iter().collect();
+    commits.sort_by(|(one, _), (other, _)| one.cmp(other));
+    let mut walker = CommitWalker::new()?;
+    let generations = commits
+        .iter()
+        .map(|(sha, _)| walker.generation(sha))
+        .collect::<Result<Vec<u32>>>()?;
+    let filters = if changed_paths {
+        let mut filters: Vec<Vec<u8>> = Vec::new();
+        for (_, commit) in &commits {
+            let parent_tree = match commit.parents.first() {
+                Some(parent) => Some(read_commit(parent)?.tree),
+                None => None,
+            };
+            filters.push(bloom::changed_paths_filter(commit, parent_tree.as_deref())?);
+        }
+        Some(filters)
+    } else {
+        None
+    };
+    let file = serialize(&commits, &chain, &generations, filters.as_deref())?;
+    let hash = hex::encode(&file[file.len() - HASH_LEN..]);
+
+    let info = info_folder()?;
+    let folder = format!("{info}commit-graphs/");
+    if split {
+        fs::create_dir_all(&folder)?;
+        write_locked(&format!("{folder}graph-{hash}.graph"), &file)?;
+        let mut hashes: Vec<String> = chain.iter().map(|layer| layer.hash.clone()).collect();
+        hashes.push(hash);
+        let lines: String = hashes.iter().map(|hash| format!("{hash}\n")).collect();
+        write_locked(&format!("{folder}commit-graph-chain"), lines)?;
+        let _ = fs::remove_file(format!("{info}commit-graph"));
+
+        // Layers merged into the new one are no longer needed
+        let kept: HashSet<String> =
+            hashes.iter().map(|hash| format!("graph-{hash}.graph")).collect();
+        for entry in fs::read_dir(&folder)? {
+            let name = entry?.file_name().to_string_lossy().to_string();
+            if name.ends_with(".graph") && !kept.contains(&name) {
+                fs::remove_file(format!("{folder}{name}"))?;
+            }
+        }
+    } else {
+        write_locked(&format!("{info}commit-graph"), &file)?;
+        let _ = fs::remove_dir_all(&folder);
+    }
+    Ok(String::new())
+}
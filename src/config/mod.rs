@@ -0,0 +1,808 @@
+use crate::refs::current_branch;
+use crate::utility::fs_utility::{ find_root_folder, write_locked };
+
+use anyhow::{ anyhow, bail, Result };
+use std::env;
+use std::fs;
+use std::ops::Range;
+use std::path::{ Path, PathBuf };
+
+/// Nesting limit of `include.path` files
+const MAX_INCLUDE_DEPTH: usize = 10;
+
+/// Config file level, values of later levels override earlier ones
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Scope {
+    System,
+    Global,
+    Local,
+    Worktree,
+    /// File given with `--file`
+    File,
+}
+
+impl Scope {
+    /// Name shown by `--show-scope`
+    pub fn name(&self) -> &'static str {
+        match self {
+            Scope::System => "system",
+            Scope::Global => "global",
+            Scope::Local => "local",
+            Scope::Worktree => "worktree",
+            Scope::File => "command",
+        }
+    }
+}
+
+/// Value type used to check and print values (`--type`, `--bool`, `--int`)
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ValueType {
+    #[default]
+    Raw,
+    Bool,
+    Int,
+}
+
+impl ValueType {
+    pub fn from_name(name: &str) -> Result<ValueType> {
+        Ok(match name {
+            "bool" => ValueType::Bool,
+            "int" => ValueType::Int,
+            _ => bail!("unrecognized --type argument, {name}"),
+        })
+    }
+
+    /// Value in canonical form of the type
+    pub fn format(&self, value: Option<&str>) -> Result<String> {
+        Ok(match self {
+            ValueType::Raw => value.unwrap_or_default().to_string(),
+            ValueType::Bool => parse_bool(value)?.to_string(),
+            ValueType::Int => parse_int(value.unwrap_or_default())?.to_string(),
+        })
+    }
+}
+
+/// Operation of the `config` command
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ConfigAction {
+    Get,
+    GetAll,
+    Set,
+    Unset,
+    UnsetAll,
+    List,
+}
+
+/// Options of the `config` command
+#[derive(Clone, Debug)]
+pub struct ConfigOptions {
+    pub action: ConfigAction,
+    /// Read and write only this level (`--global`, `--local`, ...)
+    pub scope: Option<Scope>,
+    /// Read and write only this file (`--file`)
+    pub file: Option<String>,
+    pub show_origin: bool,
+    pub show_scope: bool,
+    /// Follow includes of the selected file (`--includes`)
+    pub includes: bool,
+    pub value_type: ValueType,
+}
+
+/// One `name=value` pair with the file it comes from
+#[derive(Clone, Debug)]
+pub struct ConfigEntry {
+    /// Canonical name: `section[.subsection].key` with lowercase section and key
+    pub name: String,
+    /// None for keys written without `=` (boolean true)
+    pub value: Option<String>,
+    pub origin: String,
+    pub scope: Scope,
+}
+
+/// All entries of the config files in order of precedence
+#[derive(Clone, Debug, Default)]
+pub struct Config {
+    entries: Vec<ConfigEntry>,
+}
+
+/// Key with its position in the file text
+#[derive(Debug)]
+struct RawEntry {
+    section: String,
+    subsection: Option<String>,
+    key: String,
+    value: Option<String>,
+    /// From the key to the end of its (last) line, without newline
+    span: Range<usize>,
+}
+
+/// Section header with its position in the file text
+#[derive(Debug)]
+struct RawSection {
+    section: String,
+    subsection: Option<String>,
+    span: Range<usize>,
+}
+
+#[derive(Debug, Default)]
+struct ParsedFile {
+    sections: Vec<RawSection>,
+    entries: Vec<RawEntry>,
+}
+
+/// Parse boolean value (key without value is true)
+pub fn parse_bool(value: Option<&str>) -> Result<bool> {
+    match value.map(str::to_lowercase).as_deref() {
+        None | Some("true") | Some("yes") | Some("on") => Ok(true),
+        Some("false") | Some("no") | Some("off") | Some("") => Ok(false),
+        Some(text) => {
+            parse_int(text)
+                .map(|number| number != 0)
+                .map_err(|_| anyhow!("bad boolean config value '{text}'"))
+        }
+    }
+}
+
+/// Parse integer with optional `k`, `m` or `g` suffix
+pub fn parse_int(text: &str) -> Result<i64> {
+    let text = text.trim();
+    let factor: i64 = match text.chars().last().map(|c| c.to_ascii_lowercase()) {
+        Some('k') => 1 << 10,
+        Some('m') => 1 << 20,
+        Some('g') => 1 << 30,
+        _ => 1,
+    };
+    let digits = if factor == 1 { text } else { &text[..text.len() - 1] };
+    digits
+        .parse::<i64>()
+        .ok()
+        .and_then(|number| number.checked_mul(factor))
+        .ok_or_else(|| anyhow!("bad numeric config value '{text}'"))
+}
+
+/// Split `section[.subsection].key` into its parts, section and key are lowercased
+fn split_name(name: &str) -> Result<(String, Option<String>, String)> {
+    let (Some(first), Some(last)) = (name.find('.'), name.rfind('.')) else {
+        bail!("key does not contain a section: {name}");
+    };
+    let section = &name[..first];
+    let key = &name[last + 1..];
+    let valid_key = key.starts_with(|c: char| c.is_ascii_alphabetic()) &&
+        key.chars().all(|c| c.is_ascii_alphanumeric() || c == '-');
+    let valid_section =
+        !section.is_empty() && section.chars().all(|c| c.is_ascii_alphanumeric() || c == '-');
+    if !valid_key || !valid_section {
+        bail!("invalid key: {name}");
+    }
+    let subsection = (first != last).then(|| name[first + 1..last].to_string());
+    Ok((section.to_lowercase(), subsection, key.to_lowercase()))
+}
+
+fn canonical_name(section: &str, subsection: Option<&str>, key: &str) -> String {
+    match subsection {
+        Some(subsection) => format!("{section}.{subsection}.{key}"),
+        None => format!("{section}.{key}"),
+    }
+}
+
+/// Parse `[section]`, `[section "subsection"]` or legacy `[section.subsection]` at `pos`,
+/// returns the position after `]`
+fn parse_section_header(bytes: &[u8], mut pos: usize) -> Option<(String, Option<String>, usize)> {
+    pos += 1;
+    let start = pos;
+    while pos < bytes.len() && (bytes[pos].is_ascii_alphanumeric() || b"-.".contains(&bytes[pos])) {
+        pos += 1;
+    }
+    let name = String::from_utf8_lossy(&bytes[start..pos]).to_lowercase();
+    if name.is_empty() {
+        return None;
+    }
+
+    match bytes.get(pos)? {
+        b']' => {
+            return Some(match name.split_once('.') {
+                Some((section, subsection)) => {
+                    (section.to_string(), Some(subsection.to_string()), pos + 1)
+                }
+                None => (name, None, pos + 1),
+            });
+        }
+        b' ' | b'\t' if !name.contains('.') => {}
+        _ => {
+            return None;
+        }
+    }
+
+    // Quoted subsection is case sensitive, only `\"` and `\\` are escaped
+    while bytes.get(pos) == Some(&b' ') || bytes.get(pos) == Some(&b'\t') {
+        pos += 1;
+    }
+    if bytes.get(pos) != Some(&b'"') {
+        return None;
+    }
+    pos += 1;
+    let mut subsection: Vec<u8> = Vec::new();
+    loop {
+        match *bytes.get(pos)? {
+            b'"' => {
+                break;
+            }
+            b'\n' => {
+                return None;
+            }
+            b'\\' => {
+                pos += 1;
+                subsection.push(*bytes.get(pos)?);
+            }
+            c => subsection.push(c),
+        }
+        pos += 1;
+    }
+    if bytes.get(pos + 1) != Some(&b']') {
+        return None;
+    }
+    Some((name, Some(String::from_utf8(subsection).ok()?), pos + 2))
+}
+
+/// Parse value after `=` up to the end of line (comments and surrounding whitespace dropped,
+/// quotes and escapes resolved, escaped newlines join lines)
+fn parse_value(bytes: &[u8], pos: &mut usize, line: &mut usize) -> Option<String> {
+    let mut value: Vec<u8> = Vec::new();
+    let mut quoted = false;
+    let mut spaces = 0;
+    while *pos < bytes.len() && bytes[*pos] != b'\n' {
+        let c = bytes[*pos];
+        *pos += 1;
+        if !quoted && (c == b'#' || c == b';') {
+            while *pos < bytes.len() && bytes[*pos] != b'\n' {
+                *pos += 1;
+            }
+            break;
+        }
+        if !quoted && c.is_ascii_whitespace() {
+            // Inner whitespace is kept as spaces, leading and trailing is dropped
+            if !value.is_empty() {
+                spaces += 1;
+            }
+            continue;
+        }
+        value.extend(std::iter::repeat_n(b' ', spaces));
+        spaces = 0;
+        match c {
+            b'"' => {
+                quoted = !quoted;
+            }
+            b'\\' => {
+                let escaped = *bytes.get(*pos)?;
+                *pos += 1;
+                match escaped {
+                    b'\n' => {
+                        *line += 1;
+                    }
+                    b'n' => value.push(b'\n'),
+                    b't' => value.push(b'\t'),
+                    b'b' => value.push(8),
+                    b'"' | b'\\' => value.push(escaped),
+                    _ => {
+                        return None;
+                    }
+                }
+            }
+            _ => value.push(c),
+        }
+    }
+    if quoted {
+        return None;
+    }
+    String::from_utf8(value).ok()
+}
+
+/// Parse config file text, keeping positions of sections and keys
+fn parse_text(text: &str, origin: &str) -> Result<ParsedFile> {
+    let bytes = text.as_bytes();
+    let mut parsed = ParsedFile::default();
+    let mut current: Option<(String, Option<String>)> = None;
+    let mut pos = 0;
+    let mut line = 1;
+    let error = |line: usize| anyhow!("bad config line {line} in file {origin}");
+
+    while pos < bytes.len() {
+        match bytes[pos] {
+            b'\n' => {
+                line += 1;
+                pos += 1;
+            }
+            c if c.is_ascii_whitespace() => {
+                pos += 1;
+            }
+            b'#' | b';' => {
+                while pos < bytes.len() && bytes[pos] != b'\n' {
+                    pos += 1;
+                }
+            }
+            b'[' => {
+                let (section, subsection, end) = parse_section_header(bytes, pos).ok_or_else(||
+                    error(line)
+                )?;
+                parsed.sections.push(RawSection {
+                    section: section.clone(),
+                    subsection: subsection.clone(),
+                    span: pos..end,
+                });
+                current = Some((section, subsection));
+                pos = end;
+            }
+            c if c.is_ascii_alphabetic() => {
+                let (section, subsection) = current.clone().ok_or_else(|| error(line))?;
+                let start = pos;
+                let is_key_char = |c: u8| c.is_ascii_alphanumeric() || c == b'-';
+                while pos < bytes.len() && is_key_char(bytes[pos]) {
+                    pos += 1;
+                }
+                let key = text[start..pos].to_lowercase();
+                while pos < bytes.len() && (bytes[pos] == b' ' || bytes[pos] == b'\t') {
+                    pos += 1;
+                }
+
+                let value = match bytes.get(pos) {
+                    Some(b'=') => {
+                        pos += 1;
+                        Some(parse_value(bytes, &mut pos, &mut line).ok_or_else(|| error(line))?)
+                    }
+                    None | Some(b'\n') | Some(b'\r') | Some(b'#') | Some(b';') => {
+                        while pos < bytes.len() && bytes[pos] != b'\n' {
+                            pos += 1;
+                        }
+                        None
+                    }
+                    _ => {
+                        return Err(error(line));
+                    }
+                };
+                parsed.entries.push(RawEntry { section, subsection, key, value, span: start..pos });
+            }
+            _ => {
+                return Err(error(line));
+            }
+        }
+    }
+    Ok(parsed)
+}
+
+/// Match glob pattern (`*` and `?` stay inside one directory, `**` crosses directories)
+fn glob_match(pattern: &[u8], text: &[u8]) -> bool {
+    match pattern.split_first() {
+        None => text.is_empty(),
+        Some((b'*', rest)) if rest.first() == Some(&b'*') => {
+            let rest = &rest[1..];
+            // `**/` also matches no directory at all
+            if rest.strip_prefix(b"/").is_some_and(|after| glob_match(after, text)) {
+                return true;
+            }
+            (0..=text.len()).any(|i| glob_match(rest, &text[i..]))
+        }
+        Some((b'*', rest)) => {
+            (0..=text.len())
+                .take_while(|&i| i == 0 || text[i - 1] != b'/')
+                .any(|i| glob_match(rest, &text[i..]))
+        }
+        Some((b'?', rest)) => {
+            text.first().is_some_and(|&c| c != b'/') && glob_match(rest, &text[1..])
+        }
+        Some((c, rest)) => text.first() == Some(c) && glob_match(rest, &text[1..]),
+    }
+}
+
+fn home_dir() -> String {
+    env::var("HOME").unwrap_or_default()
+}
+
+/// Path of included file, relative paths start from the including file
+fn include_path(target: &str, including: &Path) -> PathBuf {
+    if let Some(rest) = target.strip_prefix("~/") {
+        return Path::new(&home_dir()).join(rest);
+    }
+    match including.parent() {
+        Some(folder) if !Path::new(target).is_absolute() => folder.join(target),
+        _ => PathBuf::from(target),
+    }
+}
+
+/// Check condition of `[includeIf "<condition>"]`
+fn include_condition(condition: &str, including: &Path) -> Result<bool> {
+    if let Some(pattern) = condition.strip_prefix("onbranch:") {
+        let pattern = if pattern.ends_with('/') {
+            format!("{pattern}**")
+        } else {
+            pattern.to_string()
+        };
+        return Ok(
+            current_branch()
+                .ok()
+                .flatten()
+                .is_some_and(|branch| glob_match(pattern.as_bytes(), branch.as_bytes()))
+        );
+    }
+
+    let (pattern, ignore_case) = match condition.strip_prefix("gitdir:") {
+        Some(pattern) => (pattern, false),
+        None =>
+            match condition.strip_prefix("gitdir/i:") {
+                Some(pattern) => (pattern, true),
+                None => {
+                    return Ok(false);
+                }
+            }
+    };
+    let Some(git_dir) = find_root_folder()
+        .ok()
+        .and_then(|root| fs::canonicalize(root + ".git").ok()) else {
+        return Ok(false);
+    };
+
+    // Pattern is anchored at home, the including file or anywhere
+    let mut pattern = if let Some(rest) = pattern.strip_prefix("~/") {
+        format!("{}/{rest}", home_dir())
+    } else if let Some(rest) = pattern.strip_prefix("./") {
+        let folder = including.parent().and_then(|folder| fs::canonicalize(folder).ok());
+        format!("{}/{rest}", folder.unwrap_or_default().display())
+    } else if pattern.starts_with('/') {
+        pattern.to_string()
+    } else {
+        format!("**/{pattern}")
+    };
+    if pattern.ends_with('/') {
+        pattern.push_str("**");
+    }
+    let mut git_dir = git_dir.to_string_lossy().to_string();
+    if ignore_case {
+        pattern = pattern.to_lowercase();
+        git_dir = git_dir.to_lowercase();
+    }
+    Ok(glob_match(pattern.as_bytes(), git_dir.as_bytes()))
+}
+
+/// File of the scope (used for writing and for reading a single scope)
+pub fn scope_file(scope: Scope) -> Result<String> {
+    Ok(match scope {
+        Scope::System => env::var("GIT_CONFIG_SYSTEM").unwrap_or("/etc/gitconfig".to_string()),
+        Scope::Global => {
+            env::var("GIT_CONFIG_GLOBAL").unwrap_or_else(|_| format!("{}/.gitconfig", home_dir()))
+        }
+        Scope::Local => find_root_folder()? + ".git/config",
+        Scope::Worktree => find_root_folder()? + ".git/config.worktree",
+        Scope::File => bail!("File scope has no default file"),
+    })
+}
+
+impl Config {
+    /// Read system, global, repository and worktree config
+    pub fn load() -> Result<Config> {
+        let mut config = Config::default();
+        if env::var_os("GIT_CONFIG_NOSYSTEM").is_none() {
+            config.read_file(Path::new(&scope_file(Scope::System)?), Scope::System, true, 0)?;
+        }
+        if env::var_os("GIT_CONFIG_GLOBAL").is_none() {
+            let xdg = env::var("XDG_CONFIG_HOME").unwrap_or_else(|_| home_dir() + "/.config");
+            config.read_file(&Path::new(&xdg).join("git/config"), Scope::Global, true, 0)?;
+        }
+        config.read_file(Path::new(&scope_file(Scope::Global)?), Scope::Global, true, 0)?;
+        if find_root_folder().is_ok() {
+            config.read_file(Path::new(&scope_file(Scope::Local)?), Scope::Local, true, 0)?;
+            if config.get_bool("extensions.worktreeConfig")?.unwrap_or(false) {
+                let worktree = scope_file(Scope::Worktree)?;
+                config.read_file(Path::new(&worktree), Scope::Worktree, true, 0)?;
+            }
+        }
+        Ok(config)
+    }
+
+    /// Read one config file (missing file gives empty config)
+    pub fn load_file(path: &str, scope: Scope, includes: bool) -> Result<Config> {
+        let mut config = Config::default();
+        config.read_file(Path::new(path), scope, includes, 0)?;
+        Ok(config)
+    }
+
+    /// Add entries of the file, included files are read in place of their include entry
+    fn read_file(&mut self, path: &Path, scope: Scope, includes: bool, depth: usize) -> Result<()> {
+        let Ok(text) = fs::read_to_string(path) else {
+            return Ok(());
+        };
+        let origin = path.to_string_lossy();
+        let origin = origin.strip_prefix("./").unwrap_or(&origin).to_string();
+
+        for raw in parse_text(&text, &origin)?.entries {
+            let included = match (raw.section.as_str(), &raw.subsection, raw.key.as_str()) {
+                ("include", None, "path") => true,
+                ("includeif", Some(condition), "path") => include_condition(condition, path)?,
+                _ => false,
+            };
+            let target = raw.value.clone().filter(|_| includes && included);
+            self.entries.push(ConfigEntry {
+                name: canonical_name(&raw.section, raw.subsection.as_deref(), &raw.key),
+                value: raw.value,
+                origin: origin.clone(),
+                scope,
+            });
+
+            if let Some(target) = target {
+                if depth >= MAX_INCLUDE_DEPTH {
+                    bail!(
+                        "exceeded maximum include depth ({MAX_INCLUDE_DEPTH}) \
+                         while including {target} from {origin}"
+                    );
+                }
+                self.read_file(&include_path(&target, path), scope, true, depth + 1)?;
+            }
+        }
+        Ok(())
+    }
+
+    pub fn entries(&self) -> &[ConfigEntry] {
+        &self.entries
+    }
+
+    /// All entries of the key in order
+    pub fn get_all(&self, name: &str) -> Result<Vec<&ConfigEntry>> {
+        let (section, subsection, key) = split_name(name)?;
+        let name = canonical_name(&section, subsection.as_deref(), &key);
+        Ok(
+            self.entries
+                .iter()
+                .filter(|entry| entry.name == name)
+                .collect()
+        )
+    }
+
+    /// Last entry of the key
+    pub fn get_entry(&self, name: &str) -> Result<Option<&ConfigEntry>> {
+        Ok(self.get_all(name)?.pop())
+    }
+
+    /// Last value of the key (empty for keys without value)
+    pub fn get(&self, name: &str) -> Result<Option<String>> {
+        Ok(self.get_entry(name)?.map(|entry| entry.value.clone().unwrap_or_default()))
+    }
+
+    pub fn get_bool(&self, name: &str) -> Result<Option<bool>> {
+        self.get_entry(name)?
+            .map(|entry| parse_bool(entry.value.as_deref()))
+            .transpose()
+    }
+}
+
+/// Value as written to file, quoted when whitespace or comment characters would be lost
+fn quote_value(value: &str) -> String {
+    let needs_quotes = value.starts_with(char::is_whitespace) ||
+        value.ends_with(char::is_whitespace) ||
+        value.contains(['#', ';']);
+    let mut escaped = String::new();
+    for c in value.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '\t' => escaped.push_str("\\t"),
+            _ => escaped.push(c),
+        }
+    }
+    if needs_quotes { format!("\"{escaped}\"") } else { escaped }
+}
+
+/// Entries of the key in the file text
+fn find_entries<'a>(parsed: &'a ParsedFile, name: &str) -> Result<Vec<&'a RawEntry>> {
+    let (section, subsection, key) = split_name(name)?;
+    Ok(
+        parsed.entries
+            .iter()
+            .filter(|raw| raw.section == section && raw.subsection == subsection && raw.key == key)
+            .collect()
+    )
+}
+
+/// Start of the line containing `pos`
+fn line_start(text: &str, pos: usize) -> usize {
+    text[..pos].rfind('\n').map_or(0, |i| i + 1)
+}
+
+/// Header line text of the section
+fn section_header(section: &str, subsection: Option<&str>) -> String {
+    match subsection {
+        Some(subsection) => {
+            let escaped = subsection.replace('\\', "\\\\").replace('"', "\\\"");
+            format!("[{section} \"{escaped}\"]")
+        }
+        None => format!("[{section}]"),
+    }
+}
+
+/// Split `section[.subsection]` name of a whole section
+fn split_section_name(name: &str) -> (String, Option<String>) {
+    match name.split_once('.') {
+        Some((section, subsection)) => (section.to_lowercase(), Some(subsection.to_string())),
+        None => (name.to_lowercase(), None),
+    }
+}
+
+/// Set the key in config file, keeping the rest of the file (comments, order) as is
+pub fn set_value(path: &str, name: &str, value: &str) -> Result<()> {
+    let text = fs::read_to_string(path).unwrap_or_default();
+    let parsed = parse_text(&text, path)?;
+    let (section, subsection, _) = split_name(name)?;
+    let key = &name[name.rfind('.').unwrap_or_default() + 1..];
+    let line = format!("{key} = {}", quote_value(value));
+
+    let existing = find_entries(&parsed, name)?;
+    let new_text = match existing.as_slice() {
+        [entry] => format!("{}{line}{}", &text[..entry.span.start], &text[entry.span.end..]),
+        [_, _, ..] => {
+            bail!("{name} has multiple values, cannot overwrite them with a single value");
+        }
+        [] => {
+            // New key goes after the last key of its section (or the header)
+            let in_section = |s: &str, sub: &Option<String>| s == section && *sub == subsection;
+            let position = parsed.entries
+                .iter()
+                .filter(|raw| in_section(&raw.section, &raw.subsection))
+                .map(|raw| raw.span.end)
+                .chain(
+                    parsed.sections
+                        .iter()
+                        .filter(|raw| in_section(&raw.section, &raw.subsection))
+                        .map(|raw| raw.span.end)
+                )
+                .max();
+            match position {
+                Some(position) => {
+                    format!("{}\n\t{line}{}", &text[..position], &text[position..])
+                }
+                None => {
+                    let header = section_header(&section, subsection.as_deref());
+                    let separator = if text.is_empty() || text.ends_with('\n') { "" } else { "\n" };
+                    format!("{text}{separator}{header}\n\t{line}\n")
+                }
+            }
+        }
+    };
+    write_locked(path, &new_text)
+}
+
+/// Remove the key from config file (all values with `all`), sections left empty are removed
+pub fn unset_value(path: &str, name: &str, all: bool) -> Result<()> {
+    let mut text = fs::read_to_string(path).map_err(|_| anyhow!("key '{name}' not found"))?;
+    let parsed = parse_text(&text, path)?;
+    let existing = find_entries(&parsed, name)?;
+    if existing.is_empty() {
+        bail!("key '{name}' not found");
+    }
+    if existing.len() > 1 && !all {
+        bail!("{name} has multiple values");
+    }
+
+    // Remove from the end, so earlier positions stay valid
+    let mut touched_sections: Vec<usize> = Vec::new();
+    for entry in existing.iter().rev() {
+        let start = line_start(&text, entry.span.start);
+        let range = if text[start..entry.span.start].trim().is_empty() {
+            start..(entry.span.end + 1).min(text.len())
+        } else {
+            entry.span.clone()
+        };
+        text.replace_range(range, "");
+        if let Some(index) = parsed.sections.iter().rposition(|s| s.span.end <= entry.span.start) {
+            touched_sections.push(index);
+        }
+    }
+
+    // Drop headers of sections that have nothing left (not even comments)
+    let parsed = parse_text(&text, path)?;
+    touched_sections.sort_unstable();
+    touched_sections.dedup();
+    for &index in touched_sections.iter().rev() {
+        let Some(section) = parsed.sections.get(index) else {
+            continue;
+        };
+        let body_end = parsed.sections.get(index + 1).map_or(text.len(), |next| next.span.start);
+        if text[section.span.end..body_end].trim().is_empty() {
+            let start = line_start(&text, section.span.start);
+            text.replace_range(start..body_end, "");
+        }
+    }
+    write_locked(path, &text)
+}
+
+
+/// Give all sections with the name a new name, returns whether any was found
+pub fn rename_section(path: &str, old: &str, new: &str) -> Result<bool> {
+    let Ok(mut text) = fs::read_to_string(path) else {
+        return Ok(false);
+    };
+    let parsed = parse_text(&text, path)?;
+    let (section, subsection) = split_section_name(old);
+    let (new_section, new_subsection) = split_section_name(new);
+    let header = section_header(&new_section, new_subsection.as_deref());
+
+    let matching: Vec<&RawSection> = parsed.sections
+        .iter()
+        .filter(|raw| raw.section == section && raw.subsection == subsection)
+        .collect();
+    for raw in matching.iter().rev() {
+        text.replace_range(raw.span.clone(), &header);
+    }
+    if !matching.is_empty() {
+        write_locked(path, &text)?;
+    }
+    Ok(!matching.is_empty())
+}
+
+/// Remove all sections with the name and their keys, returns whether any was found
+pub fn remove_section(path: &str, name: &str) -> Result<bool> {
+    let Ok(mut text) = fs::read_to_string(path) else {
+        return Ok(false);
+    };
+    let parsed = parse_text(&text, path)?;
+    let (section, subsection) = split_section_name(name);
+
+    let mut found = false;
+    for (index, raw) in parsed.sections.iter().enumerate().rev() {
+        if raw.section == section && raw.subsection == subsection {
+            let end = parsed.sections.get(index + 1).map_or(text.len(), |next| next.span.start);
+            text.replace_range(line_start(&text, raw.span.start)..end, "");
+            found = true;
+        }
+    }
+    if found {
+        write_locked(path, &text)?;
+    }
+    Ok(found)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_config_text() {
+        let text = "# comment\n[core]\n\tbare\n\tname = \" a \\\"b\\\" \" ; c\n\tlong = x \\\n  y\n\
+                    [remote \"Origin\"]\n\turl=u\n[Legacy.Sub]\nKey = 1\n";
+        let parsed = parse_text(text, "test").unwrap();
+        let values: Vec<(String, Option<String>)> = parsed.entries
+            .iter()
+            .map(|raw| {
+                let name = canonical_name(&raw.section, raw.subsection.as_deref(), &raw.key);
+                (name, raw.value.clone())
+            })
+            .collect();
+        assert_eq!(
+            values,
+            [
+                ("core.bare".to_string(), None),
+                ("core.name".to_string(), Some(" a \"b\" ".to_string())),
+                ("core.long".to_string(), Some("x   y".to_string())),
+                ("remote.Origin.url".to_string(), Some("u".to_string())),
+                ("legacy.sub.key".to_string(), Some("1".to_string())),
+            ]
+        );
+        assert_eq!(&text[parsed.entries[0].span.clone()], "bare");
+        assert!(parse_text("[core]\n\tname = \"open\n", "test").is_err());
+        assert!(parse_text("key = outside\n", "test").is_err());
+    }
+
+    #[test]
+    fn typed_values_and_globs() {
+        assert!(parse_bool(None).unwrap());
+        assert!(!parse_bool(Some("Off")).unwrap());
+        assert!(parse_bool(Some("2")).unwrap());
+        assert!(parse_bool(Some("maybe")).is_err());
+        assert_eq!(parse_int("10k").unwrap(), 10240);
+        assert_eq!(parse_int("-1g").unwrap(), -(1 << 30));
+        assert!(parse_int("1x").is_err());
+        assert_eq!(quote_value(" x#"), "\" x#\"");
+
+        assert!(glob_match(b"**/work/**", b"/home/me/work/repo/.git"));
+        assert!(glob_match(b"/home/*/.git", b"/home/me/.git"));
+        assert!(!glob_match(b"/home/*/.git", b"/home/me/sub/.git"));
+        assert!(glob_match(b"feature/**", b"feature/a/b"));
+    }
+}
@@ -0,0 +1,1041 @@
+use anyhow::{ bail, Result };
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::ops::Range;
+
+/// Histogram diff ignores lines repeated more often than this
+const MAX_CHAIN_LEN: usize = 64;
+
+// Limits of the Myers search (from git's xdiff)
+const MAX_EQUAL_LIMIT: usize = 1024;
+const SIMILAR_SCAN_WINDOW: usize = 100;
+const KEEP_FREQUENT_RUN: usize = 4;
+const MAX_COST_MIN: usize = 256;
+const HEURISTIC_MIN_COST: isize = 256;
+const SNAKE_COUNT: isize = 20;
+const K_HEURISTIC: isize = 4;
+
+// Weights of the indent heuristic choosing where to slide a group of changed lines (from git)
+const MAX_INDENT: i32 = 200;
+const MAX_BLANKS: i32 = 20;
+const INDENT_HEURISTIC_MAX_SLIDING: usize = 100;
+const START_OF_FILE_PENALTY: i32 = 1;
+const END_OF_FILE_PENALTY: i32 = 21;
+const TOTAL_BLANK_WEIGHT: i32 = -30;
+const POST_BLANK_WEIGHT: i32 = 6;
+const RELATIVE_INDENT_PENALTY: i32 = -4;
+const RELATIVE_INDENT_WITH_BLANK_PENALTY: i32 = 10;
+const RELATIVE_OUTDENT_PENALTY: i32 = 24;
+const RELATIVE_OUTDENT_WITH_BLANK_PENALTY: i32 = 17;
+const RELATIVE_DEDENT_PENALTY: i32 = 23;
+const RELATIVE_DEDENT_WITH_BLANK_PENALTY: i32 = 17;
+const INDENT_WEIGHT: i32 = 60;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DiffAlgorithm {
+    Myers,
+    Patience,
+    Histogram,
+}
+
+impl DiffAlgorithm {
+    /// Parse algorithm name as accepted by `--diff-algorithm`
+    pub fn from_name(name: &str) -> Result<DiffAlgorithm> {
+        match name {
+            "myers" | "default" | "minimal" => Ok(DiffAlgorithm::Myers),
+            "patience" => Ok(DiffAlgorithm::Patience),
+            "histogram" => Ok(DiffAlgorithm::Histogram),
+            _ => bail!("Unknown diff algorithm '{name}'"),
+        }
+    }
+}
+
+/// Single line of edit script
+/// Equal(<old line>, <new line>), Delete(<old line>), Insert(<new line>)
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Edit {
+    Equal(usize, usize),
+    Delete(usize),
+    Insert(usize),
+}
+
+/// Split data into lines (each line keeps its '\n')
+pub fn split_lines(data: &[u8]) -> Vec<&[u8]> {
+    data.split_inclusive(|c| *c == b'\n').collect()
+}
+
+/// Line data used for comparison (all whitespace removed if requested)
+fn comparison_key(line: &[u8], ignore_whitespace: bool) -> Cow<'_, [u8]> {
+    if ignore_whitespace {
+        Cow::Owned(
+            line
+                .iter()
+                .filter(|c| !c.is_ascii_whitespace())
+                .copied()
+                .collect()
+        )
+    } else {
+        Cow::Borrowed(line)
+    }
+}
+
+/// Compute edit script that turns `old` lines into `new` lines
+pub fn diff_lines(old: &[&[u8]], new: &[&[u8]], algorithm: DiffAlgorithm) -> Vec<Edit> {
+    diff_lines_with(old, new, algorithm, false)
+}
+
+/// Same as [diff_lines] with optional whitespace insensitive comparison
+pub fn diff_lines_with(
+    old: &[&[u8]],
+    new: &[&[u8]],
+    algorithm: DiffAlgorithm,
+    ignore_whitespace: bool
+) -> Vec<Edit> {
+    // Intern lines, so algorithms compare integers
+    let mut ids: HashMap<Vec<u8>, u32> = HashMap::new();
+    let mut intern = |line: &[u8]| -> u32 {
+        let next_id = ids.len() as u32;
+        *ids.entry(comparison_key(line, ignore_whitespace).into_owned()).or_insert(next_id)
+    };
+    let a: Vec<u32> = old
+        .iter()
+        .map(|l| intern(l))
+        .collect();
+    let b: Vec<u32> = new
+        .iter()
+        .map(|l| intern(l))
+        .collect();
+
+    // Mark changed lines, then slide the runs of them the way git does
+    let mut old_side = ChangedLines::new(old, &a);
+    let mut new_side = ChangedLines::new(new, &b);
+    let (old_range, new_range) = (0..a.len(), 0..b.len());
+    match algorithm {
+        DiffAlgorithm::Myers => myers(&mut old_side, &mut new_side, old_range, new_range),
+        DiffAlgorithm::Patience => patience(&mut old_side, &mut new_side, old_range, new_range),
+        DiffAlgorithm::Histogram => histogram(&mut old_side, &mut new_side, old_range, new_range),
+    }
+    old_side.compact(&new_side);
+    new_side.compact(&old_side);
+
+    // Turn changed lines into edit script (deletions go before insertions)
+    let mut edits: Vec<Edit> = Vec::with_capacity(a.len().max(b.len()));
+    let (mut i, mut j) = (0, 0);
+    while i < a.len() || j < b.len() {
+        if old_side.changed[i] {
+            edits.push(Edit::Delete(i));
+            i += 1;
+        } else if new_side.changed[j] {
+            edits.push(Edit::Insert(j));
+            j += 1;
+        } else {
+            edits.push(Edit::Equal(i, j));
+            i += 1;
+            j += 1;
+        }
+    }
+
+    edits
+}
+
+/// Run of changed lines `start..end` (empty between two unchanged lines)
+#[derive(Clone, Copy, Debug)]
+struct Group {
+    start: usize,
+    end: usize,
+}
+
+/// Indent measured around a split of the lines (-1 stands for blank lines or none at all)
+struct SplitMeasurement {
+    end_of_file: bool,
+    indent: i32,
+    pre_blank: i32,
+    pre_indent: i32,
+    post_blank: i32,
+    post_indent: i32,
+}
+
+/// Score of a split, lower is better
+#[derive(Clone, Copy, Default)]
+struct SplitScore {
+    effective_indent: i32,
+    penalty: i32,
+}
+
+impl SplitScore {
+    /// Add score of the split (same weights as git's indent heuristic)
+    fn add(&mut self, m: &SplitMeasurement) {
+        if m.pre_indent == -1 && m.pre_blank == 0 {
+            self.penalty += START_OF_FILE_PENALTY;
+        }
+        if m.end_of_file {
+            self.penalty += END_OF_FILE_PENALTY;
+        }
+
+        let post_blank = if m.indent == -1 { 1 + m.post_blank } else { 0 };
+        let total_blank = m.pre_blank + post_blank;
+        self.penalty += TOTAL_BLANK_WEIGHT * total_blank + POST_BLANK_WEIGHT * post_blank;
+
+        let indent = if m.indent != -1 { m.indent } else { m.post_indent };
+        let any_blanks = total_blank != 0;
+        self.effective_indent += indent;
+
+        if indent == -1 || m.pre_indent == -1 || indent == m.pre_indent {
+            return;
+        }
+        self.penalty += match (indent > m.pre_indent, any_blanks) {
+            (true, true) => RELATIVE_INDENT_WITH_BLANK_PENALTY,
+            (true, false) => RELATIVE_INDENT_PENALTY,
+            _ if m.post_indent != -1 && m.post_indent > indent => {
+                if any_blanks {
+                    RELATIVE_OUTDENT_WITH_BLANK_PENALTY
+                } else {
+                    RELATIVE_OUTDENT_PENALTY
+                }
+            }
+            (false, true) => RELATIVE_DEDENT_WITH_BLANK_PENALTY,
+            (false, false) => RELATIVE_DEDENT_PENALTY,
+        };
+    }
+
+    /// Negative when this split is better than the other one
+    fn compare(&self, other: &SplitScore) -> i32 {
+        let indents = (self.effective_indent > other.effective_indent) as i32 -
+            ((self.effective_indent < other.effective_indent) as i32);
+        INDENT_WEIGHT * indents + self.penalty - other.penalty
+    }
+}
+
+/// Indent of the line with tabs to multiples of 8 (-1 for lines of whitespace only)
+fn line_indent(line: &[u8]) -> i32 {
+    let mut indent = 0;
+    for c in line {
+        if !matches!(c, b' ' | b'\t' | b'\n' | b'\r') {
+            return indent;
+        }
+        match c {
+            b' ' => indent += 1,
+            b'\t' => indent += 8 - indent % 8,
+            _ => {}
+        }
+        if indent >= MAX_INDENT {
+            return MAX_INDENT;
+        }
+    }
+    -1
+}
+
+/// Lines of one side of the diff with flags of the changed ones (an extra last flag stays unset)
+struct ChangedLines<'a> {
+    lines: &'a [&'a [u8]],
+    ids: &'a [u32],
+    changed: Vec<bool>,
+}
+
+impl<'a> ChangedLines<'a> {
+    /// All lines start as unchanged
+    fn new(lines: &'a [&'a [u8]], ids: &'a [u32]) -> ChangedLines<'a> {
+        ChangedLines { lines, ids, changed: vec![false; ids.len() + 1] }
+    }
+
+    /// Mark the lines of the range changed
+    fn mark(&mut self, range: Range<usize>) {
+        self.changed[range].fill(true);
+    }
+
+    /// Group at the start of the lines
+    fn first_group(&self) -> Group {
+        let mut end = 0;
+        while self.changed[end] {
+            end += 1;
+        }
+        Group { start: 0, end }
+    }
+
+    /// Move to the group after the next unchanged line, false at the end
+    fn next_group(&self, group: &mut Group) -> bool {
+        if group.end == self.ids.len() {
+            return false;
+        }
+        group.start = group.end + 1;
+        group.end = group.start;
+        while self.changed[group.end] {
+            group.end += 1;
+        }
+        true
+    }
+
+    /// Move to the group before the previous unchanged line, false at the start
+    fn previous_group(&self, group: &mut Group) -> bool {
+        if group.start == 0 {
+            return false;
+        }
+        group.end = group.start - 1;
+        group.start = group.end;
+        while group.start > 0 && self.changed[group.start - 1] {
+            group.start -= 1;
+        }
+        true
+    }
+
+    /// Shift the group one line down when its first line equals the line after it,
+    /// a group following right after gets merged
+    fn slide_down(&mut self, group: &mut Group) -> bool {
+        if group.end == self.ids.len() || self.ids[group.start] != self.ids[group.end] {
+            return false;
+        }
+        self.changed[group.start] = false;
+        self.changed[group.end] = true;
+        group.start += 1;
+        group.end += 1;
+        while self.changed[group.end] {
+            group.end += 1;
+        }
+        true
+    }
+
+    /// Shift the group one line up when its last line equals the line before it,
+    /// a group right before gets merged
+    fn slide_up(&mut self, group: &mut Group) -> bool {
+        if group.start == 0 || self.ids[group.start - 1] != self.ids[group.end - 1] {
+            return false;
+        }
+        group.start -= 1;
+        group.end -= 1;
+        self.changed[group.start] = true;
+        self.changed[group.end] = false;
+        while group.start > 0 && self.changed[group.start - 1] {
+            group.start -= 1;
+        }
+        true
+    }
+
+    /// Indents around the split before the line
+    fn measure_split(&self, split: usize) -> SplitMeasurement {
+        let mut m = SplitMeasurement {
+            end_of_file: split >= self.lines.len(),
+            indent: self.lines.get(split).map_or(-1, |line| line_indent(line)),
+            pre_blank: 0,
+            pre_indent: -1,
+            post_blank: 0,
+            post_indent: -1,
+        };
+        for line in self.lines[..split.min(self.lines.len())].iter().rev() {
+            m.pre_indent = line_indent(line);
+            if m.pre_indent != -1 {
+                break;
+            }
+            m.pre_blank += 1;
+            if m.pre_blank == MAX_BLANKS {
+                m.pre_indent = 0;
+                break;
+            }
+        }
+        for line in self.lines.iter().skip(split + 1) {
+            m.post_indent = line_indent(line);
+            if m.post_indent != -1 {
+                break;
+            }
+            m.post_blank += 1;
+            if m.post_blank == MAX_BLANKS {
+                m.post_indent = 0;
+                break;
+            }
+        }
+        m
+    }
+
+    /// Slide groups of changed lines as git's `xdl_change_compact` does: merge them where
+    /// possible, line them up with a change of the other side, otherwise move them to the
+    /// place the indent heuristic scores best
+    fn compact(&mut self, other: &ChangedLines) {
+        let mut group = self.first_group();
+        let mut other_group = other.first_group();
+        loop {
+            if group.end != group.start {
+                let (mut size, mut earliest_end, mut matches_other);
+                loop {
+                    size = group.end - group.start;
+                    while self.slide_up(&mut group) {
+                        other.previous_group(&mut other_group);
+                    }
+                    earliest_end = group.end;
+                    matches_other = other_group.end > other_group.start;
+                    while self.slide_down(&mut group) {
+                        other.next_group(&mut other_group);
+                        matches_other |= other_group.end > other_group.start;
+                    }
+                    if size == group.end - group.start {
+                        break;
+                    }
+                }
+
+                if group.end == earliest_end {
+                    // No shifting was possible
+                } else if matches_other {
+                    while other_group.end == other_group.start {
+                        self.slide_up(&mut group);
+                        other.previous_group(&mut other_group);
+                    }
+                } else {
+                    let first = earliest_end
+                        .max(group.end.saturating_sub(size + 1))
+                        .max(group.end.saturating_sub(INDENT_HEURISTIC_MAX_SLIDING));
+                    let mut best: Option<(usize, SplitScore)> = None;
+                    for shift in first..=group.end {
+                        let mut score = SplitScore::default();
+                        score.add(&self.measure_split(shift));
+                        score.add(&self.measure_split(shift - size));
+                        if best.is_none_or(|(_, best_score)| score.compare(&best_score) <= 0) {
+                            best = Some((shift, score));
+                        }
+                    }
+                    let best_shift = best.map_or(group.end, |(shift, _)| shift);
+                    while group.end > best_shift {
+                        self.slide_up(&mut group);
+                        other.previous_group(&mut other_group);
+                    }
+                }
+            }
+
+            if !self.next_group(&mut group) {
+                break;
+            }
+            other.next_group(&mut other_group);
+        }
+    }
+}
+
+/// Length of common prefix
+fn common_prefix(a: &[u32], b: &[u32]) -> usize {
+    a.iter()
+        .zip(b.iter())
+        .take_while(|(x, y)| x == y)
+        .count()
+}
+
+/// Length of common suffix
+fn common_suffix(a: &[u32], b: &[u32]) -> usize {
+    a.iter()
+        .rev()
+        .zip(b.iter().rev())
+        .take_while(|(x, y)| x == y)
+        .count()
+}
+
+/// Integer approximation of the square root (git's `xdl_bogosqrt`)
+fn bogo_sqrt(mut n: usize) -> usize {
+    let mut root = 1;
+    while n > 0 {
+        root <<= 1;
+        n >>= 2;
+    }
+    root
+}
+
+/// How often a line occurs on the other side of the diff
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Matches {
+    None,
+    Some,
+    Many,
+}
+
+/// Lines of the range the Myers search has to match (git's `xdl_cleanup_records`): lines with
+/// few matches, frequent ones only when they are not in a run of mostly unmatched lines
+fn matchable_lines(
+    lines: &[u32],
+    range: Range<usize>,
+    matches: impl Fn(&u32) -> usize
+) -> Vec<usize> {
+    let limit = bogo_sqrt(lines.len()).min(MAX_EQUAL_LIMIT);
+    let kinds: Vec<Matches> = lines[range.clone()]
+        .iter()
+        .map(|line| {
+            match matches(line) {
+                0 => Matches::None,
+                count if count >= limit => Matches::Many,
+                _ => Matches::Some,
+            }
+        })
+        .collect();
+    (0..kinds.len())
+        .filter(|i| {
+            kinds[*i] == Matches::Some ||
+                (kinds[*i] == Matches::Many && !in_unmatched_run(&kinds, *i))
+        })
+        .map(|i| range.start + i)
+        .collect()
+}
+
+/// Whether the frequent line sits among mostly unmatched lines (git's `xdl_clean_mmatch`)
+fn in_unmatched_run(kinds: &[Matches], i: usize) -> bool {
+    // (<unmatched lines>, <frequent lines>) of the run up to the first line with few matches
+    let run = |lines: &mut dyn Iterator<Item = &Matches>| {
+        let (mut unmatched, mut frequent) = (0, 1);
+        for kind in lines {
+            match kind {
+                Matches::None => unmatched += 1,
+                Matches::Many => frequent += 1,
+                Matches::Some => break,
+            }
+        }
+        (unmatched, frequent)
+    };
+    let start = i.saturating_sub(SIMILAR_SCAN_WINDOW);
+    let (unmatched_before, frequent_before) = run(&mut kinds[start..i].iter().rev());
+    if unmatched_before == 0 {
+        return false;
+    }
+    let end = (i + SIMILAR_SCAN_WINDOW + 1).min(kinds.len());
+    let (unmatched_after, frequent_after) = run(&mut kinds[i + 1..end].iter());
+    if unmatched_after == 0 {
+        return false;
+    }
+    let unmatched = unmatched_before + unmatched_after;
+    let frequent = frequent_before + frequent_after;
+    frequent * KEEP_FREQUENT_RUN < frequent + unmatched
+}
+
+/// Myers diff of the ranges run as git's `xdl_do_diff` does: lines without matches on the
+/// other side (and frequent ones among them) are changed upfront, the search on the others
+/// gives up on minimal results once it gets costly
+fn myers(
+    old: &mut ChangedLines,
+    new: &mut ChangedLines,
+    old_range: Range<usize>,
+    new_range: Range<usize>
+) {
+    let (a, b) = (&old.ids[old_range.clone()], &new.ids[new_range.clone()]);
+    // Occurrences of every line on both sides
+    let mut counts: HashMap<u32, (usize, usize)> = HashMap::new();
+    for line in a {
+        counts.entry(*line).or_default().0 += 1;
+    }
+    for line in b {
+        counts.entry(*line).or_default().1 += 1;
+    }
+
+    let prefix = common_prefix(a, b);
+    let suffix = common_suffix(&a[prefix..], &b[prefix..]);
+    let kept_a = matchable_lines(a, prefix..a.len() - suffix, |line| counts[line].1);
+    let kept_b = matchable_lines(b, prefix..b.len() - suffix, |line| counts[line].0);
+
+    let search_a: Vec<u32> = kept_a.iter().map(|i| a[*i]).collect();
+    let search_b: Vec<u32> = kept_b.iter().map(|j| b[*j]).collect();
+    let mut search = MyersSearch::new(&search_a, &search_b);
+    search.compare(0, search_a.len() as isize, 0, search_b.len() as isize, false);
+
+    old.mark(old_range.start + prefix..old_range.end - suffix);
+    new.mark(new_range.start + prefix..new_range.end - suffix);
+    for (k, i) in kept_a.iter().enumerate() {
+        old.changed[old_range.start + i] = search.changed_a[k];
+    }
+    for (k, j) in kept_b.iter().enumerate() {
+        new.changed[new_range.start + j] = search.changed_b[k];
+    }
+}
+
+/// Linear space Myers search with git's heuristics (`xdl_recs_cmp` and `xdl_split`)
+struct MyersSearch<'a> {
+    a: &'a [u32],
+    b: &'a [u32],
+    changed_a: Vec<bool>,
+    changed_b: Vec<bool>,
+    /// Furthest reaching x on every diagonal (diagonal `k` is at `k + offset`)
+    forward: Vec<isize>,
+    backward: Vec<isize>,
+    offset: isize,
+    /// Edit cost after which the search takes the furthest reaching path found
+    max_cost: isize,
+}
+
+impl<'a> MyersSearch<'a> {
+    fn new(a: &'a [u32], b: &'a [u32]) -> MyersSearch<'a> {
+        let diagonals = a.len() + b.len() + 3;
+        MyersSearch {
+            a,
+            b,
+            changed_a: vec![false; a.len()],
+            changed_b: vec![false; b.len()],
+            forward: vec![0; diagonals],
+            backward: vec![0; diagonals],
+            offset: b.len() as isize + 1,
+            max_cost: bogo_sqrt(diagonals).max(MAX_COST_MIN) as isize,
+        }
+    }
+
+    /// Mark lines changed between `a[off1..lim1]` and `b[off2..lim2]`
+    fn compare(
+        &mut self,
+        mut off1: isize,
+        mut lim1: isize,
+        mut off2: isize,
+        mut lim2: isize,
+        need_min: bool
+    ) {
+        let (a, b) = (self.a, self.b);
+        while off1 < lim1 && off2 < lim2 && a[off1 as usize] == b[off2 as usize] {
+            off1 += 1;
+            off2 += 1;
+        }
+        while off1 < lim1 && off2 < lim2 && a[lim1 as usize - 1] == b[lim2 as usize - 1] {
+            lim1 -= 1;
+            lim2 -= 1;
+        }
+
+        if off1 == lim1 {
+            self.changed_b[off2 as usize..lim2 as usize].fill(true);
+        } else if off2 == lim2 {
+            self.changed_a[off1 as usize..lim1 as usize].fill(true);
+        } else {
+            let (i1, i2, min_low, min_high) = self.split(off1, lim1, off2, lim2, need_min);
+            self.compare(off1, i1, off2, i2, min_low);
+            self.compare(i1, lim1, i2, lim2, min_high);
+        }
+    }
+
+    /// Point to split the box at, from the middle snake or (for costly diffs without
+    /// `need_min`) from a good looking path, returns (<x>, <y>, <minimal before>,
+    /// <minimal after>)
+    fn split(
+        &mut self,
+        off1: isize,
+        lim1: isize,
+        off2: isize,
+        lim2: isize,
+        need_min: bool
+    ) -> (isize, isize, bool, bool) {
+        let (a, b, offset) = (self.a, self.b, self.offset);
+        let at = |diagonal: isize| (diagonal + offset) as usize;
+        let (forward, backward) = (&mut self.forward, &mut self.backward);
+        let (dmin, dmax) = (off1 - lim2, lim1 - off2);
+        let (fmid, bmid) = (off1 - off2, lim1 - lim2);
+        let odd = (fmid - bmid) & 1 != 0;
+        let (mut fmin, mut fmax) = (fmid, fmid);
+        let (mut bmin, mut bmax) = (bmid, bmid);
+        forward[at(fmid)] = off1;
+        backward[at(bmid)] = lim1;
+
+        let mut cost = 1;
+        loop {
+            let mut got_snake = false;
+
+            // Extend the forward diagonals by one, or shrink them at the box boundaries
+            if fmin > dmin {
+                fmin -= 1;
+                forward[at(fmin - 1)] = -1;
+            } else {
+                fmin += 1;
+            }
+            if fmax < dmax {
+                fmax += 1;
+                forward[at(fmax + 1)] = -1;
+            } else {
+                fmax -= 1;
+            }
+            for d in (fmin..=fmax).rev().step_by(2) {
+                let mut i1 = if forward[at(d - 1)] >= forward[at(d + 1)] {
+                    forward[at(d - 1)] + 1
+                } else {
+                    forward[at(d + 1)]
+                };
+                let prev1 = i1;
+                let mut i2 = i1 - d;
+                while i1 < lim1 && i2 < lim2 && a[i1 as usize] == b[i2 as usize] {
+                    i1 += 1;
+                    i2 += 1;
+                }
+                got_snake |= i1 - prev1 > SNAKE_COUNT;
+                forward[at(d)] = i1;
+                if odd && bmin <= d && d <= bmax && backward[at(d)] <= i1 {
+                    return (i1, i2, true, true);
+                }
+            }
+
+            // Same for the backward diagonals
+            if bmin > dmin {
+                bmin -= 1;
+                backward[at(bmin - 1)] = isize::MAX;
+            } else {
+                bmin += 1;
+            }
+            if bmax < dmax {
+                bmax += 1;
+                backward[at(bmax + 1)] = isize::MAX;
+            } else {
+                bmax -= 1;
+            }
+            for d in (bmin..=bmax).rev().step_by(2) {
+                let mut i1 = if backward[at(d - 1)] < backward[at(d + 1)] {
+                    backward[at(d - 1)]
+                } else {
+                    backward[at(d + 1)] - 1
+                };
+                let prev1 = i1;
+                let mut i2 = i1 - d;
+                while i1 > off1 && i2 > off2 && a[i1 as usize - 1] == b[i2 as usize - 1] {
+                    i1 -= 1;
+                    i2 -= 1;
+                }
+                got_snake |= prev1 - i1 > SNAKE_COUNT;
+                backward[at(d)] = i1;
+                if !odd && fmin <= d && d <= fmax && i1 <= forward[at(d)] {
+                    return (i1, i2, true, true);
+                }
+            }
+
+            if need_min {
+                cost += 1;
+                continue;
+            }
+
+            // Past the trigger cost, a diagonal far from both the start and the middle one
+            // that ends with a long snake is good enough
+            if got_snake && cost > HEURISTIC_MIN_COST {
+                let mut best = None;
+                for d in (fmin..=fmax).rev().step_by(2) {
+                    let i1 = forward[at(d)];
+                    let i2 = i1 - d;
+                    let value = (i1 - off1) + (i2 - off2) - (d - fmid).abs();
+                    let inside = off1 + SNAKE_COUNT <= i1 && i1 < lim1 &&
+                        off2 + SNAKE_COUNT <= i2 && i2 < lim2;
+                    let better = best.is_none_or(|(best_value, _, _)| value > best_value);
+                    if value > K_HEURISTIC * cost && better && inside &&
+                        (1..=SNAKE_COUNT).all(|k| a[(i1 - k) as usize] == b[(i2 - k) as usize])
+                    {
+                        best = Some((value, i1, i2));
+                    }
+                }
+                if let Some((_, i1, i2)) = best {
+                    return (i1, i2, true, false);
+                }
+
+                for d in (bmin..=bmax).rev().step_by(2) {
+                    let i1 = backward[at(d)];
+                    let i2 = i1 - d;
+                    let value = (lim1 - i1) + (lim2 - i2) - (d - bmid).abs();
+                    let inside = off1 < i1 && i1 <= lim1 - SNAKE_COUNT &&
+                        off2 < i2 && i2 <= lim2 - SNAKE_COUNT;
+                    let better = best.is_none_or(|(best_value, _, _)| value > best_value);
+                    if value > K_HEURISTIC * cost && better && inside &&
+                        (0..SNAKE_COUNT).all(|k| a[(i1 + k) as usize] == b[(i2 + k) as usize])
+                    {
+                        best = Some((value, i1, i2));
+                    }
+                }
+                if let Some((_, i1, i2)) = best {
+                    return (i1, i2, false, true);
+                }
+            }
+
+            // Too costly, take the furthest reaching path (by x + y)
+            if cost >= self.max_cost {
+                let (mut forward_best, mut forward_x) = (-1, -1);
+                for d in (fmin..=fmax).rev().step_by(2) {
+                    let mut i1 = forward[at(d)].min(lim1);
+                    let mut i2 = i1 - d;
+                    if lim2 < i2 {
+                        i1 = lim2 + d;
+                        i2 = lim2;
+                    }
+                    if forward_best < i1 + i2 {
+                        forward_best = i1 + i2;
+                        forward_x = i1;
+                    }
+                }
+                let (mut backward_best, mut backward_x) = (isize::MAX, isize::MAX);
+                for d in (bmin..=bmax).rev().step_by(2) {
+                    let mut i1 = off1.max(backward[at(d)]);
+                    let mut i2 = i1 - d;
+                    if i2 < off2 {
+                        i1 = off2 + d;
+                        i2 = off2;
+                    }
+                    if i1 + i2 < backward_best {
+                        backward_best = i1 + i2;
+                        backward_x = i1;
+                    }
+                }
+                return if (lim1 + lim2) - backward_best < forward_best - (off1 + off2) {
+                    (forward_x, forward_best - forward_x, true, false)
+                } else {
+                    (backward_x, backward_best - backward_x, false, true)
+                };
+            }
+            cost += 1;
+        }
+    }
+}
+
+/// Patience diff as git runs it: match lines unique to both ranges in their longest common
+/// order and diff the ranges between them, Myers takes the ranges without such lines
+fn patience(
+    old: &mut ChangedLines,
+    new: &mut ChangedLines,
+    old_range: Range<usize>,
+    new_range: Range<usize>
+) {
+    if old_range.is_empty() || new_range.is_empty() {
+        old.mark(old_range);
+        new.mark(new_range);
+        return;
+    }
+    let (a, b) = (old.ids, new.ids);
+
+    // (<line in old>, <line in new>, <unique on both sides>) for every line of the old range
+    let mut entries: Vec<(usize, Option<usize>, bool)> = Vec::new();
+    let mut positions: HashMap<u32, usize> = HashMap::new();
+    for i in old_range.clone() {
+        match positions.get(&a[i]) {
+            Some(k) => entries[*k].2 = false,
+            None => {
+                positions.insert(a[i], entries.len());
+                entries.push((i, None, true));
+            }
+        }
+    }
+    let mut has_matches = false;
+    for j in new_range.clone() {
+        if let Some(k) = positions.get(&b[j]) {
+            has_matches = true;
+            let entry = &mut entries[*k];
+            if entry.1.is_some() {
+                entry.2 = false;
+            }
+            entry.1 = Some(j);
+        }
+    }
+    if !has_matches {
+        old.mark(old_range);
+        new.mark(new_range);
+        return;
+    }
+
+    let unique: Vec<(usize, usize)> = entries
+        .iter()
+        .filter(|(_, _, unique)| *unique)
+        .filter_map(|(i, j, _)| j.map(|j| (*i, j)))
+        .collect();
+    let anchors = longest_increasing_subsequence(&unique);
+    if anchors.is_empty() {
+        myers(old, new, old_range, new_range);
+        return;
+    }
+
+    // Grow anchors into runs of equal lines, the ranges between them are diffed again
+    let (mut line1, mut line2) = (old_range.start, new_range.start);
+    let mut k = 0;
+    loop {
+        let (mut next1, mut next2) = match anchors.get(k) {
+            Some(anchor) => *anchor,
+            None => (old_range.end, new_range.end),
+        };
+        if k < anchors.len() {
+            while next1 > line1 && next2 > line2 && a[next1 - 1] == b[next2 - 1] {
+                next1 -= 1;
+                next2 -= 1;
+            }
+        }
+        while line1 < next1 && line2 < next2 && a[line1] == b[line2] {
+            line1 += 1;
+            line2 += 1;
+        }
+        if next1 > line1 || next2 > line2 {
+            patience(old, new, line1..next1, line2..next2);
+        }
+
+        let Some(mut anchor) = anchors.get(k).copied() else {
+            return;
+        };
+        while anchors.get(k + 1) == Some(&(anchor.0 + 1, anchor.1 + 1)) {
+            k += 1;
+            anchor = anchors[k];
+        }
+        (line1, line2) = (anchor.0 + 1, anchor.1 + 1);
+        k += 1;
+    }
+}
+
+/// Longest subsequence of pairs with increasing second element (input is sorted by first one)
+fn longest_increasing_subsequence(pairs: &[(usize, usize)]) -> Vec<(usize, usize)> {
+    // Patience sorting: tops of piles and back links
+    let mut piles: Vec<usize> = Vec::new();
+    let mut back_links: Vec<Option<usize>> = vec![None; pairs.len()];
+
+    for (idx, (_, j)) in pairs.iter().enumerate() {
+        let pile = piles.partition_point(|top| pairs[*top].1 < *j);
+        if pile > 0 {
+            back_links[idx] = Some(piles[pile - 1]);
+        }
+        if pile == piles.len() {
+            piles.push(idx);
+        } else {
+            piles[pile] = idx;
+        }
+    }
+
+    let mut result: Vec<(usize, usize)> = Vec::new();
+    let mut current = piles.last().copied();
+    while let Some(idx) = current {
+        result.push(pairs[idx]);
+        current = back_links[idx];
+    }
+    result.reverse();
+    result
+}
+
+/// Histogram diff as git runs it: split the ranges on the longest common region around the
+/// rarest lines, Myers takes the ranges where all common lines are too frequent
+fn histogram(
+    old: &mut ChangedLines,
+    new: &mut ChangedLines,
+    mut old_range: Range<usize>,
+    mut new_range: Range<usize>
+) {
+    loop {
+        if old_range.is_empty() || new_range.is_empty() {
+            old.mark(old_range);
+            new.mark(new_range);
+            return;
+        }
+        match find_histogram_region(old.ids, new.ids, old_range.clone(), new_range.clone()) {
+            HistogramRegion::Frequent => return myers(old, new, old_range, new_range),
+            HistogramRegion::Missing => {
+                old.mark(old_range);
+                new.mark(new_range);
+                return;
+            }
+            HistogramRegion::Found(i, j, len) => {
+                histogram(old, new, old_range.start..i, new_range.start..j);
+                old_range.start = i + len;
+                new_range.start = j + len;
+            }
+        }
+    }
+}
+
+/// Common region of the ranges histogram diff splits on
+enum HistogramRegion {
+    /// (<start in old>, <start in new>, <length>)
+    Found(usize, usize, usize),
+    /// Every common line is repeated too often
+    Frequent,
+    /// Nothing in common
+    Missing,
+}
+
+/// Region of equal lines around the line with the fewest occurrences in the old range,
+/// longer ones win among equally rare lines (git's `find_lcs`)
+fn find_histogram_region(
+    a: &[u32],
+    b: &[u32],
+    old_range: Range<usize>,
+    new_range: Range<usize>
+) -> HistogramRegion {
+    // First occurrence of every line of the old range, link to the next one and counts
+    let mut first: HashMap<u32, usize> = HashMap::new();
+    let mut next: Vec<Option<usize>> = vec![None; old_range.len()];
+    let mut counts: HashMap<u32, usize> = HashMap::new();
+    for i in old_range.clone().rev() {
+        next[i - old_range.start] = first.insert(a[i], i);
+        *counts.entry(a[i]).or_default() += 1;
+    }
+
+    // (<start in old>, <start in new>, <end in old>), ends are inclusive
+    let mut best: Option<(usize, usize, usize)> = None;
+    let mut best_count = MAX_CHAIN_LEN + 1;
+    let mut has_common = false;
+    let mut j = new_range.start;
+    while j < new_range.end {
+        let mut next_j = j + 1;
+        if let Some(&first_i) = first.get(&b[j]) {
+            has_common = true;
+            let count = counts[&b[j]];
+            let mut occurrence = Some(first_i).filter(|_| count <= best_count);
+            while let Some(i) = occurrence {
+                // Extend region in both directions, counting the rarest line in it
+                let (mut start_a, mut start_b, mut end_a, mut end_b) = (i, j, i, j);
+                let mut region_count = count;
+                while start_a > old_range.start && start_b > new_range.start &&
+                    a[start_a - 1] == b[start_b - 1]
+                {
+                    start_a -= 1;
+                    start_b -= 1;
+                    if region_count > 1 {
+                        region_count = region_count.min(counts[&a[start_a]]);
+                    }
+                }
+                while end_a + 1 < old_range.end && end_b + 1 < new_range.end &&
+                    a[end_a + 1] == b[end_b + 1]
+                {
+                    end_a += 1;
+                    end_b += 1;
+                    if region_count > 1 {
+                        region_count = region_count.min(counts[&a[end_a]]);
+                    }
+                }
+
+                next_j = next_j.max(end_b + 1);
+                let best_len = best.map_or(0, |(start, _, end)| end - start);
+                if best_len < end_a - start_a || region_count < best_count {
+                    best = Some((start_a, start_b, end_a));
+                    best_count = region_count;
+                }
+
+                // Next occurrence past the region
+                occurrence = next[i - old_range.start];
+                while let Some(k) = occurrence.filter(|k| *k <= end_a) {
+                    occurrence = next[k - old_range.start];
+                }
+            }
+        }
+        j = next_j;
+    }
+
+    match best {
+        _ if has_common && best_count > MAX_CHAIN_LEN => HistogramRegion::Frequent,
+        Some((i, j, end)) => HistogramRegion::Found(i, j, end - i + 1),
+        None => HistogramRegion::Missing,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn apply(old: &[&[u8]], new: &[&[u8]], edits: &[Edit]) -> Vec<Vec<u8>> {
+        edits
+            .iter()
+            .filter_map(|e| {
+                match e {
+                    Edit::Equal(i, _) => Some(old[*i].to_vec()),
+                    Edit::Insert(j) => Some(new[*j].to_vec()),
+                    Edit::Delete(_) => None,
+                }
+            })
+            .collect()
+    }
+
+    #[test]
+    fn algorithms_produce_valid_scripts() {
+        let old = split_lines(b"a\nb\nc\na\nb\nb\na\n");
+        let new = split_lines(b"c\nb\na\nb\na\nc\n");
+        for algorithm in [DiffAlgorithm::Myers, DiffAlgorithm::Patience, DiffAlgorithm::Histogram] {
+            let edits = diff_lines(&old, &new, algorithm);
+            let expected: Vec<Vec<u8>> = new
+                .iter()
+                .map(|l| l.to_vec())
+                .collect();
+            assert_eq!(apply(&old, &new, &edits), expected);
+        }
+
+        // Myers is minimal: 5 edits for the classic example
+        let edits = diff_lines(&old, &new, DiffAlgorithm::Myers);
+        let changed = edits
+            .iter()
+            .filter(|e| !matches!(e, Edit::Equal(..)))
+            .count();
+        assert_eq!(changed, 5);
+    }
+}
@@ -0,0 +1,508 @@
+use super::*;
+
+use anyhow::Result;
+
+/// Amount of bytes checked for NUL to detect binary files
+const BINARY_CHECK_L: usize = 8000;
+/// Total width of `--stat` output
+const STAT_WIDTH: usize = 80;
+/// Maximal length of function name in hunk header
+const FUNC_CONTEXT_L: usize = 80;
+const NULL_SHA: &str = "0000000000000000000000000000000000000000";
+
+/// Row of `--stat` output: (<name>, <added>, <deleted>, <binary sizes>)
+type StatRow = (String, usize, usize, Option<(usize, usize)>);
+
+/// Same heuristic as in git: NUL byte in the beginning of the file
+pub fn is_binary(data: &[u8]) -> bool {
+    data.iter()
+        .take(BINARY_CHECK_L)
+        .any(|c| *c == b'\0')
+}
+
+/// Group of changes with surrounding context (line positions are 0-based)
+#[derive(Clone, Debug)]
+pub struct Hunk {
+    pub old_start: usize,
+    pub old_len: usize,
+    pub new_start: usize,
+    pub new_len: usize,
+    pub edits: Vec<Edit>,
+}
+
+/// Split edit script into hunks with `context` lines around every change
+pub fn make_hunks(edits: &[Edit], context: usize) -> Vec<Hunk> {
+    // Positions in both files before every edit
+    let mut positions: Vec<(usize, usize)> = Vec::with_capacity(edits.len() + 1);
+    let (mut i, mut j) = (0, 0);
+    for edit in edits {
+        positions.push((i, j));
+        match edit {
+            Edit::Equal(..) => {
+                i += 1;
+                j += 1;
+            }
+            Edit::Delete(_) => {
+                i += 1;
+            }
+            Edit::Insert(_) => {
+                j += 1;
+            }
+        }
+    }
+
+    // Group changes which are close enough to share context
+    let changes: Vec<usize> = edits
+        .iter()
+        .enumerate()
+        .filter(|(_, e)| !matches!(e, Edit::Equal(..)))
+        .map(|(idx, _)| idx)
+        .collect();
+    let mut groups: Vec<(usize, usize)> = Vec::new();
+    for idx in changes {
+        match groups.last_mut() {
+            Some((_, last)) if idx - *last - 1 <= 2 * context => {
+                *last = idx;
+            }
+            _ => groups.push((idx, idx)),
+        }
+    }
+
+    groups
+        .into_iter()
+        .map(|(first, last)| {
+            let from = first.saturating_sub(context);
+            let to = (last + context + 1).min(edits.len());
+            let hunk_edits = edits[from..to].to_vec();
+            let old_len = hunk_edits
+                .iter()
+                .filter(|e| !matches!(e, Edit::Insert(_)))
+                .count();
+            let new_len = hunk_edits
+                .iter()
+                .filter(|e| !matches!(e, Edit::Delete(_)))
+                .count();
+            Hunk {
+                old_start: positions[from].0,
+                old_len,
+                new_start: positions[from].1,
+                new_len,
+                edits: hunk_edits,
+            }
+        })
+        .collect()
+}
+
+/// Range in hunk header (`start,len` with git's special cases)
+fn format_range(start: usize, len: usize) -> String {
+    match len {
+        0 => format!("{start},0"),
+        1 => format!("{}", start + 1),
+        _ => format!("{},{len}", start + 1),
+    }
+}
+
+/// Find nearest line before `before` that looks like a function header
+fn function_context(old_lines: &[&[u8]], before: usize) -> String {
+    for line in old_lines[..before.min(old_lines.len())].iter().rev() {
+        let first = line.first().copied().unwrap_or(b'\n');
+        if first.is_ascii_alphabetic() || first == b'_' || first == b'$' {
+            let mut text: &[u8] = &line[..line.len().min(FUNC_CONTEXT_L)];
+            while let Some((last, rest)) = text.split_last() {
+                if !last.is_ascii_whitespace() {
+                    break;
+                }
+                text = rest;
+            }
+            return format!(" {}", String::from_utf8_lossy(text));
+        }
+    }
+    String::new()
+}
+
+fn push_line(out: &mut String, prefix: char, line: &[u8]) {
+    out.push(prefix);
+    out.push_str(&String::from_utf8_lossy(line));
+    if !line.ends_with(b"\n") {
+        out.push_str("\n\\ No newline at end of file\n");
+    }
+}
+
+/// Unified diff hunks (without file headers) for two versions of contents
+pub fn unified_hunks(old: &[u8], new: &[u8], options: &DiffOptions) -> String {
+    let old_lines = split_lines(old);
+    let new_lines = split_lines(new);
+    let edits = diff_lines(&old_lines, &new_lines, options.algorithm);
+
+    let mut out = String::new();
+    for hunk in make_hunks(&edits, options.context) {
+        out.push_str(
+            &format!(
+                "@@ -{} +{} @@{}\n",
+                format_range(hunk.old_start, hunk.old_len),
+                format_range(hunk.new_start, hunk.new_len),
+                function_context(&old_lines, hunk.old_start)
+            )
+        );
+        for edit in hunk.edits {
+            match edit {
+                Edit::Equal(i, _) => push_line(&mut out, ' ', old_lines[i]),
+                Edit::Delete(i) => push_line(&mut out, '-', old_lines[i]),
+                Edit::Insert(j) => push_line(&mut out, '+', new_lines[j]),
+            }
+        }
+    }
+    out
+}
+
+/// Load both sides of the change (missing side is empty)
+fn load_pair(change: &FileChange) -> Result<(Vec<u8>, Vec<u8>)> {
+    let old = match &change.old {
+        Some(entry) => load_contents(entry)?,
+        None => Vec::new(),
+    };
+    let new = match &change.new {
+        Some(entry) => load_contents(entry)?,
+        None => Vec::new(),
+    };
+    Ok((old, new))
+}
+
+/// Returns (<added lines>, <deleted lines>) or None for binary files
+pub fn line_counts(change: &FileChange, algorithm: DiffAlgorithm) -> Result<Option<(usize, usize)>> {
+    let (old, new) = load_pair(change)?;
+    if is_binary(&old) || is_binary(&new) {
+        return Ok(None);
+    }
+    let edits = diff_lines(&split_lines(&old), &split_lines(&new), algorithm);
+    let added = edits
+        .iter()
+        .filter(|e| matches!(e, Edit::Insert(_)))
+        .count();
+    let deleted = edits
+        .iter()
+        .filter(|e| matches!(e, Edit::Delete(_)))
+        .count();
+    Ok(Some((added, deleted)))
+}
+
+/// Path shown for renames and copies in stats (`dir/{old => new}.txt`)
+pub fn display_path(change: &FileChange) -> String {
+    match (&change.old, &change.new) {
+        (Some(old), Some(new)) if old.path != new.path => {
+            let (a, b) = (old.path.as_bytes(), new.path.as_bytes());
+
+            // Common prefix ends with '/' and common suffix starts with it
+            let mut prefix = 0;
+            let mut k = 0;
+            while k < a.len() && k < b.len() && a[k] == b[k] {
+                if a[k] == b'/' {
+                    prefix = k + 1;
+                }
+                k += 1;
+            }
+            let limit = if prefix > 0 { prefix - 1 } else { 0 };
+            let mut suffix = 0;
+            let (mut i, mut j) = (a.len(), b.len());
+            while i > limit && j > limit && a[i - 1] == b[j - 1] {
+                if a[i - 1] == b'/' {
+                    suffix = a.len() - (i - 1);
+                }
+                i -= 1;
+                j -= 1;
+            }
+
+            if prefix == 0 && suffix == 0 {
+                format!("{} => {}", old.path, new.path)
+            } else {
+                let a_mid = a.len().saturating_sub(prefix + suffix);
+                let b_mid = b.len().saturating_sub(prefix + suffix);
+                format!(
+                    "{}{{{} => {}}}{}",
+                    String::from_utf8_lossy(&a[..prefix]),
+                    String::from_utf8_lossy(&a[prefix..prefix + a_mid]),
+                    String::from_utf8_lossy(&b[prefix..prefix + b_mid]),
+                    String::from_utf8_lossy(&a[a.len() - suffix..])
+                )
+            }
+        }
+        _ => change.path().to_string(),
+    }
+}
+
+/// Full patch for list of changes
+pub fn patch(changes: &[FileChange], options: &DiffOptions) -> Result<String> {
+    let mut out = String::new();
+    for change in changes {
+        if change.status == ChangeStatus::TypeChanged {
+            // Type changes are shown as removal and addition
+            let removal = FileChange {
+                status: ChangeStatus::Deleted,
+                old: change.old.clone(),
+                new: None,
+            };
+            let addition = FileChange {
+                status: ChangeStatus::Added,
+                old: None,
+                new: change.new.clone(),
+            };
+            patch_for_change(&removal, options, &mut out)?;
+            patch_for_change(&addition, options, &mut out)?;
+        } else {
+            patch_for_change(change, options, &mut out)?;
+        }
+    }
+    Ok(out)
+}
+
+fn patch_for_change(change: &FileChange, options: &DiffOptions, out: &mut String) -> Result<()> {
+    let old_path = change.old.as_ref().map_or(change.path(), |e| e.path.as_str());
+    let new_path = change.new.as_ref().map_or(change.path(), |e| e.path.as_str());
+    out.push_str(&format!("diff --git a/{old_path} b/{new_path}\n"));
+
+    // Extended header lines
+    match (&change.old, &change.new) {
+        (None, Some(new)) => out.push_str(&format!("new file mode {}\n", new.mode)),
+        (Some(old), None) => out.push_str(&format!("deleted file mode {}\n", old.mode)),
+        (Some(old), Some(new)) if old.mode != new.mode => {
+            out.push_str(&format!("old mode {}\nnew mode {}\n", old.mode, new.mode));
+        }
+        _ => {}
+    }
+    match change.status {
+        ChangeStatus::Renamed(score) => {
+            out.push_str(
+                &format!("similarity index {score}%\nrename from {old_path}\nrename to {new_path}\n")
+            );
+        }
+        ChangeStatus::Copied(score) => {
+            out.push_str(
+                &format!("similarity index {score}%\ncopy from {old_path}\ncopy to {new_path}\n")
+            );
+        }
+        _ => {}
+    }
+
+    let old_sha = change.old.as_ref().map_or(NULL_SHA, |e| e.sha.as_str());
+    let new_sha = change.new.as_ref().map_or(NULL_SHA, |e| e.sha.as_str());
+    if old_sha == new_sha {
+        return Ok(());
+    }
+
+    // Mode is appended only when it is the same on both sides
+    out.push_str(&format!("index {}..{}", abbrev(old_sha), abbrev(new_sha)));
+    match (&change.old, &change.new) {
+        (Some(old), Some(new)) if old.mode == new.mode => out.push_str(&format!(" {}\n", old.mode)),
+        _ => out.push('\n'),
+    }
+
+    let old_name = change.old.as_ref().map_or("/dev/null".to_string(), |_| format!("a/{old_path}"));
+    let new_name = change.new.as_ref().map_or("/dev/null".to_string(), |_| format!("b/{new_path}"));
+    let (old, new) = load_pair(change)?;
+    if is_binary(&old) || is_binary(&new) {
+        out.push_str(&format!("Binary files {old_name} and {new_name} differ\n"));
+        return Ok(());
+    }
+
+    let hunks = unified_hunks(&old, &new, options);
+    if !hunks.is_empty() {
+        out.push_str(&format!("--- {old_name}\n+++ {new_name}\n"));
+        out.push_str(&hunks);
+    }
+    Ok(())
+}
+
+/// Git-like scaling of change counts to the graph width
+fn scale_linear(value: usize, width: usize, max_change: usize) -> usize {
+    if value == 0 { 0 } else { 1 + (value * (width - 1)) / max_change }
+}
+
+/// Summary line of `--stat` output
+pub fn stat_summary(files: usize, insertions: usize, deletions: usize) -> String {
+    let mut out = format!(" {files} file{} changed", if files == 1 { "" } else { "s" });
+    if insertions > 0 || deletions == 0 {
+        out.push_str(
+            &format!(", {insertions} insertion{}(+)", if insertions == 1 { "" } else { "s" })
+        );
+    }
+    if deletions > 0 || insertions == 0 {
+        out.push_str(&format!(", {deletions} deletion{}(-)", if deletions == 1 { "" } else { "s" }));
+    }
+    out.push('\n');
+    out
+}
+
+/// `--stat` output: histogram of changes per file and summary
+pub fn stat(changes: &[FileChange], options: &DiffOptions) -> Result<String> {
+    if changes.is_empty() {
+        return Ok(String::new());
+    }
+
+    let mut rows: Vec<StatRow> = Vec::new();
+    for change in changes {
+        let name = display_path(change);
+        match line_counts(change, options.algorithm)? {
+            Some((added, deleted)) => rows.push((name, added, deleted, None)),
+            None => {
+                let (old, new) = load_pair(change)?;
+                rows.push((name, 0, 0, Some((old.len(), new.len()))));
+            }
+        }
+    }
+
+    // Compute column widths the same way as git does
+    let max_change = rows
+        .iter()
+        .map(|(_, a, d, _)| a + d)
+        .max()
+        .unwrap_or(0);
+    let max_name = rows
+        .iter()
+        .map(|(n, ..)| n.chars().count())
+        .max()
+        .unwrap_or(0);
+    let mut number_width = max_change.to_string().len();
+    if rows.iter().any(|(.., bin)| bin.is_some()) {
+        number_width = number_width.max(3);
+    }
+    let width = STAT_WIDTH.max(16 + 6 + number_width);
+    let mut graph_width = max_change;
+    let mut name_width = max_name;
+    if name_width + number_width + 6 + graph_width > width {
+        if graph_width > (width * 3) / 8 - number_width - 6 {
+            graph_width = ((width * 3) / 8 - number_width - 6).max(6);
+        }
+        if name_width > width - number_width - 6 - graph_width {
+            name_width = width - number_width - 6 - graph_width;
+        } else {
+            graph_width = width - number_width - 6 - name_width;
+        }
+    }
+
+    let mut out = String::new();
+    let (mut insertions, mut deletions) = (0, 0);
+    for (name, added, deleted, binary) in rows {
+        insertions += added;
+        deletions += deleted;
+
+        // Long names are cut from the left
+        let name_len = name.chars().count();
+        let name = if name_len > name_width {
+            let skip = name_len - name_width + 3;
+            format!("...{}", name.chars().skip(skip).collect::<String>())
+        } else {
+            name
+        };
+
+        if let Some((old_size, new_size)) = binary {
+            out.push_str(
+                &format!(
+                    " {name:<name_width$} | {:>number_width$} {old_size} -> {new_size} bytes\n",
+                    "Bin"
+                )
+            );
+            continue;
+        }
+
+        let (mut add_w, mut del_w) = (added, deleted);
+        if graph_width <= max_change {
+            let mut total = scale_linear(added + deleted, graph_width, max_change);
+            if total < 2 && added > 0 && deleted > 0 {
+                total = 2;
+            }
+            if added < deleted {
+                add_w = scale_linear(added, graph_width, max_change);
+                del_w = total - add_w;
+            } else {
+                del_w = scale_linear(deleted, graph_width, max_change);
+                add_w = total - del_w;
+            }
+        }
+        let graph = format!("{}{}", "+".repeat(add_w), "-".repeat(del_w));
+        let total = added + deleted;
+        if graph.is_empty() {
+            out.push_str(&format!(" {name:<name_width$} | {total:>number_width$}\n"));
+        } else {
+            out.push_str(&format!(" {name:<name_width$} | {total:>number_width$} {graph}\n"));
+        }
+    }
+    out.push_str(&stat_summary(changes.len(), insertions, deletions));
+    Ok(out)
+}
+
+/// `--numstat` output: machine friendly added/deleted counts
+pub fn numstat(changes: &[FileChange], options: &DiffOptions) -> Result<String> {
+    let mut out = String::new();
+    for change in changes {
+        match line_counts(change, options.algorithm)? {
+            Some((added, deleted)) => {
+                out.push_str(&format!("{added}\t{deleted}\t{}\n", display_path(change)));
+            }
+            None => out.push_str(&format!("-\t-\t{}\n", display_path(change))),
+        }
+    }
+    Ok(out)
+}
+
+/// `--name-status` output
+pub fn name_status(changes: &[FileChange]) -> String {
+    let mut out = String::new();
+    for change in changes {
+        match (&change.status, &change.old) {
+            (ChangeStatus::Renamed(_) | ChangeStatus::Copied(_), Some(old)) => {
+                out.push_str(&format!("{}\t{}\t{}\n", change.status.letter(), old.path, change.path()));
+            }
+            _ => out.push_str(&format!("{}\t{}\n", change.status.letter(), change.path())),
+        }
+    }
+    out
+}
+
+/// `--name-only` output
+pub fn name_only(changes: &[FileChange]) -> String {
+    changes
+        .iter()
+        .map(|c| format!("{}\n", c.path()))
+        .collect()
+}
+
+/// Only the totals line of `--stat` (`--shortstat`)
+pub fn short_stat(changes: &[FileChange], algorithm: DiffAlgorithm) -> Result<String> {
+    let (mut insertions, mut deletions) = (0, 0);
+    for change in changes {
+        if let Some((added, deleted)) = line_counts(change, algorithm)? {
+            insertions += added;
+            deletions += deleted;
+        }
+    }
+    Ok(stat_summary(changes.len(), insertions, deletions))
+}
+
+/// Created, deleted, renamed files and mode changes (`--summary`)
+pub fn summary(changes: &[FileChange]) -> String {
+    let mut out = String::new();
+    for change in changes {
+        match (&change.status, &change.old, &change.new) {
+            (ChangeStatus::Added, _, Some(new)) => {
+                out.push_str(&format!(" create mode {} {}\n", new.mode, new.path));
+            }
+            (ChangeStatus::Deleted, Some(old), _) => {
+                out.push_str(&format!(" delete mode {} {}\n", old.mode, old.path));
+            }
+            (ChangeStatus::Renamed(score), Some(old), Some(new)) => {
+                out.push_str(&format!(" rename {} ({score}%)\n", display_path(change)));
+                if old.mode != new.mode {
+                    out.push_str(&format!(" mode change {} => {}\n", old.mode, new.mode));
+                }
+            }
+            (ChangeStatus::Copied(score), _, _) => {
+                out.push_str(&format!(" copy {} ({score}%)\n", display_path(change)));
+            }
+            (ChangeStatus::Modified, Some(old), Some(new)) if old.mode != new.mode => {
+                out.push_str(&format!(" mode change {} => {} {}\n", old.mode, new.mode, new.path));
+            }
+            _ => {}
+        }
+    }
+    out
+}
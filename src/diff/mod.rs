@@ -0,0 +1,436 @@
+pub mod algorithm;
+pub mod format;
+pub mod tree_diff;
+
+use crate::config::Config;
+use crate::utility::fs_utility::*;
+use crate::utility::other_util::*;
+use crate::index::*;
+use crate::object::{ read_blob, read_tree, TreeEntry };
+use crate::revision::*;
+
+pub use algorithm::{ diff_lines, split_lines, DiffAlgorithm, Edit };
+pub use tree_diff::{ diff_trees, detect_renames, TreeDiffOptions };
+
+use anyhow::{ bail, Result };
+use std::collections::BTreeMap;
+use std::fs;
+use std::os::unix::fs::{ MetadataExt, PermissionsExt };
+
+// Modes of tree entries
+pub const BLOB_MODE: &str = "100644";
+pub const EXEC_MODE: &str = "100755";
+pub const SYMLINK_MODE: &str = "120000";
+pub const GITLINK_MODE: &str = "160000";
+pub const TREE_MODE: &str = "40000";
+
+/// Abbreviated SHA length used in patches
+const ABBREV_LEN: usize = 7;
+
+/// Files of a tree-like source: <path> -> (<mode>, <SHA-1>)
+pub type FileMap = BTreeMap<String, (String, String)>;
+
+/// One side of a file change
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DiffEntry {
+    pub path: String,
+    pub mode: String,
+    pub sha: String,
+    /// Contents should be read from the working tree instead of the object store
+    pub in_worktree: bool,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ChangeStatus {
+    Added,
+    Deleted,
+    Modified,
+    TypeChanged,
+    /// Rename with similarity score in percents
+    Renamed(u8),
+    /// Copy with similarity score in percents
+    Copied(u8),
+}
+
+impl ChangeStatus {
+    /// Status as printed by `--name-status` and `diff-tree` (e.g. `M`, `R086`)
+    pub fn letter(&self) -> String {
+        match self {
+            ChangeStatus::Added => "A".to_string(),
+            ChangeStatus::Deleted => "D".to_string(),
+            ChangeStatus::Modified => "M".to_string(),
+            ChangeStatus::TypeChanged => "T".to_string(),
+            ChangeStatus::Renamed(score) => format!("R{score:03}"),
+            ChangeStatus::Copied(score) => format!("C{score:03}"),
+        }
+    }
+}
+
+/// Change of a single file between two sources
+#[derive(Clone, Debug)]
+pub struct FileChange {
+    pub status: ChangeStatus,
+    pub old: Option<DiffEntry>,
+    pub new: Option<DiffEntry>,
+}
+
+impl FileChange {
+    /// Path of the changed file (destination path for renames)
+    pub fn path(&self) -> &str {
+        self.new
+            .as_ref()
+            .or(self.old.as_ref())
+            .map(|e| e.path.as_str())
+            .unwrap_or_default()
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DiffOutput {
+    Raw,
+    Patch,
+    Stat,
+    NumStat,
+    NameStatus,
+    NameOnly,
+}
+
+#[derive(Clone, Debug)]
+pub struct DiffOptions {
+    pub algorithm: DiffAlgorithm,
+    /// Amount of context lines around changes
+    pub context: usize,
+    pub output: DiffOutput,
+    /// Minimal similarity (percent) for rename detection, None disables it
+    pub renames: Option<u8>,
+    /// Minimal similarity (percent) for copy detection, None disables it
+    pub copies: Option<u8>,
+    /// Use unmodified files as copy sources too
+    pub find_copies_harder: bool,
+    /// Limit comparison to these paths (empty means everything)
+    pub paths: Vec<String>,
+}
+
+impl Default for DiffOptions {
+    fn default() -> Self {
+        DiffOptions {
+            algorithm: DiffAlgorithm::Myers,
+            context: 3,
+            output: DiffOutput::Patch,
+            renames: Some(tree_diff::DEFAULT_RENAME_SCORE),
+            copies: None,
+            find_copies_harder: false,
+            paths: Vec::new(),
+        }
+    }
+}
+
+impl DiffOptions {
+    /// Options for comparing trees with the same pathspec and similarity settings
+    pub fn tree_options(&self, recursive: bool) -> TreeDiffOptions {
+        TreeDiffOptions {
+            recursive,
+            renames: self.renames,
+            copies: self.copies,
+            find_copies_harder: self.find_copies_harder,
+            paths: self.paths.clone(),
+        }
+    }
+}
+
+/// Shorten SHA for output
+pub fn abbrev(sha: &str) -> &str {
+    &sha[..ABBREV_LEN.min(sha.len())]
+}
+
+/// Check if path is selected by pathspec (prefix match on path components)
+pub fn matches_pathspec(path: &str, pathspec: &[String]) -> bool {
+    pathspec.is_empty() ||
+        pathspec.iter().any(|spec| {
+            let spec = spec.trim_end_matches('/');
+            spec.is_empty() ||
+                spec == "." ||
+                path == spec ||
+                (path.starts_with(spec) && path.as_bytes().get(spec.len()) == Some(&b'/'))
+        })
+}
+
+/// Recursively collect all files of the tree
+pub fn flatten_tree(tree_sha: &str) -> Result<FileMap> {
+    let mut files: FileMap = FileMap::new();
+    flatten_tree_into(tree_sha, "", &mut files)?;
+    Ok(files)
+}
+
+fn flatten_tree_into(tree_sha: &str, prefix: &str, files: &mut FileMap) -> Result<()> {
+    for entry in read_tree(tree_sha)?.entries {
+        let path = format!("{prefix}{}", entry.name_text());
+        let TreeEntry { mode, sha, .. } = entry;
+        if mode == TREE_MODE {
+            flatten_tree_into(&sha, &format!("{path}/"), files)?;
+        } else {
+            files.insert(path, (mode, sha));
+        }
+    }
+    Ok(())
+}
+
+/// Files staged in the index (conflicted entries are skipped)
+pub fn index_files(index: &Index) -> FileMap {
+    index
+        .stage0()
+        .map(|e| (e.path.clone(), (e.mode_string(), e.sha.clone())))
+        .collect()
+}
+
+/// Mode of the working tree file as it would be recorded in a tree
+pub fn worktree_mode(meta: &fs::Metadata) -> &'static str {
+    if meta.file_type().is_symlink() {
+        SYMLINK_MODE
+    } else if meta.is_dir() {
+        GITLINK_MODE
+    } else if (meta.permissions().mode() & 0o111) != 0 {
+        EXEC_MODE
+    } else {
+        BLOB_MODE
+    }
+}
+
+/// Abilities of the working tree from `core.filemode` and `core.symlinks` (both default to true)
+#[derive(Clone, Copy, Debug)]
+pub struct WorktreeModes {
+    pub filemode: bool,
+    pub symlinks: bool,
+}
+
+impl WorktreeModes {
+    pub fn load() -> Result<WorktreeModes> {
+        let config = Config::load()?;
+        Ok(WorktreeModes {
+            filemode: config.get_bool("core.filemode")?.unwrap_or(true),
+            symlinks: config.get_bool("core.symlinks")?.unwrap_or(true),
+        })
+    }
+}
+
+/// Mode to record for the working tree file, untrusted exec bits and symlinks keep the tracked mode
+pub fn recorded_mode(
+    meta: &fs::Metadata,
+    tracked: Option<&str>,
+    modes: WorktreeModes
+) -> &'static str {
+    match (worktree_mode(meta), tracked) {
+        (BLOB_MODE | EXEC_MODE, Some(SYMLINK_MODE)) if !modes.symlinks => SYMLINK_MODE,
+        (BLOB_MODE | EXEC_MODE, Some(EXEC_MODE)) if !modes.filemode => EXEC_MODE,
+        (EXEC_MODE, _) if !modes.filemode => BLOB_MODE,
+        (mode, _) => mode,
+    }
+}
+
+/// Read file from working tree the way it is stored in a blob (symlinks store their target)
+pub fn read_worktree_file(path: &str) -> Result<Vec<u8>> {
+    let full_path = find_root_folder()? + path;
+    let meta = fs::symlink_metadata(&full_path)?;
+    if meta.file_type().is_symlink() {
+        Ok(fs::read_link(&full_path)?.to_string_lossy().as_bytes().to_vec())
+    } else {
+        Ok(fs::read(&full_path)?)
+    }
+}
+
+/// Hash working tree versions of tracked files (missing files are not included)
+pub fn worktree_files(index: &Index, tracked: &FileMap) -> Result<FileMap> {
+    let root = find_root_folder()?;
+    let modes = WorktreeModes::load()?;
+    let stat_info: BTreeMap<&str, &IndexEntry> = index
+        .stage0()
+        .map(|e| (e.path.as_str(), e))
+        .collect();
+
+    let mut files: FileMap = FileMap::new();
+    for (path, (tracked_mode, tracked_sha)) in tracked {
+        let meta = match fs::symlink_metadata(format!("{root}{path}")) {
+            Ok(meta) => meta,
+            Err(_) => {
+                continue;
+            }
+        };
+        let mode = recorded_mode(&meta, Some(tracked_mode), modes);
+
+        // Submodules are compared by recorded commit only
+        if tracked_mode == GITLINK_MODE || mode == GITLINK_MODE {
+            if tracked_mode == GITLINK_MODE && mode == GITLINK_MODE {
+                files.insert(path.clone(), (tracked_mode.clone(), tracked_sha.clone()));
+            }
+            continue;
+        }
+
+        // Skip hashing if stat data matches the index
+        if let Some(entry) = stat_info.get(path.as_str()) {
+            let same_stat =
+                entry.mtime == (meta.mtime() as u32, meta.mtime_nsec() as u32) &&
+                entry.size == (meta.size() as u32) &&
+                entry.mode_string() == mode;
+            if same_stat {
+                files.insert(path.clone(), (mode.to_string(), entry.sha.clone()));
+                continue;
+            }
+        }
+
+        let sha = hash_blob(&read_worktree_file(path)?);
+        files.insert(path.clone(), (mode.to_string(), sha));
+    }
+
+    Ok(files)
+}
+
+/// Kind of the tree entry (changing it is a type change rather than modification)
+pub(crate) fn mode_kind(mode: &str) -> &str {
+    match mode {
+        SYMLINK_MODE => "link",
+        GITLINK_MODE => "commit",
+        _ => "blob",
+    }
+}
+
+/// Compare two file sets and return changes sorted by path
+pub fn compare_file_maps(
+    old: &FileMap,
+    new: &FileMap,
+    new_in_worktree: bool,
+    pathspec: &[String]
+) -> Vec<FileChange> {
+    let make_entry = |path: &str, (mode, sha): &(String, String), in_worktree: bool| DiffEntry {
+        path: path.to_string(),
+        mode: mode.clone(),
+        sha: sha.clone(),
+        in_worktree,
+    };
+
+    let mut paths: Vec<&String> = old.keys().chain(new.keys()).collect();
+    paths.sort();
+    paths.dedup();
+
+    let mut changes: Vec<FileChange> = Vec::new();
+    for path in paths {
+        if !matches_pathspec(path, pathspec) {
+            continue;
+        }
+        let change = match (old.get(path), new.get(path)) {
+            (Some(o), None) =>
+                FileChange {
+                    status: ChangeStatus::Deleted,
+                    old: Some(make_entry(path, o, false)),
+                    new: None,
+                },
+            (None, Some(n)) =>
+                FileChange {
+                    status: ChangeStatus::Added,
+                    old: None,
+                    new: Some(make_entry(path, n, new_in_worktree)),
+                },
+            (Some(o), Some(n)) if o != n => {
+                let status = if mode_kind(&o.0) != mode_kind(&n.0) {
+                    ChangeStatus::TypeChanged
+                } else {
+                    ChangeStatus::Modified
+                };
+                FileChange {
+                    status,
+                    old: Some(make_entry(path, o, false)),
+                    new: Some(make_entry(path, n, new_in_worktree)),
+                }
+            }
+            _ => {
+                continue;
+            }
+        };
+        changes.push(change);
+    }
+
+    changes
+}
+
+/// Read contents of one side of the change
+pub fn load_contents(entry: &DiffEntry) -> Result<Vec<u8>> {
+    if entry.mode == GITLINK_MODE {
+        return Ok(format!("Subproject commit {}\n", entry.sha).into_bytes());
+    }
+    if entry.in_worktree {
+        return read_worktree_file(&entry.path);
+    }
+
+    Ok(read_blob(&entry.sha)?.data)
+}
+
+/// Format changes according to selected output
+pub fn format_changes(changes: &[FileChange], options: &DiffOptions) -> Result<String> {
+    match options.output {
+        DiffOutput::Raw => Ok(tree_diff::format_raw(changes)),
+        DiffOutput::Patch => format::patch(changes, options),
+        DiffOutput::Stat => format::stat(changes, options),
+        DiffOutput::NumStat => format::numstat(changes, options),
+        DiffOutput::NameStatus => Ok(format::name_status(changes)),
+        DiffOutput::NameOnly => Ok(format::name_only(changes)),
+    }
+}
+
+/// Files of HEAD commit (empty on unborn branch)
+fn head_files() -> Result<FileMap> {
+    match read_head_commit()? {
+        Some(commit) => flatten_tree(&resolve_tree_ish(&commit)?),
+        None => Ok(FileMap::new()),
+    }
+}
+
+/// Compute changes for `diff` command
+/// No revisions: index -> working tree; `--cached [<rev>]`: tree -> index;
+/// one revision: tree -> working tree; two revisions: tree -> tree
+pub fn collect_changes(
+    revisions: &[String],
+    cached: bool,
+    options: &DiffOptions
+) -> Result<Vec<FileChange>> {
+    // Allow <a>..<b> form for two trees
+    let revisions: Vec<String> = match revisions {
+        [single] if single.contains("..") => {
+            let (from, to) = single.split_once("..").unwrap_or_default();
+            let from = if from.is_empty() { "HEAD" } else { from };
+            let to = if to.is_empty() { "HEAD" } else { to };
+            vec![from.to_string(), to.to_string()]
+        }
+        _ => revisions.to_vec(),
+    };
+
+    let (old, new, in_worktree) = match (cached, revisions.as_slice()) {
+        (true, []) => (head_files()?, index_files(&read_index()?), false),
+        (true, [rev]) => (flatten_tree(&resolve_tree_ish(rev)?)?, index_files(&read_index()?), false),
+        (false, []) => {
+            let index = read_index()?;
+            let staged = index_files(&index);
+            let worktree = worktree_files(&index, &staged)?;
+            (staged, worktree, true)
+        }
+        (false, [rev]) => {
+            let index = read_index()?;
+            let old = flatten_tree(&resolve_tree_ish(rev)?)?;
+            let mut tracked = index_files(&index);
+            for (path, entry) in &old {
+                tracked.entry(path.clone()).or_insert_with(|| entry.clone());
+            }
+            let worktree = worktree_files(&index, &tracked)?;
+            (old, worktree, true)
+        }
+        (false, [from, to]) => {
+            // Trees are compared directly without flattening
+            let old = resolve_tree_ish(from)?;
+            let new = resolve_tree_ish(to)?;
+            return diff_trees(Some(&old), Some(&new), &options.tree_options(true));
+        }
+        _ => bail!("Unsupported combination of diff arguments!"),
+    };
+
+    let changes = compare_file_maps(&old, &new, in_worktree, &options.paths);
+    let copy_sources = if options.find_copies_harder { old } else { FileMap::new() };
+    detect_renames(changes, options.renames, options.copies, &copy_sources)
+}
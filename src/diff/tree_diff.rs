@@ -0,0 +1,409 @@
+use super::*;
+
+use anyhow::{ bail, Result };
+use std::cmp::Ordering;
+use std::collections::HashMap;
+
+/// Similarity scores are computed in the same units as in git
+const MAX_SCORE: usize = 60000;
+/// Lines longer than this are split into several chunks for similarity
+const MAX_CHUNK_L: usize = 64;
+pub const DEFAULT_RENAME_SCORE: u8 = 50;
+
+#[derive(Clone, Debug, Default)]
+pub struct TreeDiffOptions {
+    /// Descend into subtrees (otherwise changed subtrees are reported as entries)
+    pub recursive: bool,
+    /// Minimal similarity (percent) for rename detection
+    pub renames: Option<u8>,
+    /// Minimal similarity (percent) for copy detection
+    pub copies: Option<u8>,
+    /// Use unmodified files as copy sources too
+    pub find_copies_harder: bool,
+    pub paths: Vec<String>,
+}
+
+/// Parse similarity threshold as in `-M50%`, `-M5` (= 50%) or `-M075`
+pub fn parse_score(text: &str) -> Result<u8> {
+    if text.is_empty() {
+        return Ok(DEFAULT_RENAME_SCORE);
+    }
+    let (digits, percent) = match text.strip_suffix('%') {
+        Some(digits) => (digits, true),
+        None => (text, false),
+    };
+    if digits.is_empty() || !digits.chars().all(|c| c.is_ascii_digit()) {
+        bail!("Incorrect similarity score '{text}'");
+    }
+
+    let value: f64 = digits.parse()?;
+    let score = if percent { value } else { (value / (10f64).powi(digits.len() as i32)) * 100.0 };
+    if score > 100.0 {
+        bail!("Similarity score '{text}' is above 100%");
+    }
+    Ok(score as u8)
+}
+
+/// Compare two trees (None stands for an empty tree)
+pub fn diff_trees(
+    old_tree: Option<&str>,
+    new_tree: Option<&str>,
+    options: &TreeDiffOptions
+) -> Result<Vec<FileChange>> {
+    let mut changes: Vec<FileChange> = Vec::new();
+    walk_trees(old_tree, new_tree, "", options, &mut changes)?;
+
+    if options.renames.is_some() || options.copies.is_some() {
+        let copy_sources = match (options.find_copies_harder, old_tree) {
+            (true, Some(tree)) => flatten_tree(tree)?,
+            _ => FileMap::new(),
+        };
+        changes = detect_renames(changes, options.renames, options.copies, &copy_sources)?;
+    }
+
+    Ok(changes)
+}
+
+/// Tree entries in git order: (<name>, <mode>, <SHA-1>)
+fn read_tree_entries(tree: Option<&str>) -> Result<Vec<(String, String, String)>> {
+    match tree {
+        Some(sha) => {
+            Ok(
+                read_tree(sha)?.entries
+                    .into_iter()
+                    .map(|e| (e.name_text().into_owned(), e.mode, e.sha))
+                    .collect()
+            )
+        }
+        None => Ok(Vec::new()),
+    }
+}
+
+/// Compare names the way they are sorted in trees (directories have implicit '/')
+fn compare_entries(a: &(String, String, String), b: &(String, String, String)) -> Ordering {
+    let key = |(name, mode, _): &(String, String, String)| {
+        let mut key = name.as_bytes().to_vec();
+        if mode == TREE_MODE {
+            key.push(b'/');
+        }
+        key
+    };
+    key(a).cmp(&key(b))
+}
+
+/// Check if something inside the directory could be selected by pathspec
+fn pathspec_reaches_dir(dir: &str, pathspec: &[String]) -> bool {
+    matches_pathspec(dir, pathspec) ||
+        pathspec.iter().any(|spec| spec.starts_with(&format!("{dir}/")))
+}
+
+/// Walk both trees in lock-step, skipping identical entries
+fn walk_trees(
+    old_tree: Option<&str>,
+    new_tree: Option<&str>,
+    prefix: &str,
+    options: &TreeDiffOptions,
+    out: &mut Vec<FileChange>
+) -> Result<()> {
+    let old_entries = read_tree_entries(old_tree)?;
+    let new_entries = read_tree_entries(new_tree)?;
+
+    let (mut i, mut j) = (0, 0);
+    while i < old_entries.len() || j < new_entries.len() {
+        let order = match (old_entries.get(i), new_entries.get(j)) {
+            (Some(a), Some(b)) => compare_entries(a, b),
+            (Some(_), None) => Ordering::Less,
+            _ => Ordering::Greater,
+        };
+        let (old, new) = match order {
+            Ordering::Less => {
+                i += 1;
+                (old_entries.get(i - 1), None)
+            }
+            Ordering::Greater => {
+                j += 1;
+                (None, new_entries.get(j - 1))
+            }
+            Ordering::Equal => {
+                i += 1;
+                j += 1;
+                (old_entries.get(i - 1), new_entries.get(j - 1))
+            }
+        };
+
+        // Identical entries (including whole subtrees) are skipped
+        if let (Some(a), Some(b)) = (old, new) {
+            if a.1 == b.1 && a.2 == b.2 {
+                continue;
+            }
+        }
+
+        let (name, mode, _) = old.or(new).expect("At least one side is present");
+        let path = format!("{prefix}{name}");
+        let is_tree = mode == TREE_MODE;
+
+        if is_tree && options.recursive {
+            if pathspec_reaches_dir(&path, &options.paths) {
+                walk_trees(
+                    old.map(|e| e.2.as_str()),
+                    new.map(|e| e.2.as_str()),
+                    &format!("{path}/"),
+                    options,
+                    out
+                )?;
+            }
+            continue;
+        }
+        if !matches_pathspec(&path, &options.paths) {
+            continue;
+        }
+
+        let make_entry = |(_, mode, sha): &(String, String, String)| DiffEntry {
+            path: path.clone(),
+            mode: mode.clone(),
+            sha: sha.clone(),
+            in_worktree: false,
+        };
+        let status = match (old, new) {
+            (None, _) => ChangeStatus::Added,
+            (_, None) => ChangeStatus::Deleted,
+            (Some(a), Some(b)) if mode_kind(&a.1) != mode_kind(&b.1) => ChangeStatus::TypeChanged,
+            _ => ChangeStatus::Modified,
+        };
+        out.push(FileChange { status, old: old.map(make_entry), new: new.map(make_entry) });
+    }
+
+    Ok(())
+}
+
+/// Chunk statistics of contents: <chunk> -> <amount of bytes>
+fn chunk_counts(data: &[u8]) -> HashMap<&[u8], usize> {
+    let mut counts: HashMap<&[u8], usize> = HashMap::new();
+    for line in data.split_inclusive(|c| *c == b'\n') {
+        for chunk in line.chunks(MAX_CHUNK_L) {
+            *counts.entry(chunk).or_insert(0) += chunk.len();
+        }
+    }
+    counts
+}
+
+/// Similarity of two contents in git score units (0..=MAX_SCORE)
+fn similarity_score(src: &[u8], dst: &[u8], minimal_percent: u8) -> usize {
+    let max_size = src.len().max(dst.len());
+    let min_size = src.len().min(dst.len());
+    if max_size == 0 {
+        return MAX_SCORE;
+    }
+
+    // Too different sizes cannot reach minimal score
+    let minimal_score = ((minimal_percent as usize) * MAX_SCORE) / 100;
+    if max_size * (MAX_SCORE - minimal_score) < (max_size - min_size) * MAX_SCORE {
+        return 0;
+    }
+
+    let src_counts = chunk_counts(src);
+    let copied: usize = chunk_counts(dst)
+        .into_iter()
+        .map(|(chunk, count)| count.min(src_counts.get(chunk).copied().unwrap_or(0)))
+        .sum();
+    (copied * MAX_SCORE) / max_size
+}
+
+/// Pair deleted and added files into renames (and added files with sources into copies)
+pub fn detect_renames(
+    changes: Vec<FileChange>,
+    rename_score: Option<u8>,
+    copy_score: Option<u8>,
+    unchanged_sources: &FileMap
+) -> Result<Vec<FileChange>> {
+    // Copies imply renames
+    let rename_score = match (rename_score, copy_score) {
+        (None, Some(score)) => Some(score),
+        _ => rename_score,
+    };
+    let Some(rename_score) = rename_score else {
+        return Ok(changes);
+    };
+
+    let is_file = |entry: &DiffEntry| mode_kind(&entry.mode) == "blob" && entry.mode != TREE_MODE;
+    let deleted: Vec<usize> = (0..changes.len())
+        .filter(|idx| changes[*idx].status == ChangeStatus::Deleted)
+        .filter(|idx| changes[*idx].old.as_ref().is_some_and(is_file))
+        .collect();
+    let added: Vec<usize> = (0..changes.len())
+        .filter(|idx| changes[*idx].status == ChangeStatus::Added)
+        .filter(|idx| changes[*idx].new.as_ref().is_some_and(is_file))
+        .collect();
+
+    // <added change id> -> (<status>, <source entry>)
+    let mut matched: HashMap<usize, (ChangeStatus, DiffEntry)> = HashMap::new();
+    let mut used_sources: Vec<usize> = Vec::new();
+
+    // Exact renames first (prefer sources with the same file name)
+    for &add_idx in &added {
+        let new = changes[add_idx].new.as_ref().expect("Added change has new side");
+        let base_name = |path: &str| path.rsplit('/').next().unwrap_or(path).to_string();
+        let candidates: Vec<usize> = deleted
+            .iter()
+            .copied()
+            .filter(|del_idx| !used_sources.contains(del_idx))
+            .filter(|del_idx| changes[*del_idx].old.as_ref().is_some_and(|o| o.sha == new.sha))
+            .collect();
+        let best = candidates
+            .iter()
+            .find(|del_idx| {
+                let old = changes[**del_idx].old.as_ref().expect("Deleted change has old side");
+                base_name(&old.path) == base_name(&new.path)
+            })
+            .or(candidates.first());
+        if let Some(&del_idx) = best {
+            used_sources.push(del_idx);
+            let old = changes[del_idx].old.clone().expect("Deleted change has old side");
+            matched.insert(add_idx, (ChangeStatus::Renamed(100), old));
+        }
+    }
+
+    // Inexact renames: best scoring pairs win
+    let mut contents: HashMap<String, Vec<u8>> = HashMap::new();
+    let mut load = |entry: &DiffEntry| -> Result<Vec<u8>> {
+        if let Some(data) = contents.get(&entry.sha) {
+            return Ok(data.clone());
+        }
+        let data = load_contents(entry)?;
+        contents.insert(entry.sha.clone(), data.clone());
+        Ok(data)
+    };
+    let mut scored: Vec<(usize, usize, usize)> = Vec::new();
+    for &add_idx in added.iter().filter(|idx| !matched.contains_key(idx)) {
+        let new_data = load(changes[add_idx].new.as_ref().expect("Added change has new side"))?;
+        for &del_idx in deleted.iter().filter(|idx| !used_sources.contains(idx)) {
+            let old_data = load(changes[del_idx].old.as_ref().expect("Deleted change has old side"))?;
+            let score = similarity_score(&old_data, &new_data, rename_score);
+            if score * 100 >= (rename_score as usize) * MAX_SCORE && score > 0 {
+                scored.push((score, del_idx, add_idx));
+            }
+        }
+    }
+    scored.sort_by(|a, b| b.0.cmp(&a.0).then(a.1.cmp(&b.1)).then(a.2.cmp(&b.2)));
+    for (score, del_idx, add_idx) in scored {
+        if used_sources.contains(&del_idx) || matched.contains_key(&add_idx) {
+            continue;
+        }
+        used_sources.push(del_idx);
+        let old = changes[del_idx].old.clone().expect("Deleted change has old side");
+        let percent = ((score * 100) / MAX_SCORE) as u8;
+        matched.insert(add_idx, (ChangeStatus::Renamed(percent), old));
+    }
+
+    // Copies: sources are modified, deleted and (optionally) unchanged files
+    if let Some(copy_score) = copy_score {
+        let mut sources: Vec<DiffEntry> = changes
+            .iter()
+            .filter(|c| matches!(c.status, ChangeStatus::Modified | ChangeStatus::Deleted))
+            .filter_map(|c| c.old.clone())
+            .filter(is_file)
+            .collect();
+        for (path, (mode, sha)) in unchanged_sources {
+            if !sources.iter().any(|s| &s.path == path) && !changes.iter().any(|c| c.path() == path) {
+                sources.push(DiffEntry {
+                    path: path.clone(),
+                    mode: mode.clone(),
+                    sha: sha.clone(),
+                    in_worktree: false,
+                });
+            }
+        }
+
+        let unmatched: Vec<usize> = added
+            .iter()
+            .copied()
+            .filter(|idx| !matched.contains_key(idx))
+            .collect();
+        for add_idx in unmatched {
+            let new_data = load(changes[add_idx].new.as_ref().expect("Added change has new side"))?;
+            let mut best: Option<(usize, &DiffEntry)> = None;
+            for source in sources.iter().filter(|s| is_file(s)) {
+                let score = similarity_score(&load(source)?, &new_data, copy_score);
+                let better = match best {
+                    Some((best_score, _)) => score > best_score,
+                    None => score > 0,
+                };
+                if score * 100 >= (copy_score as usize) * MAX_SCORE && better {
+                    best = Some((score, source));
+                }
+            }
+            if let Some((score, source)) = best {
+                let percent = ((score * 100) / MAX_SCORE) as u8;
+                matched.insert(add_idx, (ChangeStatus::Copied(percent), source.clone()));
+            }
+        }
+    }
+
+    // Rebuild list: matched additions turn into renames/copies, used deletions disappear
+    let mut result: Vec<FileChange> = Vec::new();
+    for (idx, change) in changes.into_iter().enumerate() {
+        if used_sources.contains(&idx) {
+            continue;
+        }
+        match matched.remove(&idx) {
+            Some((status, old)) => result.push(FileChange { status, old: Some(old), new: change.new }),
+            None => result.push(change),
+        }
+    }
+    result.sort_by(|a, b| a.path().cmp(b.path()));
+
+    Ok(result)
+}
+
+/// Mode in raw output (6 digits, zeros for missing side)
+fn raw_mode(entry: Option<&DiffEntry>) -> String {
+    format!("{:0>6}", entry.map_or("0", |e| e.mode.as_str()))
+}
+
+/// Raw `diff-tree` output (`:<old mode> <new mode> <old sha> <new sha> <status>\t<path>`)
+pub fn format_raw(changes: &[FileChange]) -> String {
+    let null_sha = "0".repeat(40);
+    let mut out = String::new();
+    for change in changes {
+        out.push_str(
+            &format!(
+                ":{} {} {} {} {}",
+                raw_mode(change.old.as_ref()),
+                raw_mode(change.new.as_ref()),
+                change.old.as_ref().map_or(null_sha.as_str(), |e| e.sha.as_str()),
+                change.new.as_ref().map_or(null_sha.as_str(), |e| e.sha.as_str()),
+                change.status.letter()
+            )
+        );
+        match (&change.status, &change.old) {
+            (ChangeStatus::Renamed(_) | ChangeStatus::Copied(_), Some(old)) => {
+                out.push_str(&format!("\t{}\t{}\n", old.path, change.path()));
+            }
+            _ => out.push_str(&format!("\t{}\n", change.path())),
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn score_parsing() {
+        assert_eq!(parse_score("").unwrap(), 50);
+        assert_eq!(parse_score("90%").unwrap(), 90);
+        assert_eq!(parse_score("5").unwrap(), 50);
+        assert_eq!(parse_score("075").unwrap(), 7);
+        assert!(parse_score("abc").is_err());
+    }
+
+    #[test]
+    fn similarity_of_contents() {
+        let src = b"line 1\nline 2\nline 3\nline 4\n";
+        assert_eq!(similarity_score(src, src, 0), MAX_SCORE);
+        assert_eq!(similarity_score(src, b"line 1\nline 2\n", 0), MAX_SCORE / 2);
+        assert_eq!(similarity_score(src, b"line 1\nline 2\n", 60), 0);
+        assert_eq!(similarity_score(src, b"other\n", 0), 0);
+    }
+}
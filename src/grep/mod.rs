@@ -0,0 +1,255 @@
+use crate::diff::{ flatten_tree, index_files, matches_pathspec, FileMap };
+use crate::diff::{ GITLINK_MODE, SYMLINK_MODE };
+use crate::index::read_index;
+use crate::object::read_blob;
+use crate::revision::resolve_tree_ish;
+use crate::utility::fs_utility::find_root_folder;
+
+use anyhow::{ bail, Result };
+use regex::bytes::{ Regex, RegexBuilder };
+use std::path::Path;
+use std::thread;
+
+/// Syntax of the patterns
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum PatternSyntax {
+    /// POSIX basic regular expressions (`+`, `?`, `|`, groups and braces need a backslash)
+    #[default]
+    Basic,
+    /// Extended regular expressions (`-E`)
+    Extended,
+    /// Fixed strings (`-F`)
+    Fixed,
+}
+
+/// Options of `grep`
+#[derive(Clone, Debug, Default)]
+pub struct GrepOptions {
+    /// Lines matching any of the patterns are shown
+    pub patterns: Vec<String>,
+    /// Trees to search instead of the working tree, followed by paths
+    pub args: Vec<String>,
+    /// Paths given after `--`
+    pub paths: Vec<String>,
+    /// Search the index instead of the working tree
+    pub cached: bool,
+    pub syntax: PatternSyntax,
+    pub ignore_case: bool,
+    /// Matches must be whole words (`-w`)
+    pub word: bool,
+    /// Prefix lines with their number (`-n`)
+    pub line_number: bool,
+    /// Only show names of matching files (`-l`)
+    pub files_with_matches: bool,
+    /// Show the number of matching lines of each file (`-c`)
+    pub count: bool,
+}
+
+/// File to search, `name` is the path shown in the output
+struct Target {
+    name: String,
+    source: Source,
+}
+
+enum Source {
+    Worktree(String),
+    Blob(String),
+}
+
+/// Rewrite a basic regular expression in the extended syntax
+fn basic_to_extended(pattern: &str) -> String {
+    let mut out = String::new();
+    let mut chars = pattern.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => match chars.next() {
+                Some(c @ ('+' | '?' | '|' | '(' | ')' | '{' | '}')) => out.push(c),
+                Some('<' | '>') => out.push_str("\\b"),
+                Some(c) => {
+                    out.push('\\');
+                    out.push(c);
+                }
+                None => out.push_str("\\\\"),
+            },
+            '+' | '?' | '|' | '(' | ')' | '{' | '}' => {
+                out.push('\\');
+                out.push(c);
+            }
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+fn compile(options: &GrepOptions) -> Result<Regex> {
+    if options.patterns.is_empty() {
+        bail!("no pattern given");
+    }
+    let patterns: Vec<String> = options.patterns
+        .iter()
+        .map(|pattern| match options.syntax {
+            PatternSyntax::Basic => basic_to_extended(pattern),
+            PatternSyntax::Extended => pattern.clone(),
+            PatternSyntax::Fixed => regex::escape(pattern),
+        })
+        .map(|pattern| format!("(?:{pattern})"))
+        .collect();
+    Ok(RegexBuilder::new(&patterns.join("|")).case_insensitive(options.ignore_case).build()?)
+}
+
+fn is_word_byte(byte: Option<&u8>) -> bool {
+    byte.is_some_and(|byte| byte.is_ascii_alphanumeric() || *byte == b'_')
+}
+
+/// Whether the line matches, with `word` a match must not touch other word characters
+fn line_matches(regex: &Regex, line: &[u8], word: bool) -> bool {
+    if !word {
+        return regex.is_match(line);
+    }
+    let mut start = 0;
+    while let Some(found) = regex.find_at(line, start) {
+        let before = found.start().checked_sub(1).and_then(|i| line.get(i));
+        if found.start() < found.end() && !is_word_byte(before) &&
+            !is_word_byte(line.get(found.end())) {
+            return true;
+        }
+        start = found.start() + 1;
+        if start > line.len() {
+            break;
+        }
+    }
+    false
+}
+
+/// Output for one file
+fn grep_file(target: &Target, regex: &Regex, options: &GrepOptions) -> Result<String> {
+    let data = match &target.source {
+        Source::Worktree(path) => match std::fs::read(path) {
+            Ok(data) => data,
+            // Tracked files missing from the working tree are skipped
+            Err(_) => return Ok(String::new()),
+        },
+        Source::Blob(sha) => read_blob(sha)?.data,
+    };
+    let lines: Vec<(usize, &[u8])> = data
+        .split_inclusive(|byte| *byte == b'\n')
+        .map(|line| line.strip_suffix(b"\n").unwrap_or(line))
+        .enumerate()
+        .filter(|(_, line)| line_matches(regex, line, options.word))
+        .collect();
+    if lines.is_empty() {
+        return Ok(String::new());
+    }
+
+    let name = &target.name;
+    Ok(
+        if options.files_with_matches {
+            format!("{name}\n")
+        } else if options.count {
+            format!("{name}:{}\n", lines.len())
+        } else if data.contains(&0) {
+            format!("Binary file {name} matches\n")
+        } else {
+            let mut out = String::new();
+            for (number, line) in lines {
+                out.push_str(name);
+                if options.line_number {
+                    out.push_str(&format!(":{}", number + 1));
+                }
+                out.push_str(&format!(":{}\n", String::from_utf8_lossy(line)));
+            }
+            out
+        }
+    )
+}
+
+/// Split arguments into trees and paths, leading arguments resolving to trees are trees, all
+/// of them when paths are separated by `--`
+fn split_args(options: &GrepOptions) -> Result<(Vec<String>, Vec<String>)> {
+    if !options.paths.is_empty() {
+        return Ok((options.args.clone(), options.paths.clone()));
+    }
+    let trees: Vec<String> = options.args
+        .iter()
+        .take_while(|arg| resolve_tree_ish(arg).is_ok())
+        .cloned()
+        .collect();
+    let paths = options.args[trees.len()..].to_vec();
+    let root = find_root_folder()?;
+    for path in &paths {
+        if !Path::new(&format!("{root}{path}")).exists() {
+            bail!(
+                "ambiguous argument '{path}': unknown revision or path not in the working tree."
+            );
+        }
+    }
+    Ok((trees, paths))
+}
+
+/// Files of the working tree, the index or the trees to search
+fn targets(options: &GrepOptions) -> Result<Vec<Target>> {
+    let (trees, paths) = split_args(options)?;
+    if options.cached && !trees.is_empty() {
+        bail!("both --cached and trees are given");
+    }
+    let searched = |files: FileMap, prefix: &str, source: &dyn Fn(String, String) -> Source| {
+        files
+            .into_iter()
+            .filter(|(path, (mode, _))| {
+                mode != GITLINK_MODE && matches_pathspec(path, &paths)
+            })
+            .map(|(path, (_, sha))| Target {
+                name: format!("{prefix}{path}"),
+                source: source(path, sha),
+            })
+            .collect::<Vec<_>>()
+    };
+
+    if !trees.is_empty() {
+        let mut targets = Vec::new();
+        for tree in &trees {
+            let files = flatten_tree(&resolve_tree_ish(tree)?)?;
+            targets.extend(searched(files, &format!("{tree}:"), &|_, sha| Source::Blob(sha)));
+        }
+        return Ok(targets);
+    }
+    let files = index_files(&read_index()?);
+    if options.cached {
+        return Ok(searched(files, "", &|_, sha| Source::Blob(sha)));
+    }
+    let root = find_root_folder()?;
+    let files = files
+        .into_iter()
+        .filter(|(_, (mode, _))| mode != SYMLINK_MODE)
+        .collect();
+    Ok(searched(files, "", &|path, _| Source::Worktree(format!("{root}{path}"))))
+}
+
+/// Search lines of tracked files, files are searched on several threads
+pub fn grep(options: &GrepOptions) -> Result<String> {
+    let regex = compile(options)?;
+    let targets = targets(options)?;
+    let workers = thread::available_parallelism().map(|n| n.get()).unwrap_or(1);
+    let chunk_size = targets.len().div_ceil(workers).max(1);
+
+    // Chunks keep their order, so the output does not depend on the scheduling
+    let outputs: Vec<Result<String>> = thread::scope(|scope| {
+        let handles: Vec<_> = targets
+            .chunks(chunk_size)
+            .map(|chunk| {
+                let regex = &regex;
+                scope.spawn(move || {
+                    chunk
+                        .iter()
+                        .map(|target| grep_file(target, regex, options))
+                        .collect::<Result<String>>()
+                })
+            })
+            .collect();
+        handles
+            .into_iter()
+            .map(|handle| handle.join().unwrap_or_else(|_| bail!("grep worker panicked")))
+            .collect()
+    });
+    outputs.into_iter().collect()
+}
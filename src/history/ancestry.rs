@@ -0,0 +1,248 @@
+use crate::commit_graph::CommitGraph;
+use crate::object::{ read_commit, Commit };
+use crate::refs::{ read_ref, read_reflog, ZERO_SHA };
+use crate::revision::reflog_ref;
+
+use anyhow::{ anyhow, Result };
+use std::collections::{ BinaryHeap, HashMap, HashSet };
+
+// Marks of the merge base walk
+const PARENT1: u8 = 1;
+const PARENT2: u8 = 2;
+const STALE: u8 = 4;
+const RESULT: u8 = 8;
+
+/// Commit data used by ancestry queries
+#[derive(Clone, Debug)]
+struct CommitNode {
+    parents: Vec<String>,
+    time: i64,
+    /// 1 for root commits, otherwise one more than the highest generation of the parents
+    generation: u32,
+}
+
+/// Commits loaded for ancestry queries, generation numbers let walks stop early:
+/// a commit is never reachable from a commit of the same or lower generation
+#[derive(Debug)]
+pub struct CommitWalker {
+    nodes: HashMap<String, CommitNode>,
+    /// Commits of the graph come with their generation, without reading the objects
+    graph: Option<CommitGraph>,
+}
+
+impl CommitWalker {
+    pub fn new() -> Result<CommitWalker> {
+        Ok(CommitWalker { nodes: HashMap::new(), graph: CommitGraph::load()? })
+    }
+
+    /// Load the commit together with its ancestors missing a generation number
+    fn node(&mut self, sha: &str) -> Result<&CommitNode> {
+        // Parents are finished before their children, without recursion
+        let mut stack: Vec<(String, Option<Commit>)> = vec![(sha.to_string(), None)];
+        while let Some((current, loaded)) = stack.pop() {
+            if self.nodes.contains_key(&current) {
+                continue;
+            }
+            let stored = match &self.graph {
+                Some(graph) if loaded.is_none() => graph.commit(&current)?,
+                _ => None,
+            };
+            if let Some(stored) = stored.filter(|stored| stored.generation > 0) {
+                let node = CommitNode {
+                    parents: stored.parents,
+                    time: stored.time,
+                    generation: stored.generation,
+                };
+                self.nodes.insert(current, node);
+                continue;
+            }
+            let commit = match loaded {
+                Some(commit) => commit,
+                None => read_commit(&current)?,
+            };
+            let missing: Vec<String> = commit.parents
+                .iter()
+                .filter(|parent| !self.nodes.contains_key(*parent))
+                .cloned()
+                .collect();
+            if !missing.is_empty() {
+                stack.push((current, Some(commit)));
+                stack.extend(missing.into_iter().map(|parent| (parent, None)));
+                continue;
+            }
+            let generation = commit.parents
+                .iter()
+                .map(|parent| self.nodes[parent].generation)
+                .max()
+                .unwrap_or(0) + 1;
+            let node = CommitNode {
+                parents: commit.parents,
+                time: commit.committer.time,
+                generation,
+            };
+            self.nodes.insert(current, node);
+        }
+        Ok(&self.nodes[sha])
+    }
+
+    pub fn generation(&mut self, sha: &str) -> Result<u32> {
+        Ok(self.node(sha)?.generation)
+    }
+
+    /// Queue key: highest generation first, then newest
+    fn key(&mut self, sha: &str) -> Result<(u32, i64, String)> {
+        let node = self.node(sha)?;
+        Ok((node.generation, node.time, sha.to_string()))
+    }
+
+    /// Common ancestors of `one` and any of `others` not reachable from another found one
+    /// (may still be reachable from each other)
+    fn paint_down_to_common(&mut self, one: &str, others: &[String]) -> Result<Vec<String>> {
+        let mut marks: HashMap<String, u8> = HashMap::new();
+        let mut queue: BinaryHeap<(u32, i64, String)> = BinaryHeap::new();
+        marks.insert(one.to_string(), PARENT1);
+        queue.push(self.key(one)?);
+        for other in others {
+            *marks.entry(other.clone()).or_default() |= PARENT2;
+            queue.push(self.key(other)?);
+        }
+
+        let mut found: Vec<String> = Vec::new();
+        while queue.iter().any(|(_, _, sha)| marks[sha] & STALE == 0) {
+            let Some((_, _, sha)) = queue.pop() else {
+                break;
+            };
+            let mark = marks.entry(sha.clone()).or_default();
+            let mut flags = *mark & (PARENT1 | PARENT2 | STALE);
+            if flags == PARENT1 | PARENT2 {
+                if *mark & RESULT == 0 {
+                    *mark |= RESULT;
+                    found.push(sha.clone());
+                }
+                // Ancestors of a common commit are not interesting
+                flags |= STALE;
+            }
+            for parent in self.node(&sha)?.parents.clone() {
+                let parent_mark = marks.entry(parent.clone()).or_default();
+                if *parent_mark & flags == flags {
+                    continue;
+                }
+                *parent_mark |= flags;
+                queue.push(self.key(&parent)?);
+            }
+        }
+        // Found commits reached from another found one are not the best
+        Ok(found.into_iter().filter(|sha| marks[sha] & STALE == 0).collect())
+    }
+
+    /// Drop commits reachable from another one of the list
+    fn remove_redundant(&mut self, commits: Vec<String>) -> Result<Vec<String>> {
+        if commits.len() < 2 {
+            return Ok(commits);
+        }
+        let mut min_generation = u32::MAX;
+        let mut stack: Vec<String> = Vec::new();
+        for sha in &commits {
+            let node = self.node(sha)?;
+            min_generation = min_generation.min(node.generation);
+            stack.extend(node.parents.iter().cloned());
+        }
+
+        // Nothing below the lowest generation can be one of the commits
+        let mut reached: HashSet<String> = HashSet::new();
+        while let Some(sha) = stack.pop() {
+            if self.generation(&sha)? < min_generation || !reached.insert(sha.clone()) {
+                continue;
+            }
+            stack.extend(self.node(&sha)?.parents.iter().cloned());
+        }
+        Ok(commits.into_iter().filter(|sha| !reached.contains(sha)).collect())
+    }
+
+    /// Best common ancestors of `one` and a merge of `others`, newest first
+    pub fn merge_bases(&mut self, one: &str, others: &[String]) -> Result<Vec<String>> {
+        let common = self.paint_down_to_common(one, others)?;
+        let mut bases = self.remove_redundant(common)?;
+        let mut times: HashMap<String, i64> = HashMap::new();
+        for sha in &bases {
+            times.insert(sha.clone(), self.node(sha)?.time);
+        }
+        bases.sort_by_key(|sha| std::cmp::Reverse(times[sha]));
+        Ok(bases)
+    }
+
+    /// Whether `ancestor` is reachable from `descendant` (a commit is its own ancestor)
+    pub fn is_ancestor(&mut self, ancestor: &str, descendant: &str) -> Result<bool> {
+        let target = self.generation(ancestor)?;
+        let mut seen: HashSet<String> = HashSet::new();
+        let mut stack: Vec<String> = vec![descendant.to_string()];
+        while let Some(sha) = stack.pop() {
+            if sha == ancestor {
+                return Ok(true);
+            }
+            if self.generation(&sha)? <= target || !seen.insert(sha.clone()) {
+                continue;
+            }
+            stack.extend(self.node(&sha)?.parents.iter().cloned());
+        }
+        Ok(false)
+    }
+}
+
+/// Best common ancestors of two commits, newest first
+pub fn merge_bases(one: &str, other: &str) -> Result<Vec<String>> {
+    CommitWalker::new()?.merge_bases(one, &[other.to_string()])
+}
+
+pub fn is_ancestor(ancestor: &str, descendant: &str) -> Result<bool> {
+    CommitWalker::new()?.is_ancestor(ancestor, descendant)
+}
+
+/// Common ancestors of all the commits (`merge-base --octopus`)
+pub fn octopus_merge_bases(commits: &[String]) -> Result<Vec<String>> {
+    let mut walker = CommitWalker::new()?;
+    let Some((first, rest)) = commits.split_first() else {
+        return Ok(Vec::new());
+    };
+    let mut bases: Vec<String> = vec![first.clone()];
+    for commit in rest {
+        let mut next: Vec<String> = Vec::new();
+        for base in &bases {
+            for found in walker.merge_bases(base, std::slice::from_ref(commit))? {
+                if !next.contains(&found) {
+                    next.push(found);
+                }
+            }
+        }
+        bases = next;
+    }
+    Ok(bases)
+}
+
+/// Where `commit` forked from the ref: the merge base with any value the ref had
+/// according to its reflog, None unless that base is one of those values
+pub fn fork_point(name: &str, commit: &str) -> Result<Option<String>> {
+    let full_name = reflog_ref(name)?;
+    let tip = read_ref(&full_name)?.ok_or_else(|| anyhow!("Not a valid object name {name}"))?;
+    let mut values: Vec<String> = Vec::new();
+    for entry in read_reflog(&full_name)? {
+        for sha in [entry.old, entry.new] {
+            if sha != ZERO_SHA && !values.contains(&sha) {
+                values.push(sha);
+            }
+        }
+    }
+    if values.is_empty() {
+        values.push(tip);
+    }
+
+    // Values which are not commits (or were pruned) cannot be fork points
+    let mut commits: Vec<String> = Vec::new();
+    for sha in values {
+        if read_commit(&sha).is_ok() {
+            commits.push(sha);
+        }
+    }
+    let bases = CommitWalker::new()?.merge_bases(commit, &commits)?;
+    Ok(bases.into_iter().next().filter(|base| commits.contains(base)))
+}
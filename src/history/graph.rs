@@ -0,0 +1,443 @@
+use std::collections::HashSet;
+
+/// Kind of the next line of the graph
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+enum State {
+    /// Branch lines going down unchanged
+    #[default]
+    Padding,
+    /// Rows making room around an octopus merge
+    PreCommit,
+    /// Row with the commit mark
+    Commit,
+    /// Row with the edges to the parents of a merge
+    PostMerge,
+    /// Rows moving branch lines to the left until they are in their column
+    Collapsing,
+}
+
+/// Text history graph (`log --graph`) drawn the way git does, each column tracks a commit
+/// waiting to be shown
+#[derive(Clone, Debug, Default)]
+pub struct Graph {
+    /// Commits that are shown, other parents get no branch line
+    shown: HashSet<String>,
+    commit: String,
+    /// Shown parents of the commit
+    parents: Vec<String>,
+    /// Width of the graph for the lines of the commit
+    width: usize,
+    expansion_row: usize,
+    state: State,
+    prev_state: State,
+    commit_index: usize,
+    prev_commit_index: usize,
+    /// 0 when the first parent of a merge is in a column on the left, 1 otherwise
+    merge_layout: isize,
+    /// Columns added by the commit, -1 when its last edge joins the column on its right
+    edges_added: isize,
+    prev_edges_added: isize,
+    /// Columns before the commit
+    columns: Vec<String>,
+    /// Columns after the commit
+    new_columns: Vec<String>,
+    /// Column of `new_columns` each character position goes to, -1 for none
+    mapping: Vec<isize>,
+    mapping_size: usize,
+    old_mapping: Vec<isize>,
+}
+
+impl Graph {
+    pub fn new(shown: HashSet<String>) -> Graph {
+        Graph { shown, ..Graph::default() }
+    }
+
+    fn set_state(&mut self, state: State) {
+        self.prev_state = self.state;
+        self.state = state;
+    }
+
+    /// Whether all lines of the commit were shown
+    pub fn is_finished(&self) -> bool {
+        self.state == State::Padding
+    }
+
+    /// Start the lines of the next commit
+    pub fn update(&mut self, sha: &str, parents: &[String]) {
+        self.commit = sha.to_string();
+        self.parents =
+            parents.iter().filter(|parent| self.shown.contains(*parent)).cloned().collect();
+        self.prev_commit_index = self.commit_index;
+        self.update_columns();
+        self.expansion_row = 0;
+        self.state = if self.needs_pre_commit_line() { State::PreCommit } else { State::Commit };
+    }
+
+    fn update_columns(&mut self) {
+        self.columns = std::mem::take(&mut self.new_columns);
+        self.mapping_size = 2 * (self.columns.len() + self.parents.len());
+        self.mapping = vec![-1; self.mapping_size];
+        if self.old_mapping.len() < self.mapping_size {
+            self.old_mapping.resize(self.mapping_size, -1);
+        }
+        self.width = 0;
+        self.prev_edges_added = self.edges_added;
+        self.edges_added = 0;
+
+        // The commit takes the column of its first child, or a new column on the right
+        let mut seen_this = false;
+        for i in 0..=self.columns.len() {
+            let column = match self.columns.get(i) {
+                Some(column) => column.clone(),
+                None if seen_this => break,
+                None => self.commit.clone(),
+            };
+            if column == self.commit {
+                seen_this = true;
+                self.commit_index = i;
+                self.merge_layout = -1;
+                for parent in self.parents.clone() {
+                    self.insert_into_new_columns(&parent, Some(i));
+                }
+                // The commit always takes at least one column
+                if self.parents.is_empty() {
+                    self.width += 2;
+                }
+            } else {
+                self.insert_into_new_columns(&column, None);
+            }
+        }
+
+        while self.mapping_size > 1 && self.mapping[self.mapping_size - 1] < 0 {
+            self.mapping_size -= 1;
+        }
+    }
+
+    /// Add the commit to the new columns, `commit_index` is set for parents of the commit
+    fn insert_into_new_columns(&mut self, sha: &str, commit_index: Option<usize>) {
+        let i = match self.new_columns.iter().position(|column| column == sha) {
+            Some(i) => i,
+            None => {
+                self.new_columns.push(sha.to_string());
+                self.new_columns.len() - 1
+            }
+        } as isize;
+
+        let width = self.width as isize;
+        let mapping_index = match commit_index {
+            Some(index) if self.parents.len() > 1 && self.merge_layout == -1 => {
+                // The first parent of a merge decides where the edges of the merge start
+                let distance = index as isize - i;
+                let shift = if distance > 1 { 2 * distance - 3 } else { 1 };
+                self.merge_layout = if distance > 0 { 0 } else { 1 };
+                self.edges_added = self.parents.len() as isize + self.merge_layout - 2;
+                self.width += 2 * self.merge_layout as usize;
+                width + (self.merge_layout - 1) * shift
+            }
+            _ if self.edges_added > 0 && width >= 2 && self.mapping[self.width - 2] == i => {
+                // The last edge of the merge joins the column on its right right away
+                self.edges_added = -1;
+                width - 2
+            }
+            _ => {
+                self.width += 2;
+                width
+            }
+        };
+        self.mapping[mapping_index as usize] = i;
+    }
+
+    fn dashed_parents(&self) -> isize {
+        self.parents.len() as isize + self.merge_layout - 3
+    }
+
+    /// Octopus merges with columns on their right need rows to make room for their edges
+    fn needs_pre_commit_line(&self) -> bool {
+        self.parents.len() >= 3 &&
+            self.commit_index + 1 < self.columns.len() &&
+            (self.expansion_row as isize) < 2 * self.dashed_parents()
+    }
+
+    /// Whether each branch line is in its column, or one position right of it
+    fn is_mapping_correct(&self) -> bool {
+        self.mapping[..self.mapping_size]
+            .iter()
+            .enumerate()
+            .all(|(i, &target)| target < 0 || target == (i / 2) as isize)
+    }
+
+    /// Next line of the graph, and whether it is the line of the commit
+    pub fn next_line(&mut self) -> (String, bool) {
+        let commit_line = self.state == State::Commit;
+        let mut line = match self.state {
+            State::Padding => "| ".repeat(self.new_columns.len()),
+            State::PreCommit => self.pre_commit_line(),
+            State::Commit => self.commit_line(),
+            State::PostMerge => self.post_merge_line(),
+            State::Collapsing => self.collapsing_line(),
+        };
+        self.pad(&mut line);
+        (line, commit_line)
+    }
+
+    /// Lines of a commit have the same width, so the text on their right stays aligned
+    fn pad(&self, line: &mut String) {
+        let length = line.len();
+        if length < self.width {
+            line.push_str(&" ".repeat(self.width - length));
+        }
+    }
+
+    fn pre_commit_line(&mut self) -> String {
+        let mut line = String::new();
+        let mut seen_this = false;
+        for (i, column) in self.columns.iter().enumerate() {
+            if *column == self.commit {
+                seen_this = true;
+                line.push('|');
+                line.push_str(&" ".repeat(self.expansion_row));
+            } else if seen_this && self.expansion_row == 0 {
+                // Lines drawn as `\` after the previous merge keep going that way
+                let after_merge =
+                    self.prev_state == State::PostMerge && self.prev_commit_index < i;
+                line.push(if after_merge { '\\' } else { '|' });
+            } else if seen_this {
+                line.push('\\');
+            } else {
+                line.push('|');
+            }
+            line.push(' ');
+        }
+
+        self.expansion_row += 1;
+        if !self.needs_pre_commit_line() {
+            self.set_state(State::Commit);
+        }
+        line
+    }
+
+    fn commit_line(&mut self) -> String {
+        let mut line = String::new();
+        let mut seen_this = false;
+        for i in 0..=self.columns.len() {
+            let column = match self.columns.get(i) {
+                Some(column) => column,
+                None if seen_this => break,
+                None => &self.commit,
+            };
+            if *column == self.commit {
+                seen_this = true;
+                line.push('*');
+                // Octopus merges are marked as `*-.`, `*---.` and so on
+                let dashes = if self.parents.len() > 2 { self.dashed_parents().max(0) } else { 0 };
+                if dashes > 0 {
+                    line.push_str(&"--".repeat(dashes as usize));
+                    line.pop();
+                    line.push('.');
+                }
+            } else if seen_this && self.edges_added > 1 {
+                line.push('\\');
+            } else if seen_this && self.edges_added == 1 {
+                let after_merge = self.prev_state == State::PostMerge &&
+                    self.prev_edges_added > 0 &&
+                    self.prev_commit_index < i;
+                line.push(if after_merge { '\\' } else { '|' });
+            } else if self.prev_state == State::Collapsing &&
+                self.old_mapping[2 * i + 1] == i as isize &&
+                self.mapping[2 * i] < i as isize
+            {
+                // The line ends or moves left below the commit
+                line.push('/');
+            } else {
+                line.push('|');
+            }
+            line.push(' ');
+        }
+
+        if self.parents.len() > 1 {
+            self.set_state(State::PostMerge);
+        } else if self.is_mapping_correct() {
+            self.set_state(State::Padding);
+        } else {
+            self.set_state(State::Collapsing);
+        }
+        line
+    }
+
+    fn post_merge_line(&mut self) -> String {
+        const MERGE_CHARS: [char; 3] = ['/', '|', '\\'];
+        let mut line = String::new();
+        let mut seen_this = false;
+        let mut seen_first_parent = false;
+        for i in 0..=self.columns.len() {
+            let column = match self.columns.get(i) {
+                Some(column) => column,
+                None if seen_this => break,
+                None => &self.commit,
+            };
+            if *column == self.commit {
+                seen_this = true;
+                let mut kind = self.merge_layout as usize;
+                for j in 0..self.parents.len() {
+                    line.push(MERGE_CHARS[kind]);
+                    if kind < 2 {
+                        kind += 1;
+                    } else if self.edges_added > 0 || j + 1 < self.parents.len() {
+                        line.push(' ');
+                    }
+                }
+                if self.edges_added == 0 {
+                    line.push(' ');
+                }
+            } else if seen_this {
+                line.push(if self.edges_added > 0 { '\\' } else { '|' });
+                line.push(' ');
+            } else {
+                line.push('|');
+                if self.merge_layout != 0 || i + 1 != self.commit_index {
+                    line.push(if seen_first_parent { '_' } else { ' ' });
+                }
+            }
+            if *column == self.parents[0] {
+                seen_first_parent = true;
+            }
+        }
+
+        if self.is_mapping_correct() {
+            self.set_state(State::Padding);
+        } else {
+            self.set_state(State::Collapsing);
+        }
+        line
+    }
+
+    fn collapsing_line(&mut self) -> String {
+        std::mem::swap(&mut self.mapping, &mut self.old_mapping);
+        self.mapping[..self.mapping_size].fill(-1);
+
+        // Branch lines only move left, one of them may move several positions with `_`
+        let mut horizontal_edge: isize = -1;
+        let mut horizontal_edge_target: isize = -1;
+        for i in 0..self.mapping_size {
+            let target = self.old_mapping[i];
+            if target < 0 {
+                continue;
+            }
+            let home = 2 * target as usize;
+            if home == i {
+                self.mapping[i] = target;
+            } else if self.mapping[i - 1] < 0 {
+                self.mapping[i - 1] = target;
+                if horizontal_edge == -1 {
+                    horizontal_edge = i as isize;
+                    horizontal_edge_target = target;
+                    for j in (home + 3..i.saturating_sub(2)).step_by(2) {
+                        self.mapping[j] = target;
+                    }
+                }
+            } else if self.mapping[i - 1] != target {
+                // Cross the line on the left, lines going to the same commit are joined
+                self.mapping[i - 2] = target;
+                if horizontal_edge == -1 {
+                    horizontal_edge = i as isize - 1;
+                    horizontal_edge_target = target;
+                    for j in (home + 3..i.saturating_sub(2)).step_by(2) {
+                        self.mapping[j] = target;
+                    }
+                }
+            }
+        }
+
+        self.old_mapping[..self.mapping_size].copy_from_slice(&self.mapping[..self.mapping_size]);
+        if self.mapping[self.mapping_size - 1] < 0 {
+            self.mapping_size -= 1;
+        }
+
+        let mut line = String::new();
+        let mut used_horizontal = false;
+        for i in 0..self.mapping_size {
+            let target = self.mapping[i];
+            if target < 0 {
+                line.push(' ');
+            } else if 2 * target as usize == i {
+                line.push('|');
+            } else if target == horizontal_edge_target && i as isize != horizontal_edge - 1 {
+                // Only the first segment of the horizontal edge goes on to the next line
+                if i != 2 * target as usize + 3 {
+                    self.mapping[i] = -1;
+                }
+                used_horizontal = true;
+                line.push('_');
+            } else {
+                if used_horizontal && (i as isize) < horizontal_edge {
+                    self.mapping[i] = -1;
+                }
+                line.push('/');
+            }
+        }
+
+        if self.is_mapping_correct() {
+            self.set_state(State::Padding);
+        }
+        line
+    }
+
+    /// Line shown between commits, before the line of the next commit
+    pub fn padding_line(&mut self) -> String {
+        if self.state != State::Commit {
+            return self.next_line().0;
+        }
+        let mut line = String::new();
+        for column in &self.columns {
+            line.push('|');
+            if *column == self.commit && self.parents.len() > 2 {
+                line.push_str(&" ".repeat((self.parents.len() - 2) * 2));
+            } else {
+                line.push(' ');
+            }
+        }
+        self.pad(&mut line);
+        self.prev_state = State::Padding;
+        line
+    }
+
+    /// Graph lines up to the line of the commit, followed by the message with the next graph
+    /// lines before each of its lines, and the graph lines left for the commit
+    pub fn show_commit(&mut self, message: &str) -> String {
+        let mut out = String::new();
+        loop {
+            let (line, commit_line) = self.next_line();
+            out.push_str(&line);
+            if commit_line {
+                break;
+            }
+            out.push('\n');
+        }
+
+        let mut lines = message.split_inclusive('\n').peekable();
+        while let Some(line) = lines.next() {
+            out.push_str(line);
+            if lines.peek().is_some() {
+                out.push_str(&self.next_line().0);
+            }
+        }
+
+        if !self.is_finished() {
+            let newline_terminated = message.ends_with('\n');
+            if !newline_terminated {
+                out.push('\n');
+            }
+            loop {
+                out.push_str(&self.next_line().0);
+                if self.is_finished() {
+                    break;
+                }
+                out.push('\n');
+            }
+            if newline_terminated {
+                out.push('\n');
+            }
+        }
+        out
+    }
+}
@@ -0,0 +1,539 @@
+pub mod ancestry;
+pub mod graph;
+pub mod pretty;
+
+use crate::commit_graph::{ BloomQuery, CommitGraph };
+use crate::diff::{ diff_trees, TreeDiffOptions };
+use crate::object::{ read_commit, Commit };
+use crate::revision;
+use crate::utility::date_util::DateFormat;
+
+use anyhow::Result;
+use regex::{ Regex, RegexBuilder };
+use std::cmp::Reverse;
+use std::collections::{ BinaryHeap, HashMap, HashSet };
+
+pub use ancestry::{ is_ancestor, merge_bases, CommitWalker };
+pub use graph::Graph;
+pub use pretty::PrettyFormat;
+
+/// Order of the shown commits
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum CommitOrder {
+    /// Commit date order as commits are reached by the walk
+    #[default]
+    Walk,
+    /// No parent before all its children, otherwise by commit date
+    Date,
+    /// No parent before all its children, lines of history kept together
+    Topo,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct LogOptions {
+    pub max_count: Option<usize>,
+    pub first_parent: bool,
+    pub order: CommitOrder,
+    pub graph: bool,
+    /// Commits older than this are neither shown nor walked through
+    pub since: Option<i64>,
+    pub until: Option<i64>,
+    pub authors: Vec<String>,
+    pub greps: Vec<String>,
+    pub ignore_case: bool,
+    /// Limit history to commits changing these paths (empty means everything)
+    pub paths: Vec<String>,
+    pub format: PrettyFormat,
+    pub abbrev_commit: bool,
+    pub date_format: DateFormat,
+    /// Only commits descending from the excluded ones (`--ancestry-path`)
+    pub ancestry_path: bool,
+}
+
+/// Shown commit with parents rewritten to other shown commits
+#[derive(Clone, Debug)]
+pub struct LogEntry {
+    pub sha: String,
+    pub commit: Commit,
+    pub parents: Vec<String>,
+    /// Reached from the left side of a symmetric range
+    pub left: bool,
+}
+
+/// Walk state of a reached commit
+struct Visited {
+    commit: Commit,
+    shown: bool,
+    followed: Vec<String>,
+}
+
+/// Commit filters which do not change the walk (`--until`, `--author`, `--grep`)
+struct Filters {
+    until: Option<i64>,
+    authors: Vec<Regex>,
+    greps: Vec<Regex>,
+}
+
+impl Filters {
+    fn new(options: &LogOptions) -> Result<Filters> {
+        let compile = |patterns: &[String]| -> Result<Vec<Regex>> {
+            patterns
+                .iter()
+                .map(|p| Ok(RegexBuilder::new(p).case_insensitive(options.ignore_case).build()?))
+                .collect()
+        };
+        Ok(Filters {
+            until: options.until,
+            authors: compile(&options.authors)?,
+            greps: compile(&options.greps)?,
+        })
+    }
+
+    fn accepts(&self, commit: &Commit) -> bool {
+        let author = commit.author.ident();
+        let message = commit.message_text();
+        self.until.is_none_or(|until| commit.committer.time <= until) &&
+            (self.authors.is_empty() || self.authors.iter().any(|r| r.is_match(&author))) &&
+            (self.greps.is_empty() || self.greps.iter().any(|r| r.is_match(&message)))
+    }
+}
+
+/// Commits the walk starts from and stops at, as given on the command line
+struct RangeArgs {
+    include: Vec<String>,
+    exclude: Vec<String>,
+    /// Left sides of symmetric ranges (`a...b`)
+    left: Vec<String>,
+}
+
+/// Split revision arguments into included and excluded (`^rev`, `a..b`, after `--not`) commits,
+/// `a...b` includes both sides and excludes their merge bases
+fn parse_range_args(revisions: &[String]) -> Result<RangeArgs> {
+    let commit = |spec: &str| {
+        let spec = if spec.is_empty() { "HEAD" } else { spec };
+        revision::resolve_revision(&format!("{spec}^{{commit}}"))
+    };
+
+    let mut range = RangeArgs { include: Vec::new(), exclude: Vec::new(), left: Vec::new() };
+    let mut negated = false;
+    let add = |range: &mut RangeArgs, sha: String, excluded: bool| match excluded {
+        true => range.exclude.push(sha),
+        false => range.include.push(sha),
+    };
+    for spec in revisions {
+        if spec == "--not" {
+            negated = !negated;
+        } else if let Some(negative) = spec.strip_prefix('^') {
+            add(&mut range, commit(negative)?, !negated);
+        } else if let Some((from, to)) = spec.split_once("...") {
+            let (left, right) = (commit(from)?, commit(to)?);
+            for base in CommitWalker::new()?.merge_bases(&left, std::slice::from_ref(&right))? {
+                add(&mut range, base, !negated);
+            }
+            if !negated {
+                range.left.push(left.clone());
+            }
+            add(&mut range, left, negated);
+            add(&mut range, right, negated);
+        } else if let Some((from, to)) = spec.split_once("..") {
+            add(&mut range, commit(from)?, !negated);
+            add(&mut range, commit(to)?, negated);
+        } else {
+            add(&mut range, commit(spec)?, negated);
+        }
+    }
+
+    if range.include.is_empty() && range.exclude.is_empty() {
+        range.include.push(revision::head_commit()?);
+    }
+    Ok(range)
+}
+
+/// Parents and root tree of the commit, from the commit-graph when it has the commit
+fn parents_and_tree(sha: &str, graph: Option<&CommitGraph>) -> Result<(Vec<String>, String)> {
+    if let Some(stored) = graph.map(|graph| graph.commit(sha)).transpose()?.flatten() {
+        return Ok((stored.parents, stored.tree));
+    }
+    let commit = read_commit(sha)?;
+    Ok((commit.parents, commit.tree))
+}
+
+/// All commits reachable from the given ones
+pub fn ancestors(tips: &[String]) -> Result<HashSet<String>> {
+    let graph = CommitGraph::load()?;
+    let mut found: HashSet<String> = HashSet::new();
+    let mut stack: Vec<String> = tips.to_vec();
+    while let Some(sha) = stack.pop() {
+        if found.insert(sha.clone()) {
+            stack.extend(parents_and_tree(&sha, graph.as_ref())?.0);
+        }
+    }
+    Ok(found)
+}
+
+/// Returns whether commit is shown and which parents the walk continues to, changed-path
+/// filters of the graph spare diffs against the first parent
+fn simplify(
+    sha: &str,
+    commit: &Commit,
+    options: &LogOptions,
+    graph: Option<&CommitGraph>,
+    query: Option<&BloomQuery>
+) -> Result<(bool, Vec<String>)> {
+    let parents: Vec<String> = if options.first_parent {
+        commit.parents.iter().take(1).cloned().collect()
+    } else {
+        commit.parents.clone()
+    };
+    if options.paths.is_empty() {
+        return Ok((true, parents));
+    }
+
+    let tree_options = TreeDiffOptions {
+        recursive: true,
+        paths: options.paths.clone(),
+        ..Default::default()
+    };
+    if parents.is_empty() {
+        let changes = diff_trees(None, Some(&commit.tree), &tree_options)?;
+        return Ok((!changes.is_empty(), parents));
+    }
+
+    // Follow the first parent with the same content of the paths (TREESAME), hide the commit
+    if let (Some(graph), Some(query)) = (graph, query) {
+        if graph.rules_out(sha, query) {
+            return Ok((false, vec![parents[0].clone()]));
+        }
+    }
+    for parent in &parents {
+        let parent_tree = parents_and_tree(parent, graph)?.1;
+        if
+            parent_tree == commit.tree ||
+            diff_trees(Some(&parent_tree), Some(&commit.tree), &tree_options)?.is_empty()
+        {
+            return Ok((false, vec![parent.clone()]));
+        }
+    }
+    Ok((true, parents))
+}
+
+/// Commits ordered by commit date, ties are resolved in insertion order
+#[derive(Default)]
+struct DateQueue {
+    heap: BinaryHeap<(i64, Reverse<usize>)>,
+    commits: HashMap<usize, (String, Commit)>,
+    inserted: usize,
+}
+
+impl DateQueue {
+    fn push(&mut self, sha: &str) -> Result<()> {
+        let commit = read_commit(sha)?;
+        self.heap.push((commit.committer.time, Reverse(self.inserted)));
+        self.commits.insert(self.inserted, (sha.to_string(), commit));
+        self.inserted += 1;
+        Ok(())
+    }
+
+    fn pop(&mut self) -> Option<(String, Commit)> {
+        let (_, Reverse(number)) = self.heap.pop()?;
+        self.commits.remove(&number)
+    }
+}
+
+/// Walk history from revisions and return commits to show in output order
+pub fn walk(revisions: &[String], options: &LogOptions) -> Result<Vec<LogEntry>> {
+    let RangeArgs { include, exclude, left } = parse_range_args(revisions)?;
+    let excluded = ancestors(&exclude)?;
+    let filters = Filters::new(options)?;
+    let graph = CommitGraph::load()?;
+    let query = BloomQuery::new(&options.paths);
+
+    // Results can be streamed when neither ordering, graph nor ancestry path need the whole
+    // history
+    let streaming = options.order == CommitOrder::Walk && !options.graph && !options.ancestry_path;
+
+    let mut queue = DateQueue::default();
+    let mut seen: HashSet<String> = HashSet::new();
+    for sha in &include {
+        if seen.insert(sha.clone()) && !excluded.contains(sha) {
+            queue.push(sha)?;
+        }
+    }
+
+    let mut order: Vec<String> = Vec::new();
+    let mut visited: HashMap<String, Visited> = HashMap::new();
+    let mut accepted: usize = 0;
+    while let Some((sha, commit)) = queue.pop() {
+        if streaming && options.max_count.is_some_and(|max| accepted >= max) {
+            break;
+        }
+        if options.since.is_some_and(|since| commit.committer.time < since) {
+            continue;
+        }
+
+        let (simplified_shown, followed) =
+            simplify(&sha, &commit, options, graph.as_ref(), query.as_ref())?;
+        for parent in &followed {
+            if seen.insert(parent.clone()) && !excluded.contains(parent) {
+                queue.push(parent)?;
+            }
+        }
+        let shown = simplified_shown && filters.accepts(&commit);
+        if shown {
+            accepted += 1;
+        }
+        order.push(sha.clone());
+        visited.insert(sha, Visited { commit, shown, followed });
+    }
+
+    if options.ancestry_path {
+        limit_to_ancestry(&order, &exclude, &mut visited);
+    }
+    let left = reached_from(&left, &visited);
+
+    let mut entries: Vec<LogEntry> = Vec::new();
+    let mut nearest: HashMap<String, Vec<String>> = HashMap::new();
+    for sha in order.iter().filter(|sha| visited[*sha].shown) {
+        let mut parents: Vec<String> = Vec::new();
+        for parent in &visited[sha].followed {
+            for target in nearest_shown(parent, &visited, &mut nearest) {
+                if !parents.contains(&target) {
+                    parents.push(target);
+                }
+            }
+        }
+        entries.push(LogEntry {
+            sha: sha.clone(),
+            commit: visited[sha].commit.clone(),
+            parents,
+            left: left.contains(sha),
+        });
+    }
+
+    let sort = match options.order {
+        CommitOrder::Walk if options.graph => CommitOrder::Topo,
+        order => order,
+    };
+    if sort != CommitOrder::Walk {
+        entries = sort_entries(entries, sort);
+    }
+    if let Some(max) = options.max_count {
+        entries.truncate(max);
+    }
+    Ok(entries)
+}
+
+/// Hide walked commits without a path of parents to one of the excluded commits
+fn limit_to_ancestry(order: &[String], bottoms: &[String], visited: &mut HashMap<String, Visited>) {
+    // Parents mostly come later in the walk, so few passes from the end are needed
+    let mut on_path: HashSet<&String> = HashSet::new();
+    loop {
+        let mut changed = false;
+        for sha in order.iter().rev() {
+            let parents = &visited[sha].commit.parents;
+            if on_path.contains(sha) {
+                continue;
+            }
+            if parents.iter().any(|parent| bottoms.contains(parent) || on_path.contains(parent)) {
+                on_path.insert(sha);
+                changed = true;
+            }
+        }
+        if !changed {
+            break;
+        }
+    }
+    for sha in order.iter().filter(|sha| !on_path.contains(sha)) {
+        if let Some(node) = visited.get_mut(sha) {
+            node.shown = false;
+        }
+    }
+}
+
+/// Walked commits reachable from the tips through followed parents
+fn reached_from(tips: &[String], visited: &HashMap<String, Visited>) -> HashSet<String> {
+    let mut reached: HashSet<String> = HashSet::new();
+    let mut stack: Vec<&String> = tips.iter().collect();
+    while let Some(sha) = stack.pop() {
+        if let Some(node) = visited.get(sha) {
+            if reached.insert(sha.clone()) {
+                stack.extend(&node.followed);
+            }
+        }
+    }
+    reached
+}
+
+/// `rev-list` output: SHA-1 of every commit (`<` or `>` marks the side of symmetric ranges
+/// with `left_right`), or just their number
+pub fn format_rev_list(entries: &[LogEntry], count: bool, left_right: bool) -> String {
+    if count && left_right {
+        let left = entries.iter().filter(|entry| entry.left).count();
+        return format!("{left}\t{}\n", entries.len() - left);
+    }
+    if count {
+        return format!("{}\n", entries.len());
+    }
+    let mut out = String::new();
+    for entry in entries {
+        if left_right {
+            out.push(if entry.left { '<' } else { '>' });
+        }
+        out.push_str(&entry.sha);
+        out.push('\n');
+    }
+    out
+}
+
+/// Shown commits replacing a parent: itself or nearest shown ancestors through hidden commits
+fn nearest_shown(
+    start: &str,
+    visited: &HashMap<String, Visited>,
+    nearest: &mut HashMap<String, Vec<String>>
+) -> Vec<String> {
+    // Post-order traversal without recursion, results are memoized
+    let mut stack: Vec<(String, bool)> = vec![(start.to_string(), false)];
+    while let Some((sha, expanded)) = stack.pop() {
+        if nearest.contains_key(&sha) {
+            continue;
+        }
+        let result = match visited.get(&sha) {
+            None => Vec::new(), // Outside of the walk (excluded or too old)
+            Some(node) if node.shown => vec![sha.clone()],
+            Some(node) if !expanded => {
+                stack.push((sha, true));
+                stack.extend(node.followed.iter().map(|p| (p.clone(), false)));
+                continue;
+            }
+            Some(node) => {
+                let mut result: Vec<String> = Vec::new();
+                for target in node.followed.iter().flat_map(|p| nearest.get(p).into_iter().flatten()) {
+                    if !result.contains(target) {
+                        result.push(target.clone());
+                    }
+                }
+                result
+            }
+        };
+        nearest.insert(sha, result);
+    }
+    nearest[start].clone()
+}
+
+/// Sort commits so that children come before parents (same algorithm as git)
+fn sort_entries(entries: Vec<LogEntry>, order: CommitOrder) -> Vec<LogEntry> {
+    let position: HashMap<String, usize> = entries
+        .iter()
+        .enumerate()
+        .map(|(i, e)| (e.sha.clone(), i))
+        .collect();
+    let mut indegree: Vec<usize> = vec![0; entries.len()];
+    for entry in &entries {
+        for parent in &entry.parents {
+            if let Some(&i) = position.get(parent) {
+                indegree[i] += 1;
+            }
+        }
+    }
+
+    // Topo order uses a stack, date order a queue by commit date
+    let mut stack: Vec<usize> = Vec::new();
+    let mut queue: BinaryHeap<(i64, Reverse<usize>, usize)> = BinaryHeap::new();
+    let mut inserted: usize = 0;
+    let mut put = |i: usize, stack: &mut Vec<usize>, queue: &mut BinaryHeap<(i64, Reverse<usize>, usize)>| {
+        if order == CommitOrder::Topo {
+            stack.push(i);
+        } else {
+            queue.push((entries[i].commit.committer.time, Reverse(inserted), i));
+            inserted += 1;
+        }
+    };
+    for i in (0..entries.len()).filter(|i| indegree[*i] == 0) {
+        put(i, &mut stack, &mut queue);
+    }
+    stack.reverse(); // Tips are shown in the order they were reached
+
+    let mut sorted: Vec<usize> = Vec::with_capacity(entries.len());
+    loop {
+        let next = if order == CommitOrder::Topo {
+            stack.pop()
+        } else {
+            queue.pop().map(|(_, _, i)| i)
+        };
+        let Some(i) = next else {
+            break;
+        };
+        for parent in &entries[i].parents {
+            if let Some(&p) = position.get(parent) {
+                indegree[p] -= 1;
+                if indegree[p] == 0 {
+                    put(p, &mut stack, &mut queue);
+                }
+            }
+        }
+        sorted.push(i);
+    }
+
+    let mut slots: Vec<Option<LogEntry>> = entries.into_iter().map(Some).collect();
+    sorted
+        .into_iter()
+        .filter_map(|i| slots[i].take())
+        .collect()
+}
+
+/// Format walked commits, with graph if requested
+pub fn format_log(entries: &[LogEntry], options: &LogOptions) -> String {
+    if options.graph {
+        return format_graph_log(entries, options);
+    }
+    let mut out = String::new();
+    for (number, entry) in entries.iter().enumerate() {
+        // Built-in multiline formats separate commits with an empty line
+        if number > 0 && options.format.has_separator_line() {
+            out.push('\n');
+        }
+        out.push_str(&pretty::format_commit(&entry.sha, &entry.commit, options));
+        out.push('\n');
+    }
+
+    // `format:` puts newlines only between commits
+    if matches!(options.format, PrettyFormat::Format(_)) {
+        out.pop();
+    }
+    out
+}
+
+/// Format walked commits with the graph on their left
+fn format_graph_log(entries: &[LogEntry], options: &LogOptions) -> String {
+    let mut graph = Graph::new(entries.iter().map(|entry| entry.sha.clone()).collect());
+    let separated =
+        options.format.has_separator_line() || matches!(options.format, PrettyFormat::Format(_));
+    let mut out = String::new();
+    let mut missing_newline = false;
+    for (number, entry) in entries.iter().enumerate() {
+        graph.update(&entry.sha, &entry.parents);
+        // A message ending with a newline gets a graph line before the newline ending the
+        // commit, so the graph has no gap
+        if number > 0 && separated {
+            if !missing_newline {
+                out.push_str(&graph.padding_line());
+            }
+            out.push('\n');
+        }
+
+        let mut message = pretty::format_commit(&entry.sha, &entry.commit, options);
+        if options.format.has_separator_line() {
+            message.push('\n');
+        }
+        missing_newline = !message.ends_with('\n');
+        out.push_str(&graph.show_commit(&message));
+
+        if !separated {
+            if !missing_newline {
+                out.push_str(&graph.padding_line());
+            }
+            out.push('\n');
+        }
+    }
+    out
+}
@@ -0,0 +1,239 @@
+use super::LogOptions;
+use crate::diff::abbrev;
+use crate::object::{ decode, Commit, Signature };
+use crate::utility::date_util::{ format_date, DateFormat };
+
+use anyhow::{ bail, Result };
+
+/// Commit output format (`--pretty=<format>`, `--format=<format>`)
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub enum PrettyFormat {
+    Oneline,
+    Short,
+    #[default]
+    Medium,
+    Full,
+    Fuller,
+    /// User format with newlines between commits (`format:<text>`)
+    Format(String),
+    /// User format with newline after each commit (`tformat:<text>`)
+    TFormat(String),
+}
+
+impl PrettyFormat {
+    pub fn from_spec(spec: &str) -> Result<PrettyFormat> {
+        Ok(match spec {
+            "oneline" => PrettyFormat::Oneline,
+            "short" => PrettyFormat::Short,
+            "medium" => PrettyFormat::Medium,
+            "full" => PrettyFormat::Full,
+            "fuller" => PrettyFormat::Fuller,
+            _ => {
+                if let Some(format) = spec.strip_prefix("format:") {
+                    PrettyFormat::Format(format.to_string())
+                } else if let Some(format) = spec.strip_prefix("tformat:") {
+                    PrettyFormat::TFormat(format.to_string())
+                } else if spec.contains('%') {
+                    PrettyFormat::TFormat(spec.to_string())
+                } else {
+                    bail!("Invalid pretty format: {spec}");
+                }
+            }
+        })
+    }
+
+    /// Built-in multiline formats separate commits with an empty line
+    pub fn has_separator_line(&self) -> bool {
+        matches!(
+            self,
+            PrettyFormat::Short | PrettyFormat::Medium | PrettyFormat::Full | PrettyFormat::Fuller
+        )
+    }
+}
+
+/// First paragraph of the message joined into one line
+pub fn subject(message: &str) -> String {
+    message
+        .lines()
+        .skip_while(|line| line.trim().is_empty())
+        .take_while(|line| !line.trim().is_empty())
+        .map(str::trim)
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Message without the subject paragraph
+pub fn body(message: &str) -> String {
+    let mut lines = message.split_inclusive('\n').skip_while(|line| line.trim().is_empty());
+    lines.by_ref().take_while(|line| !line.trim().is_empty()).for_each(drop);
+    lines.skip_while(|line| line.trim().is_empty()).collect()
+}
+
+/// Subject turned into a file name friendly form (`%f`)
+fn sanitized_subject(message: &str) -> String {
+    let mut result = String::new();
+    for c in subject(message).chars() {
+        if c.is_ascii_alphanumeric() || c == '.' || c == '_' {
+            result.push(c);
+        } else if !result.is_empty() && !result.ends_with('-') {
+            result.push('-');
+        }
+    }
+    result.trim_end_matches(['-', '.']).to_string()
+}
+
+/// Expand placeholders of the person (`%an`, `%ae`, `%ad`, ...), returns None for unknown ones
+fn person_placeholder(
+    person: &Signature,
+    encoding: Option<&str>,
+    key: char,
+    date_format: DateFormat
+) -> Option<String> {
+    let date = |format: DateFormat| format_date(person.time, &person.tz, format);
+    let email = decode(&person.email, encoding);
+    Some(match key {
+        'n' | 'N' => decode(&person.name, encoding).into_owned(),
+        'e' | 'E' => email.into_owned(),
+        'l' | 'L' => email.split('@').next().unwrap_or_default().to_string(),
+        'd' => date(date_format),
+        'D' => date(DateFormat::Rfc),
+        'r' => date(DateFormat::Relative),
+        't' => date(DateFormat::Unix),
+        'i' => date(DateFormat::Iso),
+        'I' => date(DateFormat::IsoStrict),
+        's' => date(DateFormat::Short),
+        _ => {
+            return None;
+        }
+    })
+}
+
+/// Expand `%` placeholders of user format
+pub fn expand_format(format: &str, sha: &str, commit: &Commit, options: &LogOptions) -> String {
+    let parents = |short: bool| {
+        commit.parents
+            .iter()
+            .map(|p| if short { abbrev(p) } else { p.as_str() })
+            .collect::<Vec<_>>()
+            .join(" ")
+    };
+
+    let mut out = String::new();
+    let mut rest = format;
+    while let Some(start) = rest.find('%') {
+        out.push_str(&rest[..start]);
+        rest = &rest[start + 1..];
+
+        let mut chars = rest.chars();
+        let key = chars.next();
+        let (expanded, used): (Option<String>, usize) = match key {
+            Some('%') => (Some("%".to_string()), 1),
+            Some('n') => (Some("\n".to_string()), 1),
+            Some('H') => (Some(sha.to_string()), 1),
+            Some('h') => (Some(abbrev(sha).to_string()), 1),
+            Some('T') => (Some(commit.tree.clone()), 1),
+            Some('t') => (Some(abbrev(&commit.tree).to_string()), 1),
+            Some('P') => (Some(parents(false)), 1),
+            Some('p') => (Some(parents(true)), 1),
+            Some('s') => (Some(subject(&commit.message_text())), 1),
+            Some('f') => (Some(sanitized_subject(&commit.message_text())), 1),
+            Some('b') => (Some(body(&commit.message_text())), 1),
+            Some('B') => (Some(commit.message_text().into_owned()), 1),
+            Some('a') | Some('c') => {
+                let person = if key == Some('a') { &commit.author } else { &commit.committer };
+                let value = chars
+                    .next()
+                    .and_then(|k| {
+                        person_placeholder(person, commit.encoding(), k, options.date_format)
+                    });
+                (value, 2)
+            }
+            Some('x') => {
+                let value = rest
+                    .get(1..3)
+                    .and_then(|hex| u8::from_str_radix(hex, 16).ok())
+                    .map(|byte| (byte as char).to_string());
+                (value, 3)
+            }
+            Some('C') => {
+                let colors = [
+                    ("red", "\x1b[31m"),
+                    ("green", "\x1b[32m"),
+                    ("blue", "\x1b[34m"),
+                    ("reset", "\x1b[m"),
+                ];
+                colors
+                    .iter()
+                    .find(|(name, _)| rest[1..].starts_with(name))
+                    .map_or((None, 1), |(name, code)| (Some(code.to_string()), name.len() + 1))
+            }
+            _ => (None, 0),
+        };
+
+        match expanded {
+            Some(text) => {
+                out.push_str(&text);
+                rest = &rest[used..];
+            }
+            None => out.push('%'), // Unknown placeholders are copied as is
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+/// Indented commit message of built-in formats
+fn indented_message(message: &str, only_subject: bool) -> String {
+    let message = if only_subject { subject(message) } else { message.trim_end().to_string() };
+    message
+        .lines()
+        .map(|line| format!("    {line}"))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Text of one commit (without trailing newline)
+pub fn format_commit(full_sha: &str, commit: &Commit, options: &LogOptions) -> String {
+    let sha = if options.abbrev_commit { abbrev(full_sha) } else { full_sha };
+    let person = |person: &Signature| person.decoded_ident(commit.encoding());
+    let date = |person: &Signature| format_date(person.time, &person.tz, options.date_format);
+
+    match &options.format {
+        PrettyFormat::Oneline => {
+            return format!("{sha} {}", subject(&commit.message_text()));
+        }
+        PrettyFormat::Format(format) | PrettyFormat::TFormat(format) => {
+            return expand_format(format, full_sha, commit, options);
+        }
+        _ => {}
+    }
+
+    let mut lines: Vec<String> = vec![format!("commit {sha}")];
+    if commit.parents.len() > 1 {
+        let parents: Vec<&str> = commit.parents.iter().map(|p| abbrev(p)).collect();
+        lines.push(format!("Merge: {}", parents.join(" ")));
+    }
+    match &options.format {
+        PrettyFormat::Short => {
+            lines.push(format!("Author: {}", person(&commit.author)));
+        }
+        PrettyFormat::Medium => {
+            lines.push(format!("Author: {}", person(&commit.author)));
+            lines.push(format!("Date:   {}", date(&commit.author)));
+        }
+        PrettyFormat::Full => {
+            lines.push(format!("Author: {}", person(&commit.author)));
+            lines.push(format!("Commit: {}", person(&commit.committer)));
+        }
+        PrettyFormat::Fuller => {
+            lines.push(format!("Author:     {}", person(&commit.author)));
+            lines.push(format!("AuthorDate: {}", date(&commit.author)));
+            lines.push(format!("Commit:     {}", person(&commit.committer)));
+            lines.push(format!("CommitDate: {}", date(&commit.committer)));
+        }
+        _ => {}
+    }
+    lines.push(String::new());
+    lines.push(indented_message(&commit.message_text(), options.format == PrettyFormat::Short));
+    lines.join("\n")
+}
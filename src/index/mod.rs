@@ -0,0 +1,320 @@
+use crate::object::{ write_object, Object, Tree, TreeEntry };
+use crate::utility::fs_utility::*;
+
+use anyhow::{ anyhow, bail, Result };
+use bytes::{ Buf, BufMut, Bytes };
+use std::fs;
+use std::os::unix::fs::MetadataExt;
+
+const INDEX_SIGNATURE: &[u8] = b"DIRC";
+const ENTRY_FIXED_L: usize = 62;
+const EXTENDED_FLAG: u16 = 0x4000;
+const NAME_MASK: u16 = 0x0fff;
+const TREE_MODE: &str = "40000";
+
+/// Single staged file from the index
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct IndexEntry {
+    pub ctime: (u32, u32),
+    pub mtime: (u32, u32),
+    pub dev: u32,
+    pub ino: u32,
+    pub mode: u32,
+    pub uid: u32,
+    pub gid: u32,
+    pub size: u32,
+    pub sha: String,
+    pub flags: u16,
+    pub path: String,
+}
+
+impl IndexEntry {
+    /// Entry for the file with stat data of the working tree file
+    pub fn from_metadata(
+        path: &str,
+        mode: &str,
+        sha: &str,
+        meta: &fs::Metadata
+    ) -> Result<IndexEntry> {
+        Ok(IndexEntry {
+            ctime: (meta.ctime() as u32, meta.ctime_nsec() as u32),
+            mtime: (meta.mtime() as u32, meta.mtime_nsec() as u32),
+            dev: meta.dev() as u32,
+            ino: meta.ino() as u32,
+            mode: u32::from_str_radix(mode, 8)?,
+            uid: meta.uid(),
+            gid: meta.gid(),
+            size: meta.size() as u32,
+            sha: sha.to_string(),
+            flags: (path.len().min(NAME_MASK as usize)) as u16,
+            path: path.to_string(),
+        })
+    }
+
+    /// Entry without stat data, the working tree file is hashed when it is compared
+    pub fn new(path: &str, mode: &str, sha: &str) -> Result<IndexEntry> {
+        Ok(IndexEntry {
+            ctime: (0, 0),
+            mtime: (0, 0),
+            dev: 0,
+            ino: 0,
+            mode: u32::from_str_radix(mode, 8)?,
+            uid: 0,
+            gid: 0,
+            size: 0,
+            sha: sha.to_string(),
+            flags: path.len().min(NAME_MASK as usize) as u16,
+            path: path.to_string(),
+        })
+    }
+
+    /// Conflicted entry of a merge (no working tree file stands behind it)
+    pub fn conflicted(path: &str, mode: &str, sha: &str, stage: u8) -> Result<IndexEntry> {
+        let mut entry = IndexEntry::new(path, mode, sha)?;
+        entry.flags |= (stage as u16) << 12;
+        Ok(entry)
+    }
+
+    /// Merge stage of the entry (0 for normal entries, 1-3 for conflicts)
+    pub fn stage(&self) -> u8 {
+        ((self.flags >> 12) & 0b11) as u8
+    }
+
+    /// Mode in the same text form as in tree objects
+    pub fn mode_string(&self) -> String {
+        format!("{:o}", self.mode)
+    }
+}
+
+/// Parsed `.git/index` file
+#[derive(Clone, Debug, Default)]
+pub struct Index {
+    pub entries: Vec<IndexEntry>,
+}
+
+impl Index {
+    /// Entries without merge conflicts
+    pub fn stage0(&self) -> impl Iterator<Item = &IndexEntry> {
+        self.entries.iter().filter(|e| e.stage() == 0)
+    }
+}
+
+pub fn index_path() -> Result<String> {
+    Ok(find_root_folder()? + ".git/index")
+}
+
+/// Read index of the current repository (empty index if the file is missing)
+pub fn read_index() -> Result<Index> {
+    let path = index_path()?;
+    match fs::read(&path) {
+        Ok(bytes) => parse_index(bytes),
+        Err(_) => Ok(Index::default()),
+    }
+}
+
+/// Parse binary index data (versions 2, 3 and 4)
+pub fn parse_index(mut bytes: Vec<u8>) -> Result<Index> {
+    if bytes.len() < 32 {
+        bail!("Index file is too short!");
+    }
+
+    // Separate and check trailing checksum
+    let sha_bytes = bytes.split_off(bytes.len() - 20);
+    let checksum = hex::encode(&sha_bytes);
+    let real_checksum = crate::utility::other_util::get_hash_from_data(&bytes);
+    if checksum != real_checksum {
+        bail!("Index checksum is not correct!");
+    }
+
+    let mut buff: Bytes = Bytes::from(bytes);
+    if &buff[..4] != INDEX_SIGNATURE {
+        bail!("Incorrect index signature!");
+    }
+    buff.advance(4);
+
+    let version = buff.get_u32();
+    if !(2..=4).contains(&version) {
+        bail!("Unsupported index version: {version}");
+    }
+    let entry_count = buff.get_u32();
+
+    let mut entries: Vec<IndexEntry> = Vec::with_capacity(entry_count as usize);
+    let mut prev_path: Vec<u8> = Vec::new();
+    for _ in 0..entry_count {
+        if buff.remaining() < ENTRY_FIXED_L {
+            bail!("Index entry is truncated!");
+        }
+        let start_remaining = buff.remaining();
+
+        let ctime = (buff.get_u32(), buff.get_u32());
+        let mtime = (buff.get_u32(), buff.get_u32());
+        let dev = buff.get_u32();
+        let ino = buff.get_u32();
+        let mode = buff.get_u32();
+        let uid = buff.get_u32();
+        let gid = buff.get_u32();
+        let size = buff.get_u32();
+        let mut sha = [0u8; 20];
+        buff.copy_to_slice(&mut sha);
+        let flags = buff.get_u16();
+        if version >= 3 && (flags & EXTENDED_FLAG) != 0 {
+            buff.advance(2); // Skip extended flags
+        }
+
+        // Read path (prefix-compressed in version 4)
+        let path: Vec<u8> = if version == 4 {
+            let strip = read_offset_varint(&mut buff) as usize;
+            let keep = prev_path
+                .len()
+                .checked_sub(strip)
+                .ok_or_else(|| anyhow!("Corrupted path compression in index!"))?;
+            let mut path = prev_path[..keep].to_vec();
+            let end = buff
+                .iter()
+                .position(|c| *c == b'\0')
+                .ok_or_else(|| anyhow!("Unterminated path in index!"))?;
+            path.extend_from_slice(&buff[..end]);
+            buff.advance(end + 1);
+            path
+        } else {
+            let name_len = (flags & NAME_MASK) as usize;
+            let end = if name_len < (NAME_MASK as usize) {
+                name_len
+            } else {
+                buff
+                    .iter()
+                    .position(|c| *c == b'\0')
+                    .ok_or_else(|| anyhow!("Unterminated path in index!"))?
+            };
+            let path = buff[..end].to_vec();
+
+            // Entries are padded with NULs to a multiple of 8 bytes
+            let consumed = start_remaining - buff.remaining() + end;
+            let padded = (consumed + 8) & !7;
+            buff.advance(padded - (start_remaining - buff.remaining()));
+            path
+        };
+
+        entries.push(IndexEntry {
+            ctime,
+            mtime,
+            dev,
+            ino,
+            mode,
+            uid,
+            gid,
+            size,
+            sha: hex::encode(sha),
+            flags,
+            path: String::from_utf8(path.clone())?,
+        });
+        prev_path = path;
+    }
+
+    // Extensions (TREE, REUC, ...) are skipped for now
+    Ok(Index { entries })
+}
+
+/// Write index file (version 2, extensions are not written)
+pub fn write_index(index: &Index) -> Result<()> {
+    let mut entries: Vec<&IndexEntry> = index.entries.iter().collect();
+    entries.sort_by(|a, b| {
+        a.path.as_bytes().cmp(b.path.as_bytes()).then(a.stage().cmp(&b.stage()))
+    });
+
+    let mut data: Vec<u8> = Vec::new();
+    data.put_slice(INDEX_SIGNATURE);
+    data.put_u32(2);
+    data.put_u32(entries.len() as u32);
+    for entry in entries {
+        let start = data.len();
+        for value in [entry.ctime.0, entry.ctime.1, entry.mtime.0, entry.mtime.1] {
+            data.put_u32(value);
+        }
+        for value in [entry.dev, entry.ino, entry.mode, entry.uid, entry.gid, entry.size] {
+            data.put_u32(value);
+        }
+        data.put_slice(&hex::decode(&entry.sha)?);
+        // Version 2 has no extended flags
+        let name_len = entry.path.len().min(NAME_MASK as usize) as u16;
+        data.put_u16((entry.flags & !(EXTENDED_FLAG | NAME_MASK)) | name_len);
+        data.put_slice(entry.path.as_bytes());
+
+        // Padding with 1 to 8 NULs
+        let length = data.len() - start;
+        data.resize(start + ((length + 8) & !7), 0);
+    }
+
+    let checksum = hex::decode(crate::utility::other_util::get_hash_from_data(&data))?;
+    data.extend(checksum);
+    write_locked(&index_path()?, data)
+}
+
+/// Variable length offset encoding used by index v4 (and ofs-delta)
+fn read_offset_varint(buff: &mut Bytes) -> u64 {
+    let mut byte = buff.get_u8();
+    let mut value: u64 = (byte & 0b01111111_u8) as u64;
+    while (byte & 0b10000000_u8) != 0 {
+        byte = buff.get_u8();
+        value = ((value + 1) << 7) | ((byte & 0b01111111_u8) as u64);
+    }
+    value
+}
+
+/// Write tree objects for the staged files, returns SHA of the root tree
+pub fn write_index_tree(index: &Index) -> Result<String> {
+    if let Some(entry) = index.entries.iter().find(|e| e.stage() != 0) {
+        bail!("Cannot write tree: '{}' is unmerged", entry.path);
+    }
+    let entries: Vec<(&str, &IndexEntry)> = index.entries
+        .iter()
+        .map(|e| (e.path.as_str(), e))
+        .collect();
+    write_subtree(&entries)
+}
+
+/// Entries are sorted by path, so every directory forms a continuous run
+fn write_subtree(entries: &[(&str, &IndexEntry)]) -> Result<String> {
+    let mut tree = Tree::default();
+    let mut i = 0;
+    while i < entries.len() {
+        let (path, entry) = entries[i];
+        match path.split_once('/') {
+            None => {
+                tree.entries.push(TreeEntry {
+                    mode: entry.mode_string(),
+                    name: path.as_bytes().to_vec(),
+                    sha: entry.sha.clone(),
+                });
+                i += 1;
+            }
+            Some((dir, _)) => {
+                let children: Vec<(&str, &IndexEntry)> = entries[i..]
+                    .iter()
+                    .map_while(|(p, e)| {
+                        p.strip_prefix(dir)
+                            .and_then(|rest| rest.strip_prefix('/'))
+                            .map(|rest| (rest, *e))
+                    })
+                    .collect();
+                i += children.len();
+                tree.entries.push(TreeEntry {
+                    mode: TREE_MODE.to_string(),
+                    name: dir.as_bytes().to_vec(),
+                    sha: write_subtree(&children)?,
+                });
+            }
+        }
+    }
+
+    // Trees order directories as if their names ended with '/'
+    let key = |entry: &TreeEntry| {
+        let mut key = entry.name.clone();
+        if entry.mode == TREE_MODE {
+            key.push(b'/');
+        }
+        key
+    };
+    tree.entries.sort_by_key(key);
+    write_object(&Object::Tree(tree))
+}
@@ -1,19 +1,82 @@
 mod remote_utility;
 mod utility;
 mod checkout;
+mod index;
+mod revision;
+mod diff;
+mod history;
+mod object;
+mod refs;
+mod config;
+mod branch;
+mod submodule;
+mod tag;
+mod reflog;
+mod merge;
+mod commit_graph;
+mod rebase;
+mod sequencer;
+mod reset;
+mod stash;
+mod show;
+mod blame;
+mod grep;
+
+// Tree comparison API
+pub use diff::{ diff_trees, ChangeStatus, DiffEntry, FileChange, TreeDiffOptions };
 
 pub mod commands {
     use crate::remote_utility::{ *, pack_processing::UnpackedObject };
     use crate::utility::*;
     use crate::checkout::*;
+    use crate::diff;
+    use crate::revision;
+    use crate::history;
+    use crate::object::{ self, Blob, Commit, Object, Tree, TreeEntry, EMPTY_TREE_SHA };
+    use crate::index;
+    use crate::refs;
+    use crate::branch;
+    use crate::submodule;
+    use crate::tag;
+    use crate::reflog;
+    use crate::merge;
+    use crate::commit_graph;
+    use crate::rebase;
+    use crate::sequencer::{ self, PickKind };
+    use crate::reset;
+    use crate::stash;
+    use crate::show;
+    use crate::blame;
+    use crate::grep;
+    use crate::config::{ self as config_file, Config, ConfigEntry };
+    use crate::utility::date_util::format_date;
+
+    pub use crate::diff::{ DiffAlgorithm, DiffOptions, DiffOutput, tree_diff::parse_score };
+    pub use crate::branch::BranchAction;
+    pub use crate::checkout::SwitchOptions;
+    pub use crate::config::{ ConfigAction, ConfigOptions, Scope, ValueType };
+    pub use crate::refs::ShowRefOptions;
+    pub use crate::reflog::ReflogAction;
+    pub use crate::merge::{ FastForward, MergeAction };
+    pub use crate::commit_graph::CommitGraphAction;
+    pub use crate::rebase::RebaseAction;
+    pub use crate::sequencer::{ PickAction, PickOptions };
+    pub use crate::reset::{ ResetMode, ResetOptions, RestoreOptions };
+    pub use crate::stash::StashAction;
+    pub use crate::show::ShowOptions;
+    pub use crate::blame::{ BlameOptions, BlameOutput };
+    pub use crate::grep::{ GrepOptions, PatternSyntax };
+    pub use crate::submodule::SubmoduleAction;
+    pub use crate::tag::TagAction;
+    pub use crate::history::{ CommitOrder, LogOptions, PrettyFormat };
+    pub use crate::utility::date_util::{ approxidate, DateFormat };
 
     use anyhow::{ anyhow, bail, Result };
     use std::fs;
+    use std::os::unix::ffi::OsStrExt;
     use std::path::Path;
-    use hex;
 
     // Hardcoded constants
-    const BLOB_MODE: &str = "100644";
     const TREE_MODE: &str = "40000";
 
     /// Command to init git repository in current folder
@@ -27,30 +90,30 @@ pub mod commands {
     /// Open file and print binary data in pretty way
     pub fn cat_file_print(sha: &str) -> Result<String> {
         // Read object data
-        let buff_string: String = String::from_utf8(fs_utility::read_data_decompressed(sha)?)?;
-
-        // Divide data (header, text)
-        if let Some((header, data)) = buff_string.split_once('\0') {
-            // Print out object content
-            if let Some(("blob", _size)) | Some(("commit", _size)) = header.split_once(' ') {
-                Ok(data.to_owned())
-            } else {
-                Err(anyhow!("Not implemented for this object!")) // TODO: Implement for tree
+        let object = object::read_object(sha)?;
+
+        // Print out object content
+        match object {
+            Object::Blob(blob) => Ok(String::from_utf8(blob.data)?),
+            Object::Tree(tree) => {
+                let mut out = String::new();
+                for entry in tree.entries {
+                    let TreeEntry { mode, sha, .. } = &entry;
+                    let name = entry.name_text();
+                    out.push_str(&format!("{mode:0>6} {} {sha}\t{name}\n", entry.kind()));
+                }
+                Ok(out)
             }
-        } else {
-            Err(anyhow!("Unrecognised file structure!"))
+            other => Ok(String::from_utf8_lossy(&other.serialize()?).to_string()),
         }
     }
 
     /// Create a blob from a file
     /// Trait AsRef<Path> is for ability to call function with path in [String] or [Path] object
     pub fn hash_object_write<T: AsRef<Path>>(file_path: &T) -> Result<String> {
-        // Get data from file and format it according to git rules
-        let bytes: Vec<u8> = fs::read(file_path)?;
-        let text: Vec<u8> = other_util::add_data_prefix(b"blob", bytes);
-
-        // Write data into object
-        let hash = fs_utility::write_data(text)?;
+        // Get data from file and write it as a blob
+        let data: Vec<u8> = fs::read(file_path)?;
+        let hash = object::write_object(&Object::Blob(Blob { data }))?;
 
         // Print hash
         Ok(hash)
@@ -58,14 +121,12 @@ pub mod commands {
 
     /// Read a tree object
     pub fn read_tree_names(sha: &str) -> Result<String> {
-        // Read data from object
-        let bytes_decoded: Vec<u8> = fs_utility::read_data_decompressed(sha)?;
-
-        // Parse text and extract filenames
-        let result: Vec<_> = other_util
-            ::parse_tree(&bytes_decoded)?
+        // Read tree and extract filenames
+        let result: Vec<String> = object
+            ::read_tree(sha)?
+            .entries
             .into_iter()
-            .map(|obj| obj.0)
+            .map(|entry| entry.name_text().into_owned())
             .collect();
         Ok(result.join("\n"))
     }
@@ -74,11 +135,19 @@ pub mod commands {
     pub fn write_tree() -> Result<String> {
         // Find root folder and create tree starting from it
         let basic_path: String = fs_utility::find_root_folder()?;
-        write_tree_with_path(&basic_path)
+        // Tracked modes are kept when exec bits or symlinks are not trusted
+        let tracked = diff::index_files(&index::read_index()?);
+        let modes = diff::WorktreeModes::load()?;
+        write_tree_with_path(&basic_path, "", &tracked, modes)
     }
 
     /// Recursive function to create subtrees
-    fn write_tree_with_path<T: AsRef<Path>>(folder_path: &T) -> Result<String> {
+    fn write_tree_with_path<T: AsRef<Path>>(
+        folder_path: &T,
+        prefix: &str,
+        tracked: &diff::FileMap,
+        modes: diff::WorktreeModes
+    ) -> Result<String> {
         // Get folder entries and sort them
         let mut entries: Vec<_> = fs
             ::read_dir(folder_path)?
@@ -86,8 +155,8 @@ pub mod commands {
             .collect();
         entries.sort_by_key(|dir| { dir.file_name() });
 
-        // Accumulate data in contents vec
-        let mut contents: Vec<u8> = Vec::new();
+        // Accumulate entries of the tree
+        let mut tree = Tree::default();
 
         // Go trough dir entries
         for entry in entries {
@@ -97,72 +166,667 @@ pub mod commands {
                 .ok_or_else(|| anyhow!("Corrupted filename!"))?
                 .to_str()
                 .ok_or_else(|| anyhow!("Corrupted filename!"))?;
-            if e_path.is_dir() {
+            // Symlinks are not followed
+            let meta = fs::symlink_metadata(&e_path)?;
+            let path = format!("{prefix}{file_name}");
+            let tracked_entry = tracked.get(&path);
+            let submodule = tracked_entry.is_some_and(|(mode, _)| mode == diff::GITLINK_MODE);
+            if meta.is_dir() && (submodule || e_path.join(".git").is_dir()) {
+                // Nested repository is recorded by its checked out commit
+                let checked_out = submodule::checked_out_commit(&e_path)?;
+                let Some(sha) = checked_out.or(tracked_entry.map(|(_, sha)| sha.clone())) else {
+                    continue;
+                };
+                tree.entries.push(TreeEntry {
+                    mode: diff::GITLINK_MODE.to_string(),
+                    name: file_name.as_bytes().to_vec(),
+                    sha,
+                });
+            } else if meta.is_dir() {
                 if e_path.ends_with(".git") {
                     continue; // TODO: Parse .gitignore?
                 }
 
-                let sub_tree_sha: Vec<u8> = hex::decode(write_tree_with_path(&e_path)?)?;
-                contents.extend(format!("{TREE_MODE} {file_name}\0").bytes());
-                contents.extend(sub_tree_sha.iter()); // Add tree sha
+                tree.entries.push(TreeEntry {
+                    mode: TREE_MODE.to_string(),
+                    name: file_name.as_bytes().to_vec(),
+                    sha: write_tree_with_path(
+                        &e_path,
+                        &format!("{prefix}{file_name}/"),
+                        tracked,
+                        modes
+                    )?,
+                });
             } else {
-                let blob_sha: Vec<u8> = hex::decode(hash_object_write(&e_path)?)?;
-                contents.extend(format!("{BLOB_MODE} {file_name}\0").bytes());
-                contents.extend(blob_sha.iter()); // Add blob sha
+                let tracked_mode = tracked_entry.map(|(mode, _)| mode.as_str());
+                let mode = diff::recorded_mode(&meta, tracked_mode, modes);
+                // Symlink is stored as a blob with its target
+                let sha = if meta.file_type().is_symlink() {
+                    let data = fs::read_link(&e_path)?.as_os_str().as_bytes().to_vec();
+                    object::write_object(&Object::Blob(Blob { data }))?
+                } else {
+                    hash_object_write(&e_path)?
+                };
+                tree.entries.push(TreeEntry {
+                    mode: mode.to_string(),
+                    name: file_name.as_bytes().to_vec(),
+                    sha,
+                });
+            }
+        }
+
+        // Write tree object
+        let hash = object::write_object(&Object::Tree(tree))?;
+
+        // Print hash
+        Ok(hash)
+    }
+
+    /// Resolve object name and check it has the expected type
+    fn resolve_object_of_kind(name: &str, kind: &str) -> Result<String> {
+        let sha = revision
+            ::resolve_revision(name)
+            .map_err(|_| anyhow!("Not a valid object name {name}"))?;
+        if object::object_kind(&sha)? != kind {
+            bail!("{sha} is not a valid '{kind}' object");
+        }
+        Ok(sha)
+    }
+
+    /// Function to create commit from tree with any number of parents,
+    /// message is built from `-m` paragraphs or read from the `-F` file (stdin when neither)
+    pub fn create_commit_with_message(
+        tree: &str,
+        parents: &[String],
+        messages: &[String],
+        message_file: Option<&str>
+    ) -> Result<String> {
+        if !messages.is_empty() && message_file.is_some() {
+            bail!("Option -m cannot be combined with -F");
+        }
+        let tree_sha = resolve_object_of_kind(tree, "tree")?;
+
+        // Duplicate parents are ignored
+        let mut parent_shas: Vec<String> = Vec::new();
+        for parent in parents {
+            let sha = resolve_object_of_kind(parent, "commit")?;
+            if !parent_shas.contains(&sha) {
+                parent_shas.push(sha);
+            }
+        }
+
+        // Every -m is a separate paragraph ending with newline
+        let mut message: Vec<u8> = Vec::new();
+        for paragraph in messages {
+            if !message.is_empty() {
+                message.push(b'\n');
+            }
+            message.extend(paragraph.bytes());
+            if !message.ends_with(b"\n") {
+                message.push(b'\n');
             }
         }
+        if messages.is_empty() {
+            message.extend(fs_utility::read_file_or_stdin(message_file.unwrap_or("-"))?);
+        }
 
-        // Add header to the text
-        let text: Vec<u8> = other_util::add_data_prefix(b"tree", contents);
+        let commit = Commit {
+            tree: tree_sha,
+            parents: parent_shas,
+            author: other_util::signature("author")?,
+            committer: other_util::signature("committer")?,
+            extra_headers: Vec::new(),
+            message,
+        };
 
         // Write data into object
-        let hash = fs_utility::write_data(text)?;
+        let hash = object::write_object(&Object::Commit(commit.into_utf8()))?;
 
         // Print hash
         Ok(hash)
     }
 
-    /// Function to create commit from tree
-    pub fn create_commit_with_message(
-        tree_sha: &str,
-        parent_sha: &str,
-        message: &str
+    /// Record staged changes as a new commit and move the current branch (or detached HEAD) to it
+    pub fn commit(
+        messages: &[String],
+        message_file: Option<&str>,
+        amend: bool,
+        allow_empty: bool
+    ) -> Result<String> {
+        if !messages.is_empty() && message_file.is_some() {
+            bail!("Option -m cannot be combined with -F");
+        }
+        let head = revision::read_head_commit()?;
+        let merge_heads = merge::merge_heads()?;
+        let amended: Option<Commit> = match (&head, amend) {
+            (_, true) if !merge_heads.is_empty() => {
+                bail!("You are in the middle of a merge -- cannot amend.")
+            }
+            (Some(sha), true) => Some(object::read_commit(sha)?),
+            (None, true) => bail!("You have nothing to amend."),
+            _ => None,
+        };
+        let index = index::read_index()?;
+        if index.entries.iter().any(|entry| entry.stage() != 0) {
+            bail!("Committing is not possible because you have unmerged files.");
+        }
+
+        // Message paragraphs from -m, file from -F, message of the amended commit
+        // or the prepared merge message without its comments
+        let text = match (message_file, &amended) {
+            (Some(path), _) => fs_utility::read_file_or_stdin(path)?,
+            (None, _) if !messages.is_empty() => messages.join("\n\n").into_bytes(),
+            (None, Some(old)) => old.utf8_message(),
+            (None, None) => {
+                other_util::strip_comments(&merge::merge_message()?.unwrap_or_default())
+            }
+        };
+        let message = other_util::cleanup_message(&text);
+        if message.is_empty() {
+            bail!("Aborting commit due to empty commit message.");
+        }
+
+        // Build tree from index and compare it with the parent (merges are recorded anyway)
+        let tree = index::write_index_tree(&index)?;
+        let mut parents: Vec<String> = match (&amended, &head) {
+            (Some(old), _) => old.parents.clone(),
+            (None, Some(sha)) => vec![sha.clone()],
+            (None, None) => Vec::new(),
+        };
+        parents.extend(merge_heads.iter().cloned());
+        let parent_tree = match parents.first() {
+            Some(parent) => object::read_commit(parent)?.tree,
+            None => EMPTY_TREE_SHA.to_string(),
+        };
+        if parent_tree == tree && !allow_empty && !amend && merge_heads.is_empty() {
+            bail!("nothing to commit, working tree clean");
+        }
+
+        // Write commit (amend and cherry-picks stopped on conflicts keep the original author)
+        let picked = match (&amended, sequencer::cherry_pick_head()?) {
+            (None, Some(sha)) => Some(object::read_commit(&sha)?),
+            _ => None,
+        };
+        let committer = other_util::signature("committer")?;
+        let author = match (&amended, &picked) {
+            (Some(old), _) | (None, Some(old)) => old.author.clone(),
+            (None, None) => other_util::signature("author")?,
+        };
+        let author_date = amended
+            .as_ref()
+            .or(picked.as_ref())
+            .map(|old| format_date(old.author.time, &old.author.tz, Default::default()));
+        let commit = Commit {
+            tree: tree.clone(),
+            parents: parents.clone(),
+            author,
+            committer: committer.clone(),
+            extra_headers: Vec::new(),
+            message,
+        }.into_utf8();
+        let sha = object::write_object(&Object::Commit(commit.clone()))?;
+
+        // Move HEAD (or the branch it points to) and log it
+        let text = commit.message_text();
+        let subject = text.lines().next().unwrap_or_default();
+        let kind = if amend {
+            " (amend)"
+        } else if !merge_heads.is_empty() {
+            " (merge)"
+        } else if parents.is_empty() {
+            " (initial)"
+        } else if picked.is_some() {
+            " (cherry-pick)"
+        } else {
+            ""
+        };
+        refs::update_ref("HEAD", &sha, &committer, &format!("commit{kind}: {subject}"))?;
+        merge::remove_state()?;
+        sequencer::remove_pick_heads()?;
+
+        // Summary in the same form as git
+        let branch = refs::current_branch()?.unwrap_or_else(|| "detached HEAD".to_string());
+        let root = if parents.is_empty() { " (root-commit)" } else { "" };
+        let mut out = format!("[{branch}{root} {}] {subject}\n", diff::abbrev(&sha));
+        if commit.author.ident() != committer.ident() {
+            out.push_str(&format!(" Author: {}\n", commit.author.ident()));
+        }
+        if let Some(date) = author_date {
+            out.push_str(&format!(" Date: {date}\n"));
+        }
+        // Merge commits are shown without changes
+        if !merge_heads.is_empty() {
+            return Ok(out);
+        }
+        let options = DiffOptions::default();
+        let old_tree = (parent_tree != EMPTY_TREE_SHA).then_some(parent_tree.as_str());
+        let changes = diff::diff_trees(old_tree, Some(&tree), &options.tree_options(true))?;
+        if !changes.is_empty() {
+            out.push_str(&diff::format::short_stat(&changes, options.algorithm)?);
+            out.push_str(&diff::format::summary(&changes));
+        }
+        Ok(out)
+    }
+
+    /// Show changes between working tree, index and trees
+    pub fn diff(revisions: &[String], cached: bool, options: &DiffOptions) -> Result<String> {
+        let changes = diff::collect_changes(revisions, cached, options)?;
+        diff::format_changes(&changes, options)
+    }
+
+    /// Compare two trees or a commit with its parent
+    pub fn diff_tree(
+        revisions: &[String],
+        recursive: bool,
+        show_root: bool,
+        show_commit_id: bool,
+        options: &DiffOptions
     ) -> Result<String> {
-        // Variable to store commit text
-        let mut contents: Vec<u8> = Vec::new();
+        let tree_options = options.tree_options(recursive);
+        let (commit_id, changes) = match revisions {
+            [from, to] => {
+                let old = revision::resolve_tree_ish(from)?;
+                let new = revision::resolve_tree_ish(to)?;
+                (None, diff::diff_trees(Some(&old), Some(&new), &tree_options)?)
+            }
+            [rev] => {
+                let commit = revision::resolve_revision(&format!("{rev}^{{commit}}"))?;
+                let Commit { tree, parents, .. } = object::read_commit(&commit)?;
+                let changes = match parents.as_slice() {
+                    [] if show_root => diff::diff_trees(None, Some(&tree), &tree_options)?,
+                    [parent] => {
+                        let parent_tree = object::read_commit(parent)?.tree;
+                        diff::diff_trees(Some(&parent_tree), Some(&tree), &tree_options)?
+                    }
+                    _ => Vec::new(), // Root commits (without --root) and merges show nothing
+                };
+                (Some(commit), changes)
+            }
+            _ => bail!("'diff-tree' needs a commit or two trees"),
+        };
 
-        // Create timestamp
-        let timestamp: String = other_util::get_time_stamp_string()?;
+        if changes.is_empty() {
+            return Ok(String::new());
+        }
+        let mut out = String::new();
+        if let (Some(commit), true) = (commit_id, show_commit_id) {
+            out.push_str(&format!("{commit}\n"));
+        }
+        out.push_str(&diff::format_changes(&changes, options)?);
+        Ok(out)
+    }
 
-        // Add tree sha
-        contents.extend("tree ".bytes());
-        contents.extend(tree_sha.bytes());
-        contents.push(b'\n');
+    /// Show commit history starting from revisions (HEAD by default)
+    pub fn log(revisions: &[String], options: &LogOptions) -> Result<String> {
+        let entries = history::walk(revisions, options)?;
+        Ok(history::format_log(&entries, options))
+    }
 
-        // Add parent sha
-        contents.extend("parent ".bytes());
-        contents.extend(parent_sha.bytes());
-        contents.push(b'\n');
+    /// Show objects: commits with their changes, tags, trees and blobs
+    pub fn show(objects: &[String], options: &ShowOptions) -> Result<String> {
+        show::show(objects, options)
+    }
+
+    /// Show the commit which last changed each line of a file
+    pub fn blame(options: &BlameOptions) -> Result<String> {
+        blame::blame(options)
+    }
 
-        // Add author and committer (hardcoded using consts)
-        contents.extend("author ".bytes());
-        other_util::append_committer_data(&mut contents, &timestamp);
-        contents.extend("committer ".bytes());
-        other_util::append_committer_data(&mut contents, &timestamp);
+    /// Search tracked files of the working tree, the index or trees for lines matching patterns
+    pub fn grep(options: &GrepOptions) -> Result<String> {
+        grep::grep(options)
+    }
 
-        // Add message
-        contents.push(b'\n');
-        contents.extend(message.bytes());
-        contents.push(b'\n');
+    /// List commits reachable from revisions, or count them
+    pub fn rev_list(
+        revisions: &[String],
+        options: &LogOptions,
+        count: bool,
+        left_right: bool
+    ) -> Result<String> {
+        let entries = history::walk(revisions, options)?;
+        Ok(history::format_rev_list(&entries, count, left_right))
+    }
 
-        // Add header to the text
-        let text: Vec<u8> = other_util::add_data_prefix(b"commit", contents);
+    /// Best common ancestors of the first commit and the others (only one without `all`),
+    /// with `octopus` common ancestors of all of them
+    pub fn merge_base(commits: &[String], all: bool, octopus: bool) -> Result<String> {
+        let shas = commits
+            .iter()
+            .map(|name| revision::resolve_commit(name))
+            .collect::<Result<Vec<String>>>()?;
+        let bases = match shas.split_first() {
+            Some(_) if octopus => history::ancestry::octopus_merge_bases(&shas)?,
+            Some((first, others)) if !others.is_empty() => {
+                history::CommitWalker::new()?.merge_bases(first, others)?
+            }
+            _ => bail!("merge-base needs at least two commits"),
+        };
+        let shown = if all { bases.len() } else { 1 };
+        Ok(bases.iter().take(shown).map(|sha| format!("{sha}\n")).collect())
+    }
 
-        // Write data into object
-        let hash = fs_utility::write_data(text)?;
+    /// Whether the first commit is an ancestor of the second one (`merge-base --is-ancestor`)
+    pub fn is_ancestor(ancestor: &str, descendant: &str) -> Result<bool> {
+        let ancestor = revision::resolve_commit(ancestor)?;
+        let descendant = revision::resolve_commit(descendant)?;
+        history::is_ancestor(&ancestor, &descendant)
+    }
 
-        // Print hash
-        Ok(hash)
+    /// Commit (HEAD by default) branched off the ref according to its reflog
+    pub fn fork_point(name: &str, commit: Option<&str>) -> Result<String> {
+        let commit = revision::resolve_commit(commit.unwrap_or("HEAD"))?;
+        Ok(match history::ancestry::fork_point(name, &commit)? {
+            Some(sha) => format!("{sha}\n"),
+            None => String::new(),
+        })
+    }
+
+    /// Query or change config values, reading all levels unless one file is selected
+    pub fn config(
+        name: Option<&str>,
+        value: Option<&str>,
+        options: &ConfigOptions
+    ) -> Result<String> {
+        let selected = match (&options.file, options.scope) {
+            (Some(path), _) => Some((path.clone(), Scope::File)),
+            (None, Some(scope)) => Some((config_file::scope_file(scope)?, scope)),
+            (None, None) => None,
+        };
+        let load = || {
+            match &selected {
+                Some((path, scope)) => Config::load_file(path, *scope, options.includes),
+                None => Config::load(),
+            }
+        };
+        let origin = |entry: &ConfigEntry| {
+            let mut prefix = String::new();
+            if options.show_scope {
+                prefix += &format!("{}\t", entry.scope.name());
+            }
+            if options.show_origin {
+                prefix += &format!("file:{}\t", entry.origin);
+            }
+            prefix
+        };
+        let name = || name.ok_or_else(|| anyhow!("key name is required"));
+
+        let mut output = String::new();
+        match options.action {
+            ConfigAction::List => {
+                for entry in load()?.entries() {
+                    output += &origin(entry);
+                    output += &entry.name;
+                    if let Some(value) = &entry.value {
+                        output += &format!("={value}");
+                    }
+                    output.push('\n');
+                }
+            }
+            ConfigAction::Get | ConfigAction::GetAll => {
+                let config = load()?;
+                let mut entries = config.get_all(name()?)?;
+                if options.action == ConfigAction::Get {
+                    entries = entries.split_off(entries.len().saturating_sub(1));
+                }
+                if entries.is_empty() {
+                    bail!("key '{}' not found", name()?);
+                }
+                for entry in entries {
+                    output += &origin(entry);
+                    output += &options.value_type.format(entry.value.as_deref())?;
+                    output.push('\n');
+                }
+            }
+            ConfigAction::Set | ConfigAction::Unset | ConfigAction::UnsetAll => {
+                let path = match selected {
+                    Some((path, _)) => path,
+                    None => config_file::scope_file(Scope::Local)?,
+                };
+                if options.action == ConfigAction::Set {
+                    let value = value.ok_or_else(|| anyhow!("value is required"))?;
+                    let value = match options.value_type {
+                        ValueType::Raw => value.to_string(),
+                        value_type => value_type.format(Some(value))?,
+                    };
+                    config_file::set_value(&path, name()?, &value)?;
+                } else {
+                    let all = options.action == ConfigAction::UnsetAll;
+                    config_file::unset_value(&path, name()?, all)?;
+                }
+            }
+        }
+        Ok(output)
+    }
+
+    /// List refs (loose and packed) whose names end with one of the patterns
+    pub fn show_ref(patterns: &[String], options: &ShowRefOptions) -> Result<String> {
+        let mut selected: Vec<(String, String)> = Vec::new();
+        if options.verify {
+            // Only full names are accepted, HEAD included
+            for name in patterns {
+                let sha = match name == "HEAD" || name.starts_with("refs/") {
+                    true => refs::read_ref(name)?,
+                    false => None,
+                };
+                let sha = sha.ok_or_else(|| anyhow!("'{name}' - not a valid ref"))?;
+                selected.push((name.clone(), sha));
+            }
+        } else {
+            if options.head {
+                if let Some(sha) = refs::read_ref("HEAD")? {
+                    selected.push(("HEAD".to_string(), sha));
+                }
+            }
+            for (name, sha) in refs::list_refs("refs/")? {
+                let kind = (!options.heads && !options.tags) ||
+                    (options.heads && name.starts_with("refs/heads/")) ||
+                    (options.tags && name.starts_with("refs/tags/"));
+                let matched = patterns.is_empty() ||
+                    patterns.iter().any(|pattern| {
+                        name == *pattern || name.ends_with(&format!("/{pattern}"))
+                    });
+                if kind && matched {
+                    selected.push((name, sha));
+                }
+            }
+        }
+        if options.quiet {
+            return Ok(String::new());
+        }
+
+        let line = |sha: &str, name: &str| match options.hash_only {
+            true => format!("{sha}\n"),
+            false => format!("{sha} {name}\n"),
+        };
+        let mut output = String::new();
+        for (name, sha) in selected {
+            output += &line(&sha, &name);
+            if options.dereference {
+                if let Some(peeled) = refs::peeled_ref(&name, &sha)? {
+                    output += &line(&peeled, &format!("{name}^{{}}"));
+                }
+            }
+        }
+        Ok(output)
+    }
+
+    /// Point the ref to the new object or delete it, `old` must match its current value
+    /// (empty or zero SHA: the ref must not exist)
+    pub fn update_ref(
+        name: &str,
+        new: Option<&str>,
+        old: Option<&str>,
+        no_deref: bool,
+        message: Option<&str>
+    ) -> Result<String> {
+        if name != "HEAD" && !refs::is_valid_ref_name(name) {
+            bail!("update_ref failed for ref '{name}': refusing to update ref with bad name");
+        }
+        let old = match old {
+            Some("") | Some(refs::ZERO_SHA) => Some(refs::ZERO_SHA.to_string()),
+            Some(old) => Some(revision::resolve_revision(old)?),
+            None => None,
+        };
+
+        // Zero SHA as the new value deletes the ref as `-d` does
+        match new {
+            Some(new) if new != refs::ZERO_SHA => {
+                let new = revision::resolve_revision(new)?;
+                let committer = other_util::signature("committer")?;
+                let message = message.unwrap_or_default();
+                let old = old.as_deref();
+                refs::update_ref_checked(name, &new, old, no_deref, &committer, message)?;
+            }
+            _ => {
+                let target = match no_deref {
+                    true => name.to_string(),
+                    false => refs::resolve_ref(name)?.0,
+                };
+                refs::delete_ref_checked(&target, old.as_deref())?;
+            }
+        }
+        Ok(String::new())
+    }
+
+    /// List, create, delete or rename branches and set their upstream
+    pub fn branch(action: &BranchAction) -> Result<String> {
+        match action {
+            BranchAction::List { verbose, all, remotes } => branch::list(*verbose, *all, *remotes),
+            BranchAction::Create { name, start, force } => {
+                branch::create(name, start.as_deref(), *force)
+            }
+            BranchAction::Delete { names, force, remotes } => {
+                branch::delete(names, *force, *remotes)
+            }
+            BranchAction::Rename { old, new, force } => branch::rename(old.as_deref(), new, *force),
+            BranchAction::SetUpstream { upstream, branch } => {
+                branch::set_upstream(upstream, branch.as_deref())
+            }
+            BranchAction::UnsetUpstream { branch } => branch::unset_upstream(branch.as_deref()),
+        }
+    }
+
+    /// Switch branches (or detach HEAD) carrying local changes over when it is safe
+    pub fn switch(options: &SwitchOptions) -> Result<String> {
+        crate::checkout::switch(options)
+    }
+
+    /// List, create or delete tags
+    pub fn tag(action: &TagAction) -> Result<String> {
+        match action {
+            TagAction::List { patterns, lines } => tag::list(patterns, *lines),
+            TagAction::Create { name, target, annotate, messages, message_file, force } => {
+                tag::create(
+                    name,
+                    target.as_deref(),
+                    *annotate,
+                    messages,
+                    message_file.as_deref(),
+                    *force
+                )
+            }
+            TagAction::Delete { names } => tag::delete(names),
+        }
+    }
+
+    /// Show, expire or delete reflog entries
+    pub fn reflog(action: &ReflogAction) -> Result<String> {
+        match action {
+            ReflogAction::Show { name, max_count } => reflog::show(name.as_deref(), *max_count),
+            ReflogAction::Expire { expire, expire_unreachable, all, names } => {
+                reflog::expire(expire.as_deref(), expire_unreachable.as_deref(), *all, names)
+            }
+            ReflogAction::Delete { entries } => reflog::delete(entries),
+        }
+    }
+
+    /// Merge a commit into HEAD, or continue or abort the merge in progress
+    pub fn merge(action: &MergeAction) -> Result<String> {
+        match action {
+            MergeAction::Start { commit: name, messages, fast_forward } => {
+                merge::merge(name, messages, *fast_forward)
+            }
+            MergeAction::Continue => {
+                if merge::merge_heads()?.is_empty() {
+                    bail!("There is no merge in progress (MERGE_HEAD missing).");
+                }
+                commit(&[], None, false, false)
+            }
+            MergeAction::Abort => merge::abort(),
+        }
+    }
+
+    /// Apply the changes of commits on top of HEAD, or continue, skip or abort the cherry-pick
+    pub fn cherry_pick(action: &PickAction) -> Result<String> {
+        replay(PickKind::CherryPick, action)
+    }
+
+    /// Undo the changes of commits with new commits, or continue, skip or abort the revert
+    pub fn revert(action: &PickAction) -> Result<String> {
+        replay(PickKind::Revert, action)
+    }
+
+    fn replay(kind: PickKind, action: &PickAction) -> Result<String> {
+        match action {
+            PickAction::Start { commits, options } => sequencer::start(kind, commits, options),
+            PickAction::Continue => sequencer::resume(),
+            PickAction::Skip => sequencer::skip(),
+            PickAction::Abort => sequencer::abort(),
+        }
+    }
+
+    /// Replay commits onto another base, or continue, skip or abort the rebase in progress
+    pub fn rebase(action: &RebaseAction) -> Result<String> {
+        match action {
+            RebaseAction::Start { upstream, interactive } => rebase::start(upstream, *interactive),
+            RebaseAction::Continue => rebase::resume(),
+            RebaseAction::Skip => rebase::skip(),
+            RebaseAction::Abort => rebase::abort(),
+        }
+    }
+
+    /// Move HEAD to the commit resetting the index and the working tree, or unstage paths
+    pub fn reset(options: &ResetOptions) -> Result<String> {
+        reset::reset(options)
+    }
+
+    /// Restore working tree files and/or index entries from the index or a tree
+    pub fn restore(options: &RestoreOptions) -> Result<String> {
+        reset::restore(options)
+    }
+
+    /// Save local changes away, or list, show, reapply and remove saved entries
+    pub fn stash(action: &StashAction) -> Result<String> {
+        match action {
+            StashAction::Push { message, include_untracked, keep_index } => {
+                stash::push(message.as_deref(), *include_untracked, *keep_index)
+            }
+            StashAction::List => stash::list(),
+            StashAction::Show { stash, patch } => stash::show(stash.as_ref(), *patch),
+            StashAction::Apply { stash, index } => stash::apply(stash.as_ref(), *index),
+            StashAction::Pop { stash, index } => stash::pop(stash.as_ref(), *index),
+            StashAction::Drop { stash } => stash::drop_entry(stash.as_ref()),
+            StashAction::Clear => stash::clear(),
+        }
+    }
+
+    /// Write the commit-graph which speeds up history walks
+    pub fn commit_graph(action: &CommitGraphAction) -> Result<String> {
+        match action {
+            CommitGraphAction::Write { split, changed_paths } => {
+                commit_graph::write(*split, *changed_paths)
+            }
+        }
+    }
+
+    /// Register, populate or show submodules
+    pub fn submodule(action: &SubmoduleAction) -> Result<String> {
+        match action {
+            SubmoduleAction::Init { paths } => submodule::init(paths),
+            SubmoduleAction::Update { init, paths } => submodule::update(*init, paths),
+            SubmoduleAction::Status { paths } => submodule::status(paths),
+        }
     }
 
     /// Command to clone remote repo <repo_url> into folder <folder_path>
@@ -225,6 +889,16 @@ pub mod commands {
         // Setup remote config
         write_config(repo_url)?;
 
+        // Reflogs start with the clone (only when the identity is known)
+        let committer = other_util::signature("committer");
+        if let (Ok(committer), Some(sha)) = (committer, revision::read_head_commit()?) {
+            let message = format!("clone: from {repo_url}");
+            if let Some(branch) = refs::symbolic_target("HEAD")? {
+                refs::append_reflog(&branch, refs::ZERO_SHA, &sha, &committer, &message)?;
+            }
+            refs::append_reflog("HEAD", refs::ZERO_SHA, &sha, &committer, &message)?;
+        }
+
         Ok(format!("Repository '{repo_url}' succesfully cloned into '{folder_path}'"))
     }
 
@@ -243,7 +917,7 @@ pub mod commands {
         #[test]
         fn send_request_to_clone() {
             fs::remove_dir_all("/tmp/clone_repo_test").unwrap();
-            let res = clone_repo(&TEST_REPO_2.to_string(), &"/tmp/clone_repo_test".to_string());
+            let res = clone_repo(TEST_REPO_2, &"/tmp/clone_repo_test".to_string());
             println!("{:?}", res);
             assert!(res.is_ok());
         }
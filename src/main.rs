@@ -1,5 +1,42 @@
-use git_starter_rust::commands;
-use clap::{ Parser, Subcommand };
+use git_starter_rust::commands::{
+    self,
+    approxidate,
+    parse_score,
+    BlameOptions,
+    BlameOutput,
+    BranchAction,
+    CommitGraphAction,
+    CommitOrder,
+    ConfigAction,
+    ConfigOptions,
+    DateFormat,
+    DiffAlgorithm,
+    DiffOptions,
+    DiffOutput,
+    FastForward,
+    GrepOptions,
+    LogOptions,
+    MergeAction,
+    PatternSyntax,
+    PickAction,
+    PickOptions,
+    PrettyFormat,
+    RebaseAction,
+    ReflogAction,
+    ResetMode,
+    ResetOptions,
+    RestoreOptions,
+    Scope,
+    ShowOptions,
+    ShowRefOptions,
+    StashAction,
+    SubmoduleAction,
+    SwitchOptions,
+    TagAction,
+    ValueType,
+};
+use clap::{ Args, Parser, Subcommand };
+use anyhow::{ anyhow, bail, Result };
 
 #[derive(Parser)]
 #[command(version)]
@@ -8,6 +45,763 @@ struct Cli {
     command: Commands,
 }
 
+/// Flags controlling diff output (shared by commands that print patches)
+#[derive(Args)]
+struct DiffFlags {
+    #[clap(short = 'U', long = "unified", default_value_t = 3)]
+    unified: usize,
+    #[clap(long)]
+    stat: bool,
+    #[clap(long)]
+    numstat: bool,
+    #[clap(long = "name-status")]
+    name_status: bool,
+    #[clap(long = "name-only")]
+    name_only: bool,
+    #[clap(long)]
+    patience: bool,
+    #[clap(long)]
+    histogram: bool,
+    #[clap(long)]
+    minimal: bool,
+    #[clap(long = "diff-algorithm")]
+    diff_algorithm: Option<String>,
+    #[clap(short = 'p', long = "patch")]
+    patch: bool,
+    #[clap(short = 'M', long = "find-renames", num_args = 0..=1, require_equals = true, default_missing_value = "")]
+    find_renames: Option<String>,
+    #[clap(short = 'C', long = "find-copies", num_args = 0..=1, require_equals = true, default_missing_value = "")]
+    find_copies: Option<String>,
+    #[clap(long = "find-copies-harder")]
+    find_copies_harder: bool,
+    #[clap(long = "no-renames")]
+    no_renames: bool,
+}
+
+impl DiffFlags {
+    /// Build options, `default_output` and `default_renames` differ between commands
+    fn to_options(
+        &self,
+        paths: &[String],
+        default_output: DiffOutput,
+        default_renames: bool
+    ) -> Result<DiffOptions> {
+        let algorithm = if let Some(name) = &self.diff_algorithm {
+            DiffAlgorithm::from_name(name)?
+        } else if self.patience {
+            DiffAlgorithm::Patience
+        } else if self.histogram {
+            DiffAlgorithm::Histogram
+        } else {
+            DiffAlgorithm::Myers
+        };
+
+        let output = if self.stat {
+            DiffOutput::Stat
+        } else if self.numstat {
+            DiffOutput::NumStat
+        } else if self.name_status {
+            DiffOutput::NameStatus
+        } else if self.name_only {
+            DiffOutput::NameOnly
+        } else if self.patch {
+            DiffOutput::Patch
+        } else {
+            default_output
+        };
+
+        let copies = match &self.find_copies {
+            Some(score) => Some(parse_score(score)?),
+            None if self.find_copies_harder => Some(parse_score("")?),
+            None => None,
+        };
+        // Copy detection shares similarity threshold with renames
+        let renames = match &self.find_renames {
+            _ if self.no_renames => None,
+            _ if copies.is_some() => copies,
+            Some(score) => Some(parse_score(score)?),
+            None if default_renames => Some(parse_score("")?),
+            None => None,
+        };
+
+        Ok(DiffOptions {
+            algorithm,
+            context: self.unified,
+            output,
+            renames,
+            copies,
+            find_copies_harder: self.find_copies_harder,
+            paths: paths.to_vec(),
+        })
+    }
+}
+
+/// Flags of `log` selecting and formatting commits
+#[derive(Args)]
+struct LogFlags {
+    #[clap(long)]
+    oneline: bool,
+    #[clap(long, alias = "pretty")]
+    format: Option<String>,
+    #[clap(long = "abbrev-commit")]
+    abbrev_commit: bool,
+    #[clap(long)]
+    date: Option<String>,
+    #[clap(short = 'n', long = "max-count")]
+    max_count: Option<usize>,
+    #[clap(long = "first-parent")]
+    first_parent: bool,
+    #[clap(long = "topo-order")]
+    topo_order: bool,
+    #[clap(long = "date-order")]
+    date_order: bool,
+    #[clap(long)]
+    graph: bool,
+    #[clap(long, alias = "after")]
+    since: Option<String>,
+    #[clap(long, alias = "before")]
+    until: Option<String>,
+    #[clap(long)]
+    author: Vec<String>,
+    #[clap(long, allow_hyphen_values = true)]
+    grep: Vec<String>,
+    #[clap(short = 'i', long = "regexp-ignore-case")]
+    ignore_case: bool,
+    #[clap(long = "ancestry-path")]
+    ancestry_path: bool,
+}
+
+impl LogFlags {
+    fn to_options(&self, paths: &[String]) -> Result<LogOptions> {
+        let format = match &self.format {
+            Some(spec) => PrettyFormat::from_spec(spec)?,
+            None if self.oneline => PrettyFormat::Oneline,
+            None => PrettyFormat::Medium,
+        };
+        let order = if self.topo_order {
+            CommitOrder::Topo
+        } else if self.date_order {
+            CommitOrder::Date
+        } else {
+            CommitOrder::Walk
+        };
+
+        Ok(LogOptions {
+            max_count: self.max_count,
+            first_parent: self.first_parent,
+            order,
+            graph: self.graph,
+            since: self.since.as_deref().map(approxidate).transpose()?,
+            until: self.until.as_deref().map(approxidate).transpose()?,
+            authors: self.author.clone(),
+            greps: self.grep.clone(),
+            ignore_case: self.ignore_case,
+            paths: paths.to_vec(),
+            format,
+            abbrev_commit: self.abbrev_commit || self.oneline,
+            date_format: self.date.as_deref().map(DateFormat::from_name).transpose()?.unwrap_or_default(),
+            ancestry_path: self.ancestry_path,
+        })
+    }
+}
+
+/// Flags of `blame`
+#[derive(Args)]
+struct BlameFlags {
+    #[clap(short = 'L')]
+    lines: Option<String>,
+    #[clap(short = 'p', long, conflicts_with = "line_porcelain")]
+    porcelain: bool,
+    #[clap(long = "line-porcelain")]
+    line_porcelain: bool,
+    #[clap(short = 's')]
+    suppress_author: bool,
+    #[clap(short = 'e', long = "show-email")]
+    show_email: bool,
+    #[clap(short = 'w')]
+    ignore_whitespace: bool,
+    #[clap(long = "ignore-rev")]
+    ignore_revs: Vec<String>,
+    #[clap(long = "ignore-revs-file")]
+    ignore_revs_file: Option<String>,
+}
+
+impl BlameFlags {
+    /// `blame [<rev>] [--] <file>`
+    fn to_options(&self, args: &[String], paths: &[String]) -> Result<BlameOptions> {
+        let (revision, path) = match (args, paths) {
+            ([], [path]) | ([path], []) => (None, path.clone()),
+            ([revision], [path]) | ([revision, path], []) => (Some(revision.clone()), path.clone()),
+            _ => bail!("usage: blame [<rev>] [--] <file>"),
+        };
+        let output = if self.line_porcelain {
+            BlameOutput::LinePorcelain
+        } else if self.porcelain {
+            BlameOutput::Porcelain
+        } else {
+            BlameOutput::Default
+        };
+        Ok(BlameOptions {
+            revision,
+            path,
+            line_range: self.lines.clone(),
+            output,
+            suppress_author: self.suppress_author,
+            show_email: self.show_email,
+            ignore_whitespace: self.ignore_whitespace,
+            ignore_revs: self.ignore_revs.clone(),
+            ignore_revs_file: self.ignore_revs_file.clone(),
+        })
+    }
+}
+
+/// Flags of `grep`
+#[derive(Args)]
+struct GrepFlags {
+    #[clap(long)]
+    cached: bool,
+    #[clap(short = 'e', allow_hyphen_values = true)]
+    patterns: Vec<String>,
+    #[clap(short = 'E', long = "extended-regexp", conflicts_with = "fixed_strings")]
+    extended_regexp: bool,
+    #[clap(short = 'F', long = "fixed-strings")]
+    fixed_strings: bool,
+    #[clap(short = 'i', long = "ignore-case")]
+    ignore_case: bool,
+    #[clap(short = 'w', long = "word-regexp")]
+    word_regexp: bool,
+    #[clap(short = 'n', long = "line-number")]
+    line_number: bool,
+    #[clap(short = 'l', long = "files-with-matches", conflicts_with = "count")]
+    files_with_matches: bool,
+    #[clap(short = 'c', long)]
+    count: bool,
+}
+
+impl GrepFlags {
+    /// Without `-e` the first argument is the pattern
+    fn to_options(&self, args: &[String], paths: &[String]) -> Result<GrepOptions> {
+        let (patterns, args) = match (self.patterns.is_empty(), args) {
+            (false, _) => (self.patterns.clone(), args),
+            (true, [pattern, args @ ..]) => (vec![pattern.clone()], args),
+            (true, []) => bail!("no pattern given"),
+        };
+        let syntax = if self.fixed_strings {
+            PatternSyntax::Fixed
+        } else if self.extended_regexp {
+            PatternSyntax::Extended
+        } else {
+            PatternSyntax::Basic
+        };
+        Ok(GrepOptions {
+            patterns,
+            args: args.to_vec(),
+            paths: paths.to_vec(),
+            cached: self.cached,
+            syntax,
+            ignore_case: self.ignore_case,
+            word: self.word_regexp,
+            line_number: self.line_number,
+            files_with_matches: self.files_with_matches,
+            count: self.count,
+        })
+    }
+}
+
+/// Flags of `show` formatting commits
+#[derive(Args)]
+struct ShowFlags {
+    #[clap(long)]
+    oneline: bool,
+    #[clap(long, alias = "pretty")]
+    format: Option<String>,
+    #[clap(long = "abbrev-commit")]
+    abbrev_commit: bool,
+    #[clap(long)]
+    date: Option<String>,
+    #[clap(short = 's', long = "no-patch")]
+    no_patch: bool,
+}
+
+impl ShowFlags {
+    fn to_options(&self, diff: DiffOptions) -> Result<ShowOptions> {
+        let format = match &self.format {
+            Some(spec) => PrettyFormat::from_spec(spec)?,
+            None if self.oneline => PrettyFormat::Oneline,
+            None => PrettyFormat::Medium,
+        };
+        let date_format = self.date.as_deref().map(DateFormat::from_name).transpose()?;
+        let log = LogOptions {
+            format,
+            abbrev_commit: self.abbrev_commit || self.oneline,
+            date_format: date_format.unwrap_or_default(),
+            ..LogOptions::default()
+        };
+        Ok(ShowOptions { log, diff, no_patch: self.no_patch })
+    }
+}
+
+/// Flags of `config` selecting the operation and the file
+#[derive(Args)]
+struct ConfigFlags {
+    #[clap(long)]
+    system: bool,
+    #[clap(long)]
+    global: bool,
+    #[clap(long)]
+    local: bool,
+    #[clap(long)]
+    worktree: bool,
+    #[clap(short = 'f', long = "file")]
+    file: Option<String>,
+    #[clap(long)]
+    get: bool,
+    #[clap(long = "get-all")]
+    get_all: bool,
+    #[clap(long)]
+    set: bool,
+    #[clap(long)]
+    unset: bool,
+    #[clap(long = "unset-all")]
+    unset_all: bool,
+    #[clap(short = 'l', long)]
+    list: bool,
+    #[clap(long = "show-origin")]
+    show_origin: bool,
+    #[clap(long = "show-scope")]
+    show_scope: bool,
+    #[clap(long)]
+    includes: bool,
+    #[clap(long = "type")]
+    value_type: Option<String>,
+    #[clap(long)]
+    bool: bool,
+    #[clap(long)]
+    int: bool,
+}
+
+impl ConfigFlags {
+    fn to_options(&self, has_value: bool) -> Result<ConfigOptions> {
+        // Without an explicit operation a value means set, otherwise get
+        let action = if self.list {
+            ConfigAction::List
+        } else if self.get_all {
+            ConfigAction::GetAll
+        } else if self.unset_all {
+            ConfigAction::UnsetAll
+        } else if self.unset {
+            ConfigAction::Unset
+        } else if self.set || (has_value && !self.get) {
+            ConfigAction::Set
+        } else {
+            ConfigAction::Get
+        };
+        let scope = [
+            (self.system, Scope::System),
+            (self.global, Scope::Global),
+            (self.local, Scope::Local),
+            (self.worktree, Scope::Worktree),
+        ]
+            .into_iter()
+            .find_map(|(selected, scope)| selected.then_some(scope));
+        let value_type = match &self.value_type {
+            Some(name) => ValueType::from_name(name)?,
+            None if self.bool => ValueType::Bool,
+            None if self.int => ValueType::Int,
+            None => ValueType::Raw,
+        };
+
+        Ok(ConfigOptions {
+            action,
+            scope,
+            file: self.file.clone(),
+            show_origin: self.show_origin,
+            show_scope: self.show_scope,
+            includes: self.includes,
+            value_type,
+        })
+    }
+}
+
+/// Flags of `branch` selecting the operation
+#[derive(Args)]
+struct BranchFlags {
+    #[clap(short = 'v', long = "verbose", action = clap::ArgAction::Count)]
+    verbose: u8,
+    #[clap(short = 'a', long)]
+    all: bool,
+    #[clap(short = 'r', long)]
+    remotes: bool,
+    #[clap(short = 'd', long)]
+    delete: bool,
+    #[clap(short = 'D')]
+    force_delete: bool,
+    #[clap(short = 'm', long = "move")]
+    rename: bool,
+    #[clap(short = 'M')]
+    force_rename: bool,
+    #[clap(short = 'f', long)]
+    force: bool,
+    #[clap(short = 'u', long = "set-upstream-to")]
+    upstream: Option<String>,
+    #[clap(long = "unset-upstream")]
+    unset_upstream: bool,
+}
+
+impl BranchFlags {
+    fn to_action(&self, names: &[String]) -> Result<BranchAction> {
+        let second = names.get(1).cloned();
+        Ok(
+            if self.delete || self.force_delete {
+                BranchAction::Delete {
+                    names: names.to_vec(),
+                    force: self.force || self.force_delete,
+                    remotes: self.remotes,
+                }
+            } else if self.rename || self.force_rename {
+                let (old, new) = match names {
+                    [new] => (None, new.clone()),
+                    [old, new] => (Some(old.clone()), new.clone()),
+                    _ => bail!("branch name required"),
+                };
+                BranchAction::Rename { old, new, force: self.force || self.force_rename }
+            } else if let Some(upstream) = &self.upstream {
+                BranchAction::SetUpstream {
+                    upstream: upstream.clone(),
+                    branch: names.first().cloned(),
+                }
+            } else if self.unset_upstream {
+                BranchAction::UnsetUpstream { branch: names.first().cloned() }
+            } else if let Some(name) = names.first() {
+                BranchAction::Create { name: name.clone(), start: second, force: self.force }
+            } else {
+                BranchAction::List { verbose: self.verbose, all: self.all, remotes: self.remotes }
+            }
+        )
+    }
+}
+
+/// Flags of `tag` selecting the operation
+#[derive(Args)]
+struct TagFlags {
+    #[clap(short = 'l', long)]
+    list: bool,
+    /// Lines of the message shown with each tag (`-n` alone shows one)
+    #[clap(short = 'n', num_args = 0..=1, require_equals = true, default_missing_value = "1")]
+    lines: Option<usize>,
+    #[clap(short = 'a', long)]
+    annotate: bool,
+    #[clap(short = 'm', long = "message", allow_hyphen_values = true)]
+    messages: Vec<String>,
+    #[clap(short = 'F', long = "file")]
+    file: Option<String>,
+    #[clap(short = 'd', long)]
+    delete: bool,
+    #[clap(short = 'f', long)]
+    force: bool,
+}
+
+impl TagFlags {
+    fn to_action(&self, args: &[String]) -> Result<TagAction> {
+        Ok(
+            if self.delete {
+                TagAction::Delete { names: args.to_vec() }
+            } else if self.list || self.lines.is_some() || args.is_empty() {
+                TagAction::List { patterns: args.to_vec(), lines: self.lines.unwrap_or(0) }
+            } else {
+                let [name, target @ ..] = args else {
+                    bail!("tag name required");
+                };
+                if target.len() > 1 {
+                    bail!("too many arguments");
+                }
+                TagAction::Create {
+                    name: name.clone(),
+                    target: target.first().cloned(),
+                    annotate: self.annotate,
+                    messages: self.messages.clone(),
+                    message_file: self.file.clone(),
+                    force: self.force,
+                }
+            }
+        )
+    }
+}
+
+/// Flags of `merge` selecting the operation
+#[derive(Args)]
+struct MergeFlags {
+    #[clap(short = 'm')]
+    messages: Vec<String>,
+    #[clap(long = "no-ff", conflicts_with = "ff_only")]
+    no_ff: bool,
+    #[clap(long = "ff-only")]
+    ff_only: bool,
+    #[clap(long, conflicts_with = "abort")]
+    r#continue: bool,
+    #[clap(long)]
+    abort: bool,
+}
+
+impl MergeFlags {
+    fn to_action(&self, commit: Option<&String>) -> Result<MergeAction> {
+        Ok(
+            if self.abort {
+                MergeAction::Abort
+            } else if self.r#continue {
+                MergeAction::Continue
+            } else {
+                let Some(commit) = commit else {
+                    bail!("No commit specified to merge");
+                };
+                let fast_forward = match (self.no_ff, self.ff_only) {
+                    (true, _) => FastForward::Never,
+                    (_, true) => FastForward::Only,
+                    _ => FastForward::Allow,
+                };
+                MergeAction::Start {
+                    commit: commit.clone(),
+                    messages: self.messages.clone(),
+                    fast_forward,
+                }
+            }
+        )
+    }
+}
+
+/// Flags of `rebase` selecting the operation
+#[derive(Args)]
+struct RebaseFlags {
+    #[clap(short, long)]
+    interactive: bool,
+    #[clap(long, conflicts_with_all = ["skip", "abort"])]
+    r#continue: bool,
+    #[clap(long, conflicts_with = "abort")]
+    skip: bool,
+    #[clap(long)]
+    abort: bool,
+}
+
+impl RebaseFlags {
+    fn to_action(&self, upstream: Option<&String>) -> Result<RebaseAction> {
+        Ok(
+            if self.abort {
+                RebaseAction::Abort
+            } else if self.skip {
+                RebaseAction::Skip
+            } else if self.r#continue {
+                RebaseAction::Continue
+            } else {
+                let Some(upstream) = upstream else {
+                    bail!("No upstream specified to rebase onto");
+                };
+                RebaseAction::Start { upstream: upstream.clone(), interactive: self.interactive }
+            }
+        )
+    }
+}
+
+/// Flags of `cherry-pick` and `revert` selecting the operation
+#[derive(Args)]
+struct PickFlags {
+    #[clap(short, long)]
+    mainline: Option<usize>,
+    #[clap(short = 'n', long)]
+    no_commit: bool,
+    #[clap(short, long, conflicts_with = "no_edit")]
+    edit: bool,
+    #[clap(long)]
+    no_edit: bool,
+    #[clap(long, conflicts_with_all = ["skip", "abort"])]
+    r#continue: bool,
+    #[clap(long, conflicts_with = "abort")]
+    skip: bool,
+    #[clap(long)]
+    abort: bool,
+}
+
+impl PickFlags {
+    fn to_action(&self, commits: &[String], record_origin: bool) -> Result<PickAction> {
+        Ok(
+            if self.abort {
+                PickAction::Abort
+            } else if self.skip {
+                PickAction::Skip
+            } else if self.r#continue {
+                PickAction::Continue
+            } else {
+                if commits.is_empty() {
+                    bail!("No commit specified");
+                }
+                let edit = match (self.edit, self.no_edit) {
+                    (true, _) => Some(true),
+                    (_, true) => Some(false),
+                    _ => None,
+                };
+                let options = PickOptions {
+                    mainline: self.mainline,
+                    record_origin,
+                    no_commit: self.no_commit,
+                    edit,
+                };
+                PickAction::Start { commits: commits.to_vec(), options }
+            }
+        )
+    }
+}
+
+/// Subcommands of `submodule`
+#[derive(Subcommand)]
+enum SubmoduleCommands {
+    #[command(name = "init")] Init {
+        paths: Vec<String>,
+    },
+    #[command(name = "update")] Update {
+        #[clap(long)]
+        init: bool,
+        paths: Vec<String>,
+    },
+    #[command(name = "status")] Status {
+        paths: Vec<String>,
+    },
+}
+
+impl SubmoduleCommands {
+    fn to_action(&self) -> SubmoduleAction {
+        match self {
+            SubmoduleCommands::Init { paths } => SubmoduleAction::Init { paths: paths.clone() },
+            SubmoduleCommands::Update { init, paths } => {
+                SubmoduleAction::Update { init: *init, paths: paths.clone() }
+            }
+            SubmoduleCommands::Status { paths } => SubmoduleAction::Status { paths: paths.clone() },
+        }
+    }
+}
+
+/// Subcommands of `stash`
+#[derive(Subcommand)]
+enum StashCommands {
+    #[command(name = "push")] Push {
+        #[clap(short, long)]
+        message: Option<String>,
+        #[clap(short = 'u', long = "include-untracked")]
+        include_untracked: bool,
+        #[clap(short = 'k', long = "keep-index")]
+        keep_index: bool,
+    },
+    #[command(name = "list")] List,
+    #[command(name = "show")] Show {
+        #[clap(short = 'p', long)]
+        patch: bool,
+        stash: Option<String>,
+    },
+    #[command(name = "apply")] Apply {
+        #[clap(long)]
+        index: bool,
+        stash: Option<String>,
+    },
+    #[command(name = "pop")] Pop {
+        #[clap(long)]
+        index: bool,
+        stash: Option<String>,
+    },
+    #[command(name = "drop")] Drop {
+        stash: Option<String>,
+    },
+    #[command(name = "clear")] Clear,
+}
+
+impl StashCommands {
+    fn to_action(&self) -> StashAction {
+        match self {
+            StashCommands::Push { message, include_untracked, keep_index } => {
+                StashAction::Push {
+                    message: message.clone(),
+                    include_untracked: *include_untracked,
+                    keep_index: *keep_index,
+                }
+            }
+            StashCommands::List => StashAction::List,
+            StashCommands::Show { patch, stash } => {
+                StashAction::Show { stash: stash.clone(), patch: *patch }
+            }
+            StashCommands::Apply { index, stash } => {
+                StashAction::Apply { stash: stash.clone(), index: *index }
+            }
+            StashCommands::Pop { index, stash } => {
+                StashAction::Pop { stash: stash.clone(), index: *index }
+            }
+            StashCommands::Drop { stash } => StashAction::Drop { stash: stash.clone() },
+            StashCommands::Clear => StashAction::Clear,
+        }
+    }
+}
+
+/// Subcommands of `reflog`
+#[derive(Subcommand)]
+enum ReflogCommands {
+    #[command(name = "show")] Show {
+        #[clap(short = 'n', long = "max-count")]
+        max_count: Option<usize>,
+        name: Option<String>,
+    },
+    #[command(name = "expire")] Expire {
+        #[clap(long)]
+        expire: Option<String>,
+        #[clap(long = "expire-unreachable")]
+        expire_unreachable: Option<String>,
+        #[clap(long)]
+        all: bool,
+        names: Vec<String>,
+    },
+    #[command(name = "delete")] Delete {
+        entries: Vec<String>,
+    },
+}
+
+impl ReflogCommands {
+    fn to_action(&self) -> ReflogAction {
+        match self {
+            ReflogCommands::Show { max_count, name } => {
+                ReflogAction::Show { name: name.clone(), max_count: *max_count }
+            }
+            ReflogCommands::Expire { expire, expire_unreachable, all, names } => {
+                ReflogAction::Expire {
+                    expire: expire.clone(),
+                    expire_unreachable: expire_unreachable.clone(),
+                    all: *all,
+                    names: names.clone(),
+                }
+            }
+            ReflogCommands::Delete { entries } => ReflogAction::Delete { entries: entries.clone() },
+        }
+    }
+}
+
+/// Subcommands of `commit-graph`
+#[derive(Subcommand)]
+enum CommitGraphCommands {
+    /// Only commits reachable from refs are written, `--reachable` is accepted for compatibility
+    #[command(name = "write")] Write {
+        #[clap(long)]
+        reachable: bool,
+        #[clap(long)]
+        split: bool,
+        #[clap(long = "changed-paths")]
+        changed_paths: bool,
+    },
+}
+
+impl CommitGraphCommands {
+    fn to_action(&self) -> CommitGraphAction {
+        match self {
+            CommitGraphCommands::Write { reachable: _, split, changed_paths } => {
+                CommitGraphAction::Write { split: *split, changed_paths: *changed_paths }
+            }
+        }
+    }
+}
+
 #[derive(Subcommand)]
 enum Commands {
     #[command(name = "init")] Init,
@@ -29,19 +823,290 @@ enum Commands {
     #[command(name = "write-tree")] WriteTree,
     #[command(name = "commit-tree")] CommitTree {
         tree_sha: String,
-        #[clap(short)]
-        parent: Option<String>,
-        #[clap(short)]
+        #[clap(short = 'p')]
+        parents: Vec<String>,
+        #[clap(short = 'm', allow_hyphen_values = true)]
+        messages: Vec<String>,
+        #[clap(short = 'F')]
+        file: Option<String>,
+    },
+    #[command(name = "commit")] Commit {
+        #[clap(short = 'm', long = "message", allow_hyphen_values = true)]
+        messages: Vec<String>,
+        #[clap(short = 'F', long = "file")]
+        file: Option<String>,
+        #[clap(long)]
+        amend: bool,
+        #[clap(long = "allow-empty")]
+        allow_empty: bool,
+    },
+    #[command(name = "branch")] Branch {
+        #[command(flatten)]
+        flags: BranchFlags,
+        names: Vec<String>,
+    },
+    #[command(name = "checkout")] Checkout {
+        #[clap(short = 'b')]
+        create: Option<String>,
+        #[clap(short = 'B')]
+        force_create: Option<String>,
+        #[clap(long)]
+        detach: bool,
+        #[clap(short = 'f', long)]
+        force: bool,
+        target: Option<String>,
+    },
+    #[command(name = "switch")] Switch {
+        #[clap(short = 'c', long)]
+        create: Option<String>,
+        #[clap(short = 'C', long = "force-create")]
+        force_create: Option<String>,
+        #[clap(short = 'd', long)]
+        detach: bool,
+        #[clap(short = 'f', long, alias = "discard-changes")]
+        force: bool,
+        target: Option<String>,
+    },
+    #[command(name = "tag")] Tag {
+        #[command(flatten)]
+        flags: TagFlags,
+        args: Vec<String>,
+    },
+    #[command(name = "merge")] Merge {
+        #[command(flatten)]
+        flags: MergeFlags,
+        commit: Option<String>,
+    },
+    #[command(name = "submodule")] Submodule {
+        #[command(subcommand)]
+        command: Option<SubmoduleCommands>,
+    },
+    #[command(name = "show-ref")] ShowRef {
+        #[clap(long)]
+        heads: bool,
+        #[clap(long)]
+        tags: bool,
+        #[clap(long)]
+        head: bool,
+        #[clap(short = 'd', long)]
+        dereference: bool,
+        #[clap(short = 's', long)]
+        hash: bool,
+        #[clap(long)]
+        verify: bool,
+        #[clap(short = 'q', long)]
+        quiet: bool,
+        patterns: Vec<String>,
+    },
+    #[command(name = "update-ref")] UpdateRef {
+        #[clap(short = 'm')]
         message: Option<String>,
+        #[clap(long = "no-deref")]
+        no_deref: bool,
+        #[clap(short = 'd')]
+        delete: bool,
+        name: String,
+        values: Vec<String>,
+    },
+    /// `reflog [<ref>]` shows the log like `reflog show`
+    #[command(name = "reflog", args_conflicts_with_subcommands = true)] Reflog {
+        #[command(subcommand)]
+        command: Option<ReflogCommands>,
+        #[clap(short = 'n', long = "max-count")]
+        max_count: Option<usize>,
+        name: Option<String>,
+    },
+    #[command(name = "config")] Config {
+        #[command(flatten)]
+        flags: ConfigFlags,
+        name: Option<String>,
+        value: Option<String>,
     },
     #[command(name = "clone")] Clone {
         repo_url: String,
         folder: Option<String>,
     },
+    #[command(name = "diff")] Diff {
+        #[clap(long, alias = "staged")]
+        cached: bool,
+        #[command(flatten)]
+        flags: DiffFlags,
+        revisions: Vec<String>,
+        #[clap(last = true)]
+        paths: Vec<String>,
+    },
+    #[command(name = "show")] Show {
+        #[command(flatten)]
+        flags: ShowFlags,
+        #[command(flatten)]
+        diff_flags: DiffFlags,
+        objects: Vec<String>,
+        #[clap(last = true)]
+        paths: Vec<String>,
+    },
+    #[command(name = "blame")] Blame {
+        #[command(flatten)]
+        flags: BlameFlags,
+        args: Vec<String>,
+        #[clap(last = true)]
+        paths: Vec<String>,
+    },
+    #[command(name = "grep")] Grep {
+        #[command(flatten)]
+        flags: GrepFlags,
+        args: Vec<String>,
+        #[clap(last = true)]
+        paths: Vec<String>,
+    },
+    #[command(name = "diff-tree")] DiffTree {
+        #[clap(short)]
+        recursive: bool,
+        #[clap(long)]
+        root: bool,
+        #[clap(long = "no-commit-id")]
+        no_commit_id: bool,
+        #[command(flatten)]
+        flags: DiffFlags,
+        revisions: Vec<String>,
+        #[clap(last = true)]
+        paths: Vec<String>,
+    },
+    #[command(name = "log")] Log {
+        #[command(flatten)]
+        flags: LogFlags,
+        revisions: Vec<String>,
+        #[clap(last = true)]
+        paths: Vec<String>,
+    },
+    /// `--not` among the revisions excludes the following ones
+    #[command(name = "rev-list")] RevList {
+        #[clap(long)]
+        count: bool,
+        #[clap(long = "left-right")]
+        left_right: bool,
+        #[clap(long = "ancestry-path")]
+        ancestry_path: bool,
+        #[clap(short = 'n', long = "max-count")]
+        max_count: Option<usize>,
+        #[clap(long = "first-parent")]
+        first_parent: bool,
+        #[clap(long = "topo-order")]
+        topo_order: bool,
+        #[clap(allow_hyphen_values = true)]
+        revisions: Vec<String>,
+        #[clap(last = true)]
+        paths: Vec<String>,
+    },
+    /// The todo list of `-i` is edited with `GIT_SEQUENCE_EDITOR` (or `sequence.editor`)
+    #[command(name = "rebase")] Rebase {
+        #[command(flatten)]
+        flags: RebaseFlags,
+        upstream: Option<String>,
+    },
+    #[command(name = "cherry-pick")] CherryPick {
+        #[command(flatten)]
+        flags: PickFlags,
+        /// Add `(cherry picked from commit ...)` to the messages
+        #[clap(short = 'x')]
+        record_origin: bool,
+        commits: Vec<String>,
+    },
+    /// Messages are edited when run from a terminal, unless `--no-edit` is given
+    #[command(name = "revert")] Revert {
+        #[command(flatten)]
+        flags: PickFlags,
+        commits: Vec<String>,
+    },
+    #[command(name = "reset")] Reset {
+        #[clap(long, conflicts_with_all = ["mixed", "hard"])]
+        soft: bool,
+        #[clap(long, conflicts_with = "hard")]
+        mixed: bool,
+        #[clap(long)]
+        hard: bool,
+        args: Vec<String>,
+        #[clap(last = true)]
+        paths: Vec<String>,
+    },
+    /// Without `--staged` or `--worktree` only the working tree is restored
+    #[command(name = "restore")] Restore {
+        #[clap(short = 's', long)]
+        source: Option<String>,
+        #[clap(short = 'S', long)]
+        staged: bool,
+        #[clap(short = 'W', long)]
+        worktree: bool,
+        paths: Vec<String>,
+    },
+    #[command(name = "stash")] Stash {
+        #[command(subcommand)]
+        command: Option<StashCommands>,
+    },
+    #[command(name = "commit-graph")] CommitGraph {
+        #[command(subcommand)]
+        command: CommitGraphCommands,
+    },
+    #[command(name = "merge-base")] MergeBase {
+        #[clap(short = 'a', long)]
+        all: bool,
+        #[clap(long)]
+        octopus: bool,
+        #[clap(long = "is-ancestor", conflicts_with_all = ["octopus", "fork_point"])]
+        is_ancestor: bool,
+        #[clap(long = "fork-point", conflicts_with = "octopus")]
+        fork_point: bool,
+        commits: Vec<String>,
+    },
+}
+
+/// Options of `log`, the diff commands and `tag` whose value may come as the next argument
+const VALUE_OPTIONS: &[&str] = &[
+    "-U", "--unified", "--diff-algorithm", "-n", "--max-count", "--format", "--pretty", "--date",
+    "--since", "--after", "--until", "--before", "--author", "--grep", "-m", "--message", "-F",
+    "--file",
+];
+
+/// Git allows values glued to short flags (`-M50%`, `-C5`, `-3`, `-n3`), turn them into long
+/// options for `log`, `diff`, `diff-tree` and `show` (and `tag -n3` into `-n=3`, which suits
+/// its optional value). Other commands and the values of options such as `-m` are left alone
+fn expand_glued_flags(args: impl Iterator<Item = String>) -> Vec<String> {
+    let mut args = args.collect::<Vec<_>>();
+    let command = args.iter().skip(1).position(|arg| !arg.starts_with('-')).map(|i| i + 1);
+    let Some(command) = command else { return args };
+    let tag = match args[command].as_str() {
+        "log" | "diff" | "diff-tree" | "show" => false,
+        "tag" => true,
+        _ => return args,
+    };
+    let is_score = |score: &str| {
+        !score.is_empty() && score.chars().all(|c| c.is_ascii_digit() || c == '%')
+    };
+    let is_number = |text: &str| !text.is_empty() && text.chars().all(|c| c.is_ascii_digit());
+    let mut takes_value = false;
+    for arg in args.iter_mut().skip(command + 1) {
+        if arg == "--" {
+            break;
+        }
+        if std::mem::replace(&mut takes_value, VALUE_OPTIONS.contains(&arg.as_str())) {
+            continue;
+        }
+        let glued = (arg.strip_prefix("-M"), arg.strip_prefix("-C"), arg.strip_prefix('-'));
+        let expanded = match glued {
+            (Some(score), _, _) if !tag && is_score(score) => format!("--find-renames={score}"),
+            (_, Some(score), _) if !tag && is_score(score) => format!("--find-copies={score}"),
+            (_, _, Some(count)) if !tag && is_number(count) => format!("--max-count={count}"),
+            (_, _, Some(flag)) if flag.strip_prefix('n').is_some_and(is_number) => {
+                format!("-n={}", &flag[1..])
+            }
+            _ => continue,
+        };
+        *arg = expanded;
+    }
+    args
 }
 
 fn main() {
-    let cli = Cli::parse();
+    let cli = Cli::parse_from(expand_glued_flags(std::env::args()));
 
     match &cli.command {
         Commands::Init => {
@@ -86,21 +1151,121 @@ fn main() {
                 Err(err) => eprintln!("Error: {}", err),
             }
         }
-        Commands::CommitTree { tree_sha, parent, message } => {
-            if let Some(par) = parent {
-                if let Some(text) = message {
-                    match commands::create_commit_with_message(tree_sha, par, text) {
-                        Ok(r) => println!("{r}"),
-                        Err(err) => eprintln!("Error: {}", err),
-                    }
-                } else {
-                    match commands::create_commit_with_message(tree_sha, par, "<No message>") {
-                        Ok(r) => println!("{r}"),
-                        Err(err) => eprintln!("Error: {}", err),
-                    }
+        Commands::CommitTree { tree_sha, parents, messages, file } => {
+            match
+                commands::create_commit_with_message(tree_sha, parents, messages, file.as_deref())
+            {
+                Ok(r) => println!("{r}"),
+                Err(err) => eprintln!("Error: {}", err),
+            }
+        }
+        Commands::Commit { messages, file, amend, allow_empty } => {
+            match commands::commit(messages, file.as_deref(), *amend, *allow_empty) {
+                Ok(r) => print!("{r}"),
+                Err(err) => eprintln!("Error: {}", err),
+            }
+        }
+        Commands::Branch { flags, names } => {
+            match flags.to_action(names).and_then(|action| commands::branch(&action)) {
+                Ok(r) => print!("{r}"),
+                Err(err) => eprintln!("Error: {}", err),
+            }
+        }
+        Commands::Checkout { create, force_create, detach, force, target } |
+        Commands::Switch { create, force_create, detach, force, target } => {
+            let options = SwitchOptions {
+                target: target.clone(),
+                create: force_create.clone().or(create.clone()),
+                force_create: force_create.is_some(),
+                detach: *detach,
+                force: *force,
+                branch_only: matches!(cli.command, Commands::Switch { .. }),
+            };
+            match commands::switch(&options) {
+                Ok(r) => print!("{r}"),
+                Err(err) => eprintln!("Error: {}", err),
+            }
+        }
+        Commands::Tag { flags, args } => {
+            match flags.to_action(args).and_then(|action| commands::tag(&action)) {
+                Ok(r) => print!("{r}"),
+                Err(err) => eprintln!("Error: {}", err),
+            }
+        }
+        Commands::Merge { flags, commit } => {
+            match flags.to_action(commit.as_ref()).and_then(|action| commands::merge(&action)) {
+                Ok(r) => print!("{r}"),
+                Err(err) => eprintln!("Error: {}", err),
+            }
+        }
+        Commands::Submodule { command } => {
+            // Plain `submodule` shows the status
+            let action = match command {
+                Some(command) => command.to_action(),
+                None => SubmoduleAction::Status { paths: Vec::new() },
+            };
+            match commands::submodule(&action) {
+                Ok(r) => print!("{r}"),
+                Err(err) => eprintln!("Error: {}", err),
+            }
+        }
+        Commands::ShowRef { heads, tags, head, dereference, hash, verify, quiet, patterns } => {
+            let options = ShowRefOptions {
+                heads: *heads,
+                tags: *tags,
+                head: *head,
+                dereference: *dereference,
+                hash_only: *hash,
+                verify: *verify,
+                quiet: *quiet,
+            };
+            match commands::show_ref(patterns, &options) {
+                Ok(r) => print!("{r}"),
+                Err(err) => eprintln!("Error: {}", err),
+            }
+        }
+        Commands::UpdateRef { message, no_deref, delete, name, values } => {
+            // `-d <ref> [<old>]` or `<ref> <new> [<old>]`
+            let result = match (*delete, values.as_slice()) {
+                (true, [] | [_]) => {
+                    let old = values.first().map(String::as_str);
+                    commands::update_ref(name, None, old, *no_deref, message.as_deref())
                 }
-            } else {
-                eprintln!("'commit-tree' needs parent provided with '-p'");
+                (false, [new]) | (false, [new, _]) => {
+                    let old = values.get(1).map(String::as_str);
+                    commands::update_ref(name, Some(new), old, *no_deref, message.as_deref())
+                }
+                _ => {
+                    Err(
+                        anyhow!(
+                            "usage: update-ref [-m <reason>] [--no-deref] \
+                             (-d <ref> [<old>] | <ref> <new> [<old>])"
+                        )
+                    )
+                }
+            };
+            match result {
+                Ok(r) => print!("{r}"),
+                Err(err) => eprintln!("Error: {}", err),
+            }
+        }
+        Commands::Reflog { command, max_count, name } => {
+            let action = match command {
+                Some(command) => command.to_action(),
+                None => ReflogAction::Show { name: name.clone(), max_count: *max_count },
+            };
+            match commands::reflog(&action) {
+                Ok(r) => print!("{r}"),
+                Err(err) => eprintln!("Error: {}", err),
+            }
+        }
+        Commands::Config { flags, name, value } => {
+            let result = flags
+                .to_options(value.is_some())
+                .and_then(|options| commands::config(name.as_deref(), value.as_deref(), &options));
+            match result {
+                Ok(r) => print!("{r}"),
+                Err(err) => eprintln!("Error: {}", err),
             }
         }
         Commands::Clone { repo_url, folder } => {
@@ -118,5 +1283,178 @@ fn main() {
                 eprintln!("'clone' has incorrect url");
             }
         }
+        Commands::Diff { cached, flags, revisions, paths } => {
+            let result = flags
+                .to_options(paths, DiffOutput::Patch, true)
+                .and_then(|options| commands::diff(revisions, *cached, &options));
+            match result {
+                Ok(r) => print!("{r}"),
+                Err(err) => eprintln!("Error: {}", err),
+            }
+        }
+        Commands::Blame { flags, args, paths } => {
+            let result = flags
+                .to_options(args, paths)
+                .and_then(|options| commands::blame(&options));
+            match result {
+                Ok(r) => print!("{r}"),
+                Err(err) => eprintln!("Error: {}", err),
+            }
+        }
+        Commands::Grep { flags, args, paths } => {
+            let result = flags
+                .to_options(args, paths)
+                .and_then(|options| commands::grep(&options));
+            match result {
+                Ok(r) => print!("{r}"),
+                Err(err) => eprintln!("Error: {}", err),
+            }
+        }
+        Commands::Show { flags, diff_flags, objects, paths } => {
+            let result = diff_flags
+                .to_options(paths, DiffOutput::Patch, true)
+                .and_then(|diff| flags.to_options(diff))
+                .and_then(|options| commands::show(objects, &options));
+            match result {
+                Ok(r) => print!("{r}"),
+                Err(err) => eprintln!("Error: {}", err),
+            }
+        }
+        Commands::DiffTree { recursive, root, no_commit_id, flags, revisions, paths } => {
+            let result = flags
+                .to_options(paths, DiffOutput::Raw, false)
+                .and_then(|options| {
+                    commands::diff_tree(revisions, *recursive, *root, !*no_commit_id, &options)
+                });
+            match result {
+                Ok(r) => print!("{r}"),
+                Err(err) => eprintln!("Error: {}", err),
+            }
+        }
+        Commands::RevList {
+            count,
+            left_right,
+            ancestry_path,
+            max_count,
+            first_parent,
+            topo_order,
+            revisions,
+            paths,
+        } => {
+            let options = LogOptions {
+                max_count: *max_count,
+                first_parent: *first_parent,
+                order: if *topo_order { CommitOrder::Topo } else { CommitOrder::Walk },
+                paths: paths.clone(),
+                ancestry_path: *ancestry_path,
+                ..Default::default()
+            };
+            match commands::rev_list(revisions, &options, *count, *left_right) {
+                Ok(r) => print!("{r}"),
+                Err(err) => eprintln!("Error: {}", err),
+            }
+        }
+        Commands::MergeBase { all, octopus, is_ancestor, fork_point, commits } => {
+            // `--is-ancestor` answers with the exit status only
+            if *is_ancestor {
+                let result = match commits.as_slice() {
+                    [ancestor, descendant] => commands::is_ancestor(ancestor, descendant),
+                    _ => Err(anyhow!("--is-ancestor takes exactly two commits")),
+                };
+                match result {
+                    Ok(true) => {}
+                    Ok(false) => std::process::exit(1),
+                    Err(err) => eprintln!("Error: {}", err),
+                }
+                return;
+            }
+            let result = match (*fork_point, commits.as_slice()) {
+                (true, [name]) => commands::fork_point(name, None),
+                (true, [name, commit]) => commands::fork_point(name, Some(commit)),
+                (true, _) => Err(anyhow!("--fork-point takes a ref and an optional commit")),
+                (false, _) => commands::merge_base(commits, *all, *octopus),
+            };
+            match result {
+                Ok(r) => print!("{r}"),
+                Err(err) => eprintln!("Error: {}", err),
+            }
+        }
+        Commands::Rebase { flags, upstream } => {
+            let result = flags
+                .to_action(upstream.as_ref())
+                .and_then(|action| commands::rebase(&action));
+            match result {
+                Ok(r) => print!("{r}"),
+                Err(err) => eprintln!("Error: {}", err),
+            }
+        }
+        Commands::CherryPick { flags, record_origin, commits } => {
+            let result = flags
+                .to_action(commits, *record_origin)
+                .and_then(|action| commands::cherry_pick(&action));
+            match result {
+                Ok(r) => print!("{r}"),
+                Err(err) => eprintln!("Error: {}", err),
+            }
+        }
+        Commands::Revert { flags, commits } => {
+            match flags.to_action(commits, false).and_then(|action| commands::revert(&action)) {
+                Ok(r) => print!("{r}"),
+                Err(err) => eprintln!("Error: {}", err),
+            }
+        }
+        Commands::Reset { soft, mixed, hard, args, paths } => {
+            let mode = match (soft, mixed, hard) {
+                (true, _, _) => Some(ResetMode::Soft),
+                (_, true, _) => Some(ResetMode::Mixed),
+                (_, _, true) => Some(ResetMode::Hard),
+                _ => None,
+            };
+            let options = ResetOptions { mode, args: args.clone(), paths: paths.clone() };
+            match commands::reset(&options) {
+                Ok(r) => print!("{r}"),
+                Err(err) => eprintln!("Error: {}", err),
+            }
+        }
+        Commands::Restore { source, staged, worktree, paths } => {
+            let options = RestoreOptions {
+                source: source.clone(),
+                staged: *staged,
+                worktree: *worktree || !*staged,
+                paths: paths.clone(),
+            };
+            match commands::restore(&options) {
+                Ok(r) => print!("{r}"),
+                Err(err) => eprintln!("Error: {}", err),
+            }
+        }
+        Commands::Stash { command } => {
+            // Plain `stash` saves the changes
+            let action = match command {
+                Some(command) => command.to_action(),
+                None => {
+                    StashAction::Push { message: None, include_untracked: false, keep_index: false }
+                }
+            };
+            match commands::stash(&action) {
+                Ok(r) => print!("{r}"),
+                Err(err) => eprintln!("Error: {}", err),
+            }
+        }
+        Commands::CommitGraph { command } => {
+            match commands::commit_graph(&command.to_action()) {
+                Ok(r) => print!("{r}"),
+                Err(err) => eprintln!("Error: {}", err),
+            }
+        }
+        Commands::Log { flags, revisions, paths } => {
+            let result = flags
+                .to_options(paths)
+                .and_then(|options| commands::log(revisions, &options));
+            match result {
+                Ok(r) => print!("{r}"),
+                Err(err) => eprintln!("Error: {}", err),
+            }
+        }
     }
 }
\ No newline at end of file
@@ -0,0 +1,270 @@
+use crate::diff::{ diff_lines, split_lines, DiffAlgorithm, Edit };
+
+use std::ops::Range;
+
+/// Length of conflict markers
+const MARKER_SIZE: usize = 7;
+/// Conflicts separated by at most this many lines are shown as one
+const MAX_CONFLICT_GAP: usize = 3;
+
+/// How conflicting hunks are written (`merge.conflictStyle`)
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ConflictStyle {
+    /// Only our and their lines
+    #[default]
+    Merge,
+    /// Lines of the merge base are shown between them too
+    Diff3,
+}
+
+impl ConflictStyle {
+    pub fn from_name(name: &str) -> ConflictStyle {
+        match name {
+            "diff3" | "zdiff3" => ConflictStyle::Diff3,
+            _ => ConflictStyle::Merge,
+        }
+    }
+}
+
+/// Names written after conflict markers
+#[derive(Clone, Copy, Debug)]
+pub struct MergeLabels<'a> {
+    pub ours: &'a str,
+    pub base: &'a str,
+    pub theirs: &'a str,
+}
+
+/// Merged contents with the number of conflicting hunks left in them
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ContentMerge {
+    pub data: Vec<u8>,
+    pub conflicts: usize,
+}
+
+/// Region changed on at least one side, ranges are line numbers of each version
+#[derive(Clone, Debug)]
+struct Chunk {
+    base: Range<usize>,
+    ours: Range<usize>,
+    theirs: Range<usize>,
+    resolution: Resolution,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Resolution {
+    Ours,
+    Theirs,
+    Conflict,
+}
+
+/// Line of `new` matching each line of `old` (None for changed lines)
+fn matching_lines(old: &[&[u8]], new: &[&[u8]]) -> Vec<Option<usize>> {
+    let mut matches: Vec<Option<usize>> = vec![None; old.len()];
+    for edit in diff_lines(old, new, DiffAlgorithm::Myers) {
+        if let Edit::Equal(i, j) = edit {
+            matches[i] = Some(j);
+        }
+    }
+    matches
+}
+
+/// Split the versions into chunks between lines unchanged on both sides
+fn find_chunks(base: &[&[u8]], ours: &[&[u8]], theirs: &[&[u8]]) -> Vec<Chunk> {
+    let our_matches = matching_lines(base, ours);
+    let their_matches = matching_lines(base, theirs);
+    let stable = |i: usize, j: usize, k: usize| {
+        our_matches[i] == Some(j) && their_matches[i] == Some(k)
+    };
+
+    let mut chunks: Vec<Chunk> = Vec::new();
+    let (mut i, mut j, mut k) = (0, 0, 0);
+    while i < base.len() || j < ours.len() || k < theirs.len() {
+        if i < base.len() && stable(i, j, k) {
+            (i, j, k) = (i + 1, j + 1, k + 1);
+            continue;
+        }
+        // Chunk ends at the next base line kept by both sides
+        let end = (i..base.len()).find_map(|next| {
+            match (our_matches[next], their_matches[next]) {
+                (Some(our_next), Some(their_next)) => Some((next, our_next, their_next)),
+                _ => None,
+            }
+        });
+        let (next_i, next_j, next_k) = end.unwrap_or((base.len(), ours.len(), theirs.len()));
+        let (b, o, t) = (&base[i..next_i], &ours[j..next_j], &theirs[k..next_k]);
+        let resolution = if o == b || o == t {
+            if o == t { Resolution::Ours } else { Resolution::Theirs }
+        } else if t == b {
+            Resolution::Ours
+        } else {
+            Resolution::Conflict
+        };
+        chunks.push(Chunk { base: i..next_i, ours: j..next_j, theirs: k..next_k, resolution });
+        (i, j, k) = (next_i, next_j, next_k);
+    }
+    chunks
+}
+
+/// Shrink conflicts to the lines where both sides differ, every differing run is a conflict
+fn refine_conflicts(chunks: Vec<Chunk>, ours: &[&[u8]], theirs: &[&[u8]]) -> Vec<Chunk> {
+    let mut refined: Vec<Chunk> = Vec::new();
+    for chunk in chunks {
+        if chunk.resolution != Resolution::Conflict {
+            refined.push(chunk);
+            continue;
+        }
+        let (our_lines, their_lines) = (&ours[chunk.ours.clone()], &theirs[chunk.theirs.clone()]);
+        let (o, t) = (chunk.ours.start, chunk.theirs.start);
+
+        // Cursors in both sides and the start of the run being collected
+        let (mut i, mut j) = (0, 0);
+        let mut start: Option<(usize, usize)> = None;
+        let mut close = |start: &mut Option<(usize, usize)>, i: usize, j: usize| {
+            if let Some((start_i, start_j)) = start.take() {
+                refined.push(Chunk {
+                    base: chunk.base.clone(),
+                    ours: o + start_i..o + i,
+                    theirs: t + start_j..t + j,
+                    resolution: Resolution::Conflict,
+                });
+            }
+        };
+        for edit in diff_lines(our_lines, their_lines, DiffAlgorithm::Myers) {
+            match edit {
+                Edit::Equal(next_i, next_j) => {
+                    close(&mut start, i, j);
+                    (i, j) = (next_i + 1, next_j + 1);
+                }
+                Edit::Delete(next_i) => {
+                    start.get_or_insert((i, j));
+                    i = next_i + 1;
+                }
+                Edit::Insert(next_j) => {
+                    start.get_or_insert((i, j));
+                    j = next_j + 1;
+                }
+            }
+        }
+        close(&mut start, i, j);
+    }
+    refined
+}
+
+/// Join conflicts separated only by a few unchanged lines
+fn join_close_conflicts(chunks: Vec<Chunk>) -> Vec<Chunk> {
+    let mut joined: Vec<Chunk> = Vec::new();
+    for chunk in chunks {
+        if let Some(last) = joined.last_mut() {
+            let both = last.resolution == Resolution::Conflict &&
+                chunk.resolution == Resolution::Conflict;
+            if both && chunk.ours.start - last.ours.end <= MAX_CONFLICT_GAP {
+                last.base = last.base.start.min(chunk.base.start)..chunk.base.end;
+                last.ours.end = chunk.ours.end;
+                last.theirs.end = chunk.theirs.end;
+                continue;
+            }
+        }
+        joined.push(chunk);
+    }
+    joined
+}
+
+/// Append lines, the last one gets a newline so a marker can follow
+fn push_lines(output: &mut Vec<u8>, lines: &[&[u8]]) {
+    for line in lines {
+        output.extend_from_slice(line);
+    }
+    if !lines.is_empty() && !output.ends_with(b"\n") {
+        output.push(b'\n');
+    }
+}
+
+fn push_marker(output: &mut Vec<u8>, marker: char, label: &str) {
+    output.extend(marker.to_string().repeat(MARKER_SIZE).bytes());
+    if !label.is_empty() {
+        output.push(b' ');
+        output.extend(label.bytes());
+    }
+    output.push(b'\n');
+}
+
+/// Three-way merge of text, changes of both sides to the same lines become conflicts
+pub fn merge_content(
+    base: &[u8],
+    ours: &[u8],
+    theirs: &[u8],
+    labels: MergeLabels,
+    style: ConflictStyle
+) -> ContentMerge {
+    let (base, ours, theirs) = (split_lines(base), split_lines(ours), split_lines(theirs));
+    let mut chunks = find_chunks(&base, &ours, &theirs);
+    // Base lines cannot be shown for parts of a conflict
+    if style == ConflictStyle::Merge {
+        chunks = join_close_conflicts(refine_conflicts(chunks, &ours, &theirs));
+    }
+
+    let mut output: Vec<u8> = Vec::new();
+    let mut conflicts = 0;
+    let mut position = 0;
+    for chunk in chunks {
+        for line in &ours[position..chunk.ours.start] {
+            output.extend_from_slice(line);
+        }
+        position = chunk.ours.end;
+        match chunk.resolution {
+            Resolution::Ours => {
+                for line in &ours[chunk.ours] {
+                    output.extend_from_slice(line);
+                }
+            }
+            Resolution::Theirs => {
+                for line in &theirs[chunk.theirs] {
+                    output.extend_from_slice(line);
+                }
+            }
+            Resolution::Conflict => {
+                conflicts += 1;
+                push_marker(&mut output, '<', labels.ours);
+                push_lines(&mut output, &ours[chunk.ours]);
+                if style == ConflictStyle::Diff3 {
+                    push_marker(&mut output, '|', labels.base);
+                    push_lines(&mut output, &base[chunk.base]);
+                }
+                push_marker(&mut output, '=', "");
+                push_lines(&mut output, &theirs[chunk.theirs]);
+                push_marker(&mut output, '>', labels.theirs);
+            }
+        }
+    }
+    for line in &ours[position..] {
+        output.extend_from_slice(line);
+    }
+    ContentMerge { data: output, conflicts }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LABELS: MergeLabels = MergeLabels { ours: "ours", base: "base", theirs: "theirs" };
+
+    #[test]
+    fn merge_content_styles() {
+        let base = b"1\n2\n3\n4\n5\n6\n";
+        let ours = b"1\nA\nsame\n4\n5\nsix";
+        let theirs = b"1\nB\nsame\n4\n5\n6\n";
+        let merged = merge_content(base, ours, theirs, LABELS, ConflictStyle::Merge);
+        assert_eq!(merged.conflicts, 1);
+        assert_eq!(
+            String::from_utf8(merged.data).unwrap(),
+            "1\n<<<<<<< ours\nA\n=======\nB\n>>>>>>> theirs\nsame\n4\n5\nsix"
+        );
+
+        let merged = merge_content(base, ours, theirs, LABELS, ConflictStyle::Diff3);
+        assert_eq!(
+            String::from_utf8(merged.data).unwrap(),
+            "1\n<<<<<<< ours\nA\nsame\n||||||| base\n2\n3\n=======\nB\nsame\n\
+             >>>>>>> theirs\n4\n5\nsix"
+        );
+    }
+}
@@ -0,0 +1,685 @@
+pub mod content;
+
+use crate::checkout::{ apply_updates, commit_files, plan_updates, Update, UpdateKind };
+use crate::config::Config;
+use crate::diff::{ self, abbrev, compare_file_maps, detect_renames, index_files, mode_kind };
+use crate::diff::{ worktree_files, ChangeStatus, DiffOptions, FileMap };
+use crate::diff::{ format::is_binary, tree_diff::DEFAULT_RENAME_SCORE, GITLINK_MODE, SYMLINK_MODE };
+use crate::history::merge_bases;
+use crate::index::{ read_index, write_index, write_index_tree, IndexEntry };
+use crate::object::{ read_blob, read_commit, write_object, Blob, Commit, Object };
+use crate::refs::{ self, current_branch, read_ref };
+use crate::revision::{ read_head_commit, resolve_revision };
+use crate::utility::fs_utility::{ find_root_folder, state_path, write_locked };
+use crate::utility::other_util::{ cleanup_message, signature };
+
+use anyhow::{ anyhow, bail, Result };
+use content::{ merge_content, ConflictStyle, MergeLabels };
+use std::collections::{ BTreeMap, BTreeSet, HashSet };
+use std::fs;
+
+/// Label of our side in conflict markers and messages
+const OURS_LABEL: &str = "HEAD";
+/// Label of the base merged from several merge bases
+const VIRTUAL_BASE_LABEL: &str = "merged common ancestors";
+
+/// When the merge may just move HEAD forward (`--ff`, `--no-ff`, `--ff-only`)
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum FastForward {
+    #[default]
+    Allow,
+    Never,
+    Only,
+}
+
+/// Operation of the `merge` command
+#[derive(Clone, Debug)]
+pub enum MergeAction {
+    /// Merge the commit into HEAD, `messages` replace the generated message
+    Start {
+        commit: String,
+        messages: Vec<String>,
+        fast_forward: FastForward,
+    },
+    /// Commit the merge once conflicts are resolved
+    Continue,
+    /// Bring the index and the working tree back to HEAD
+    Abort,
+}
+
+/// Version of a path in a tree: (<mode>, <SHA-1>), None when it is missing
+type FileVersion = Option<(String, String)>;
+
+/// Path left unmerged with its base, our and their versions (index stages 1-3)
+#[derive(Clone, Debug)]
+struct Conflict {
+    path: String,
+    stages: [FileVersion; 3],
+}
+
+/// Merged files for the working tree (conflicting hunks with markers) and conflicts
+#[derive(Clone, Debug, Default)]
+struct TreeMerge {
+    files: FileMap,
+    conflicts: Vec<Conflict>,
+    /// Messages about each path, shown in the order of the paths
+    messages: BTreeMap<String, String>,
+}
+
+impl TreeMerge {
+    fn message(&mut self, path: &str, text: &str) {
+        self.messages.entry(path.to_string()).or_default().push_str(text);
+    }
+}
+
+/// Commits being merged into HEAD (empty when no merge is in progress)
+pub fn merge_heads() -> Result<Vec<String>> {
+    Ok(match fs::read_to_string(state_path("", "MERGE_HEAD")?) {
+        Ok(text) => text.lines().map(str::to_string).collect(),
+        Err(_) => Vec::new(),
+    })
+}
+
+/// Prepared message of the merge in progress
+pub fn merge_message() -> Result<Option<Vec<u8>>> {
+    Ok(fs::read(state_path("", "MERGE_MSG")?).ok())
+}
+
+/// Forget the merge in progress
+pub fn remove_state() -> Result<()> {
+    for name in ["MERGE_HEAD", "MERGE_MSG", "MERGE_MODE"] {
+        let _ = fs::remove_file(state_path("", name)?);
+    }
+    Ok(())
+}
+
+/// Default message: `Merge branch 'x'` (with ` into y` unless merging into main or master)
+fn default_message(name: &str) -> Result<String> {
+    let kind = if read_ref(&format!("refs/heads/{name}"))?.is_some() {
+        "branch"
+    } else if read_ref(&format!("refs/tags/{name}"))?.is_some() {
+        "tag"
+    } else if read_ref(&format!("refs/remotes/{name}"))?.is_some() {
+        "remote-tracking branch"
+    } else {
+        "commit"
+    };
+    let into = match current_branch()? {
+        Some(branch) if branch != "main" && branch != "master" => format!(" into {branch}"),
+        _ => String::new(),
+    };
+    Ok(format!("Merge {kind} '{name}'{into}\n"))
+}
+
+/// Merge two versions of a file changed on both sides, returns the version for the working
+/// tree and whether it is conflicted
+fn merge_file(
+    path: &str,
+    base: Option<&(String, String)>,
+    ours: &(String, String),
+    theirs: &(String, String),
+    labels: MergeLabels,
+    style: ConflictStyle,
+    messages: &mut String
+) -> Result<((String, String), bool)> {
+    // Mode changed on one side only is kept, modes changed differently on both sides conflict
+    let base_mode = base.map(|(mode, _)| mode);
+    let mode = match base_mode {
+        Some(mode) if *mode == ours.0 => theirs.0.clone(),
+        _ => ours.0.clone(),
+    };
+    let mode_conflict =
+        ours.0 != theirs.0 && base_mode != Some(&ours.0) && base_mode != Some(&theirs.0);
+    let kind = if base.is_some() { "content" } else { "add/add" };
+    let conflict = format!("CONFLICT ({kind}): Merge conflict in {path}\n");
+    // Contents changed on one side only are taken without merging
+    let base_sha = base.map(|(_, sha)| sha);
+    if ours.1 == theirs.1 || base_sha == Some(&ours.1) || base_sha == Some(&theirs.1) {
+        if mode_conflict {
+            *messages += &conflict;
+        }
+        let sha = if base_sha == Some(&ours.1) { &theirs.1 } else { &ours.1 };
+        return Ok(((mode, sha.clone()), mode_conflict));
+    }
+
+    // Links have no lines to merge, our version stays in the working tree
+    let special = |mode: &str| mode == SYMLINK_MODE || mode == GITLINK_MODE;
+    if special(&ours.0) || special(&theirs.0) {
+        *messages += &conflict;
+        return Ok((ours.clone(), true));
+    }
+    let base_data = match base {
+        Some((mode, sha)) if !special(mode) => read_blob(sha)?.data,
+        _ => Vec::new(),
+    };
+    let (our_data, their_data) = (read_blob(&ours.1)?.data, read_blob(&theirs.1)?.data);
+    if [&base_data, &our_data, &their_data].iter().any(|data| is_binary(data)) {
+        *messages += &format!(
+            "warning: Cannot merge binary files: {path} ({} vs. {})\n",
+            labels.ours,
+            labels.theirs
+        );
+        *messages += &format!("Auto-merging {path}\n{conflict}");
+        return Ok((ours.clone(), true));
+    }
+
+    *messages += &format!("Auto-merging {path}\n");
+    let merged = merge_content(&base_data, &our_data, &their_data, labels, style);
+    let sha = write_object(&Object::Blob(Blob { data: merged.data }))?;
+    let conflicted = merged.conflicts > 0 || mode_conflict;
+    if conflicted {
+        *messages += &conflict;
+    }
+    Ok(((mode, sha), conflicted))
+}
+
+/// Renamed files from the base to the side: <base path> -> <new path>
+fn renames(base: &FileMap, side: &FileMap) -> Result<BTreeMap<String, String>> {
+    let changes = compare_file_maps(base, side, false, &[]);
+    let changes = detect_renames(changes, Some(DEFAULT_RENAME_SCORE), None, &FileMap::new())?;
+    Ok(
+        changes
+            .into_iter()
+            .filter(|change| matches!(change.status, ChangeStatus::Renamed(_)))
+            .filter_map(|change| Some((change.old?.path, change.new?.path)))
+            .collect()
+    )
+}
+
+/// Path not used by any side for a file moved out of the way: `<path>~<label>`
+fn unique_path(path: &str, label: &str, sides: &[FileMap; 3]) -> String {
+    let taken = |candidate: &str| sides.iter().any(|side| side.contains_key(candidate));
+    let mut unique = format!("{path}~{}", label.replace('/', "_"));
+    let prefix = unique.clone();
+    let mut number = 0;
+    while taken(&unique) {
+        unique = format!("{prefix}_{number}");
+        number += 1;
+    }
+    unique
+}
+
+/// Move renamed files to their new path on all sides (base, ours, theirs), so their changes
+/// are merged there. Renames the other side deleted, or renamed elsewhere, conflict.
+fn align_renames(
+    sides: &mut [FileMap; 3],
+    labels: MergeLabels,
+    result: &mut TreeMerge
+) -> Result<()> {
+    if sides[0].is_empty() {
+        return Ok(());
+    }
+    let our_renames = renames(&sides[0], &sides[1])?;
+    let their_renames = renames(&sides[0], &sides[2])?;
+    let old_paths: BTreeSet<String> =
+        our_renames.keys().chain(their_renames.keys()).cloned().collect();
+    for old in old_paths {
+        let Some(base_version) = sides[0].get(&old).cloned() else {
+            continue;
+        };
+        match (our_renames.get(&old), their_renames.get(&old)) {
+            (Some(ours), Some(theirs)) if ours == theirs => {
+                sides[0].remove(&old);
+                sides[0].insert(ours.clone(), base_version);
+            }
+            (Some(ours), Some(theirs)) => {
+                let text = format!(
+                    "CONFLICT (rename/rename): {old} renamed to {ours} in {} and to {theirs} \
+                     in {}.\n",
+                    labels.ours,
+                    labels.theirs
+                );
+                result.message(&old, &text);
+                sides[0].remove(&old);
+                let our_version = sides[1].remove(ours);
+                let their_version = sides[2].remove(theirs);
+                for (path, stages) in [
+                    (&old, [Some(base_version), None, None]),
+                    (ours, [None, our_version, None]),
+                    (theirs, [None, None, their_version]),
+                ] {
+                    if let Some(version) = stages[1].as_ref().or(stages[2].as_ref()) {
+                        result.files.insert(path.clone(), version.clone());
+                    }
+                    result.conflicts.push(Conflict { path: path.clone(), stages });
+                }
+            }
+            (renamed, _) => {
+                let (new, side, other, label, other_label) = match renamed {
+                    Some(new) => (new, 1, 2, labels.ours, labels.theirs),
+                    None => (&their_renames[&old], 2, 1, labels.theirs, labels.ours),
+                };
+                // A file added at the new path by the other side is merged as it is
+                if sides[other].contains_key(new) {
+                    continue;
+                }
+                sides[0].remove(&old);
+                if let Some(version) = sides[other].remove(&old) {
+                    sides[0].insert(new.clone(), base_version);
+                    sides[other].insert(new.clone(), version);
+                    continue;
+                }
+                let text = format!(
+                    "CONFLICT (rename/delete): {old} renamed to {new} in {label}, but deleted \
+                     in {other_label}.\n"
+                );
+                result.message(new, &text);
+                let version = sides[side].remove(new);
+                if version.as_ref().is_some_and(|(_, sha)| *sha != base_version.1) {
+                    let text = format!(
+                        "CONFLICT (modify/delete): {new} deleted in {other_label} and modified \
+                         in {label}.  Version {label} of {new} left in tree.\n"
+                    );
+                    result.message(new, &text);
+                }
+                let mut stages = [Some(base_version), None, None];
+                stages[side].clone_from(&version);
+                if let Some(version) = version {
+                    result.files.insert(new.clone(), version);
+                }
+                result.conflicts.push(Conflict { path: new.clone(), stages });
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Whether the path is in the merge result, without merging contents
+fn is_kept(path: &str, sides: &[FileMap; 3]) -> bool {
+    let [b, o, t] = sides.each_ref().map(|side| side.get(path));
+    if o == t || b == t {
+        o.is_some()
+    } else if b == o {
+        t.is_some()
+    } else {
+        true
+    }
+}
+
+/// Files of a side in the way of a directory of the other side are moved to `<path>~<side>`,
+/// returns their new paths
+fn move_files_in_the_way(
+    sides: &mut [FileMap; 3],
+    labels: MergeLabels,
+    result: &mut TreeMerge
+) -> BTreeSet<String> {
+    let paths: BTreeSet<String> = sides.iter().flat_map(|side| side.keys()).cloned().collect();
+    let kept: Vec<&String> = paths.iter().filter(|path| is_kept(path, sides)).collect();
+    let folders: HashSet<&str> = kept
+        .iter()
+        .flat_map(|path| path.match_indices('/').map(|(i, _)| &path[..i]))
+        .collect();
+
+    let mut moved = BTreeSet::new();
+    for path in kept.into_iter().filter(|path| folders.contains(path.as_str())) {
+        let label = if sides[1].contains_key(path) { labels.ours } else { labels.theirs };
+        let new = unique_path(path, label, sides);
+        let text = format!(
+            "CONFLICT (file/directory): directory in the way of {path} from {label}; moving \
+             it to {new} instead.\n"
+        );
+        result.message(path, &text);
+        for side in sides.iter_mut() {
+            if let Some(version) = side.remove(path) {
+                side.insert(new.clone(), version);
+            }
+        }
+        moved.insert(new);
+    }
+    moved
+}
+
+/// A file, a link or a submodule on each side: both are kept, the regular file (both when
+/// there is none) moved to `<path>~<side>`
+fn split_distinct_types(
+    path: &str,
+    versions: [FileVersion; 3],
+    labels: MergeLabels,
+    sides: &[FileMap; 3],
+    result: &mut TreeMerge
+) {
+    let [base, ours, theirs] = versions;
+    let kind = |version: &FileVersion| {
+        version.as_ref().map(|(mode, _)| mode_kind(mode).to_string())
+    };
+    let regular = |version: &FileVersion| kind(version).as_deref() == Some("blob");
+    let (move_ours, move_theirs) = match (regular(&ours), regular(&theirs)) {
+        (true, _) => (true, false),
+        (false, true) => (false, true),
+        (false, false) => (true, true),
+    };
+    let which = if move_ours && move_theirs { "both of them" } else { "one of them" };
+    let text = format!(
+        "CONFLICT (distinct types): {path} had different types on each side; renamed {which} so \
+         each can be recorded somewhere.\n"
+    );
+    result.message(path, &text);
+
+    for (side, moved, label) in [(1, move_ours, labels.ours), (2, move_theirs, labels.theirs)] {
+        let version = if side == 1 { &ours } else { &theirs };
+        let target = if moved { unique_path(path, label, sides) } else { path.to_string() };
+        let mut stages: [FileVersion; 3] = [None, None, None];
+        if kind(&base) == kind(version) {
+            stages[0].clone_from(&base);
+        }
+        stages[side].clone_from(version);
+        if let Some(version) = version {
+            result.files.insert(target.clone(), version.clone());
+        }
+        result.conflicts.push(Conflict { path: target, stages });
+    }
+}
+
+/// Three-way merge of file lists, a side changing a path wins over the base
+fn merge_trees(
+    base: &FileMap,
+    ours: &FileMap,
+    theirs: &FileMap,
+    labels: MergeLabels,
+    style: ConflictStyle
+) -> Result<TreeMerge> {
+    let mut result = TreeMerge::default();
+    let mut sides = [base.clone(), ours.clone(), theirs.clone()];
+    align_renames(&mut sides, labels, &mut result)?;
+    let moved = move_files_in_the_way(&mut sides, labels, &mut result);
+
+    let [base, ours, theirs] = &sides;
+    let paths: BTreeSet<&String> = base.keys().chain(ours.keys()).chain(theirs.keys()).collect();
+    for path in paths {
+        let (b, o, t) = (base.get(path), ours.get(path), theirs.get(path));
+        let stages = [b.cloned(), o.cloned(), t.cloned()];
+        let merged = if o == t || b == t {
+            o.cloned()
+        } else if b == o {
+            t.cloned()
+        } else {
+            match (o, t) {
+                (Some(o), Some(t)) if mode_kind(&o.0) != mode_kind(&t.0) => {
+                    split_distinct_types(path, stages, labels, &sides, &mut result);
+                    continue;
+                }
+                (Some(o), Some(t)) => {
+                    let messages = result.messages.entry(path.clone()).or_default();
+                    let (version, conflicted) = merge_file(path, b, o, t, labels, style, messages)?;
+                    if conflicted {
+                        result.conflicts.push(Conflict { path: path.clone(), stages });
+                    }
+                    Some(version)
+                }
+                // Modified version stays when the other side deleted the file
+                (modified, _) => {
+                    let (deleted_in, kept) = match modified {
+                        Some(_) => (labels.theirs, labels.ours),
+                        None => (labels.ours, labels.theirs),
+                    };
+                    let text = format!(
+                        "CONFLICT (modify/delete): {path} deleted in {deleted_in} and modified \
+                         in {kept}.  Version {kept} of {path} left in tree.\n"
+                    );
+                    result.message(path, &text);
+                    result.conflicts.push(Conflict { path: path.clone(), stages });
+                    o.or(t).cloned()
+                }
+            }
+        };
+        if let Some(version) = merged {
+            result.files.insert(path.clone(), version);
+        }
+    }
+
+    // Files moved out of the way of a directory stay unmerged
+    for path in moved {
+        if !result.conflicts.iter().any(|conflict| conflict.path == path) {
+            let stages = sides.each_ref().map(|side| side.get(&path).cloned());
+            result.conflicts.push(Conflict { path, stages });
+        }
+    }
+    result.conflicts.sort_by(|a, b| a.path.cmp(&b.path));
+    Ok(result)
+}
+
+/// Files of the merge base, several bases are merged into one first (conflicts included)
+fn base_files(bases: &[String], style: ConflictStyle) -> Result<FileMap> {
+    let Some((first, rest)) = bases.split_first() else {
+        return Ok(FileMap::new());
+    };
+    let mut files = commit_files(Some(first))?;
+    for other in rest {
+        let inner = base_files(&merge_bases(first, other)?, style)?;
+        let labels = MergeLabels {
+            ours: "Temporary merge branch 1",
+            base: VIRTUAL_BASE_LABEL,
+            theirs: "Temporary merge branch 2",
+        };
+        files = merge_trees(&inner, &files, &commit_files(Some(other))?, labels, style)?.files;
+    }
+    Ok(files)
+}
+
+/// Replace entries of conflicted paths with their index stages
+fn record_conflicts(conflicts: &[Conflict]) -> Result<()> {
+    let mut index = read_index()?;
+    index.entries.retain(|entry| !conflicts.iter().any(|conflict| conflict.path == entry.path));
+    for Conflict { path, stages } in conflicts {
+        for (stage, version) in (1..).zip(stages) {
+            if let Some((mode, sha)) = version {
+                index.entries.push(IndexEntry::conflicted(path, mode, sha, stage)?);
+            }
+        }
+    }
+    write_index(&index)
+}
+
+/// Diffstat and summary of the changes between two commits
+fn stat(old: &str, new: &str) -> Result<String> {
+    let options = DiffOptions::default();
+    let changes = diff::diff_trees(
+        Some(&read_commit(old)?.tree),
+        Some(&read_commit(new)?.tree),
+        &options.tree_options(true)
+    )?;
+    Ok(diff::format::stat(&changes, &options)? + &diff::format::summary(&changes))
+}
+
+/// Staged changes would be lost or committed with the merge
+fn check_clean_index(head: &FileMap, index: &FileMap) -> Result<()> {
+    let staged: BTreeSet<&String> = head
+        .keys()
+        .chain(index.keys())
+        .filter(|path| head.get(*path) != index.get(*path))
+        .collect();
+    if !staged.is_empty() {
+        let paths: Vec<&str> = staged.into_iter().map(String::as_str).collect();
+        bail!(
+            "Your local changes to the following files would be overwritten by merge:\n\t{}\n\
+             Please commit your changes or stash them before you merge.\nAborting",
+            paths.join("\n\t")
+        );
+    }
+    Ok(())
+}
+
+/// Outcome of a merge applied to the index and the working tree
+#[derive(Clone, Debug, Default)]
+pub(crate) struct MergeResult {
+    /// `Auto-merging` and `CONFLICT` lines
+    pub messages: String,
+    /// Paths left unmerged
+    pub conflicts: Vec<String>,
+}
+
+/// Three-way merge of `theirs` into HEAD over the bases (the empty tree without any), the
+/// result goes to the index and the working tree, conflicts with their stages
+pub(crate) fn merge_into_head(
+    head: &str,
+    bases: &[String],
+    theirs: Option<&str>,
+    labels: MergeLabels
+) -> Result<MergeResult> {
+    let index = read_index()?;
+    let index_map = index_files(&index);
+    let worktree = worktree_files(&index, &index_map)?;
+    let head_files = commit_files(Some(head))?;
+    let config = Config::load()?;
+    let style = ConflictStyle::from_name(&config.get("merge.conflictstyle")?.unwrap_or_default());
+    let their_files = commit_files(theirs)?;
+    let base_files = base_files(bases, style)?;
+    let result = merge_trees(&base_files, &head_files, &their_files, labels, style)?;
+    let kind = UpdateKind::Merge;
+    let updates: Vec<Update> =
+        plan_updates(&head_files, &result.files, &index_map, &worktree, false, kind)?;
+    apply_updates(&updates)?;
+    if !result.conflicts.is_empty() {
+        record_conflicts(&result.conflicts)?;
+    }
+    Ok(MergeResult {
+        messages: result.messages.into_values().collect(),
+        conflicts: result.conflicts.into_iter().map(|conflict| conflict.path).collect(),
+    })
+}
+
+/// Three-way merge of file lists leaving the index and the working tree alone, None when any
+/// path conflicts
+pub(crate) fn merge_files(
+    base: &FileMap,
+    ours: &FileMap,
+    theirs: &FileMap,
+    labels: MergeLabels
+) -> Result<Option<FileMap>> {
+    let config = Config::load()?;
+    let style = ConflictStyle::from_name(&config.get("merge.conflictstyle")?.unwrap_or_default());
+    let result = merge_trees(base, ours, theirs, labels, style)?;
+    Ok(result.conflicts.is_empty().then_some(result.files))
+}
+
+/// Merge the commit into HEAD: fast-forward when possible, otherwise a merge commit with
+/// both parents, or conflicts left in the index and the working tree
+pub fn merge(name: &str, messages: &[String], fast_forward: FastForward) -> Result<String> {
+    let index = read_index()?;
+    if index.entries.iter().any(|entry| entry.stage() != 0) {
+        bail!(
+            "Merging is not possible because you have unmerged files.\n\
+             hint: Fix them up in the work tree, and then use 'git add/rm <file>'\n\
+             hint: as appropriate to mark resolution and make a commit."
+        );
+    }
+    if !merge_heads()?.is_empty() {
+        bail!(
+            "You have not concluded your merge (MERGE_HEAD exists).\n\
+             Please, commit your changes before you merge."
+        );
+    }
+    let theirs = resolve_revision(&format!("{name}^{{commit}}"))
+        .map_err(|_| anyhow!("{name} - not something we can merge"))?;
+    let head = read_head_commit()?;
+    let bases = match &head {
+        Some(head) => merge_bases(head, &theirs)?,
+        None => Vec::new(),
+    };
+    if bases.contains(&theirs) {
+        return Ok("Already up to date.\n".to_string());
+    }
+
+    let head_files = commit_files(head.as_deref())?;
+    let index_map = index_files(&index);
+    let worktree = worktree_files(&index, &index_map)?;
+    let committer = signature("committer")?;
+    let root = find_root_folder()?;
+    if let Some(head) = &head {
+        write_locked(&format!("{root}.git/ORIG_HEAD"), format!("{head}\n"))?;
+    }
+
+    // HEAD behind the commit (or unborn) just moves forward
+    let behind = match &head {
+        Some(head) => bases.contains(head),
+        None => true,
+    };
+    if behind && fast_forward != FastForward::Never {
+        let target_files = commit_files(Some(&theirs))?;
+        let kind = UpdateKind::Merge;
+        let updates = plan_updates(&head_files, &target_files, &index_map, &worktree, false, kind)?;
+        apply_updates(&updates)?;
+        refs::update_ref("HEAD", &theirs, &committer, &format!("merge {name}: Fast-forward"))?;
+        return Ok(match &head {
+            Some(head) => {
+                let range = format!("{}..{}", abbrev(head), abbrev(&theirs));
+                format!("Updating {range}\nFast-forward\n{}", stat(head, &theirs)?)
+            }
+            None => String::new(),
+        });
+    }
+    if fast_forward == FastForward::Only {
+        bail!("Not possible to fast-forward, aborting.");
+    }
+    let Some(head) = head else {
+        bail!("Cannot merge into an unborn branch without fast-forward");
+    };
+
+    // Merge trees and bring the working tree to the result
+    check_clean_index(&head_files, &index_map)?;
+    let base_label = match bases.as_slice() {
+        [base] => abbrev(base).to_string(),
+        _ => VIRTUAL_BASE_LABEL.to_string(),
+    };
+    let labels = MergeLabels { ours: OURS_LABEL, base: &base_label, theirs: name };
+    let result = merge_into_head(&head, &bases, Some(&theirs), labels)?;
+
+    let message = match messages.is_empty() {
+        true => default_message(name)?.into_bytes(),
+        false => cleanup_message(messages.join("\n\n").as_bytes()),
+    };
+    if !result.conflicts.is_empty() {
+        let mut text = message;
+        text.extend(b"\n# Conflicts:\n");
+        for path in &result.conflicts {
+            text.extend(format!("#\t{path}\n").bytes());
+        }
+        write_locked(&format!("{root}.git/MERGE_HEAD"), format!("{theirs}\n"))?;
+        write_locked(&format!("{root}.git/MERGE_MODE"), "")?;
+        write_locked(&format!("{root}.git/MERGE_MSG"), text)?;
+        return Ok(
+            result.messages + "Automatic merge failed; fix conflicts and then commit the result.\n"
+        );
+    }
+
+    let commit = Commit {
+        tree: write_index_tree(&read_index()?)?,
+        parents: vec![head.clone(), theirs],
+        author: signature("author")?,
+        committer: committer.clone(),
+        extra_headers: Vec::new(),
+        message,
+    };
+    let sha = write_object(&Object::Commit(commit))?;
+    let strategy = "Merge made by the 'ort' strategy.";
+    refs::update_ref("HEAD", &sha, &committer, &format!("merge {name}: {strategy}"))?;
+    Ok(format!("{}{strategy}\n{}", result.messages, stat(&head, &sha)?))
+}
+
+/// Throw away the merge in progress, paths changed by it get their HEAD versions back
+pub fn abort() -> Result<String> {
+    if merge_heads()?.is_empty() {
+        bail!("There is no merge to abort (MERGE_HEAD missing).");
+    }
+    let head_files = commit_files(read_head_commit()?.as_deref())?;
+    let index = read_index()?;
+    let index_map = index_files(&index);
+    let paths: BTreeSet<&String> = index.entries
+        .iter()
+        .filter(|entry| entry.stage() != 0)
+        .map(|entry| &entry.path)
+        .chain(
+            head_files
+                .keys()
+                .chain(index_map.keys())
+                .filter(|path| head_files.get(*path) != index_map.get(*path))
+        )
+        .collect();
+    let updates: Vec<Update> = paths
+        .into_iter()
+        .map(|path| (path.clone(), head_files.get(path).cloned()))
+        .collect();
+    apply_updates(&updates)?;
+    remove_state()?;
+    Ok(String::new())
+}
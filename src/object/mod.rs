@@ -0,0 +1,537 @@
+use crate::utility::fs_utility::*;
+use crate::utility::other_util::*;
+
+use anyhow::{ anyhow, bail, Result };
+use std::borrow::Cow;
+use std::fmt;
+
+/// Tree without entries, git knows it even when it is not stored
+pub const EMPTY_TREE_SHA: &str = "4b825dc642cb6eb9a060e54bf8d69288fbee4904";
+
+/// Author, committer or tagger line, name and email are kept as stored
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Signature {
+    pub name: Vec<u8>,
+    pub email: Vec<u8>,
+    pub time: i64,
+    pub tz: String,
+}
+
+impl Signature {
+    /// Parse `Name <email> <unix time> <tz>`
+    pub fn parse(line: &[u8]) -> Result<Signature> {
+        let incorrect = || anyhow!("Incorrect signature '{}'", String::from_utf8_lossy(line));
+        let close = line.iter().rposition(|c| *c == b'>').ok_or_else(incorrect)?;
+        let open = line[..close].iter().position(|c| *c == b'<').ok_or_else(incorrect)?;
+        let name = &line[..open];
+        let date = std::str::from_utf8(&line[close + 1..])?;
+        let (time, tz) = date
+            .trim_start()
+            .split_once(' ')
+            .ok_or_else(|| anyhow!("Incorrect signature date '{date}'"))?;
+
+        Ok(Signature {
+            name: name.strip_suffix(b" ").unwrap_or(name).to_vec(),
+            email: line[open + 1..close].to_vec(),
+            time: time.parse()?,
+            tz: tz.to_string(),
+        })
+    }
+
+    /// `Name <email>` part, decoded from the encoding of the object
+    pub fn decoded_ident(&self, encoding: Option<&str>) -> String {
+        format!("{} <{}>", decode(&self.name, encoding), decode(&self.email, encoding))
+    }
+
+    /// `Name <email>` part
+    pub fn ident(&self) -> String {
+        self.decoded_ident(None)
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut data = self.name.clone();
+        data.extend(b" <");
+        data.extend(&self.email);
+        data.extend(format!("> {} {}", self.time, self.tz).bytes());
+        data
+    }
+}
+
+impl fmt::Display for Signature {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", String::from_utf8_lossy(&self.to_bytes()))
+    }
+}
+
+/// File contents
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Blob {
+    pub data: Vec<u8>,
+}
+
+/// Single entry of a tree, the name is kept as stored
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TreeEntry {
+    pub mode: String,
+    pub name: Vec<u8>,
+    pub sha: String,
+}
+
+impl TreeEntry {
+    /// Kind of the referenced object, gitlinks (`160000`) point to commits of a submodule
+    pub fn kind(&self) -> &'static str {
+        match self.mode.as_str() {
+            "40000" => "tree",
+            "160000" => "commit",
+            _ => "blob",
+        }
+    }
+
+    /// Name for paths and display, invalid UTF-8 is replaced
+    pub fn name_text(&self) -> Cow<'_, str> {
+        String::from_utf8_lossy(&self.name)
+    }
+}
+
+/// Directory listing
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Tree {
+    pub entries: Vec<TreeEntry>,
+}
+
+/// Commit with headers after `committer` (`encoding`, `gpgsig`, `mergetag`, ...) kept in order,
+/// the message is kept as stored
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Commit {
+    pub tree: String,
+    pub parents: Vec<String>,
+    pub author: Signature,
+    pub committer: Signature,
+    pub extra_headers: Vec<Header>,
+    pub message: Vec<u8>,
+}
+
+/// Annotated tag
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Tag {
+    pub object: String,
+    pub target_type: String,
+    pub name: String,
+    pub tagger: Option<Signature>,
+    pub extra_headers: Vec<Header>,
+    pub message: Vec<u8>,
+}
+
+/// Any object stored in the repository
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Object {
+    Blob(Blob),
+    Tree(Tree),
+    Commit(Commit),
+    Tag(Tag),
+}
+
+impl Tree {
+    pub fn parse(data: &[u8]) -> Result<Tree> {
+        let mut entries: Vec<TreeEntry> = Vec::new();
+        let mut rest = data;
+        while !rest.is_empty() {
+            let space = rest
+                .iter()
+                .position(|c| *c == b' ')
+                .ok_or_else(|| anyhow!("Cannot separate tree entry mode!"))?;
+            let nul = rest
+                .iter()
+                .position(|c| *c == b'\0')
+                .ok_or_else(|| anyhow!("Cannot separate file name!"))?;
+            if nul < space || rest.len() < nul + 21 {
+                bail!("Corrupted tree entry!");
+            }
+
+            entries.push(TreeEntry {
+                mode: String::from_utf8(rest[..space].to_vec())?,
+                name: rest[space + 1..nul].to_vec(),
+                sha: hex::encode(&rest[nul + 1..nul + 21]),
+            });
+            rest = &rest[nul + 21..];
+        }
+        Ok(Tree { entries })
+    }
+
+    pub fn serialize(&self) -> Result<Vec<u8>> {
+        let mut data: Vec<u8> = Vec::new();
+        for entry in &self.entries {
+            data.extend(format!("{} ", entry.mode).bytes());
+            data.extend(&entry.name);
+            data.push(b'\0');
+            data.extend(hex::decode(&entry.sha)?);
+        }
+        Ok(data)
+    }
+}
+
+fn is_utf8_encoding(encoding: Option<&str>) -> bool {
+    encoding.is_none_or(|e| e.eq_ignore_ascii_case("utf-8") || e.eq_ignore_ascii_case("utf8"))
+}
+
+/// Text of stored bytes for display: encodings other than UTF-8 are read byte per char,
+/// invalid UTF-8 is replaced
+pub fn decode<'a>(bytes: &'a [u8], encoding: Option<&str>) -> Cow<'a, str> {
+    if is_utf8_encoding(encoding) {
+        String::from_utf8_lossy(bytes)
+    } else {
+        Cow::Owned(bytes.iter().map(|b| *b as char).collect())
+    }
+}
+
+/// Stored bytes in UTF-8 for a new object: other encodings are read byte per char, so are
+/// bytes which are not valid UTF-8 (git takes them as Latin-1)
+pub fn to_utf8(bytes: &[u8], encoding: Option<&str>) -> Vec<u8> {
+    if !is_utf8_encoding(encoding) {
+        return decode(bytes, encoding).into_owned().into_bytes();
+    }
+    let mut out = Vec::new();
+    for chunk in bytes.utf8_chunks() {
+        out.extend(chunk.valid().as_bytes());
+        out.extend(chunk.invalid().iter().flat_map(|b| (*b as char).to_string().into_bytes()));
+    }
+    out
+}
+
+/// Header name and value (continuation lines joined with newlines)
+pub type Header = (String, Vec<u8>);
+
+/// Split object text into headers and message
+fn parse_headers(data: &[u8]) -> Result<(Vec<Header>, &[u8])> {
+    let (header_end, message_start) = match data.windows(2).position(|w| w == b"\n\n") {
+        Some(position) => (position, position + 2),
+        None => (data.strip_suffix(b"\n").unwrap_or(data).len(), data.len()),
+    };
+
+    let mut headers: Vec<Header> = Vec::new();
+    for line in data[..header_end].split(|c| *c == b'\n') {
+        if let Some(continuation) = line.strip_prefix(b" ") {
+            let (_, value) = headers
+                .last_mut()
+                .ok_or_else(|| anyhow!("Continuation line without header!"))?;
+            value.push(b'\n');
+            value.extend(continuation);
+        } else {
+            let space = line.iter().position(|c| *c == b' ').unwrap_or(line.len());
+            let value = line.get(space + 1..).unwrap_or_default();
+            headers.push((String::from_utf8(line[..space].to_vec())?, value.to_vec()));
+        }
+    }
+    Ok((headers, &data[message_start..]))
+}
+
+fn write_headers(data: &mut Vec<u8>, headers: &[Header]) {
+    for (key, value) in headers {
+        data.extend(key.bytes());
+        data.push(b' ');
+        for byte in value {
+            data.push(*byte);
+            if *byte == b'\n' {
+                data.push(b' ');
+            }
+        }
+        data.push(b'\n');
+    }
+}
+
+fn header_value<'a>(headers: &'a [Header], key: &str) -> Option<&'a [u8]> {
+    headers
+        .iter()
+        .find(|(k, _)| k == key)
+        .map(|(_, v)| v.as_slice())
+}
+
+impl Commit {
+    pub fn parse(data: &[u8]) -> Result<Commit> {
+        let (headers, message) = parse_headers(data)?;
+        let mut headers = headers.into_iter().peekable();
+
+        let tree = match headers.next() {
+            Some((key, value)) if key == "tree" => String::from_utf8(value)?,
+            _ => bail!("Commit does not start with a tree!"),
+        };
+        let mut parents: Vec<String> = Vec::new();
+        while let Some((_, value)) = headers.next_if(|(key, _)| key == "parent") {
+            parents.push(String::from_utf8(value)?);
+        }
+        let author = match headers.next() {
+            Some((key, value)) if key == "author" => Signature::parse(&value)?,
+            _ => bail!("Commit has no author!"),
+        };
+        let committer = match headers.next() {
+            Some((key, value)) if key == "committer" => Signature::parse(&value)?,
+            _ => bail!("Commit has no committer!"),
+        };
+
+        Ok(Commit {
+            tree,
+            parents,
+            author,
+            committer,
+            extra_headers: headers.collect(),
+            message: message.to_vec(),
+        })
+    }
+
+    pub fn serialize(&self) -> Vec<u8> {
+        let mut headers: Vec<Header> = vec![("tree".to_string(), self.tree.clone().into_bytes())];
+        headers.extend(self.parents.iter().map(|p| ("parent".to_string(), p.clone().into_bytes())));
+        headers.push(("author".to_string(), self.author.to_bytes()));
+        headers.push(("committer".to_string(), self.committer.to_bytes()));
+        headers.extend(self.extra_headers.iter().cloned());
+
+        let mut data: Vec<u8> = Vec::new();
+        write_headers(&mut data, &headers);
+        data.push(b'\n');
+        data.extend(&self.message);
+        data
+    }
+
+    /// Value of the extra header (e.g. `gpgsig`)
+    pub fn header(&self, key: &str) -> Option<&[u8]> {
+        header_value(&self.extra_headers, key)
+    }
+
+    pub fn encoding(&self) -> Option<&str> {
+        self.header("encoding").and_then(|value| std::str::from_utf8(value).ok())
+    }
+
+    /// Message decoded for display
+    pub fn message_text(&self) -> Cow<'_, str> {
+        decode(&self.message, self.encoding())
+    }
+
+    /// Message in UTF-8, for new commits reusing it
+    pub fn utf8_message(&self) -> Vec<u8> {
+        to_utf8(&self.message, self.encoding())
+    }
+
+    /// New commit as git records it: bytes of the signatures and the message which are not
+    /// valid UTF-8 are taken as Latin-1
+    pub fn into_utf8(mut self) -> Commit {
+        for person in [&mut self.author, &mut self.committer] {
+            person.name = to_utf8(&person.name, None);
+            person.email = to_utf8(&person.email, None);
+        }
+        self.message = to_utf8(&self.message, None);
+        self
+    }
+}
+
+impl Tag {
+    pub fn parse(data: &[u8]) -> Result<Tag> {
+        let (headers, message) = parse_headers(data)?;
+        let mut headers = headers.into_iter().peekable();
+
+        let mut field = |name: &str| -> Result<String> {
+            match headers.next() {
+                Some((key, value)) if key == name => Ok(String::from_utf8(value)?),
+                _ => bail!("Tag has no '{name}' header!"),
+            }
+        };
+        let object = field("object")?;
+        let target_type = field("type")?;
+        let name = field("tag")?;
+        // Very old tags have no tagger
+        let tagger = match headers.next_if(|(key, _)| key == "tagger") {
+            Some((_, value)) => Some(Signature::parse(&value)?),
+            None => None,
+        };
+
+        Ok(Tag {
+            object,
+            target_type,
+            name,
+            tagger,
+            extra_headers: headers.collect(),
+            message: message.to_vec(),
+        })
+    }
+
+    pub fn serialize(&self) -> Vec<u8> {
+        let mut headers: Vec<Header> = vec![
+            ("object".to_string(), self.object.clone().into_bytes()),
+            ("type".to_string(), self.target_type.clone().into_bytes()),
+            ("tag".to_string(), self.name.clone().into_bytes())
+        ];
+        if let Some(tagger) = &self.tagger {
+            headers.push(("tagger".to_string(), tagger.to_bytes()));
+        }
+        headers.extend(self.extra_headers.iter().cloned());
+
+        let mut data: Vec<u8> = Vec::new();
+        write_headers(&mut data, &headers);
+        data.push(b'\n');
+        data.extend(&self.message);
+        data
+    }
+
+    pub fn encoding(&self) -> Option<&str> {
+        header_value(&self.extra_headers, "encoding")
+            .and_then(|value| std::str::from_utf8(value).ok())
+    }
+
+    /// Message decoded for display
+    pub fn message_text(&self) -> Cow<'_, str> {
+        decode(&self.message, self.encoding())
+    }
+}
+
+impl Object {
+    /// Parse object contents of the given type (without `<type> <size>\0` header)
+    pub fn parse(kind: &str, data: &[u8]) -> Result<Object> {
+        Ok(match kind {
+            "blob" => Object::Blob(Blob { data: data.to_vec() }),
+            "tree" => Object::Tree(Tree::parse(data)?),
+            "commit" => Object::Commit(Commit::parse(data)?),
+            "tag" => Object::Tag(Tag::parse(data)?),
+            _ => bail!("Unknown object type '{kind}'"),
+        })
+    }
+
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Object::Blob(_) => "blob",
+            Object::Tree(_) => "tree",
+            Object::Commit(_) => "commit",
+            Object::Tag(_) => "tag",
+        }
+    }
+
+    /// Object contents without header
+    pub fn serialize(&self) -> Result<Vec<u8>> {
+        Ok(match self {
+            Object::Blob(blob) => blob.data.clone(),
+            Object::Tree(tree) => tree.serialize()?,
+            Object::Commit(commit) => commit.serialize(),
+            Object::Tag(tag) => tag.serialize(),
+        })
+    }
+}
+
+/// Split stored object into type and contents
+fn split_header(sha: &str, data: &[u8]) -> Result<(String, usize)> {
+    let nul = data
+        .iter()
+        .position(|c| *c == b'\0')
+        .ok_or_else(|| anyhow!("Corrupted object {sha}!"))?;
+    let header = std::str::from_utf8(&data[..nul])?;
+    let (kind, _size) = header.split_once(' ').ok_or_else(|| anyhow!("Corrupted object {sha}!"))?;
+    Ok((kind.to_string(), nul + 1))
+}
+
+/// Decompressed object with header, the empty tree is available without the file
+fn read_stored(sha: &str) -> Result<Vec<u8>> {
+    match read_data_decompressed(sha) {
+        Err(_) if sha == EMPTY_TREE_SHA => Ok(b"tree 0\0".to_vec()),
+        result => result,
+    }
+}
+
+/// Type of the stored object
+pub fn object_kind(sha: &str) -> Result<String> {
+    let data = read_stored(sha)?;
+    Ok(split_header(sha, &data)?.0)
+}
+
+pub fn read_object(sha: &str) -> Result<Object> {
+    let data = read_stored(sha)?;
+    let (kind, start) = split_header(sha, &data)?;
+    Object::parse(&kind, &data[start..])
+}
+
+pub fn read_blob(sha: &str) -> Result<Blob> {
+    match read_object(sha)? {
+        Object::Blob(blob) => Ok(blob),
+        other => bail!("Object {sha} is a {}, not a blob", other.kind()),
+    }
+}
+
+pub fn read_tree(sha: &str) -> Result<Tree> {
+    match read_object(sha)? {
+        Object::Tree(tree) => Ok(tree),
+        other => bail!("Object {sha} is a {}, not a tree", other.kind()),
+    }
+}
+
+pub fn read_commit(sha: &str) -> Result<Commit> {
+    match read_object(sha)? {
+        Object::Commit(commit) => Ok(commit),
+        other => bail!("Object {sha} is a {}, not a commit", other.kind()),
+    }
+}
+
+/// Store object and return its SHA
+pub fn write_object(object: &Object) -> Result<String> {
+    write_data(add_data_prefix(object.kind().as_bytes(), object.serialize()?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn commit_round_trip() {
+        let data = b"tree 4b825dc642cb6eb9a060e54bf8d69288fbee4904\n\
+parent 1111111111111111111111111111111111111111\n\
+parent 2222222222222222222222222222222222222222\n\
+author A U Thor <author@example.com> 1112911993 -0700\n\
+committer C O Mitter <committer@example.com> 1112912053 +0130\n\
+mergetag object 3333333333333333333333333333333333333333\n type commit\n tag v1\n \n signed\n\
+x-custom value\n\
+gpgsig -----BEGIN PGP SIGNATURE-----\n \n abc\n -----END PGP SIGNATURE-----\n\
+\n\
+Merge things\n\nBody\n";
+        let commit = Commit::parse(data).unwrap();
+        assert_eq!(commit.parents.len(), 2);
+        assert_eq!(commit.committer.tz, "+0130");
+        assert_eq!(commit.header("x-custom"), Some(&b"value"[..]));
+        assert!(commit.header("gpgsig").unwrap().ends_with(b"\n-----END PGP SIGNATURE-----"));
+        assert_eq!(commit.serialize(), data.to_vec());
+
+        // Latin-1 message survives unchanged
+        let latin = b"tree 4b825dc642cb6eb9a060e54bf8d69288fbee4904\n\
+author \xe9 <e@x> 1 +0000\ncommitter \xe9 <e@x> 1 +0000\nencoding ISO-8859-1\n\ncaf\xe9\n";
+        let commit = Commit::parse(latin).unwrap();
+        assert_eq!(commit.message, b"caf\xe9\n");
+        assert_eq!(commit.message_text(), "caf\u{e9}\n");
+        assert_eq!(commit.author.ident(), "\u{fffd} <e@x>");
+        assert_eq!(commit.serialize(), latin.to_vec());
+
+        // Invalid UTF-8 without an encoding is kept as stored
+        let invalid = b"tree 4b825dc642cb6eb9a060e54bf8d69288fbee4904\n\
+author \xff <e@x> 1 +0000\ncommitter C <c@x> 1 +0000\n\ncaf\xe9\n";
+        assert_eq!(Commit::parse(invalid).unwrap().serialize(), invalid.to_vec());
+
+        // New commits take them as Latin-1, like git
+        let commit = Commit::parse(invalid).unwrap().into_utf8();
+        assert_eq!(commit.author.name, "\u{ff}".as_bytes());
+        assert_eq!(commit.message, "caf\u{e9}\n".as_bytes());
+        assert_eq!(Commit::parse(latin).unwrap().utf8_message(), "caf\u{e9}\n".as_bytes());
+    }
+
+    #[test]
+    fn tag_and_tree_round_trip() {
+        let data = b"object 4b825dc642cb6eb9a060e54bf8d69288fbee4904\ntype tree\ntag v1.0\n\
+tagger T <t@example.com> 1112911993 +0000\n\nRelease\n";
+        let tag = Tag::parse(data).unwrap();
+        assert_eq!(tag.name, "v1.0");
+        assert_eq!(tag.serialize(), data.to_vec());
+
+        let mut tree = b"100644 a.txt\0".to_vec();
+        tree.extend([0xab; 20]);
+        tree.extend(b"40000 dir\0");
+        tree.extend([0xcd; 20]);
+        tree.extend(b"100644 caf\xe9\0");
+        tree.extend([0xef; 20]);
+        let parsed = Tree::parse(&tree).unwrap();
+        assert_eq!(parsed.entries[1].mode, "40000");
+        assert_eq!(parsed.entries[2].name, b"caf\xe9");
+        assert_eq!(parsed.serialize().unwrap(), tree);
+    }
+}
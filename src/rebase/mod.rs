@@ -0,0 +1,495 @@
+use crate::checkout::{ commit_files, reset_hard };
+use crate::diff::{ abbrev, diff_trees, format_changes, index_files, worktree_files, DiffOptions };
+use crate::history::{ merge_bases, pretty::subject, walk, CommitOrder, LogOptions };
+use crate::index::{ read_index, write_index_tree };
+use crate::object::{ read_commit, Commit, EMPTY_TREE_SHA };
+use crate::refs::{ self, current_branch };
+use crate::revision::{ head_commit, read_head_commit, resolve_revision };
+use crate::sequencer::{ commit_summary, parse_todo, pick, read_todo, record };
+use crate::sequencer::{ write_todo, TodoCommand, TodoItem };
+use crate::utility::fs_utility::{ find_root_folder, state_path, write_locked };
+use crate::utility::other_util::{ get_hash_from_data, launch_editor, signature, strip_comments };
+
+use anyhow::{ anyhow, bail, Result };
+use std::collections::HashSet;
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+
+/// Folder keeping the state of the rebase in progress
+const STATE_FOLDER: &str = "rebase-merge/";
+/// Commands still to run, also the file edited by the sequence editor
+const TODO_FILE: &str = "git-rebase-todo";
+
+const CONFLICT_HINT: &str =
+    "hint: Resolve all conflicts manually, mark them as resolved with\n\
+     hint: \"git add/rm <conflicted_files>\", then run \"git rebase --continue\".\n\
+     hint: You can instead skip this commit: run \"git rebase --skip\".\n\
+     hint: To abort and get back to the state before \"git rebase\", run \"git rebase --abort\".\n";
+
+const TODO_HELP: &str = "#
+# Commands:
+# p, pick <commit> = use commit
+# r, reword <commit> = use commit, but edit the commit message
+# e, edit <commit> = use commit, but stop for amending
+# s, squash <commit> = use commit, but meld into previous commit
+# f, fixup <commit> = like \"squash\" but keep only the previous
+#                    commit's log message
+# x, exec <command> = run command (the rest of the line) using shell
+# d, drop <commit> = remove commit
+#
+# These lines can be re-ordered; they are executed from top to bottom.
+#
+# If you remove a line here THAT COMMIT WILL BE LOST.
+#
+# However, if you remove everything, the rebase will be aborted.
+#
+";
+
+/// Operation of the `rebase` command
+#[derive(Clone, Debug)]
+pub enum RebaseAction {
+    /// Replay commits of HEAD missing from `upstream` on top of it, `interactive` lets the
+    /// sequence editor change the todo list first
+    Start {
+        upstream: String,
+        interactive: bool,
+    },
+    /// Record the resolved commit and go on with the todo list
+    Continue,
+    /// Drop the commit which failed to apply and go on
+    Skip,
+    /// Return to the branch as it was before the rebase
+    Abort,
+}
+
+fn read_state(name: &str) -> Result<Option<String>> {
+    Ok(fs::read_to_string(state_path(STATE_FOLDER, name)?).ok())
+}
+
+fn write_state(name: &str, contents: &str) -> Result<()> {
+    Ok(fs::write(state_path(STATE_FOLDER, name)?, contents)?)
+}
+
+fn remove_state(name: &str) -> Result<()> {
+    let _ = fs::remove_file(state_path(STATE_FOLDER, name)?);
+    Ok(())
+}
+
+/// Todo list of the rebase, once commands were done squashes may meld into a picked commit
+fn read_rebase_todo() -> Result<Vec<TodoItem>> {
+    let done = Path::new(&state_path(STATE_FOLDER, "done")?).exists();
+    read_todo(&state_path(STATE_FOLDER, TODO_FILE)?, done)
+}
+
+/// Identity of the changes made by the commit: its patch without line numbers, blob names and
+/// whitespace (like `git patch-id`)
+fn patch_id(commit: &Commit) -> Result<String> {
+    let parent_tree = match commit.parents.first() {
+        Some(parent) => Some(read_commit(parent)?.tree),
+        None => None,
+    };
+    let options = DiffOptions { renames: None, ..Default::default() };
+    let tree_options = options.tree_options(true);
+    let changes = diff_trees(parent_tree.as_deref(), Some(&commit.tree), &tree_options)?;
+    let text: String = format_changes(&changes, &options)?
+        .lines()
+        .filter(|line| !line.starts_with("index "))
+        .map(|line| if line.starts_with("@@") { "@@" } else { line })
+        .flat_map(|line| line.chars().filter(|c| !c.is_whitespace()))
+        .collect();
+    Ok(get_hash_from_data(text.as_bytes()))
+}
+
+/// Rebase in progress
+struct RebaseState {
+    /// Branch being rebased, None for detached HEAD
+    head_name: Option<String>,
+    onto: String,
+    orig_head: String,
+}
+
+impl RebaseState {
+    fn load() -> Result<RebaseState> {
+        let read = |name: &str| -> Result<String> {
+            Ok(read_state(name)?.ok_or_else(|| anyhow!("No rebase in progress?"))?.trim().into())
+        };
+        let head_name = read("head-name")?;
+        Ok(RebaseState {
+            head_name: head_name.starts_with("refs/").then_some(head_name),
+            onto: read("onto")?,
+            orig_head: read("orig-head")?,
+        })
+    }
+
+    fn save(&self) -> Result<()> {
+        fs::create_dir_all(state_path(STATE_FOLDER, "")?)?;
+        let head_name = self.head_name.as_deref().unwrap_or("detached HEAD");
+        write_state("head-name", &format!("{head_name}\n"))?;
+        write_state("onto", &format!("{}\n", self.onto))?;
+        write_state("orig-head", &format!("{}\n", self.orig_head))
+    }
+
+    fn remove(&self) -> Result<()> {
+        Ok(fs::remove_dir_all(state_path(STATE_FOLDER, "")?)?)
+    }
+}
+
+/// Staged or unstaged changes would be lost by the rebase
+fn check_clean(head: &str) -> Result<()> {
+    let index = read_index()?;
+    let index_map = index_files(&index);
+    let unmerged = index.entries.iter().any(|entry| entry.stage() != 0);
+    if unmerged || commit_files(Some(head))? != index_map {
+        bail!(
+            "cannot rebase: Your index contains uncommitted changes.\n\
+             Please commit or stash them."
+        );
+    }
+    if worktree_files(&index, &index_map)? != index_map {
+        bail!("cannot rebase: You have unstaged changes.\nPlease commit or stash them.");
+    }
+    Ok(())
+}
+
+fn comment_lines(text: &[u8]) -> Vec<u8> {
+    let mut out = Vec::new();
+    for line in text.split_inclusive(|c| *c == b'\n') {
+        let line = line.strip_suffix(b"\n").unwrap_or(line);
+        out.push(b'#');
+        if !line.is_empty() {
+            out.push(b' ');
+            out.extend(line);
+        }
+        out.push(b'\n');
+    }
+    out
+}
+
+/// Add the message of a squashed commit (commented out for `fixup`) to the combined message
+/// of the chain, returns the combined message with the comments stripped
+fn squash_message(item: &TodoItem, previous: &Commit, original: &Commit) -> Result<Vec<u8>> {
+    let fixups = read_state("current-fixups")?.unwrap_or_default();
+    let count = fixups.lines().count() + 2;
+    let mut text = match fs::read(state_path(STATE_FOLDER, "message-squash")?) {
+        Ok(text) if !fixups.is_empty() => {
+            let start = text.iter().position(|c| *c == b'\n').map_or(text.len(), |end| end + 1);
+            let header = format!("# This is a combination of {count} commits.\n");
+            let mut combined = header.into_bytes();
+            combined.extend(&text[start..]);
+            combined
+        }
+        _ => {
+            let mut combined = b"# This is a combination of 2 commits.\n\
+                                 # This is the 1st commit message:\n\n".to_vec();
+            combined.extend(previous.utf8_message());
+            combined
+        }
+    };
+    match item.command {
+        TodoCommand::Squash => {
+            text.extend(format!("\n# This is the commit message #{count}:\n\n").bytes());
+            text.extend(original.utf8_message());
+        }
+        _ => {
+            text.extend(format!("\n# The commit message #{count} will be skipped:\n\n").bytes());
+            text.extend(comment_lines(&original.utf8_message()));
+        }
+    }
+    fs::write(state_path(STATE_FOLDER, "message-squash")?, &text)?;
+    write_state("current-fixups", &format!("{fixups}{} {}\n", item.command.name(), item.arg))?;
+    Ok(text)
+}
+
+/// Commit the picked changes staged in the index, returns the output and whether the rebase
+/// stopped (`edit`, unless `resumed` after conflicts)
+fn record_item(item: &TodoItem, resumed: bool) -> Result<(String, bool)> {
+    let original = read_commit(&item.arg)?;
+    let head = head_commit()?;
+    let previous = read_commit(&head)?;
+    let name = if resumed { "continue" } else { item.command.name() };
+    let action = format!("rebase ({name})");
+
+    if item.command.melds() {
+        // Commits in the middle of the chain keep the combined message as it is
+        let mut message = squash_message(item, &previous, &original)?;
+        let mut edited = false;
+        let next = read_rebase_todo()?.into_iter().next().map(|next| next.command);
+        if !next.is_some_and(TodoCommand::melds) {
+            // The message of the whole chain is edited once, when it has a squash
+            let fixups = read_state("current-fixups")?.unwrap_or_default();
+            edited = fixups.lines().any(|line| line.starts_with("squash "));
+            if !edited {
+                message = strip_comments(&message);
+            }
+            remove_state("current-fixups")?;
+            remove_state("message-squash")?;
+        }
+        let author = previous.author.clone();
+        let sha = record(previous.parents.clone(), author, message, edited, &action)?;
+        return Ok((if edited { commit_summary(&sha, true)? } else { String::new() }, false));
+    }
+
+    // Commits made empty by changes already in the new base are dropped
+    let tree = write_index_tree(&read_index()?)?;
+    let parent_tree = match original.parents.first() {
+        Some(parent) => read_commit(parent)?.tree,
+        None => EMPTY_TREE_SHA.to_string(),
+    };
+    if tree == previous.tree && original.tree != parent_tree {
+        return Ok((String::new(), false));
+    }
+    let reword = item.command == TodoCommand::Reword;
+    let mut output = String::new();
+    if original.parents == [head.clone()] && !reword {
+        // Already on top of HEAD, the commit is kept as it is
+        let committer = signature("committer")?;
+        let log = format!("{action}: {}", subject(&original.message_text()));
+        refs::update_ref("HEAD", &item.arg, &committer, &log)?;
+    } else {
+        let message = original.utf8_message();
+        let sha = record(vec![head], original.author.clone(), message, reword, &action)?;
+        if reword || resumed {
+            output = commit_summary(&sha, reword)?;
+        }
+    }
+
+    if item.command == TodoCommand::Edit && !resumed {
+        write_state("amend", &format!("{}\n", head_commit()?))?;
+        output += &format!(
+            "Stopped at {}...  {}\nYou can amend the commit now, with\n\n  \
+             git commit --amend \n\nOnce you are satisfied with your changes, run\n\n  \
+             git rebase --continue\n",
+            abbrev(&item.arg),
+            subject(&original.message_text())
+        );
+        return Ok((output, true));
+    }
+    Ok((output, false))
+}
+
+/// Apply the changes of the commit on top of HEAD, returns the output and whether the rebase
+/// stopped (conflicts or `edit`)
+fn replay(item: &TodoItem) -> Result<(String, bool)> {
+    let commit = read_commit(&item.arg)?;
+    let short = abbrev(&item.arg);
+    let subject = subject(&commit.message_text());
+    // Changes of merges are taken against their first parent
+    let result = pick(TodoCommand::Pick, &item.arg, &commit, Some(1), &head_commit()?)?;
+
+    // Merge messages are only shown for conflicts
+    if !result.conflicts.is_empty() {
+        write_state("stopped-sha", &format!("{}\n", item.arg))?;
+        let output = result.messages + &format!(
+            "error: could not apply {short}... {subject}\n{CONFLICT_HINT}\
+             Could not apply {short}... {subject}\n"
+        );
+        return Ok((output, true));
+    }
+    record_item(item, false)
+}
+
+/// Run the todo list until it is done or a command stops the rebase
+fn run(state: &RebaseState, mut output: String) -> Result<String> {
+    loop {
+        let mut todo = read_rebase_todo()?;
+        if todo.is_empty() {
+            return Ok(output + &finish(state)?);
+        }
+        let item = todo.remove(0);
+        write_todo(&state_path(STATE_FOLDER, TODO_FILE)?, &todo, false)?;
+        let done = read_state("done")?.unwrap_or_default();
+        write_state("done", &(done + &item.line(false)?))?;
+
+        match item.command {
+            TodoCommand::Drop => {}
+            TodoCommand::Exec => {
+                output += &format!("Executing: {}\n", item.arg);
+                let result = Command::new("sh")
+                    .arg("-c")
+                    .arg(&item.arg)
+                    .current_dir(find_root_folder()?)
+                    .output()?;
+                output += &String::from_utf8_lossy(&result.stdout);
+                output += &String::from_utf8_lossy(&result.stderr);
+                if !result.status.success() {
+                    output += &format!(
+                        "warning: execution failed: {}\nYou can fix the problem, and then run\n\n  \
+                         git rebase --continue\n\n",
+                        item.arg
+                    );
+                    return Ok(output);
+                }
+            }
+            _ => {
+                let (text, stopped) = replay(&item)?;
+                output += &text;
+                if stopped {
+                    return Ok(output);
+                }
+            }
+        }
+    }
+}
+
+/// Move the branch to the rebased commits and check it out again
+fn finish(state: &RebaseState) -> Result<String> {
+    let head = head_commit()?;
+    let committer = signature("committer")?;
+    let name = match &state.head_name {
+        Some(name) => {
+            let message = format!("rebase (finish): {name} onto {}", state.onto);
+            refs::update_ref(name, &head, &committer, &message)?;
+            let branch = name.strip_prefix("refs/heads/").unwrap_or(name);
+            let message = format!("rebase (finish): returning to {name}");
+            refs::set_head(Some(branch), &head, &committer, &message)?;
+            name.clone()
+        }
+        None => "detached HEAD".to_string(),
+    };
+    state.remove()?;
+    Ok(format!("Successfully rebased and updated {name}.\n"))
+}
+
+/// Start rebasing HEAD onto the upstream commit
+pub fn start(upstream: &str, interactive: bool) -> Result<String> {
+    if Path::new(&state_path(STATE_FOLDER, "")?).exists() {
+        bail!(
+            "It seems that there is already a rebase-merge directory, and\n\
+             I wonder if you are in the middle of another rebase.  If that is the\n\
+             case, please try\n\tgit rebase (--continue | --abort | --skip)"
+        );
+    }
+    let head = head_commit()?;
+    let onto = resolve_revision(&format!("{upstream}^{{commit}}"))
+        .map_err(|_| anyhow!("invalid upstream '{upstream}'"))?;
+    check_clean(&head)?;
+    let branch = current_branch()?;
+    if !interactive && merge_bases(&head, &onto)?.contains(&onto) {
+        let name = branch.as_deref().unwrap_or("HEAD");
+        return Ok(format!("Current branch {name} is up to date.\n"));
+    }
+
+    // Commits of HEAD missing from the upstream, oldest first and without merges, changes
+    // already picked into the upstream are left out
+    let options = LogOptions { order: CommitOrder::Topo, ..Default::default() };
+    let commits = walk(&[format!("{onto}..{head}")], &options)?;
+    let upstream_patches = match commits.is_empty() {
+        true => HashSet::new(),
+        false => {
+            walk(&[format!("{head}..{onto}")], &options)?
+                .iter()
+                .filter(|entry| entry.commit.parents.len() < 2)
+                .map(|entry| patch_id(&entry.commit))
+                .collect::<Result<HashSet<String>>>()?
+        }
+    };
+    let mut output = String::new();
+    let mut todo: Vec<TodoItem> = Vec::new();
+    for entry in commits.into_iter().rev().filter(|entry| entry.commit.parents.len() < 2) {
+        if !upstream_patches.is_empty() && upstream_patches.contains(&patch_id(&entry.commit)?) {
+            let short = abbrev(&entry.sha);
+            output += &format!("warning: skipped previously applied commit {short}\n");
+            continue;
+        }
+        todo.push(TodoItem { command: TodoCommand::Pick, arg: entry.sha });
+    }
+    if !output.is_empty() {
+        output += "hint: use --reapply-cherry-picks to include skipped commits\n\
+            hint: Disable this message with \"git config advice.skippedCherryPicks false\"\n";
+    }
+
+    let state = RebaseState {
+        head_name: branch.map(|branch| format!("refs/heads/{branch}")),
+        onto: onto.clone(),
+        orig_head: head.clone(),
+    };
+    state.save()?;
+    if interactive {
+        write_state("interactive", "")?;
+        let mut text = String::new();
+        for item in &todo {
+            text += &item.line(true)?;
+        }
+        let count = todo.len();
+        let plural = if count == 1 { "" } else { "s" };
+        let (onto_short, head_short) = (abbrev(&onto), abbrev(&head));
+        text += &format!(
+            "\n# Rebase {onto_short}..{head_short} onto {onto_short} ({count} command{plural})\n"
+        );
+        write_state(TODO_FILE, &(text + TODO_HELP))?;
+        let edited = launch_editor(&state_path(STATE_FOLDER, TODO_FILE)?, true)
+            .and_then(|_| read_rebase_todo());
+        match edited {
+            Ok(items) if !items.is_empty() => todo = items,
+            Ok(_) => {
+                state.remove()?;
+                bail!("Nothing to do");
+            }
+            Err(err) => {
+                state.remove()?;
+                return Err(err);
+            }
+        }
+    }
+    write_todo(&state_path(STATE_FOLDER, TODO_FILE)?, &todo, false)?;
+
+    // Detach HEAD at the new base
+    let committer = signature("committer")?;
+    write_locked(&(find_root_folder()? + ".git/ORIG_HEAD"), format!("{head}\n"))?;
+    reset_hard(Some(&head), Some(&onto))?;
+    refs::set_head(None, &onto, &committer, &format!("rebase (start): checkout {upstream}"))?;
+    run(&state, output)
+}
+
+/// Commit the resolved conflicts (or the changes made after `edit`) and go on
+pub fn resume() -> Result<String> {
+    let state = RebaseState::load()?;
+    let index = read_index()?;
+    if index.entries.iter().any(|entry| entry.stage() != 0) {
+        bail!("You must edit all merge conflicts and then\nmark them as resolved using git add");
+    }
+
+    let mut output = String::new();
+    if let Some(amend) = read_state("amend")? {
+        // Changes staged after `edit` amend the commit unless the user already did
+        let head = head_commit()?;
+        let commit = read_commit(&head)?;
+        if head == amend.trim() && write_index_tree(&index)? != commit.tree {
+            let (parents, author) = (commit.parents.clone(), commit.author.clone());
+            let sha = record(parents, author, commit.utf8_message(), true, "rebase (continue)")?;
+            output = commit_summary(&sha, true)?;
+        }
+        remove_state("amend")?;
+    } else if read_state("stopped-sha")?.is_some() {
+        let done = parse_todo(&read_state("done")?.unwrap_or_default(), true)?;
+        if let Some(item) = done.last() {
+            output = record_item(item, true)?.0;
+        }
+    }
+    remove_state("stopped-sha")?;
+    run(&state, output)
+}
+
+/// Throw away the changes of the commit which failed to apply and go on
+pub fn skip() -> Result<String> {
+    let state = RebaseState::load()?;
+    let head = head_commit()?;
+    reset_hard(Some(&head), Some(&head))?;
+    remove_state("stopped-sha")?;
+    remove_state("amend")?;
+    run(&state, String::new())
+}
+
+/// Check out the branch as it was before the rebase
+pub fn abort() -> Result<String> {
+    let state = RebaseState::load()?;
+    reset_hard(read_head_commit()?.as_deref(), Some(&state.orig_head))?;
+    let committer = signature("committer")?;
+    let target = state.head_name.as_deref().unwrap_or(&state.orig_head);
+    let message = format!("rebase (abort): returning to {target}");
+    let branch = state.head_name.as_deref().and_then(|name| name.strip_prefix("refs/heads/"));
+    refs::set_head(branch, &state.orig_head, &committer, &message)?;
+    state.remove()?;
+    Ok(String::new())
+}
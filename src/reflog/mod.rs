@@ -0,0 +1,123 @@
+use crate::config::Config;
+use crate::diff::abbrev;
+use crate::history::ancestors;
+use crate::refs::{ self, read_ref, read_reflog, write_reflog, ZERO_SHA };
+use crate::revision::{ reflog_ref, resolve_revision };
+use crate::utility::date_util::approxidate;
+
+use anyhow::{ anyhow, Result };
+
+/// Default age of entries removed by `expire` (`gc.reflogExpire`)
+const DEFAULT_EXPIRE: &str = "90.days.ago";
+/// Default age of removed entries no longer reachable from the ref (`gc.reflogExpireUnreachable`)
+const DEFAULT_EXPIRE_UNREACHABLE: &str = "30.days.ago";
+
+/// Operation of the `reflog` command
+#[derive(Clone, Debug)]
+pub enum ReflogAction {
+    /// Show entries of the ref (HEAD by default) from the newest one
+    Show {
+        name: Option<String>,
+        max_count: Option<usize>,
+    },
+    /// Remove old entries of the refs (all reflogs with `all`)
+    Expire {
+        expire: Option<String>,
+        expire_unreachable: Option<String>,
+        all: bool,
+        names: Vec<String>,
+    },
+    /// Remove single entries given as `<ref>@{N}`
+    Delete {
+        entries: Vec<String>,
+    },
+}
+
+/// Show reflog entries as `<sha> <name>@{N}: <message>`
+pub fn show(name: Option<&str>, max_count: Option<usize>) -> Result<String> {
+    let name = name.unwrap_or("HEAD");
+    let entries = read_reflog(&reflog_ref(name)?)?;
+    let mut output = String::new();
+    for (index, entry) in entries.iter().rev().enumerate().take(max_count.unwrap_or(usize::MAX)) {
+        output += &format!("{} {name}@{{{index}}}: {}\n", abbrev(&entry.new), entry.message);
+    }
+    Ok(output)
+}
+
+/// Timestamp entries older than which are removed (`never` keeps all, `all` or `now` none)
+fn expiry_time(value: Option<&str>, config_name: &str, default: &str) -> Result<i64> {
+    let configured = match value {
+        Some(value) => value.to_string(),
+        None => Config::load()?.get(config_name)?.unwrap_or_else(|| default.to_string()),
+    };
+    Ok(match configured.as_str() {
+        "never" | "false" => i64::MIN,
+        "all" | "now" => i64::MAX,
+        text => approxidate(text)?,
+    })
+}
+
+/// Remove entries older than `expire`, or than `expire_unreachable` when their commits are not
+/// reachable from the current value of the ref
+pub fn expire(
+    expire: Option<&str>,
+    expire_unreachable: Option<&str>,
+    all: bool,
+    names: &[String]
+) -> Result<String> {
+    let expire = expiry_time(expire, "gc.reflogexpire", DEFAULT_EXPIRE)?;
+    let unreachable = expiry_time(
+        expire_unreachable,
+        "gc.reflogexpireunreachable",
+        DEFAULT_EXPIRE_UNREACHABLE
+    )?;
+    let names = match all {
+        true => refs::list_reflogs()?,
+        false => names.iter().map(|name| reflog_ref(name)).collect::<Result<Vec<_>>>()?,
+    };
+
+    for name in names {
+        // HEAD entries only need to be reachable from any ref
+        let tips = match name.as_str() {
+            "HEAD" => refs::list_refs("refs/")?.into_iter().map(|(_, sha)| sha).collect(),
+            _ => Vec::new(),
+        };
+        let commits: Vec<String> = tips
+            .into_iter()
+            .chain(read_ref(&name)?)
+            .filter_map(|sha| resolve_revision(&format!("{sha}^{{commit}}")).ok())
+            .collect();
+        let reachable = ancestors(&commits)?;
+        let is_reachable = |sha: &String| sha == ZERO_SHA || reachable.contains(sha);
+
+        let mut entries = read_reflog(&name)?;
+        entries.retain(|entry| {
+            let time = entry.committer.time;
+            let kept = is_reachable(&entry.old) && is_reachable(&entry.new);
+            time >= expire && (time >= unreachable || kept)
+        });
+        write_reflog(&name, &entries)?;
+    }
+    Ok(String::new())
+}
+
+/// Delete entries given as `<ref>@{N}` one after another (later numbers count without the
+/// entries deleted before)
+pub fn delete(specs: &[String]) -> Result<String> {
+    for spec in specs {
+        let (name, index) = spec
+            .strip_suffix('}')
+            .and_then(|rest| rest.split_once("@{"))
+            .ok_or_else(|| anyhow!("not a reflog: {spec}"))?;
+        let index: usize = index.parse().map_err(|_| anyhow!("invalid reflog entry: {spec}"))?;
+        let name = reflog_ref(name)?;
+        let mut entries = read_reflog(&name)?;
+        let position = entries
+            .len()
+            .checked_sub(index + 1)
+            .ok_or_else(|| anyhow!("reflog entry '{spec}' does not exist"))?;
+        entries.remove(position);
+        write_reflog(&name, &entries)?;
+    }
+    Ok(String::new())
+}
@@ -0,0 +1,504 @@
+use crate::config::Config;
+use crate::object::{ read_object, Object, Signature };
+use crate::utility::fs_utility::*;
+
+use anyhow::{ anyhow, bail, Result };
+use std::fs;
+use std::io::prelude::*;
+use std::path::Path;
+
+/// Old value in reflog for created refs
+pub const ZERO_SHA: &str = "0000000000000000000000000000000000000000";
+
+/// Selection and format of `show-ref` output
+#[derive(Clone, Debug, Default)]
+pub struct ShowRefOptions {
+    pub heads: bool,
+    pub tags: bool,
+    /// Show HEAD too (`--head`)
+    pub head: bool,
+    /// Add `<name>^{}` lines with the objects annotated tags point to
+    pub dereference: bool,
+    pub hash_only: bool,
+    /// Patterns are full ref names that must exist
+    pub verify: bool,
+    pub quiet: bool,
+}
+
+/// Longest chain of symbolic refs that is followed (same limit as in git)
+const MAX_SYMREF_DEPTH: usize = 5;
+
+/// Header written to `packed-refs`, peeled lines follow every annotated tag
+const PACKED_REFS_HEADER: &str = "# pack-refs with: peeled fully-peeled sorted \n";
+
+/// Line of `packed-refs`, `peeled` is the object an annotated tag points to (`^<sha>` line)
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PackedRef {
+    pub name: String,
+    pub sha: String,
+    pub peeled: Option<String>,
+}
+
+/// Stored value of a ref before symbolic refs are followed
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum RefValue {
+    Direct(String),
+    Symbolic(String),
+}
+
+fn is_sha(text: &str) -> bool {
+    text.len() == 40 && text.chars().all(|c| c.is_ascii_hexdigit())
+}
+
+/// Refs stored in `.git/packed-refs`, empty when the file does not exist
+pub fn packed_refs() -> Result<Vec<PackedRef>> {
+    let path = find_root_folder()? + ".git/packed-refs";
+    let Ok(text) = fs::read_to_string(path) else {
+        return Ok(Vec::new());
+    };
+    let mut refs: Vec<PackedRef> = Vec::new();
+    for line in text.lines() {
+        if line.starts_with('#') || line.is_empty() {
+            continue;
+        }
+        if let Some(peeled) = line.strip_prefix('^') {
+            match refs.last_mut() {
+                Some(last) if is_sha(peeled) => last.peeled = Some(peeled.to_string()),
+                _ => bail!("unexpected line in packed-refs: '{line}'"),
+            }
+            continue;
+        }
+        match line.split_once(' ') {
+            Some((sha, name)) if is_sha(sha) => refs.push(PackedRef {
+                name: name.to_string(),
+                sha: sha.to_string(),
+                peeled: None,
+            }),
+            _ => bail!("unexpected line in packed-refs: '{line}'"),
+        }
+    }
+    Ok(refs)
+}
+
+/// Rewrite `.git/packed-refs` without the ref, returns whether it was packed
+fn remove_packed_ref(name: &str) -> Result<bool> {
+    let path = find_root_folder()? + ".git/packed-refs";
+    if !Path::new(&path).exists() {
+        return Ok(false);
+    }
+    // Lock is taken before reading, so concurrent writers cannot be lost
+    let lock = LockFile::acquire(&path)?;
+    let mut refs = packed_refs()?;
+    let count = refs.len();
+    refs.retain(|packed| packed.name != name);
+    if refs.len() == count {
+        return Ok(false);
+    }
+
+    let mut text = PACKED_REFS_HEADER.to_string();
+    for packed in &refs {
+        text += &format!("{} {}\n", packed.sha, packed.name);
+        if let Some(peeled) = &packed.peeled {
+            text += &format!("^{peeled}\n");
+        }
+    }
+    lock.commit(text)?;
+    Ok(true)
+}
+
+/// Value of the ref itself: loose file first, then `packed-refs`
+fn read_raw(name: &str) -> Result<Option<RefValue>> {
+    let path = find_root_folder()? + ".git/" + name;
+    if Path::new(&path).is_file() {
+        let contents = fs::read_to_string(&path)?;
+        let contents = contents.trim_end();
+        if let Some(target) = contents.strip_prefix("ref: ") {
+            return Ok(Some(RefValue::Symbolic(target.to_string())));
+        }
+        if !is_sha(contents) {
+            bail!("Incorrect ref contents in '{name}'!");
+        }
+        return Ok(Some(RefValue::Direct(contents.to_string())));
+    }
+    Ok(
+        packed_refs()?
+            .into_iter()
+            .find(|packed| packed.name == name)
+            .map(|packed| RefValue::Direct(packed.sha))
+    )
+}
+
+/// Follow symbolic refs, returns the final ref name with its object (None when unborn)
+pub fn resolve_ref(name: &str) -> Result<(String, Option<String>)> {
+    let mut current = name.to_string();
+    let mut visited: Vec<String> = Vec::new();
+    while visited.len() <= MAX_SYMREF_DEPTH {
+        match read_raw(&current)? {
+            Some(RefValue::Symbolic(target)) => {
+                if visited.contains(&target) || target == name {
+                    bail!("Symbolic ref loop detected at '{target}' while resolving '{name}'");
+                }
+                visited.push(std::mem::replace(&mut current, target));
+            }
+            Some(RefValue::Direct(sha)) => {
+                return Ok((current, Some(sha)));
+            }
+            None => {
+                return Ok((current, None));
+            }
+        }
+    }
+    bail!("Too deep symbolic ref chain for '{name}'")
+}
+
+/// Object the ref points to after following symbolic refs, None for missing (unborn) refs
+pub fn read_ref(name: &str) -> Result<Option<String>> {
+    Ok(resolve_ref(name)?.1)
+}
+
+/// Ref the symbolic ref points to (e.g. `refs/heads/master` for HEAD), None when detached
+pub fn symbolic_target(name: &str) -> Result<Option<String>> {
+    Ok(match read_raw(name)? {
+        Some(RefValue::Symbolic(target)) => Some(target),
+        _ => None,
+    })
+}
+
+/// Object an annotated tag ref finally points to, None for refs to other objects
+pub fn peeled_ref(name: &str, sha: &str) -> Result<Option<String>> {
+    // Packed refs store the peeled value, it is valid while the ref was not changed
+    let packed = packed_refs()?.into_iter().find(|packed| packed.name == name && packed.sha == sha);
+    if let Some(peeled) = packed.and_then(|packed| packed.peeled) {
+        return Ok(Some(peeled));
+    }
+    let mut current = sha.to_string();
+    while let Object::Tag(tag) = read_object(&current)? {
+        current = tag.object;
+    }
+    Ok((current != sha).then_some(current))
+}
+
+/// Short branch name HEAD points to, None when detached
+pub fn current_branch() -> Result<Option<String>> {
+    Ok(
+        symbolic_target("HEAD")?.map(|target| {
+            target.strip_prefix("refs/heads/").unwrap_or(&target).to_string()
+        })
+    )
+}
+
+/// Append line to the reflog of the ref
+pub fn append_reflog(
+    name: &str,
+    old: &str,
+    new: &str,
+    committer: &Signature,
+    message: &str
+) -> Result<()> {
+    let path = find_root_folder()? + ".git/logs/" + name;
+    if let Some(parent) = Path::new(&path).parent() {
+        fs::create_dir_all(parent)?;
+    }
+    // Message is kept on one line, without message there is no tab
+    let message = match message.lines().next() {
+        Some(line) if !line.is_empty() => format!("\t{line}"),
+        _ => String::new(),
+    };
+    let mut log = fs::OpenOptions::new().create(true).append(true).open(path)?;
+    log.write_all(format!("{old} {new} {committer}{message}\n").as_bytes())?;
+    Ok(())
+}
+
+/// Line of a reflog, `old` is ZERO_SHA for created refs
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ReflogEntry {
+    pub old: String,
+    pub new: String,
+    pub committer: Signature,
+    pub message: String,
+}
+
+/// Entries of the reflog from the oldest one, missing log has none
+pub fn read_reflog(name: &str) -> Result<Vec<ReflogEntry>> {
+    let path = find_root_folder()? + ".git/logs/" + name;
+    let Ok(text) = fs::read_to_string(path) else {
+        return Ok(Vec::new());
+    };
+    let mut entries: Vec<ReflogEntry> = Vec::new();
+    for line in text.lines() {
+        let (head, message) = line.split_once('\t').unwrap_or((line, ""));
+        let fields: Vec<&str> = head.splitn(3, ' ').collect();
+        let [old, new, committer] = fields[..] else {
+            bail!("Incorrect line in reflog of '{name}': '{line}'");
+        };
+        entries.push(ReflogEntry {
+            old: old.to_string(),
+            new: new.to_string(),
+            committer: Signature::parse(committer.as_bytes())?,
+            message: message.to_string(),
+        });
+    }
+    Ok(entries)
+}
+
+/// Replace the reflog with the entries (used when expiring or deleting them)
+pub fn write_reflog(name: &str, entries: &[ReflogEntry]) -> Result<()> {
+    // Rewritten lines always have the tab, even without message
+    let mut text = String::new();
+    for entry in entries {
+        text += &format!("{} {} {}\t{}\n", entry.old, entry.new, entry.committer, entry.message);
+    }
+    write_locked(&(find_root_folder()? + ".git/logs/" + name), text)
+}
+
+/// Names of all refs with a reflog (HEAD first)
+pub fn list_reflogs() -> Result<Vec<String>> {
+    let logs = find_root_folder()? + ".git/logs/";
+    let mut names: Vec<String> = Vec::new();
+    let mut folders: Vec<String> = vec!["refs".to_string()];
+    while let Some(folder) = folders.pop() {
+        let Ok(entries) = fs::read_dir(format!("{logs}{folder}")) else {
+            continue;
+        };
+        for entry in entries {
+            let entry = entry?;
+            let name = format!("{folder}/{}", entry.file_name().to_string_lossy());
+            if entry.file_type()?.is_dir() {
+                folders.push(name);
+            } else if !name.ends_with(".lock") {
+                names.push(name);
+            }
+        }
+    }
+    names.sort();
+    if Path::new(&format!("{logs}HEAD")).is_file() {
+        names.insert(0, "HEAD".to_string());
+    }
+    Ok(names)
+}
+
+/// Whether updates of the ref are logged (`core.logAllRefUpdates`, existing logs are kept)
+fn should_log(name: &str) -> Result<bool> {
+    if Path::new(&(find_root_folder()? + ".git/logs/" + name)).is_file() {
+        return Ok(true);
+    }
+    let setting = Config::load()?.get("core.logallrefupdates")?;
+    Ok(match setting.as_deref() {
+        Some("always") => true,
+        Some("false") => false,
+        // Stash entries live in the reflog only
+        _ =>
+            name == "HEAD" ||
+                name == "refs/stash" ||
+                ["refs/heads/", "refs/remotes/", "refs/notes/"]
+                    .iter()
+                    .any(|prefix| name.starts_with(prefix)),
+    })
+}
+
+/// Point ref to the new commit (HEAD updates the branch it points to) and log the change
+pub fn update_ref(name: &str, new: &str, committer: &Signature, message: &str) -> Result<()> {
+    update_ref_checked(name, new, None, false, committer, message)
+}
+
+/// Check the current value of the locked ref, `expected` ZERO_SHA means it must not exist
+fn check_old_value(name: &str, current: Option<&str>, expected: Option<&str>) -> Result<()> {
+    match (expected, current) {
+        (None, _) => Ok(()),
+        (Some(ZERO_SHA), None) => Ok(()),
+        (Some(ZERO_SHA), Some(_)) => bail!("cannot lock ref '{name}': reference already exists"),
+        (Some(expected), Some(current)) if expected == current => Ok(()),
+        (Some(expected), Some(current)) => {
+            bail!("cannot lock ref '{name}': is at {current} but expected {expected}")
+        }
+        (Some(_), None) => bail!("cannot lock ref '{name}': unable to resolve reference '{name}'"),
+    }
+}
+
+/// Update ref under its lock when it still has the expected value, symbolic refs are followed
+/// unless `no_deref` replaces the ref itself
+pub fn update_ref_checked(
+    name: &str,
+    new: &str,
+    expected: Option<&str>,
+    no_deref: bool,
+    committer: &Signature,
+    message: &str
+) -> Result<()> {
+    if !is_sha(new) {
+        bail!("Cannot update ref '{name}' to '{new}'");
+    }
+    let target = if no_deref { name.to_string() } else { resolve_ref(name)?.0 };
+    let path = find_root_folder()? + ".git/" + &target;
+    let lock = LockFile::acquire(&path)?;
+    let old = read_ref(&target)?;
+    if let Err(err) = check_old_value(&target, old.as_deref(), expected) {
+        drop(lock);
+        remove_empty_parents(&path);
+        return Err(err);
+    }
+    lock.commit(format!("{new}\n"))?;
+
+    // Changes of the current branch are also recorded in the HEAD log
+    let old = old.unwrap_or_else(|| ZERO_SHA.to_string());
+    if should_log(&target)? {
+        append_reflog(&target, &old, new, committer, message)?;
+    }
+    if target != "HEAD" && symbolic_target("HEAD")?.as_deref() == Some(target.as_str()) {
+        append_reflog("HEAD", &old, new, committer, message)?;
+    }
+    Ok(())
+}
+
+/// Point ref to the object without logging (tags have no reflog)
+pub fn write_ref(name: &str, sha: &str) -> Result<()> {
+    write_locked(&(find_root_folder()? + ".git/" + name), format!("{sha}\n"))
+}
+
+/// Point HEAD to the branch, or detach it at the commit, and log the move in the HEAD reflog
+pub fn set_head(
+    branch: Option<&str>,
+    sha: &str,
+    committer: &Signature,
+    message: &str
+) -> Result<()> {
+    let old = read_ref("HEAD")?.unwrap_or_else(|| ZERO_SHA.to_string());
+    let contents = match branch {
+        Some(branch) => format!("ref: refs/heads/{branch}\n"),
+        None => format!("{sha}\n"),
+    };
+    write_locked(&(find_root_folder()? + ".git/HEAD"), contents)?;
+    append_reflog("HEAD", &old, sha, committer, message)
+}
+
+/// Check ref name rules of `git check-ref-format`
+pub fn is_valid_ref_name(name: &str) -> bool {
+    let valid_component = |component: &str| {
+        !component.is_empty() && !component.starts_with('.') && !component.ends_with(".lock")
+    };
+    !name.is_empty() &&
+        name != "@" &&
+        !name.contains("..") &&
+        !name.contains("@{") &&
+        !name.ends_with('.') &&
+        !name.chars().any(|c| c.is_ascii_control() || " ~^:?*[\\".contains(c)) &&
+        name.split('/').all(valid_component)
+}
+
+/// Refs under the prefix (e.g. `refs/heads/`) with their commits, sorted by name
+pub fn list_refs(prefix: &str) -> Result<Vec<(String, String)>> {
+    let git_dir = find_root_folder()? + ".git/";
+    let mut names: Vec<String> = Vec::new();
+    let mut folders: Vec<String> = vec![prefix.trim_end_matches('/').to_string()];
+    while let Some(folder) = folders.pop() {
+        let Ok(entries) = fs::read_dir(format!("{git_dir}{folder}")) else {
+            continue;
+        };
+        for entry in entries {
+            let entry = entry?;
+            let name = format!("{folder}/{}", entry.file_name().to_string_lossy());
+            if entry.file_type()?.is_dir() {
+                folders.push(name);
+            } else if !name.ends_with(".lock") {
+                names.push(name);
+            }
+        }
+    }
+    // Loose refs take precedence over the packed ones
+    let mut refs: Vec<(String, String)> = Vec::new();
+    for name in &names {
+        if let Some(sha) = read_ref(name)? {
+            refs.push((name.clone(), sha));
+        }
+    }
+    for packed in packed_refs()? {
+        if packed.name.starts_with(prefix) && !names.contains(&packed.name) {
+            refs.push((packed.name, packed.sha));
+        }
+    }
+    refs.sort();
+    Ok(refs)
+}
+
+/// Remove folders left empty after deleting the file (`refs/<kind>` folders are kept)
+fn remove_empty_parents(path: &str) {
+    let mut folder = Path::new(path).parent();
+    while let Some(current) = folder {
+        let top =
+            current.ends_with("refs") || current.parent().is_some_and(|p| p.ends_with("refs"));
+        if top || fs::remove_dir(current).is_err() {
+            break;
+        }
+        folder = current.parent();
+    }
+}
+
+/// Remove loose and packed copies of the ref, returns whether any existed
+fn remove_ref_storage(name: &str) -> Result<bool> {
+    let path = find_root_folder()? + ".git/" + name;
+    let loose = fs::remove_file(&path).is_ok();
+    if loose {
+        remove_empty_parents(&path);
+    }
+    Ok(remove_packed_ref(name)? || loose)
+}
+
+/// Delete ref together with its reflog
+pub fn delete_ref(name: &str) -> Result<()> {
+    delete_ref_checked(name, None)
+}
+
+/// Delete ref when it still has the expected value
+pub fn delete_ref_checked(name: &str, expected: Option<&str>) -> Result<()> {
+    let git_dir = find_root_folder()? + ".git/";
+    let path = format!("{git_dir}{name}");
+    let lock = LockFile::acquire(&path)?;
+    let result = check_old_value(name, read_ref(name)?.as_deref(), expected).and_then(|_| {
+        match remove_ref_storage(name)? {
+            true => Ok(()),
+            false => Err(anyhow!("Cannot delete ref '{name}'")),
+        }
+    });
+    // Folders are removed only after the lock file is gone
+    drop(lock);
+    remove_empty_parents(&path);
+    result?;
+
+    let log = format!("{git_dir}logs/{name}");
+    if fs::remove_file(&log).is_ok() {
+        remove_empty_parents(&log);
+    }
+    Ok(())
+}
+
+/// Move ref and its reflog to a new name, HEAD follows when it points to the old name
+pub fn rename_ref(old: &str, new: &str, committer: &Signature, message: &str) -> Result<()> {
+    let git_dir = find_root_folder()? + ".git/";
+    let head_target = symbolic_target("HEAD")?;
+
+    if let Some(sha) = read_ref(old)? {
+        let old_log = format!("{git_dir}logs/{old}");
+        let new_log = format!("{git_dir}logs/{new}");
+        let had_log = Path::new(&old_log).exists();
+        if had_log {
+            if let Some(parent) = Path::new(&new_log).parent() {
+                fs::create_dir_all(parent)?;
+            }
+            fs::rename(&old_log, &new_log)?;
+            remove_empty_parents(&old_log);
+        }
+
+        if new != old {
+            remove_ref_storage(old)?;
+        }
+        write_locked(&format!("{git_dir}{new}"), format!("{sha}\n"))?;
+        if had_log {
+            append_reflog(new, &sha, &sha, committer, message)?;
+        }
+    }
+
+    if head_target.as_deref() == Some(old) {
+        write_locked(&(git_dir + "HEAD"), format!("ref: {new}\n"))?;
+    }
+    Ok(())
+}
@@ -32,6 +32,11 @@ enum ParsedObject {
         obj_ref: String,
         delta: Vec<u8>,
     },
+    /// Delta against the object starting at the offset in the PACK (used by local packs)
+    OffsetDelta {
+        base_offset: usize,
+        delta: Vec<u8>,
+    },
     Unsupported(u8),
 }
 
@@ -62,6 +67,7 @@ pub fn validate_and_get_heart(mut bytes: Vec<u8>) -> Result<Vec<UnpackedObject>>
     // Separate main part and 20 bytes checksum at the end
     let sha_bytes = bytes.split_off(bytes.len() - 20);
     let mut buff: Bytes = Bytes::from(bytes);
+    let pack_len = buff.len();
 
     // Compare Checksum
     let checksum = hex::encode(&sha_bytes);
@@ -88,61 +94,65 @@ pub fn validate_and_get_heart(mut bytes: Vec<u8>) -> Result<Vec<UnpackedObject>>
 
     let mut unpacked_objects: Vec<UnpackedObject> = Vec::new();
     let mut ref_to_id: HashMap<String, usize> = HashMap::new();
+    let mut offset_to_id: HashMap<usize, usize> = HashMap::new();
     // Go through all objects in PACK
     for _obj_id in 0..object_number {
-        let obj: ParsedObject = parse_object(&mut buff)?;
-        match obj {
+        offset_to_id.insert(pack_len - buff.len(), _obj_id as usize);
+        let obj: ParsedObject = parse_object(&mut buff, pack_len)?;
+        let (obj_id, delta) = match obj {
             ParsedObject::Unsupported(id) => {
                 bail!("Unsupported object type! ID: {id}");
             }
             ParsedObject::Default { obj_type, hash, obj_data } => {
                 ref_to_id.insert(hash.clone(), _obj_id as usize);
                 unpacked_objects.push(UnpackedObject { obj_type, hash, contents: obj_data });
+                continue;
             }
             ParsedObject::Delta { obj_type: _ot, obj_ref, delta } => {
                 let obj_id: usize = *ref_to_id
                     .get(&obj_ref)
                     .ok_or_else(|| anyhow!("No such object in list: {}!", obj_ref))?;
+                (obj_id, delta)
+            }
+            ParsedObject::OffsetDelta { base_offset, delta } => {
+                let obj_id: usize = *offset_to_id
+                    .get(&base_offset)
+                    .ok_or_else(|| anyhow!("No object at offset {base_offset} in PACK!"))?;
+                (obj_id, delta)
+            }
+        };
 
-                // println!("{}: {obj_ref}\n{delta:?}", String::from_utf8(_ot.clone())?);
-
-                // Prepare data to apply delta
-                let mut dlt_iter: Bytes = Bytes::from(delta);
-                let _: usize = get_delta_size(&mut dlt_iter); // Skip source size
-                let target_size: usize = get_delta_size(&mut dlt_iter);
-                let referenced_object = unpacked_objects
-                    .get(obj_id)
-                    .ok_or_else(|| anyhow!("Expected to find object {obj_ref} by id {obj_id}"))?;
-                let refered_object_data: Bytes = Bytes::from(referenced_object.contents.clone());
-
-                // Apply delta and store new object
-                let updated_data: Vec<u8> = apply_delta(
-                    &mut dlt_iter,
-                    &refered_object_data,
-                    target_size
-                )?;
-                let obj_type = referenced_object.obj_type.clone();
-
-                let hash = other_util::get_hash_from_data(
-                    other_util::add_data_prefix(&obj_type, updated_data.clone()).as_slice()
-                );
+        // Prepare data to apply delta
+        let mut dlt_iter: Bytes = Bytes::from(delta);
+        let _: usize = get_delta_size(&mut dlt_iter); // Skip source size
+        let target_size: usize = get_delta_size(&mut dlt_iter);
+        let referenced_object = unpacked_objects
+            .get(obj_id)
+            .ok_or_else(|| anyhow!("Expected to find object by id {obj_id}"))?;
+        let refered_object_data: Bytes = Bytes::from(referenced_object.contents.clone());
 
-                ref_to_id.insert(hash.clone(), _obj_id as usize);
-                unpacked_objects.push(UnpackedObject { obj_type, hash, contents: updated_data });
-            }
-        }
+        // Apply delta and store new object
+        let updated_data: Vec<u8> = apply_delta(&mut dlt_iter, &refered_object_data, target_size)?;
+        let obj_type = referenced_object.obj_type.clone();
+
+        let hash = other_util::get_hash_from_data(
+            other_util::add_data_prefix(&obj_type, updated_data.clone()).as_slice()
+        );
+
+        ref_to_id.insert(hash.clone(), _obj_id as usize);
+        unpacked_objects.push(UnpackedObject { obj_type, hash, contents: updated_data });
     }
 
     Ok(unpacked_objects)
 }
 
-/// Parse single object
-fn parse_object(buff: &mut Bytes) -> Result<ParsedObject> {
+/// Parse single object (`pack_len` is the length of the whole PACK, to locate offset bases)
+fn parse_object(buff: &mut Bytes, pack_len: usize) -> Result<ParsedObject> {
+    let start = pack_len - buff.len();
     let (_obj_size, obj_type_id) = get_size_and_typeid(buff)?;
     let obj_type: Vec<u8> = OBJ_TYPES.get(obj_type_id as usize)
         .ok_or_else(|| anyhow!("Unexpected type id in PACK: {}", obj_type_id))?
         .to_vec();
-    println!("{obj_type_id}");
 
     if (1..=4).contains(&obj_type_id) {
         // Try to decompress and drop consumed data
@@ -169,6 +179,21 @@ fn parse_object(buff: &mut Bytes) -> Result<ParsedObject> {
         buff.advance(consumed_amt);
 
         Ok(ParsedObject::Delta { obj_type, obj_ref: hash, delta: decoded_data.to_vec() })
+    } else if obj_type_id == 6 {
+        // Negative offset of the base: big-endian groups of 7 bits, each continuation adds one
+        let mut byte: u8 = buff.get_u8();
+        let mut offset: usize = (byte & 0b01111111_u8) as usize;
+        while (byte & 0b10000000_u8) != 0 {
+            byte = buff.get_u8();
+            offset = ((offset + 1) << 7) | ((byte & 0b01111111_u8) as usize);
+        }
+        let base_offset = start
+            .checked_sub(offset)
+            .ok_or_else(|| anyhow!("Incorrect delta offset in PACK: {offset}"))?;
+        let (consumed_amt, decoded_data) = decompress_all(buff.clone())?;
+        buff.advance(consumed_amt);
+
+        Ok(ParsedObject::OffsetDelta { base_offset, delta: decoded_data.to_vec() })
     } else {
         Ok(ParsedObject::Unsupported(obj_type_id))
     }
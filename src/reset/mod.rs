@@ -0,0 +1,232 @@
+use crate::checkout::{ commit_files, reset_hard, stage_files, update_worktree, Update };
+use crate::diff::{ abbrev, compare_file_maps, format, index_files, matches_pathspec, FileMap };
+use crate::diff::{ flatten_tree, worktree_files };
+use crate::history::pretty::subject;
+use crate::index::{ read_index, write_index };
+use crate::merge;
+use crate::object::read_commit;
+use crate::refs;
+use crate::revision::{ read_head_commit, resolve_commit, resolve_tree_ish };
+use crate::sequencer;
+use crate::utility::fs_utility::{ find_root_folder, write_locked };
+use crate::utility::other_util::signature;
+
+use anyhow::{ bail, Result };
+use std::collections::BTreeMap;
+use std::path::Path;
+
+/// What `reset` updates besides HEAD
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ResetMode {
+    /// Only move HEAD
+    Soft,
+    /// Move HEAD and reset the index
+    Mixed,
+    /// Move HEAD, reset the index and the working tree
+    Hard,
+}
+
+impl ResetMode {
+    fn name(&self) -> &'static str {
+        match self {
+            ResetMode::Soft => "soft",
+            ResetMode::Mixed => "mixed",
+            ResetMode::Hard => "hard",
+        }
+    }
+}
+
+/// Options of `reset`
+#[derive(Clone, Debug, Default)]
+pub struct ResetOptions {
+    /// Mode given on the command line (mixed by default)
+    pub mode: Option<ResetMode>,
+    /// Commit followed by paths, the commit is optional
+    pub args: Vec<String>,
+    /// Paths given after `--`
+    pub paths: Vec<String>,
+}
+
+/// Options of `restore`
+#[derive(Clone, Debug, Default)]
+pub struct RestoreOptions {
+    /// Tree to take the files from (index for the working tree, HEAD for the index by default)
+    pub source: Option<String>,
+    /// Restore the index
+    pub staged: bool,
+    /// Restore the working tree
+    pub worktree: bool,
+    pub paths: Vec<String>,
+}
+
+/// Split `reset` arguments into the commit and the paths, the first argument is a commit when
+/// it resolves to one or when the paths are separated by `--`
+fn split_args(options: &ResetOptions) -> Result<(Option<String>, Vec<String>)> {
+    let Some((first, rest)) = options.args.split_first() else {
+        return Ok((None, options.paths.clone()));
+    };
+    if !options.paths.is_empty() {
+        if !rest.is_empty() {
+            bail!("Only one commit may be given before '--'");
+        }
+        return Ok((Some(first.clone()), options.paths.clone()));
+    }
+    if resolve_commit(first).is_ok() {
+        return Ok((Some(first.clone()), rest.to_vec()));
+    }
+
+    let root = find_root_folder()?;
+    let tracked = index_files(&read_index()?);
+    for path in &options.args {
+        let known = Path::new(&format!("{root}{path}")).exists() ||
+            tracked.keys().any(|file| matches_pathspec(file, std::slice::from_ref(path)));
+        if !known {
+            bail!(
+                "ambiguous argument '{path}': unknown revision or path not in the working tree."
+            );
+        }
+    }
+    Ok((None, options.args.clone()))
+}
+
+/// Tracked files differing between the index and the working tree (`M\t<path>` lines)
+fn unstaged_changes() -> Result<String> {
+    let index = read_index()?;
+    let staged = index_files(&index);
+    let worktree = worktree_files(&index, &staged)?;
+    let changes = format::name_status(&compare_file_maps(&staged, &worktree, true, &[]));
+    Ok(
+        if changes.is_empty() {
+            String::new()
+        } else {
+            format!("Unstaged changes after reset:\n{changes}")
+        }
+    )
+}
+
+/// Point HEAD at the commit, resetting the index (mixed) and the working tree (hard)
+fn reset_commit(name: &str, mode: ResetMode) -> Result<String> {
+    let head = read_head_commit()?;
+    // Resetting to HEAD of an unborn branch only empties the index
+    let target = match (head.as_ref(), name) {
+        (None, "HEAD") => None,
+        _ => Some(resolve_commit(name)?),
+    };
+    if mode == ResetMode::Soft && !merge::merge_heads()?.is_empty() {
+        bail!("Cannot do a soft reset in the middle of a merge.");
+    }
+
+    let output = match mode {
+        ResetMode::Soft => String::new(),
+        ResetMode::Mixed => {
+            stage_files(&commit_files(target.as_deref())?, &[])?;
+            unstaged_changes()?
+        }
+        ResetMode::Hard => {
+            reset_hard(head.as_deref(), target.as_deref())?;
+            match &target {
+                Some(sha) => {
+                    let message = subject(&read_commit(sha)?.message_text());
+                    format!("HEAD is now at {} {message}\n", abbrev(sha))
+                }
+                None => String::new(),
+            }
+        }
+    };
+
+    if let (Some(head), Some(target)) = (head, target) {
+        let root = find_root_folder()?;
+        write_locked(&format!("{root}.git/ORIG_HEAD"), format!("{head}\n"))?;
+        let committer = signature("committer")?;
+        refs::update_ref("HEAD", &target, &committer, &format!("reset: moving to {name}"))?;
+    }
+    merge::remove_state()?;
+    sequencer::remove_pick_heads()?;
+    Ok(output)
+}
+
+/// Reset HEAD to the commit, or unstage the paths (reset their index entries to the commit)
+pub fn reset(options: &ResetOptions) -> Result<String> {
+    let (commit, paths) = split_args(options)?;
+    if paths.is_empty() {
+        let name = commit.unwrap_or_else(|| "HEAD".to_string());
+        return reset_commit(&name, options.mode.unwrap_or(ResetMode::Mixed));
+    }
+
+    if let Some(mode @ (ResetMode::Soft | ResetMode::Hard)) = options.mode {
+        bail!("Cannot do {} reset with paths.", mode.name());
+    }
+    let files = match commit {
+        Some(commit) => commit_files(Some(&resolve_commit(&commit)?))?,
+        None => commit_files(read_head_commit()?.as_deref())?,
+    };
+    stage_files(&files, &paths)?;
+    unstaged_changes()
+}
+
+/// Restore files of the working tree and/or the index from the source
+pub fn restore(options: &RestoreOptions) -> Result<String> {
+    if options.paths.is_empty() {
+        bail!("you must specify path(s) to restore");
+    }
+    let index = read_index()?;
+    let source: FileMap = match &options.source {
+        Some(source) => flatten_tree(&resolve_tree_ish(source)?)?,
+        None if options.staged => commit_files(read_head_commit()?.as_deref())?,
+        None => {
+            let unmerged = index.entries
+                .iter()
+                .find(|entry| entry.stage() != 0 && matches_pathspec(&entry.path, &options.paths));
+            if let Some(entry) = unmerged {
+                bail!("path '{}' is unmerged", entry.path);
+            }
+            index_files(&index)
+        }
+    };
+
+    let staged = index_files(&index);
+    for spec in &options.paths {
+        let spec = std::slice::from_ref(spec);
+        let known = source
+            .keys()
+            .chain(staged.keys())
+            .any(|path| matches_pathspec(path, spec));
+        if !known {
+            bail!("pathspec '{}' did not match any file(s) known to git", spec[0]);
+        }
+    }
+
+    if options.worktree {
+        // Paths missing in the source are removed, unchanged files are left alone
+        let worktree = worktree_files(&index, &staged)?;
+        let updates: Vec<Update> = source
+            .keys()
+            .chain(staged.keys())
+            .filter(|path| matches_pathspec(path, &options.paths))
+            .map(|path| (path.clone(), source.get(path).cloned()))
+            .filter(|(path, new)| new.is_none() || worktree.get(path) != new.as_ref())
+            .collect::<BTreeMap<_, _>>()
+            .into_iter()
+            .collect();
+        let written = update_worktree(&updates)?;
+
+        // Entries of files written with their staged contents get fresh stat data
+        if !options.staged && !written.is_empty() {
+            let mut index = read_index()?;
+            for entry in index.entries.iter_mut().filter(|entry| entry.stage() == 0) {
+                let fresh = written
+                    .iter()
+                    .find(|new| new.path == entry.path && new.sha == entry.sha);
+                if let Some(fresh) = fresh.filter(|new| new.mode == entry.mode) {
+                    *entry = fresh.clone();
+                }
+            }
+            write_index(&index)?;
+        }
+    }
+    if options.staged {
+        stage_files(&source, &options.paths)?;
+    }
+    Ok(String::new())
+}
+
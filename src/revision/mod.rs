@@ -0,0 +1,266 @@
+use crate::index::read_index;
+use crate::object::{ self, read_commit, Object };
+use crate::refs::{ self, read_ref, ZERO_SHA };
+use crate::utility::date_util::approxidate;
+use crate::utility::fs_utility::*;
+
+use anyhow::{ anyhow, bail, Result };
+use std::fs;
+use std::path::Path;
+
+/// Places where a short ref name is looked up (same order as in git)
+const REF_RULES: [&str; 6] = [
+    "{}",
+    "refs/{}",
+    "refs/tags/{}",
+    "refs/heads/{}",
+    "refs/remotes/{}",
+    "refs/remotes/{}/HEAD",
+];
+
+/// Returns commit referenced by HEAD or None on unborn branch
+pub fn read_head_commit() -> Result<Option<String>> {
+    read_ref("HEAD")
+}
+
+/// Commit referenced by HEAD, failing on unborn branch
+pub fn head_commit() -> Result<String> {
+    read_head_commit()?.ok_or_else(|| anyhow!("Your current branch does not have any commits yet"))
+}
+
+/// Expand abbreviated object name to the full SHA
+fn expand_short_sha(short: &str) -> Result<Option<String>> {
+    if short.len() < 4 || short.len() > 40 || !short.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Ok(None);
+    }
+    let short = short.to_ascii_lowercase();
+    let folder = find_root_folder()? + ".git/objects/" + &short[..2];
+
+    let mut found: Vec<String> = Vec::new();
+    if let Ok(entries) = fs::read_dir(folder) {
+        for entry in entries.filter_map(|e| e.ok()) {
+            let name = entry.file_name().to_string_lossy().to_string();
+            if name.starts_with(&short[2..]) {
+                found.push(format!("{}{name}", &short[..2]));
+            }
+        }
+    }
+
+    match found.len() {
+        0 => Ok(None),
+        1 => Ok(found.pop()),
+        _ => bail!("Short object ID {short} is ambiguous"),
+    }
+}
+
+/// Full name of the ref whose reflog the name refers to (`@{N}` alone uses the current branch)
+pub fn reflog_ref(name: &str) -> Result<String> {
+    if name.is_empty() {
+        return Ok(refs::symbolic_target("HEAD")?.unwrap_or_else(|| "HEAD".to_string()));
+    }
+    let name = if name == "@" { "HEAD" } else { name };
+    let logs = find_root_folder()? + ".git/logs/";
+    for rule in REF_RULES {
+        let full = rule.replace("{}", name);
+        if Path::new(&format!("{logs}{full}")).is_file() || read_ref(&full)?.is_some() {
+            return Ok(full);
+        }
+    }
+    Err(anyhow!("Unknown revision '{name}'"))
+}
+
+/// Object selected from the reflog by `<ref>@{N}` (N-th prior value) or `<ref>@{<date>}`
+fn resolve_reflog(name: &str, selector: &str) -> Result<String> {
+    let full = reflog_ref(name)?;
+    let short = if name.is_empty() { full.trim_start_matches("refs/heads/") } else { name };
+    let entries = refs::read_reflog(&full)?;
+    if selector.starts_with('-') {
+        bail!("Previous branch syntax '@{{{selector}}}' is not supported");
+    }
+
+    // Like in git, large numbers are dates (seconds since the epoch)
+    if let Some(index) = selector.parse::<usize>().ok().filter(|index| *index < 100_000_000) {
+        // Entries are stored from the oldest, the one before the first is its old value
+        let first = entries.first().filter(|first| first.old != ZERO_SHA);
+        return match (entries.len().checked_sub(index + 1), first) {
+            (Some(position), _) => Ok(entries[position].new.clone()),
+            (None, Some(first)) if index == entries.len() => Ok(first.old.clone()),
+            _ => bail!("log for '{short}' only has {} entries", entries.len()),
+        };
+    }
+
+    // Latest value at the date, dates before the log use its first value
+    let time = approxidate(selector)?;
+    let first = entries.first().ok_or_else(|| anyhow!("log for '{short}' is empty"))?;
+    Ok(
+        match entries.iter().rev().find(|entry| entry.committer.time <= time) {
+            Some(entry) => entry.new.clone(),
+            None if first.old != ZERO_SHA => first.old.clone(),
+            None => first.new.clone(),
+        }
+    )
+}
+
+/// Resolve name without any suffixes into object SHA
+fn resolve_base(name: &str) -> Result<String> {
+    if let Some((name, selector)) = name.split_once("@{") {
+        let selector = selector
+            .strip_suffix('}')
+            .ok_or_else(|| anyhow!("Unclosed '@{{' in '{name}'"))?;
+        return resolve_reflog(name, selector);
+    }
+    let name = if name == "@" { "HEAD" } else { name };
+
+    for rule in REF_RULES {
+        if let Some(sha) = read_ref(&rule.replace("{}", name))? {
+            return Ok(sha);
+        }
+    }
+    if let Some(sha) = expand_short_sha(name)? {
+        return Ok(sha);
+    }
+
+    Err(anyhow!("Unknown revision '{name}'"))
+}
+
+/// Peel object (tags and commits) until it has requested type, empty type peels only tags
+fn peel_to(sha: &str, target: &str) -> Result<String> {
+    let mut sha = sha.to_string();
+    loop {
+        let obj_type = object::object_kind(&sha)?;
+        if obj_type == target || (target.is_empty() && obj_type != "tag") {
+            return Ok(sha);
+        }
+        sha = match (object::read_object(&sha)?, target) {
+            (Object::Tag(tag), _) => tag.object,
+            (Object::Commit(commit), "tree") => commit.tree,
+            _ => bail!("Object {sha} ({obj_type}) cannot be peeled to {target}"),
+        };
+    }
+}
+
+/// Position of the `:` starting a path (colons of reflog selectors like `@{10:00}` do not count)
+fn path_separator(spec: &str) -> Option<usize> {
+    let mut depth = 0;
+    for (position, c) in spec.char_indices() {
+        match c {
+            '{' => depth += 1,
+            '}' => depth -= 1,
+            ':' if depth == 0 => return Some(position),
+            _ => {}
+        }
+    }
+    None
+}
+
+/// Object at the path of the tree (the tree itself for an empty path)
+fn tree_path(tree: &str, path: &str, spec: &str) -> Result<String> {
+    let mut sha = tree.to_string();
+    for name in path.split('/').filter(|name| !name.is_empty()) {
+        let found = match object::read_object(&sha)? {
+            Object::Tree(tree) => {
+                tree.entries.into_iter().find(|entry| entry.name == name.as_bytes())
+            }
+            _ => None,
+        };
+        sha = found
+            .map(|entry| entry.sha)
+            .ok_or_else(|| anyhow!("path '{path}' does not exist in '{spec}'"))?;
+    }
+    Ok(sha)
+}
+
+/// Blob staged for the path (`:<path>`, `:<stage>:<path>`)
+fn staged_path(path: &str) -> Result<String> {
+    let (stage, path) = match path.split_once(':') {
+        Some((stage @ ("0" | "1" | "2" | "3"), path)) => (stage.parse::<u8>()?, path),
+        _ => (0, path),
+    };
+    read_index()?
+        .entries
+        .into_iter()
+        .find(|entry| entry.path == path && entry.stage() == stage)
+        .map(|entry| entry.sha)
+        .ok_or_else(|| anyhow!("path '{path}' does not exist (neither on disk nor in the index)"))
+}
+
+/// Resolve revision (<sha>, <short sha>, <ref>, with `^`, `^N`, `~N`, `^{type}` suffixes),
+/// refs may select older values from their reflog (`@{N}`, `@{<date>}`), `<rev>:<path>` names
+/// an object in the tree of the revision and `:<path>` a staged file
+pub fn resolve_revision(spec: &str) -> Result<String> {
+    if let Some(position) = path_separator(spec) {
+        let (rev, path) = (&spec[..position], &spec[position + 1..]);
+        return match rev {
+            "" => staged_path(path),
+            _ => tree_path(&peel_to(&resolve_revision(rev)?, "tree")?, path, rev),
+        };
+    }
+
+    // Reflog selectors (`@{...}`) may contain any characters
+    let selector_end = spec
+        .rfind("@{")
+        .and_then(|at| spec[at..].find('}').map(|close| at + close + 1))
+        .unwrap_or(0);
+    let base_end = spec[selector_end..]
+        .find(['^', '~'])
+        .map_or(spec.len(), |position| selector_end + position);
+    let mut sha = resolve_base(&spec[..base_end])?;
+
+    let mut rest = &spec[base_end..];
+    while !rest.is_empty() {
+        let op = rest.as_bytes()[0];
+        rest = &rest[1..];
+
+        // Peeling suffix: ^{type}
+        if op == b'^' && rest.starts_with('{') {
+            let close = rest.find('}').ok_or_else(|| anyhow!("Unclosed '{{' in '{spec}'"))?;
+            let target = &rest[1..close];
+            rest = &rest[close + 1..];
+            sha = match target {
+                "" | "commit" | "tree" | "blob" | "tag" => peel_to(&sha, target)?,
+                _ => bail!("Unknown peel target '{target}' in '{spec}'"),
+            };
+            continue;
+        }
+
+        // Numeric argument of ^ or ~ (defaults to 1)
+        let digits = rest
+            .find(|c: char| !c.is_ascii_digit())
+            .unwrap_or(rest.len());
+        let number: usize = if digits == 0 { 1 } else { rest[..digits].parse()? };
+        rest = &rest[digits..];
+
+        let commit = peel_to(&sha, "commit")?;
+        if op == b'^' {
+            if number == 0 {
+                sha = commit;
+                continue;
+            }
+            sha = read_commit(&commit)?.parents
+                .get(number - 1)
+                .ok_or_else(|| anyhow!("Revision '{spec}' has no parent {number}"))?
+                .clone();
+        } else {
+            sha = commit;
+            for _ in 0..number {
+                sha = read_commit(&sha)?.parents
+                    .first()
+                    .ok_or_else(|| anyhow!("Revision '{spec}' goes beyond root commit"))?
+                    .clone();
+            }
+        }
+    }
+
+    Ok(sha)
+}
+
+/// Resolve name of a commit (or of a tag pointing to one)
+pub fn resolve_commit(name: &str) -> Result<String> {
+    resolve_revision(&format!("{name}^{{commit}}"))
+        .map_err(|_| anyhow!("Not a valid commit name {name}"))
+}
+
+/// Resolve revision and peel it to a tree
+pub fn resolve_tree_ish(spec: &str) -> Result<String> {
+    peel_to(&resolve_revision(spec)?, "tree")
+}
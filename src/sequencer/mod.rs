@@ -0,0 +1,720 @@
+use crate::checkout::{ commit_files, reset_hard };
+use crate::config::{ set_value, Config, Scope };
+use crate::diff::{ abbrev, diff_trees, index_files, DiffOptions };
+use crate::diff::format::{ short_stat, summary };
+use crate::history::{ pretty::subject, walk, LogOptions };
+use crate::index::{ read_index, write_index_tree };
+use crate::merge::{ self, content::MergeLabels, merge_into_head, MergeResult };
+use crate::object::{ read_commit, write_object, Commit, Object, Signature };
+use crate::refs::{ self, current_branch };
+use crate::revision::{ head_commit, resolve_revision };
+use crate::utility::date_util::format_date;
+use crate::utility::fs_utility::{ state_path, write_locked };
+use crate::utility::other_util::{ cleanup_message, launch_editor, signature, strip_comments };
+
+use anyhow::{ anyhow, bail, Result };
+use std::fs;
+use std::io::IsTerminal;
+use std::path::Path;
+
+/// Folder keeping the commits still to pick when several were given
+const STATE_FOLDER: &str = "sequencer/";
+
+const NO_COMMIT_HINT: &str = "hint: after resolving the conflicts, mark the corrected paths\n\
+                              hint: with 'git add <paths>' or 'git rm <paths>'\n";
+
+/// Command replaying commits on top of HEAD
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PickKind {
+    /// Apply the changes of the commits
+    CherryPick,
+    /// Undo the changes of the commits
+    Revert,
+}
+
+impl PickKind {
+    fn name(&self) -> &'static str {
+        match self {
+            PickKind::CherryPick => "cherry-pick",
+            PickKind::Revert => "revert",
+        }
+    }
+
+    /// Command of the todo list
+    fn command(&self) -> TodoCommand {
+        match self {
+            PickKind::CherryPick => TodoCommand::Pick,
+            PickKind::Revert => TodoCommand::Revert,
+        }
+    }
+
+    /// File naming the commit the command stopped on
+    fn head_file(&self) -> &'static str {
+        match self {
+            PickKind::CherryPick => "CHERRY_PICK_HEAD",
+            PickKind::Revert => "REVERT_HEAD",
+        }
+    }
+}
+
+/// Options of `cherry-pick` and `revert`
+#[derive(Clone, Debug, Default)]
+pub struct PickOptions {
+    /// Parent (from 1) the changes of merge commits are taken against (`-m`)
+    pub mainline: Option<usize>,
+    /// Add `(cherry picked from commit ...)` to the messages (`-x`)
+    pub record_origin: bool,
+    /// Only bring the changes to the index and the working tree (`-n`)
+    pub no_commit: bool,
+    /// Edit the messages, by default only reverts run from a terminal are edited
+    pub edit: Option<bool>,
+}
+
+/// Operation of the `cherry-pick` and `revert` commands
+#[derive(Clone, Debug)]
+pub enum PickAction {
+    /// Replay the commits (and ranges) in order
+    Start {
+        commits: Vec<String>,
+        options: PickOptions,
+    },
+    /// Commit the resolved conflicts and go on with the remaining commits
+    Continue,
+    /// Throw away the changes of the commit which stopped and go on
+    Skip,
+    /// Go back to the state before the command
+    Abort,
+}
+
+/// Command of a todo list line, `revert` comes from `revert` and the others from `rebase`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum TodoCommand {
+    Pick,
+    Revert,
+    Reword,
+    Edit,
+    Squash,
+    Fixup,
+    Drop,
+    Exec,
+}
+
+impl TodoCommand {
+    fn parse(word: &str) -> Option<TodoCommand> {
+        Some(match word {
+            "p" | "pick" => TodoCommand::Pick,
+            "revert" => TodoCommand::Revert,
+            "r" | "reword" => TodoCommand::Reword,
+            "e" | "edit" => TodoCommand::Edit,
+            "s" | "squash" => TodoCommand::Squash,
+            "f" | "fixup" => TodoCommand::Fixup,
+            "d" | "drop" => TodoCommand::Drop,
+            "x" | "exec" => TodoCommand::Exec,
+            _ => return None,
+        })
+    }
+
+    pub(crate) fn name(self) -> &'static str {
+        match self {
+            TodoCommand::Pick => "pick",
+            TodoCommand::Revert => "revert",
+            TodoCommand::Reword => "reword",
+            TodoCommand::Edit => "edit",
+            TodoCommand::Squash => "squash",
+            TodoCommand::Fixup => "fixup",
+            TodoCommand::Drop => "drop",
+            TodoCommand::Exec => "exec",
+        }
+    }
+
+    /// Whether the commit is melded into the previous one
+    pub(crate) fn melds(self) -> bool {
+        matches!(self, TodoCommand::Squash | TodoCommand::Fixup)
+    }
+}
+
+/// Line of a todo list: the command with its commit (the shell command for `exec`)
+#[derive(Clone, Debug)]
+pub(crate) struct TodoItem {
+    pub command: TodoCommand,
+    pub arg: String,
+}
+
+impl TodoItem {
+    /// Todo line with the commit subject (and the commit abbreviated for the user)
+    pub(crate) fn line(&self, short: bool) -> Result<String> {
+        if self.command == TodoCommand::Exec {
+            return Ok(format!("exec {}\n", self.arg));
+        }
+        let sha = if short { abbrev(&self.arg) } else { &self.arg };
+        let subject = subject(&read_commit(&self.arg)?.message_text());
+        Ok(format!("{} {sha} {subject}\n", self.command.name()))
+    }
+}
+
+/// Parse todo list lines, comments and empty lines are skipped, once commands were `started`
+/// squashes may meld into an already picked commit
+pub(crate) fn parse_todo(text: &str, started: bool) -> Result<Vec<TodoItem>> {
+    let mut items: Vec<TodoItem> = Vec::new();
+    for (number, line) in (1..).zip(text.lines()) {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let (word, rest) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+        let rest = rest.trim();
+        let invalid = || anyhow!("invalid line {number}: {line}");
+        let command = TodoCommand::parse(word).ok_or_else(invalid)?;
+        let arg = match command {
+            TodoCommand::Exec if rest.is_empty() => return Err(invalid()),
+            TodoCommand::Exec => rest.to_string(),
+            _ => {
+                let name = rest.split_whitespace().next().ok_or_else(invalid)?;
+                resolve_revision(&format!("{name}^{{commit}}")).map_err(|_| invalid())?
+            }
+        };
+        let picked = started || items.iter().any(|item| {
+            !matches!(item.command, TodoCommand::Drop | TodoCommand::Exec)
+        });
+        if command.melds() && !picked {
+            bail!("cannot '{}' without a previous commit", command.name());
+        }
+        items.push(TodoItem { command, arg });
+    }
+    Ok(items)
+}
+
+/// Todo list kept in the file, none when it is missing
+pub(crate) fn read_todo(path: &str, started: bool) -> Result<Vec<TodoItem>> {
+    parse_todo(&fs::read_to_string(path).unwrap_or_default(), started)
+}
+
+pub(crate) fn write_todo(path: &str, items: &[TodoItem], short: bool) -> Result<()> {
+    let mut text = String::new();
+    for item in items {
+        text += &item.line(short)?;
+    }
+    Ok(fs::write(path, text)?)
+}
+
+/// Commit a `cherry-pick` stopped on, its author is kept by `commit`
+pub fn cherry_pick_head() -> Result<Option<String>> {
+    Ok(
+        fs::read_to_string(state_path("", PickKind::CherryPick.head_file())?)
+            .ok()
+            .map(|text| text.trim().to_string())
+    )
+}
+
+/// Forget the commit a `cherry-pick` or `revert` stopped on
+pub fn remove_pick_heads() -> Result<()> {
+    for kind in [PickKind::CherryPick, PickKind::Revert] {
+        let _ = fs::remove_file(state_path("", kind.head_file())?);
+    }
+    Ok(())
+}
+
+/// Command and commit the last `cherry-pick` or `revert` stopped on
+fn stopped_pick() -> Result<Option<(PickKind, String)>> {
+    for kind in [PickKind::CherryPick, PickKind::Revert] {
+        if let Ok(text) = fs::read_to_string(state_path("", kind.head_file())?) {
+            return Ok(Some((kind, text.trim().to_string())));
+        }
+    }
+    Ok(None)
+}
+
+/// Options are kept in the same config format as git
+fn save_options(options: &PickOptions) -> Result<()> {
+    let path = state_path(STATE_FOLDER, "opts")?;
+    fs::write(&path, "")?;
+    if let Some(mainline) = options.mainline {
+        set_value(&path, "options.mainline", &mainline.to_string())?;
+    }
+    if options.record_origin {
+        set_value(&path, "options.record-origin", "true")?;
+    }
+    if options.no_commit {
+        set_value(&path, "options.no-commit", "true")?;
+    }
+    if let Some(edit) = options.edit {
+        set_value(&path, "options.edit", if edit { "true" } else { "false" })?;
+    }
+    Ok(())
+}
+
+fn load_options() -> Result<PickOptions> {
+    let config = Config::load_file(&state_path(STATE_FOLDER, "opts")?, Scope::Local, false)?;
+    let mainline = match config.get("options.mainline")? {
+        Some(number) => Some(number.parse()?),
+        None => None,
+    };
+    Ok(PickOptions {
+        mainline,
+        record_origin: config.get_bool("options.record-origin")?.unwrap_or(false),
+        no_commit: config.get_bool("options.no-commit")?.unwrap_or(false),
+        edit: config.get_bool("options.edit")?,
+    })
+}
+
+/// Todo list lines: `<command> <abbreviated SHA-1> <subject>`, the first one being picked
+fn write_sequence(items: &[TodoItem]) -> Result<()> {
+    write_todo(&state_path(STATE_FOLDER, "todo")?, items, true)
+}
+
+fn read_sequence() -> Result<Vec<TodoItem>> {
+    read_todo(&state_path(STATE_FOLDER, "todo")?, true)
+}
+
+/// Let the user change the message in `.git/COMMIT_EDITMSG`
+pub(crate) fn edit_message(message: &[u8]) -> Result<Vec<u8>> {
+    let path = state_path("", "COMMIT_EDITMSG")?;
+    let mut text = message.to_vec();
+    text.extend(
+        b"\n# Please enter the commit message for your changes. Lines starting\n\
+          # with '#' will be ignored, and an empty message aborts the commit.\n"
+    );
+    fs::write(&path, text)?;
+    launch_editor(&path, false)?;
+    let edited = strip_comments(&fs::read(&path)?);
+    if edited.is_empty() {
+        bail!("Aborting commit due to empty commit message.");
+    }
+    Ok(edited)
+}
+
+/// Summary of a commit made by a replayed command, in the same form as git (`date` for
+/// commits reusing the date of an older one)
+pub(crate) fn commit_summary(sha: &str, date: bool) -> Result<String> {
+    let commit = read_commit(sha)?;
+    let branch = current_branch()?.unwrap_or_else(|| "detached HEAD".to_string());
+    let mut out = format!("[{branch} {}] {}\n", abbrev(sha), subject(&commit.message_text()));
+    if commit.author.ident() != commit.committer.ident() {
+        out.push_str(&format!(" Author: {}\n", commit.author.ident()));
+    }
+    if date {
+        let date = format_date(commit.author.time, &commit.author.tz, Default::default());
+        out.push_str(&format!(" Date: {date}\n"));
+    }
+    let parent_tree = match commit.parents.first() {
+        Some(parent) => Some(read_commit(parent)?.tree),
+        None => None,
+    };
+    let options = DiffOptions::default();
+    let tree_options = options.tree_options(true);
+    let changes = diff_trees(parent_tree.as_deref(), Some(&commit.tree), &tree_options)?;
+    if !changes.is_empty() {
+        out.push_str(&short_stat(&changes, options.algorithm)?);
+        out.push_str(&summary(&changes));
+    }
+    Ok(out)
+}
+
+/// Parent the changes of the commit are taken against (None for root commits)
+fn mainline_parent(sha: &str, commit: &Commit, mainline: Option<usize>) -> Result<Option<String>> {
+    let count = commit.parents.len();
+    let number = match mainline {
+        None if count > 1 => bail!("commit {sha} is a merge but no -m option was given."),
+        None => return Ok(commit.parents.first().cloned()),
+        Some(number) => number,
+    };
+    // The first parent may be given for any commit
+    if number == 0 || number > count.max(1) {
+        bail!("commit {sha} does not have parent {number}");
+    }
+    Ok(commit.parents.get(number - 1).cloned())
+}
+
+/// Whether the line looks like `Token: value`
+fn is_trailer(line: &str) -> bool {
+    line.split_once(": ")
+        .is_some_and(|(token, _)| !token.is_empty() && !token.contains(char::is_whitespace))
+}
+
+/// Add the origin line of `-x`, in the trailers when the message ends with some
+fn record_origin(message: &[u8], sha: &str) -> Vec<u8> {
+    let message = message.trim_ascii_end();
+    let origin = "(cherry picked from commit ";
+    let in_trailers = match message.windows(2).rposition(|w| w == b"\n\n") {
+        Some(end) => String::from_utf8_lossy(&message[end + 2..])
+            .lines()
+            .all(|line| is_trailer(line) || line.starts_with(origin)),
+        None => false,
+    };
+    let separator = if in_trailers { "\n" } else { "\n\n" };
+    let mut text = message.to_vec();
+    text.extend(format!("{separator}{origin}{sha})\n").bytes());
+    text
+}
+
+/// Message of the commit replaying `commit`
+fn pick_message(kind: PickKind, sha: &str, commit: &Commit, options: &PickOptions) -> Vec<u8> {
+    match kind {
+        PickKind::CherryPick if options.record_origin => record_origin(&commit.utf8_message(), sha),
+        PickKind::CherryPick => commit.utf8_message(),
+        PickKind::Revert => {
+            let mut text = format!(
+                "Revert \"{}\"\n\nThis reverts commit {sha}",
+                subject(&commit.message_text())
+            );
+            if let (Some(number), [_, _, ..]) = (options.mainline, commit.parents.as_slice()) {
+                text += &format!(", reversing\nchanges made to {}", commit.parents[number - 1]);
+            }
+            (text + ".\n").into_bytes()
+        }
+    }
+}
+
+fn conflict_hint(kind: PickKind) -> String {
+    let name = kind.name();
+    format!(
+        "hint: After resolving the conflicts, mark them with\n\
+         hint: \"git add/rm <pathspec>\", then run\n\
+         hint: \"git {name} --continue\".\n\
+         hint: You can instead skip this commit with \"git {name} --skip\".\n\
+         hint: To abort and get back to the state before \"git {name}\",\n\
+         hint: run \"git {name} --abort\".\n"
+    )
+}
+
+fn empty_hint(kind: PickKind) -> String {
+    let name = kind.name();
+    format!(
+        "The previous {name} is now empty, possibly due to conflict resolution.\n\
+         If you wish to commit it anyway, use:\n\n    git commit --allow-empty\n\n\
+         Otherwise, please use 'git {name} --skip'\n"
+    )
+}
+
+/// With `-n` the changes are merged with the index rather than HEAD: it is recorded in a
+/// commit which stands for HEAD in the merge
+fn index_commit(head: &str) -> Result<String> {
+    let tree = write_index_tree(&read_index()?)?;
+    let head_commit = read_commit(head)?;
+    if tree == head_commit.tree {
+        return Ok(head.to_string());
+    }
+    let commit = Commit { tree, parents: vec![head.to_string()], ..head_commit };
+    write_object(&Object::Commit(commit))
+}
+
+/// Commit the index on top of the parents and move HEAD to it
+pub(crate) fn write_commit(
+    parents: Vec<String>,
+    author: Signature,
+    message: Vec<u8>,
+    log: &str
+) -> Result<String> {
+    let committer = signature("committer")?;
+    let commit = Commit {
+        tree: write_index_tree(&read_index()?)?,
+        parents,
+        author,
+        committer: committer.clone(),
+        extra_headers: Vec::new(),
+        message,
+    };
+    let sha = write_object(&Object::Commit(commit.into_utf8()))?;
+    refs::update_ref("HEAD", &sha, &committer, log)?;
+    Ok(sha)
+}
+
+/// Commit the picked changes staged in the index on top of the parents, the message is edited
+/// first when asked, `action` starts the reflog message
+pub(crate) fn record(
+    parents: Vec<String>,
+    author: Signature,
+    message: Vec<u8>,
+    edit: bool,
+    action: &str
+) -> Result<String> {
+    let message = match edit {
+        true => edit_message(message.trim_ascii_end())?,
+        false => message,
+    };
+    let log = format!("{action}: {}", subject(&String::from_utf8_lossy(&message)));
+    write_commit(parents, author, message, &log)
+}
+
+/// Merge the changes of the commit (undone for `revert`) into `ours`, the changes are taken
+/// against the `mainline` parent
+pub(crate) fn pick(
+    command: TodoCommand,
+    sha: &str,
+    commit: &Commit,
+    mainline: Option<usize>,
+    ours: &str
+) -> Result<MergeResult> {
+    let parent = mainline_parent(sha, commit, mainline)?;
+    let label = format!("{} ({})", abbrev(sha), subject(&commit.message_text()));
+    let parent_label = format!("parent of {label}");
+
+    // Reverting merges the parent with the commit as the base
+    match command {
+        TodoCommand::Revert => {
+            let theirs = if parent.is_some() { parent_label.as_str() } else { "(empty tree)" };
+            let labels = MergeLabels { ours: "HEAD", base: &label, theirs };
+            merge_into_head(ours, &[sha.to_string()], parent.as_deref(), labels)
+        }
+        _ => {
+            let labels = MergeLabels { ours: "HEAD", base: &parent_label, theirs: &label };
+            let bases: Vec<String> = parent.into_iter().collect();
+            merge_into_head(ours, &bases, Some(sha), labels)
+        }
+    }
+}
+
+/// Replay one commit on top of HEAD, returns the output and whether the command stopped
+fn replay(kind: PickKind, sha: &str, options: &PickOptions) -> Result<(String, bool)> {
+    let commit = read_commit(sha)?;
+    let head = head_commit()?;
+    let ours = match options.no_commit {
+        true => index_commit(&head)?,
+        false => head.clone(),
+    };
+    let title = subject(&commit.message_text());
+    let short = abbrev(sha);
+    let result = pick(kind.command(), sha, &commit, options.mainline, &ours)?;
+
+    let message = pick_message(kind, sha, &commit, options);
+    let message_path = state_path("", "MERGE_MSG")?;
+    if !result.conflicts.is_empty() {
+        let mut text = message;
+        text.extend(b"\n# Conflicts:\n");
+        for path in &result.conflicts {
+            text.extend(format!("#\t{path}\n").bytes());
+        }
+        write_locked(&message_path, text)?;
+        let verb = match kind {
+            PickKind::CherryPick => "apply",
+            PickKind::Revert => "revert",
+        };
+        let mut output = result.messages + &format!("error: could not {verb} {short}... {title}\n");
+        if options.no_commit {
+            output += NO_COMMIT_HINT;
+        } else {
+            write_locked(&state_path("", kind.head_file())?, format!("{sha}\n"))?;
+            output += &conflict_hint(kind);
+        }
+        return Ok((output, true));
+    }
+    if options.no_commit {
+        write_locked(&message_path, message)?;
+        return Ok((result.messages, false));
+    }
+    if write_index_tree(&read_index()?)? == read_commit(&head)?.tree {
+        write_locked(&state_path("", kind.head_file())?, format!("{sha}\n"))?;
+        write_locked(&message_path, message)?;
+        return Ok((result.messages + &empty_hint(kind), true));
+    }
+
+    let edit = options.edit.unwrap_or(kind == PickKind::Revert && std::io::stdin().is_terminal());
+    let message = if edit { message } else { cleanup_message(&message) };
+    let author = match kind {
+        PickKind::CherryPick => commit.author,
+        PickKind::Revert => signature("author")?,
+    };
+    let sha = record(vec![head], author, message, edit, kind.name())?;
+    // Reverts are new changes, shown without the date
+    Ok((result.messages + &commit_summary(&sha, kind == PickKind::CherryPick)?, false))
+}
+
+/// Replay the commits in order until one stops, the todo list is kept for a sequence
+fn run(items: &mut Vec<TodoItem>, options: &PickOptions, sequence: bool) -> Result<String> {
+    let mut output = String::new();
+    while let Some(item) = items.first().cloned() {
+        if sequence {
+            write_sequence(items)?;
+        }
+        let kind = match item.command {
+            TodoCommand::Revert => PickKind::Revert,
+            _ => PickKind::CherryPick,
+        };
+        // Commands failing (like merges without -m) stop the sequence too, to be skipped
+        let picked = replay(kind, &item.arg, options);
+        if sequence && !matches!(picked, Ok((_, false))) {
+            fs::write(state_path(STATE_FOLDER, "abort-safety")?, format!("{}\n", head_commit()?))?;
+        }
+        let (text, stopped) = match picked {
+            Ok(picked) => picked,
+            Err(err) if sequence => return Ok(output + &format!("error: {err}\n")),
+            Err(err) => return Err(err),
+        };
+        output += &text;
+        if stopped {
+            return Ok(output);
+        }
+        items.remove(0);
+    }
+    if sequence {
+        fs::remove_dir_all(state_path(STATE_FOLDER, "")?)?;
+    }
+    Ok(output)
+}
+
+/// Commits of the arguments, ranges are walked (oldest first for `cherry-pick`)
+fn resolve_commits(kind: PickKind, revisions: &[String]) -> Result<Vec<String>> {
+    if !revisions.iter().any(|name| name.contains("..") || name.starts_with('^')) {
+        return revisions
+            .iter()
+            .map(|name| {
+                resolve_revision(&format!("{name}^{{commit}}"))
+                    .map_err(|_| anyhow!("bad revision '{name}'"))
+            })
+            .collect();
+    }
+    let mut commits: Vec<String> = walk(revisions, &LogOptions::default())?
+        .into_iter()
+        .map(|entry| entry.sha)
+        .collect();
+    if kind == PickKind::CherryPick {
+        commits.reverse();
+    }
+    Ok(commits)
+}
+
+/// Apply (or undo) the changes of the commits on top of HEAD, one commit each
+pub fn start(kind: PickKind, revisions: &[String], options: &PickOptions) -> Result<String> {
+    let name = kind.name();
+    if Path::new(&state_path(STATE_FOLDER, "")?).exists() {
+        bail!(
+            "{name} is already in progress\n\
+             hint: try \"git {name} (--continue | --abort | --skip)\""
+        );
+    }
+    let commits = resolve_commits(kind, revisions)?;
+    if commits.is_empty() {
+        bail!("empty commit set passed");
+    }
+    let head = head_commit()?;
+    let index = read_index()?;
+    if index.entries.iter().any(|entry| entry.stage() != 0) {
+        let doing = match kind {
+            PickKind::CherryPick => "Cherry-picking",
+            PickKind::Revert => "Reverting",
+        };
+        bail!(
+            "{doing} is not possible because you have unmerged files.\n\
+             hint: Fix them up in the work tree, and then use 'git add/rm <file>'\n\
+             hint: as appropriate to mark resolution and make a commit."
+        );
+    }
+    if !options.no_commit && commit_files(Some(&head))? != index_files(&index) {
+        bail!(
+            "your local changes would be overwritten by {name}.\n\
+             hint: commit your changes or stash them to proceed."
+        );
+    }
+
+    // Several commits need a todo list to go on after conflicts
+    let sequence = commits.len() > 1;
+    if sequence {
+        fs::create_dir_all(state_path(STATE_FOLDER, "")?)?;
+        fs::write(state_path(STATE_FOLDER, "head")?, format!("{head}\n"))?;
+        save_options(options)?;
+    }
+    let mut items: Vec<TodoItem> = commits
+        .into_iter()
+        .map(|sha| TodoItem { command: kind.command(), arg: sha })
+        .collect();
+    run(&mut items, options, sequence)
+}
+
+/// Commit the resolved changes of the commit the command stopped on
+fn commit_stopped(kind: PickKind, sha: &str) -> Result<String> {
+    let text = merge::merge_message()?.unwrap_or_default();
+    let message = strip_comments(&text);
+    if message.is_empty() {
+        bail!("Aborting commit due to empty commit message.");
+    }
+    if write_index_tree(&read_index()?)? == read_commit(&head_commit()?)?.tree {
+        bail!("{}", empty_hint(kind).trim_end());
+    }
+    // Like `commit`, only cherry-picks keep the original author (and show its date)
+    let (author, log) = match kind {
+        PickKind::CherryPick => (read_commit(sha)?.author, "commit (cherry-pick)"),
+        PickKind::Revert => (signature("author")?, "commit"),
+    };
+    let log = format!("{log}: {}", subject(&String::from_utf8_lossy(&message)));
+    let new_sha = write_commit(vec![head_commit()?], author, message, &log)?;
+    remove_pick_heads()?;
+    merge::remove_state()?;
+    commit_summary(&new_sha, kind == PickKind::CherryPick)
+}
+
+/// Commit the resolved conflicts and go on with the remaining commits
+pub fn resume() -> Result<String> {
+    let sequence = Path::new(&state_path(STATE_FOLDER, "todo")?).exists();
+    let stopped = stopped_pick()?;
+    if !sequence && stopped.is_none() {
+        bail!("no cherry-pick or revert in progress");
+    }
+    if read_index()?.entries.iter().any(|entry| entry.stage() != 0) {
+        bail!("Committing is not possible because you have unmerged files.");
+    }
+    let mut output = String::new();
+    if let Some((kind, sha)) = stopped {
+        output = commit_stopped(kind, &sha)?;
+    }
+    if !sequence {
+        return Ok(output);
+    }
+    // The first command is the one which stopped (its commit may have been made by `commit`)
+    let mut items = read_sequence()?;
+    if !items.is_empty() {
+        items.remove(0);
+    }
+    Ok(output + &run(&mut items, &load_options()?, true)?)
+}
+
+/// Throw away the changes of the commit the command stopped on and go on
+pub fn skip() -> Result<String> {
+    let sequence = Path::new(&state_path(STATE_FOLDER, "todo")?).exists();
+    if !sequence && stopped_pick()?.is_none() {
+        bail!("no cherry-pick or revert in progress");
+    }
+    let head = head_commit()?;
+    reset_hard(Some(&head), Some(&head))?;
+    let committer = signature("committer")?;
+    refs::update_ref("HEAD", &head, &committer, &format!("reset: moving to {head}"))?;
+    remove_pick_heads()?;
+    merge::remove_state()?;
+    if !sequence {
+        return Ok(String::new());
+    }
+    let mut items = read_sequence()?;
+    if !items.is_empty() {
+        items.remove(0);
+    }
+    run(&mut items, &load_options()?, true)
+}
+
+/// Go back to HEAD as it was before the command, unless it was moved since it stopped
+pub fn abort() -> Result<String> {
+    let sequence = Path::new(&state_path(STATE_FOLDER, "head")?).exists();
+    if !sequence && stopped_pick()?.is_none() {
+        bail!("no cherry-pick or revert in progress");
+    }
+    let head = head_commit()?;
+    let mut output = String::new();
+    if sequence {
+        let target = fs::read_to_string(state_path(STATE_FOLDER, "head")?)?.trim().to_string();
+        let safety = fs::read_to_string(state_path(STATE_FOLDER, "abort-safety")?)
+            .unwrap_or_default();
+        if !safety.is_empty() && safety.trim() != head {
+            output = "warning: You seem to have moved HEAD. Not rewinding, check your HEAD!\n"
+                .to_string();
+        } else {
+            reset_hard(Some(&head), Some(&target))?;
+            write_locked(&state_path("", "ORIG_HEAD")?, format!("{head}\n"))?;
+            let committer = signature("committer")?;
+            refs::update_ref("HEAD", &target, &committer, &format!("reset: moving to {target}"))?;
+        }
+        fs::remove_dir_all(state_path(STATE_FOLDER, "")?)?;
+    } else {
+        reset_hard(Some(&head), Some(&head))?;
+    }
+    remove_pick_heads()?;
+    merge::remove_state()?;
+    Ok(output)
+}
@@ -0,0 +1,105 @@
+use crate::diff::{ diff_trees, format_changes, DiffOptions, TREE_MODE };
+use crate::history::pretty::{ format_commit, PrettyFormat };
+use crate::history::LogOptions;
+use crate::object::{ read_commit, read_object, Commit, Object, Tag, Tree };
+use crate::revision::resolve_revision;
+use crate::utility::date_util::format_date;
+
+use anyhow::Result;
+
+/// Options of `show`
+#[derive(Clone, Debug, Default)]
+pub struct ShowOptions {
+    /// Format of commit headers (only `format`, `abbrev_commit` and `date_format` are used)
+    pub log: LogOptions,
+    /// Output of the changes of commits
+    pub diff: DiffOptions,
+    /// Commits are shown without their changes (`-s`)
+    pub no_patch: bool,
+}
+
+/// Commit header and message followed by its changes against the first parent
+fn show_commit(sha: &str, commit: &Commit, options: &ShowOptions, out: &mut String) -> Result<()> {
+    if !out.is_empty() && options.log.format.has_separator_line() {
+        out.push('\n');
+    }
+    out.push_str(&format_commit(sha, commit, &options.log));
+    out.push('\n');
+    if options.no_patch {
+        return Ok(());
+    }
+    // Combined diffs of merges are not shown, only the line separating them
+    if commit.parents.len() > 1 {
+        if options.log.format != PrettyFormat::Oneline {
+            out.push('\n');
+        }
+        return Ok(());
+    }
+
+    let parent_tree = match commit.parents.first() {
+        Some(parent) => Some(read_commit(parent)?.tree),
+        None => None,
+    };
+    let tree_options = options.diff.tree_options(true);
+    let changes = diff_trees(parent_tree.as_deref(), Some(&commit.tree), &tree_options)?;
+    let text = format_changes(&changes, &options.diff)?;
+    if !text.is_empty() && options.log.format != PrettyFormat::Oneline {
+        out.push('\n');
+    }
+    out.push_str(&text);
+    Ok(())
+}
+
+/// Tag header and message, the tagged object follows (shown under the name of the tag)
+fn show_tag(name: &str, tag: &Tag, options: &ShowOptions, out: &mut String) -> Result<()> {
+    if !out.is_empty() {
+        out.push('\n');
+    }
+    out.push_str(&format!("tag {}\n", tag.name));
+    if let Some(tagger) = &tag.tagger {
+        let date = format_date(tagger.time, &tagger.tz, options.log.date_format);
+        let ident = tagger.decoded_ident(tag.encoding());
+        out.push_str(&format!("Tagger: {ident}\nDate:   {date}\n"));
+    }
+    out.push('\n');
+    let message = tag.message_text();
+    out.push_str(&message);
+    if !message.is_empty() && !message.ends_with('\n') {
+        out.push('\n');
+    }
+    show_object(name, &tag.object, options, out)
+}
+
+/// Names of the entries, folders with a trailing `/`
+fn show_tree(name: &str, tree: &Tree, out: &mut String) {
+    if !out.is_empty() {
+        out.push('\n');
+    }
+    out.push_str(&format!("tree {name}\n\n"));
+    for entry in &tree.entries {
+        let suffix = if entry.mode == TREE_MODE { "/" } else { "" };
+        out.push_str(&format!("{}{suffix}\n", entry.name_text()));
+    }
+}
+
+fn show_object(name: &str, sha: &str, options: &ShowOptions, out: &mut String) -> Result<()> {
+    match read_object(sha)? {
+        Object::Blob(blob) => out.push_str(&String::from_utf8_lossy(&blob.data)),
+        Object::Tree(tree) => show_tree(name, &tree, out),
+        Object::Commit(commit) => show_commit(sha, &commit, options, out)?,
+        Object::Tag(tag) => show_tag(name, &tag, options, out)?,
+    }
+    Ok(())
+}
+
+/// Show objects (HEAD by default): commits with their changes, tags followed by the tagged
+/// object, trees as listings and blobs as they are
+pub fn show(objects: &[String], options: &ShowOptions) -> Result<String> {
+    let head = ["HEAD".to_string()];
+    let objects = if objects.is_empty() { &head[..] } else { objects };
+    let mut out = String::new();
+    for name in objects {
+        show_object(name, &resolve_revision(name)?, options, &mut out)?;
+    }
+    Ok(out)
+}
@@ -0,0 +1,353 @@
+use crate::checkout::{ apply_updates, commit_files, reset_hard, stage_files, update_worktree };
+use crate::checkout::Update;
+use crate::diff::{ abbrev, diff_trees, format, index_files, read_worktree_file, worktree_files };
+use crate::diff::{ recorded_mode, DiffOptions, FileMap, WorktreeModes };
+use crate::history::pretty::subject;
+use crate::index::{ read_index, write_index_tree, Index, IndexEntry };
+use crate::merge::{ content::MergeLabels, merge_files, merge_into_head };
+use crate::object::{ read_commit, write_object, Blob, Commit, Object };
+use crate::refs::{ self, current_branch, delete_ref, read_ref, read_reflog, write_reflog };
+use crate::revision::{ head_commit, read_head_commit, reflog_ref, resolve_revision };
+use crate::utility::fs_utility::find_root_folder;
+use crate::utility::other_util::signature;
+
+use anyhow::{ anyhow, bail, Result };
+use std::collections::BTreeSet;
+use std::fs;
+
+/// Ref of the latest entry, older ones are kept in its reflog
+const STASH_REF: &str = "refs/stash";
+
+const LABELS: MergeLabels<'static> = MergeLabels {
+    ours: "Updated upstream",
+    base: "Stash base",
+    theirs: "Stashed changes",
+};
+
+/// Operation of the `stash` command, entries are named `stash@{N}` (or just `N`)
+#[derive(Clone, Debug)]
+pub enum StashAction {
+    /// Save local changes (and untracked files with `include_untracked`), then reset to HEAD
+    Push {
+        message: Option<String>,
+        include_untracked: bool,
+        keep_index: bool,
+    },
+    List,
+    Show {
+        stash: Option<String>,
+        patch: bool,
+    },
+    /// `index` restores the staged changes as staged
+    Apply {
+        stash: Option<String>,
+        index: bool,
+    },
+    /// The entry is kept when it conflicts
+    Pop {
+        stash: Option<String>,
+        index: bool,
+    },
+    Drop {
+        stash: Option<String>,
+    },
+    Clear,
+}
+
+/// Untracked files of the working tree (nested repositories are skipped)
+fn untracked_files(index: &Index) -> Result<Vec<String>> {
+    let root = find_root_folder()?;
+    let tracked: BTreeSet<&str> = index.entries.iter().map(|entry| entry.path.as_str()).collect();
+    let mut files: Vec<String> = Vec::new();
+    let mut folders: Vec<String> = vec![String::new()];
+    while let Some(folder) = folders.pop() {
+        for entry in fs::read_dir(format!("{root}{folder}"))? {
+            let entry = entry?;
+            let path = format!("{folder}{}", entry.file_name().to_string_lossy());
+            if tracked.contains(path.as_str()) {
+                continue;
+            }
+            if !entry.file_type()?.is_dir() {
+                files.push(path);
+            } else if path != ".git" && !entry.path().join(".git").exists() {
+                folders.push(path + "/");
+            }
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+/// Write blobs of the working tree files
+fn write_worktree_blobs<'a>(paths: impl Iterator<Item = &'a String>) -> Result<FileMap> {
+    let root = find_root_folder()?;
+    let modes = WorktreeModes::load()?;
+    let mut files = FileMap::new();
+    for path in paths {
+        let meta = fs::symlink_metadata(format!("{root}{path}"))?;
+        let sha = write_object(&Object::Blob(Blob { data: read_worktree_file(path)? }))?;
+        files.insert(path.clone(), (recorded_mode(&meta, None, modes).to_string(), sha));
+    }
+    Ok(files)
+}
+
+/// Tree of the files, their blobs must exist
+fn write_files_tree(files: &FileMap) -> Result<String> {
+    let entries = files
+        .iter()
+        .map(|(path, (mode, sha))| IndexEntry::new(path, mode, sha))
+        .collect::<Result<Vec<_>>>()?;
+    write_index_tree(&Index { entries })
+}
+
+/// Record the index and the working tree (`WIP on <branch>` commit with HEAD and the index
+/// commit as parents, untracked files as the third one) and reset them to HEAD
+pub fn push(message: Option<&str>, include_untracked: bool, keep_index: bool) -> Result<String> {
+    let Some(head) = read_head_commit()? else {
+        bail!("You do not have the initial commit yet");
+    };
+    let index = read_index()?;
+    if let Some(entry) = index.entries.iter().find(|entry| entry.stage() != 0) {
+        bail!("{}: needs merge\nCannot save the current index state", entry.path);
+    }
+    let head_commit = read_commit(&head)?;
+    let staged = index_files(&index);
+    let index_tree = write_index_tree(&index)?;
+    let worktree = worktree_files(&index, &staged)?;
+    let untracked = match include_untracked {
+        true => untracked_files(&index)?,
+        false => Vec::new(),
+    };
+    if index_tree == head_commit.tree && worktree == staged && untracked.is_empty() {
+        return Ok("No local changes to save\n".to_string());
+    }
+
+    let branch = current_branch()?.unwrap_or_else(|| "(no branch)".to_string());
+    let on = format!("{branch}: {} {}", abbrev(&head), subject(&head_commit.message_text()));
+    let author = signature("author")?;
+    let committer = signature("committer")?;
+    let write_commit = |tree: String, parents: Vec<String>, message: String| {
+        write_object(
+            &Object::Commit(Commit {
+                tree,
+                parents,
+                author: author.clone(),
+                committer: committer.clone(),
+                extra_headers: Vec::new(),
+                message: message.into_bytes(),
+            })
+        )
+    };
+
+    let index_commit = write_commit(index_tree, vec![head.clone()], format!("index on {on}\n"))?;
+    let mut parents = vec![head.clone(), index_commit];
+    if !untracked.is_empty() {
+        let tree = write_files_tree(&write_worktree_blobs(untracked.iter())?)?;
+        parents.push(write_commit(tree, Vec::new(), format!("untracked files on {on}\n"))?);
+    }
+    // Changed files are stored as blobs, the others already have theirs
+    let changed = worktree.keys().filter(|path| staged.get(*path) != worktree.get(*path));
+    let mut files = worktree.clone();
+    files.extend(write_worktree_blobs(changed)?);
+    let title = match message {
+        Some(message) => format!("On {branch}: {message}"),
+        None => format!("WIP on {on}"),
+    };
+    // Message of the stash commit has no trailing newline
+    let stash = write_commit(write_files_tree(&files)?, parents, title.clone())?;
+    refs::update_ref(STASH_REF, &stash, &committer, &title)?;
+
+    reset_hard(Some(&head), Some(&head))?;
+    refs::update_ref("HEAD", &head, &committer, "reset: moving to HEAD")?;
+    let removed: Vec<Update> = untracked.into_iter().map(|path| (path, None)).collect();
+    update_worktree(&removed)?;
+    if keep_index {
+        let head_files = commit_files(Some(&head))?;
+        let updates: Vec<Update> = head_files
+            .keys()
+            .chain(staged.keys())
+            .filter(|path| head_files.get(*path) != staged.get(*path))
+            .map(|path| (path.clone(), staged.get(path).cloned()))
+            .collect::<BTreeSet<_>>()
+            .into_iter()
+            .collect();
+        apply_updates(&updates)?;
+    }
+    Ok(format!("Saved working directory and index state {title}\n"))
+}
+
+/// Entries from the latest one as `stash@{N}: <message>`
+pub fn list() -> Result<String> {
+    Ok(
+        read_reflog(STASH_REF)?
+            .iter()
+            .rev()
+            .enumerate()
+            .map(|(index, entry)| format!("stash@{{{index}}}: {}\n", entry.message))
+            .collect()
+    )
+}
+
+/// Full name of the entry: `N` stands for `refs/stash@{N}`, the latest one is the default
+fn entry_name(stash: Option<&String>) -> String {
+    match stash {
+        Some(name) if name.is_empty() || !name.bytes().all(|byte| byte.is_ascii_digit()) => {
+            name.clone()
+        }
+        Some(number) => format!("{STASH_REF}@{{{number}}}"),
+        None => format!("{STASH_REF}@{{0}}"),
+    }
+}
+
+fn check_entries() -> Result<()> {
+    if read_ref(STASH_REF)?.is_none() {
+        bail!("No stash entries found.");
+    }
+    Ok(())
+}
+
+/// Commit of the entry, it must have the parents of a stash
+fn stash_commit(name: &str) -> Result<(String, Commit)> {
+    check_entries()?;
+    let sha = resolve_revision(&format!("{name}^{{commit}}"))
+        .map_err(|_| anyhow!("{name} is not a valid reference"))?;
+    let commit = read_commit(&sha)?;
+    if !(2..=3).contains(&commit.parents.len()) {
+        bail!("'{name}' is not a stash-like commit");
+    }
+    Ok((sha, commit))
+}
+
+/// Position of the entry in the stash reflog (counted from the latest one)
+fn entry_position(name: &str) -> Result<usize> {
+    name.strip_suffix('}')
+        .and_then(|rest| rest.split_once("@{"))
+        .filter(|(ref_name, _)| reflog_ref(ref_name).ok().as_deref() == Some(STASH_REF))
+        .and_then(|(_, position)| position.parse().ok())
+        .ok_or_else(|| anyhow!("'{name}' is not a stash reference"))
+}
+
+/// Diffstat of the entry against the commit it was made on, or the patch
+pub fn show(stash: Option<&String>, patch: bool) -> Result<String> {
+    let (_, stash) = stash_commit(&entry_name(stash))?;
+    let options = DiffOptions::default();
+    let base = read_commit(&stash.parents[0])?.tree;
+    let changes = diff_trees(Some(&base), Some(&stash.tree), &options.tree_options(true))?;
+    match patch {
+        true => format::patch(&changes, &options),
+        false => format::stat(&changes, &options),
+    }
+}
+
+/// Merge the entry into the working tree (untracked files are written first), returns the merge
+/// messages and whether it conflicted
+fn apply_entry(name: &str, restore_index: bool) -> Result<(String, bool)> {
+    let (sha, stash) = stash_commit(name)?;
+    let head = head_commit()?;
+    let index = read_index()?;
+    if index.entries.iter().any(|entry| entry.stage() != 0) {
+        bail!("Cannot apply a stash in the middle of a merge");
+    }
+    let base = &stash.parents[0];
+    let current = index_files(&index);
+
+    // Staged changes must apply cleanly to the index
+    let staged = match restore_index {
+        true => {
+            let stashed = commit_files(Some(&stash.parents[1]))?;
+            let merged = merge_files(&commit_files(Some(base))?, &current, &stashed, LABELS)?;
+            Some(merged.ok_or_else(|| anyhow!("Conflicts in index. Try without --index."))?)
+        }
+        false => None,
+    };
+
+    if let Some(untracked) = stash.parents.get(2) {
+        let root = find_root_folder()?;
+        let files = commit_files(Some(untracked))?;
+        let existing: Vec<String> = files
+            .keys()
+            .filter(|path| fs::symlink_metadata(format!("{root}{path}")).is_ok())
+            .map(|path| format!("{path} already exists, no checkout\n"))
+            .collect();
+        if !existing.is_empty() {
+            bail!("{}could not restore untracked files from stash", existing.concat());
+        }
+        let updates: Vec<Update> = files
+            .into_iter()
+            .map(|(path, file)| (path, Some(file)))
+            .collect();
+        update_worktree(&updates)?;
+    }
+
+    let result = merge_into_head(&head, std::slice::from_ref(base), Some(&sha), LABELS)?;
+    if !result.conflicts.is_empty() {
+        return Ok((result.messages, true));
+    }
+    // Without `--index` the changes stay unstaged, only new files are added
+    let files = match staged {
+        Some(files) => files,
+        None => {
+            let mut files = current;
+            for (path, file) in index_files(&read_index()?) {
+                files.entry(path).or_insert(file);
+            }
+            files
+        }
+    };
+    stage_files(&files, &[])?;
+    Ok((result.messages, false))
+}
+
+/// Remove the entry from the reflog, the ref follows the latest remaining one
+fn remove_entry(name: &str) -> Result<String> {
+    check_entries()?;
+    let position = entry_position(name)?;
+    let mut entries = read_reflog(STASH_REF)?;
+    let position = entries
+        .len()
+        .checked_sub(position + 1)
+        .ok_or_else(|| anyhow!("{name} is not a valid reference"))?;
+    let removed = entries.remove(position);
+    // The next entry starts where the dropped one did
+    if let Some(next) = entries.get_mut(position) {
+        next.old = removed.old.clone();
+    }
+    match entries.last() {
+        Some(last) => {
+            refs::write_ref(STASH_REF, &last.new)?;
+            write_reflog(STASH_REF, &entries)?;
+        }
+        None => delete_ref(STASH_REF)?,
+    }
+    Ok(format!("Dropped {name} ({})\n", removed.new))
+}
+
+/// Reapply the entry, `index` restores the staged changes as staged
+pub fn apply(stash: Option<&String>, index: bool) -> Result<String> {
+    Ok(apply_entry(&entry_name(stash), index)?.0)
+}
+
+/// Reapply the entry and drop it unless it conflicts
+pub fn pop(stash: Option<&String>, index: bool) -> Result<String> {
+    let name = entry_name(stash);
+    check_entries()?;
+    entry_position(&name)?;
+    let (output, conflicted) = apply_entry(&name, index)?;
+    if conflicted {
+        return Ok(output + "The stash entry is kept in case you need it again.\n");
+    }
+    Ok(output + &remove_entry(&name)?)
+}
+
+/// Remove the entry
+pub fn drop_entry(stash: Option<&String>) -> Result<String> {
+    remove_entry(&entry_name(stash))
+}
+
+/// Remove all entries
+pub fn clear() -> Result<String> {
+    if read_ref(STASH_REF)?.is_some() {
+        delete_ref(STASH_REF)?;
+    }
+    Ok(String::new())
+}
@@ -0,0 +1,309 @@
+use crate::checkout::{ switch, write_config, SwitchOptions };
+use crate::config::{ self, Config, Scope };
+use crate::diff::{ abbrev, matches_pathspec, GITLINK_MODE };
+use crate::index::read_index;
+use crate::object::read_commit;
+use crate::refs::list_refs;
+use crate::remote_utility::pack_processing::validate_and_get_heart;
+use crate::revision::read_head_commit;
+use crate::utility::fs_utility::{ copy_dir, find_root_folder, in_directory, write_data };
+use crate::utility::other_util::add_data_prefix;
+
+use anyhow::{ anyhow, bail, Result };
+use std::fs;
+use std::path::Path;
+
+/// Operation of the `submodule` command
+#[derive(Clone, Debug)]
+pub enum SubmoduleAction {
+    Init {
+        paths: Vec<String>,
+    },
+    /// Clone missing submodules and check out recorded commits (`--init` registers them first)
+    Update {
+        init: bool,
+        paths: Vec<String>,
+    },
+    Status {
+        paths: Vec<String>,
+    },
+}
+
+/// Submodule from `.gitmodules`
+#[derive(Clone, Debug)]
+struct Submodule {
+    name: String,
+    path: String,
+    url: Option<String>,
+}
+
+/// Read `submodule.<name>.path` and `.url` entries of `.gitmodules`
+fn read_gitmodules(root: &str) -> Result<Vec<Submodule>> {
+    let gitmodules = Config::load_file(&format!("{root}.gitmodules"), Scope::File, false)?;
+    let mut submodules: Vec<Submodule> = Vec::new();
+    for entry in gitmodules.entries() {
+        let Some((name, key)) = entry.name
+            .strip_prefix("submodule.")
+            .and_then(|rest| rest.rsplit_once('.')) else {
+            continue;
+        };
+        if key == "path" {
+            submodules.push(Submodule {
+                name: name.to_string(),
+                path: entry.value.clone().unwrap_or_default(),
+                url: gitmodules.get(&format!("submodule.{name}.url"))?,
+            });
+        }
+    }
+    Ok(submodules)
+}
+
+/// Submodules recorded in the index (gitlinks) selected by pathspec, with their commits
+fn selected_submodules(root: &str, paths: &[String]) -> Result<Vec<(Submodule, String)>> {
+    let submodules = read_gitmodules(root)?;
+    let mut selected: Vec<(Submodule, String)> = Vec::new();
+    for entry in read_index()?.stage0() {
+        if entry.mode_string() != GITLINK_MODE || !matches_pathspec(&entry.path, paths) {
+            continue;
+        }
+        let submodule = submodules
+            .iter()
+            .find(|submodule| submodule.path == entry.path)
+            .ok_or_else(|| {
+                anyhow!("no submodule mapping found in .gitmodules for path '{}'", entry.path)
+            })?;
+        selected.push((submodule.clone(), entry.sha.clone()));
+    }
+    Ok(selected)
+}
+
+/// Resolve `./` and `../` URLs against the superproject remote (or its folder)
+fn resolve_url(url: &str, config: &Config, root: &str) -> Result<String> {
+    if !url.starts_with("./") && !url.starts_with("../") {
+        return Ok(url.to_string());
+    }
+    let mut base = match config.get("remote.origin.url")? {
+        Some(remote) => remote.trim_end_matches('/').to_string(),
+        None => fs::canonicalize(root)?.to_string_lossy().to_string(),
+    };
+    let mut rest = url;
+    loop {
+        if let Some(stripped) = rest.strip_prefix("./") {
+            rest = stripped;
+        } else if let Some(stripped) = rest.strip_prefix("../") {
+            rest = stripped;
+            base = match base.rsplit_once('/') {
+                Some((parent, _)) => parent.to_string(),
+                None => bail!("cannot strip one component off url '{base}'"),
+            };
+        } else {
+            break;
+        }
+    }
+    Ok(format!("{base}/{rest}"))
+}
+
+/// Commit checked out in the submodule folder, None when it is not populated (no repository, or
+/// its HEAD commit cannot be read)
+pub fn checked_out_commit(path: impl AsRef<Path>) -> Result<Option<String>> {
+    if !path.as_ref().join(".git").is_dir() {
+        return Ok(None);
+    }
+    in_directory(path, || {
+        Ok(read_head_commit().ok().flatten().filter(|sha| read_commit(sha).is_ok()))
+    })
+}
+
+/// Name of the checked out commit for `status`: tag or branch pointing to it, or short SHA
+fn describe(sha: &str) -> Result<String> {
+    let refs = list_refs("refs/")?;
+    let tag = refs
+        .iter()
+        .find(|(name, target)| name.starts_with("refs/tags/") && target == sha)
+        .map(|(name, _)| name.trim_start_matches("refs/tags/").to_string());
+    let other = refs
+        .iter()
+        .find(|(_, target)| target == sha)
+        .map(|(name, _)| name.trim_start_matches("refs/").to_string());
+    Ok(tag.or(other).unwrap_or_else(|| abbrev(sha).to_string()))
+}
+
+/// Clone repository from local path (or `file://` URL) into the current folder
+fn clone_local(url: &str) -> Result<()> {
+    let source = url.strip_prefix("file://").unwrap_or(url);
+    if source.contains("://") || (source.contains(':') && !Path::new(source).exists()) {
+        bail!("only local submodule URLs are supported: '{url}'");
+    }
+    let source = Path::new(source);
+    let git_dir = match source.join(".git") {
+        git_dir if git_dir.is_dir() => git_dir,
+        _ => source.to_path_buf(),
+    };
+    if !git_dir.join("objects").is_dir() {
+        bail!("repository '{url}' does not exist");
+    }
+
+    // Objects are copied (packs unpacked, only loose objects are read), branches become
+    // remote-tracking ones
+    copy_dir(git_dir.join("objects"), ".git/objects")?;
+    if let Ok(entries) = fs::read_dir(git_dir.join("objects/pack")) {
+        for entry in entries {
+            let pack = entry?.path();
+            if pack.extension().is_some_and(|extension| extension == "pack") {
+                for object in validate_and_get_heart(fs::read(&pack)?)? {
+                    write_data(add_data_prefix(&object.obj_type, object.contents))?;
+                }
+            }
+        }
+    }
+    fs::create_dir_all(".git/refs/heads")?;
+    if git_dir.join("refs/heads").is_dir() {
+        copy_dir(git_dir.join("refs/heads"), ".git/refs/remotes/origin")?;
+    }
+    if let Ok(packed) = fs::read_to_string(git_dir.join("packed-refs")) {
+        for (sha, name) in packed.lines().filter_map(|line| line.split_once(' ')) {
+            if let Some(branch) = name.strip_prefix("refs/heads/") {
+                let path = format!(".git/refs/remotes/origin/{branch}");
+                if !Path::new(&path).exists() {
+                    fs::create_dir_all(Path::new(&path).parent().unwrap_or(Path::new(".")))?;
+                    fs::write(path, format!("{sha}\n"))?;
+                }
+            }
+        }
+    }
+
+    // Local branch for the default branch of the source
+    let head = fs::read_to_string(git_dir.join("HEAD"))?;
+    let branch = head
+        .trim_end()
+        .strip_prefix("ref: refs/heads/")
+        .ok_or_else(|| anyhow!("repository '{url}' has detached HEAD"))?;
+    let remote_branch = format!(".git/refs/remotes/origin/{branch}");
+    fs::write(".git/refs/remotes/origin/HEAD", format!("ref: refs/remotes/origin/{branch}\n"))?;
+    fs::write(".git/HEAD", format!("ref: refs/heads/{branch}\n"))?;
+    if Path::new(&remote_branch).exists() {
+        fs::copy(&remote_branch, format!(".git/refs/heads/{branch}"))?;
+    }
+    write_config(url)
+}
+
+/// Clone into a temporary folder and check out the commit there, the result is moved into the
+/// submodule folder only when both succeed
+fn clone_submodule(
+    root: &str,
+    name: &str,
+    url: &str,
+    folder: &str,
+    checkout: impl FnOnce() -> Result<String>
+) -> Result<()> {
+    if Path::new(folder).join(".git").exists() {
+        bail!("'{folder}' already has a broken repository, remove it to clone again");
+    }
+    let temp = format!("{root}.git/submodule-clone-{}", name.replace('/', "-"));
+    if Path::new(&temp).exists() {
+        fs::remove_dir_all(&temp)?;
+    }
+    fs::create_dir_all(&temp)?;
+    let result = in_directory(&temp, || {
+        clone_local(url)?;
+        checkout()
+    }).and_then(|_| {
+        // The repository goes last, so a failed move leaves the folder unpopulated
+        fs::create_dir_all(folder)?;
+        let mut entries = fs::read_dir(&temp)?
+            .map(|entry| Ok(entry?.file_name()))
+            .collect::<Result<Vec<_>>>()?;
+        entries.sort_by_key(|name| name == ".git");
+        for entry in entries {
+            fs::rename(Path::new(&temp).join(&entry), Path::new(folder).join(&entry))?;
+        }
+        Ok(())
+    });
+    fs::remove_dir_all(&temp)?;
+    result
+}
+
+/// Register submodules in the local config
+pub fn init(paths: &[String]) -> Result<String> {
+    let root = find_root_folder()?;
+    let config = Config::load()?;
+    let local = config::scope_file(Scope::Local)?;
+
+    let mut output = String::new();
+    for (submodule, _) in selected_submodules(&root, paths)? {
+        let Submodule { name, path, url } = submodule;
+        if config.get(&format!("submodule.{name}.url"))?.is_some() {
+            continue;
+        }
+        let url = url.ok_or_else(|| {
+            anyhow!("No url found for submodule path '{path}' in .gitmodules")
+        })?;
+        let url = resolve_url(&url, &config, &root)?;
+        config::set_value(&local, &format!("submodule.{name}.active"), "true")?;
+        config::set_value(&local, &format!("submodule.{name}.url"), &url)?;
+        output += &format!("Submodule '{name}' ({url}) registered for path '{path}'\n");
+    }
+    Ok(output)
+}
+
+/// Clone registered submodules that are missing and check out the recorded commits
+pub fn update(init_first: bool, paths: &[String]) -> Result<String> {
+    let mut output = if init_first { init(paths)? } else { String::new() };
+    let root = find_root_folder()?;
+    let config = Config::load()?;
+
+    for (submodule, sha) in selected_submodules(&root, paths)? {
+        let Submodule { name, path, .. } = submodule;
+        // Submodules without registered URL are not active
+        let Some(url) = config.get(&format!("submodule.{name}.url"))? else {
+            continue;
+        };
+        let folder = format!("{root}{path}");
+        let current = checked_out_commit(&folder)?;
+        if current.as_ref() == Some(&sha) {
+            continue;
+        }
+
+        let checkout = || {
+            read_commit(&sha).map_err(|_| anyhow!("commit not found in the repository ({url})"))?;
+            // Fresh clone has nothing checked out yet, so every file is written
+            let options = SwitchOptions {
+                target: Some(sha.clone()),
+                detach: true,
+                force: current.is_none(),
+                ..Default::default()
+            };
+            switch(&options)
+        };
+        let result = match current {
+            Some(_) => in_directory(&folder, checkout).map(|_| ()),
+            None => clone_submodule(&root, &name, &url, &folder, checkout),
+        };
+        result.map_err(|err| {
+            anyhow!("Unable to checkout '{sha}' in submodule path '{path}': {err}")
+        })?;
+        output += &format!("Submodule path '{path}': checked out '{sha}'\n");
+    }
+    Ok(output)
+}
+
+/// Show recorded commits of submodules: `-` not populated, `+` different commit checked out
+pub fn status(paths: &[String]) -> Result<String> {
+    let root = find_root_folder()?;
+    let mut output = String::new();
+    for entry in read_index()?.stage0() {
+        if entry.mode_string() != GITLINK_MODE || !matches_pathspec(&entry.path, paths) {
+            continue;
+        }
+        let folder = format!("{root}{}", entry.path);
+        output += &match checked_out_commit(&folder)? {
+            None => format!("-{} {}\n", entry.sha, entry.path),
+            Some(current) => {
+                let marker = if current == entry.sha { ' ' } else { '+' };
+                let name = in_directory(&folder, || describe(&current))?;
+                format!("{marker}{current} {} ({name})\n", entry.path)
+            }
+        };
+    }
+    Ok(output)
+}
@@ -0,0 +1,151 @@
+use crate::diff::abbrev;
+use crate::object::{ object_kind, read_object, write_object, Object, Tag };
+use crate::refs::{ self, is_valid_ref_name, list_refs, read_ref };
+use crate::revision::resolve_revision;
+use crate::utility::fs_utility::read_file_or_stdin;
+use crate::utility::other_util::{ cleanup_message, signature };
+
+use anyhow::{ anyhow, bail, Result };
+
+/// Width of the name column of `tag -n`
+const NAME_WIDTH: usize = 15;
+
+/// Operation of the `tag` command
+#[derive(Clone, Debug)]
+pub enum TagAction {
+    /// List tags matching any of the patterns, with `lines` of their messages (`-n`)
+    List {
+        patterns: Vec<String>,
+        lines: usize,
+    },
+    /// Create tag, annotated when a message is given (`-a`, `-m`, `-F`)
+    Create {
+        name: String,
+        target: Option<String>,
+        annotate: bool,
+        messages: Vec<String>,
+        message_file: Option<String>,
+        force: bool,
+    },
+    Delete {
+        names: Vec<String>,
+    },
+}
+
+/// Match tag name against the pattern (`*` and `?` also match `/`)
+fn pattern_match(pattern: &[u8], text: &[u8]) -> bool {
+    match pattern.split_first() {
+        None => text.is_empty(),
+        Some((b'*', rest)) => (0..=text.len()).any(|i| pattern_match(rest, &text[i..])),
+        Some((b'?', rest)) => !text.is_empty() && pattern_match(rest, &text[1..]),
+        Some((c, rest)) => text.first() == Some(c) && pattern_match(rest, &text[1..]),
+    }
+}
+
+/// Message of the tag or the tagged commit (signature of the tag is not shown)
+fn tag_contents(sha: &str) -> Result<String> {
+    Ok(match read_object(sha)? {
+        Object::Tag(tag) => {
+            let message = tag.message_text();
+            let signature = message.find("-----BEGIN PGP SIGNATURE-----");
+            message[..signature.unwrap_or(message.len())].to_string()
+        }
+        Object::Commit(commit) => commit.message_text().into_owned(),
+        _ => String::new(),
+    })
+}
+
+/// List tags sorted by name, `lines` > 0 adds the first lines of their messages
+pub fn list(patterns: &[String], lines: usize) -> Result<String> {
+    let mut output = String::new();
+    for (name, sha) in list_refs("refs/tags/")? {
+        let short = name.trim_start_matches("refs/tags/");
+        let selected = patterns.is_empty() ||
+            patterns.iter().any(|pattern| pattern_match(pattern.as_bytes(), short.as_bytes()));
+        if !selected {
+            continue;
+        }
+        if lines == 0 {
+            output += &format!("{short}\n");
+            continue;
+        }
+
+        // Object of the ref itself, tags of tags are not peeled
+        let contents = tag_contents(&sha)?;
+        let text: Vec<&str> = contents.lines().take(lines).collect();
+        output += &format!("{short:<NAME_WIDTH$} {}\n", text.join("\n    "));
+    }
+    Ok(output)
+}
+
+/// Create lightweight or annotated tag pointing to the object (HEAD by default),
+/// existing tag is replaced only with `force`
+pub fn create(
+    name: &str,
+    target: Option<&str>,
+    annotate: bool,
+    messages: &[String],
+    message_file: Option<&str>,
+    force: bool
+) -> Result<String> {
+    if !messages.is_empty() && message_file.is_some() {
+        bail!("Option -m cannot be combined with -F");
+    }
+    let ref_name = format!("refs/tags/{name}");
+    if !is_valid_ref_name(&ref_name) || name.starts_with('-') {
+        bail!("'{name}' is not a valid tag name.");
+    }
+    let previous = read_ref(&ref_name)?;
+    if previous.is_some() && !force {
+        bail!("tag '{name}' already exists");
+    }
+    let target = target.unwrap_or("HEAD");
+    let object = resolve_revision(target).map_err(|_| {
+        anyhow!("Failed to resolve '{target}' as a valid ref.")
+    })?;
+
+    // Annotated tag wraps the object with a message and the tagger
+    let annotated = annotate || !messages.is_empty() || message_file.is_some();
+    let sha = if annotated {
+        let text = match message_file {
+            Some(path) => read_file_or_stdin(path)?,
+            None if !messages.is_empty() => messages.join("\n\n").into_bytes(),
+            None => bail!("no tag message?"),
+        };
+        let tag = Tag {
+            target_type: object_kind(&object)?,
+            object,
+            name: name.to_string(),
+            tagger: Some(signature("committer")?),
+            extra_headers: Vec::new(),
+            message: cleanup_message(&text),
+        };
+        write_object(&Object::Tag(tag))?
+    } else {
+        object
+    };
+
+    refs::write_ref(&ref_name, &sha)?;
+    Ok(match previous {
+        Some(old) if old != sha => format!("Updated tag '{name}' (was {})\n", abbrev(&old)),
+        _ => String::new(),
+    })
+}
+
+/// Delete tags, all of them must exist
+pub fn delete(names: &[String]) -> Result<String> {
+    let mut deleted: Vec<(&String, String)> = Vec::new();
+    for name in names {
+        let sha = read_ref(&format!("refs/tags/{name}"))?.ok_or_else(|| {
+            anyhow!("tag '{name}' not found.")
+        })?;
+        deleted.push((name, sha));
+    }
+
+    let mut output = String::new();
+    for (name, sha) in deleted {
+        refs::delete_ref(&format!("refs/tags/{name}"))?;
+        output += &format!("Deleted tag '{name}' (was {})\n", abbrev(&sha));
+    }
+    Ok(output)
+}
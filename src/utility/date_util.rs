@@ -0,0 +1,335 @@
+use anyhow::{ anyhow, bail, Result };
+use chrono::{ DateTime, Datelike, FixedOffset, Local, NaiveDate, NaiveDateTime, NaiveTime };
+use chrono::{ SecondsFormat, TimeZone };
+use std::time::SystemTime;
+
+/// Output style of dates (`--date=<format>`)
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum DateFormat {
+    #[default]
+    Default,
+    Iso,
+    IsoStrict,
+    Rfc,
+    Short,
+    Raw,
+    Unix,
+    Relative,
+}
+
+impl DateFormat {
+    pub fn from_name(name: &str) -> Result<DateFormat> {
+        match name {
+            "default" => Ok(DateFormat::Default),
+            "iso" | "iso8601" => Ok(DateFormat::Iso),
+            "iso-strict" | "iso8601-strict" => Ok(DateFormat::IsoStrict),
+            "rfc" | "rfc2822" => Ok(DateFormat::Rfc),
+            "short" => Ok(DateFormat::Short),
+            "raw" => Ok(DateFormat::Raw),
+            "unix" => Ok(DateFormat::Unix),
+            "relative" => Ok(DateFormat::Relative),
+            _ => bail!("Unknown date format '{name}'"),
+        }
+    }
+}
+
+pub fn now_timestamp() -> i64 {
+    SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .map(|d| d.as_secs() as i64)
+        .unwrap_or(0)
+}
+
+/// Current time with the local UTC offset
+pub fn local_now() -> (i64, String) {
+    let now = Local::now();
+    (now.timestamp(), format_tz_offset(now.offset().local_minus_utc()))
+}
+
+/// Parse timezone like `+0130` into seconds east of UTC
+pub fn parse_tz_offset(tz: &str) -> Result<i32> {
+    let sign = match tz.as_bytes().first() {
+        Some(b'+') => 1,
+        Some(b'-') => -1,
+        _ => bail!("Incorrect timezone '{tz}'"),
+    };
+    let digits = &tz[1..];
+    if digits.len() != 4 || !digits.chars().all(|c| c.is_ascii_digit()) {
+        bail!("Incorrect timezone '{tz}'");
+    }
+    let hours: i32 = digits[..2].parse()?;
+    let minutes: i32 = digits[2..].parse()?;
+    Ok(sign * (hours * 3600 + minutes * 60))
+}
+
+/// Timezone text (`+0130`) for offset in seconds
+pub fn format_tz_offset(offset: i32) -> String {
+    let sign = if offset < 0 { '-' } else { '+' };
+    let minutes = offset.abs() / 60;
+    format!("{sign}{:02}{:02}", minutes / 60, minutes % 60)
+}
+
+fn to_datetime(timestamp: i64, tz: &str) -> DateTime<FixedOffset> {
+    let offset = FixedOffset::east_opt(parse_tz_offset(tz).unwrap_or(0)).unwrap_or(
+        FixedOffset::east_opt(0).unwrap()
+    );
+    offset.timestamp_opt(timestamp, 0).single().unwrap_or_else(|| offset.timestamp_opt(0, 0).unwrap())
+}
+
+/// Format timestamp in its original timezone
+pub fn format_date(timestamp: i64, tz: &str, format: DateFormat) -> String {
+    let date = to_datetime(timestamp, tz);
+    match format {
+        DateFormat::Default => format!("{} {tz}", date.format("%a %b %-d %H:%M:%S %Y")),
+        DateFormat::Iso => format!("{} {tz}", date.format("%Y-%m-%d %H:%M:%S")),
+        DateFormat::IsoStrict => date.to_rfc3339_opts(SecondsFormat::Secs, false),
+        DateFormat::Rfc => format!("{} {tz}", date.format("%a, %-d %b %Y %H:%M:%S")),
+        DateFormat::Short => date.format("%Y-%m-%d").to_string(),
+        DateFormat::Raw => format!("{timestamp} {tz}"),
+        DateFormat::Unix => timestamp.to_string(),
+        DateFormat::Relative => relative_date(timestamp, now_timestamp()),
+    }
+}
+
+fn plural(count: i64, unit: &str) -> String {
+    if count == 1 { format!("1 {unit}") } else { format!("{count} {unit}s") }
+}
+
+/// Human readable age of the timestamp (same rounding as in git)
+pub fn relative_date(timestamp: i64, now: i64) -> String {
+    if now < timestamp {
+        return "in the future".to_string();
+    }
+    let mut diff = now - timestamp;
+    if diff < 90 {
+        return plural(diff, "second") + " ago";
+    }
+    diff = (diff + 30) / 60;
+    if diff < 90 {
+        return plural(diff, "minute") + " ago";
+    }
+    diff = (diff + 30) / 60;
+    if diff < 36 {
+        return plural(diff, "hour") + " ago";
+    }
+    diff = (diff + 12) / 24;
+    if diff < 14 {
+        return plural(diff, "day") + " ago";
+    }
+    if diff < 70 {
+        return plural((diff + 3) / 7, "week") + " ago";
+    }
+    if diff < 365 {
+        return plural((diff + 15) / 30, "month") + " ago";
+    }
+    if diff < 1825 {
+        let total_months = (diff * 12 * 2 + 365) / (365 * 2);
+        let (years, months) = (total_months / 12, total_months % 12);
+        if months != 0 {
+            return format!("{}, {} ago", plural(years, "year"), plural(months, "month"));
+        }
+        return plural(years, "year") + " ago";
+    }
+    plural((diff + 183) / 365, "year") + " ago"
+}
+
+/// Timestamp of naive date-time in local timezone
+fn local_timestamp(naive: NaiveDateTime) -> Result<(i64, String)> {
+    let date = Local
+        .from_local_datetime(&naive)
+        .earliest()
+        .ok_or_else(|| anyhow!("Nonexistent local time '{naive}'"))?;
+    Ok((date.timestamp(), format_tz_offset(date.offset().local_minus_utc())))
+}
+
+/// Parse absolute date: raw (`<unix> <tz>`), `@<unix>`, ISO 8601, RFC 2822 and git default format
+pub fn parse_date(text: &str) -> Result<(i64, String)> {
+    let text = text.trim();
+
+    // Raw format and unix timestamps (`@<unix> [<tz>]`)
+    if let Some(raw) = text.strip_prefix('@') {
+        let (seconds, tz) = raw.split_once(' ').unwrap_or((raw, "+0000"));
+        parse_tz_offset(tz.trim())?;
+        return Ok((seconds.parse()?, tz.trim().to_string()));
+    }
+    if let Some((seconds, tz)) = text.split_once(' ') {
+        if let (Ok(timestamp), Ok(_)) = (seconds.parse::<i64>(), parse_tz_offset(tz)) {
+            return Ok((timestamp, tz.to_string()));
+        }
+    }
+
+    // Formats with explicit timezone
+    let with_zone: [&str; 5] = [
+        "%Y-%m-%dT%H:%M:%S%z",
+        "%Y-%m-%dT%H:%M:%S %z",
+        "%Y-%m-%d %H:%M:%S %z",
+        "%Y-%m-%dT%H:%M:%S%:z",
+        "%a %b %e %H:%M:%S %Y %z",
+    ];
+    for pattern in with_zone {
+        if let Ok(date) = DateTime::parse_from_str(text, pattern) {
+            return Ok((date.timestamp(), format_tz_offset(date.offset().local_minus_utc())));
+        }
+    }
+    if let Ok(date) = DateTime::parse_from_rfc2822(text) {
+        return Ok((date.timestamp(), format_tz_offset(date.offset().local_minus_utc())));
+    }
+    if let Some(utc) = text.strip_suffix('Z') {
+        let naive = NaiveDateTime::parse_from_str(utc, "%Y-%m-%dT%H:%M:%S")?;
+        return Ok((naive.and_utc().timestamp(), "+0000".to_string()));
+    }
+
+    // Local time
+    for pattern in ["%Y-%m-%dT%H:%M:%S", "%Y-%m-%d %H:%M:%S", "%Y-%m-%d %H:%M"] {
+        if let Ok(naive) = NaiveDateTime::parse_from_str(text, pattern) {
+            return local_timestamp(naive);
+        }
+    }
+    if let Ok(day) = NaiveDate::parse_from_str(text, "%Y-%m-%d") {
+        return local_timestamp(day.and_hms_opt(0, 0, 0).unwrap());
+    }
+
+    Err(anyhow!("Cannot parse date '{text}'"))
+}
+
+const MONTHS: [&str; 12] = [
+    "january", "february", "march", "april", "may", "june",
+    "july", "august", "september", "october", "november", "december",
+];
+
+/// Seconds of a relative date unit (`week`, `days`, ...)
+fn unit_seconds(unit: &str) -> Option<i64> {
+    match unit.trim_end_matches('s') {
+        "second" | "sec" => Some(1),
+        "minute" | "min" => Some(60),
+        "hour" => Some(3600),
+        "day" => Some(86400),
+        "week" => Some(7 * 86400),
+        "month" => Some(30 * 86400),
+        "year" => Some(365 * 86400),
+        _ => None,
+    }
+}
+
+/// Month (1-based) of a name or its abbreviation of at least three letters
+fn month_number(word: &str) -> Option<u32> {
+    MONTHS
+        .iter()
+        .position(|month| word.len() >= 3 && month.starts_with(word))
+        .map(|index| index as u32 + 1)
+}
+
+/// Parse date for filtering (`--since`) and reflog selectors: absolute dates (`Oct 18 2026`,
+/// `2026-10-18.23:40:00`, seconds since the epoch) and relative ones like `2.weeks.ago`
+pub fn approxidate(text: &str) -> Result<i64> {
+    // Like git, a day without time means the current time of that day
+    if let Ok(day) = NaiveDate::parse_from_str(text.trim(), "%Y-%m-%d") {
+        return Ok(local_timestamp(day.and_time(Local::now().time()))?.0);
+    }
+    if let Ok((timestamp, _)) = parse_date(text) {
+        return Ok(timestamp);
+    }
+
+    let error = || anyhow!("Cannot parse date '{text}'");
+    let words: Vec<String> = text
+        .split(|c: char| c.is_whitespace() || c == '.' || c == ',')
+        .filter(|w| !w.is_empty())
+        .map(|w| w.to_ascii_lowercase())
+        .collect();
+    // Large numbers are seconds since the epoch
+    if let [number] = words.as_slice() {
+        if let Ok(timestamp) = number.parse::<i64>() {
+            if timestamp >= 100_000_000 {
+                return Ok(timestamp);
+            }
+        }
+    }
+
+    let (mut year, mut month, mut day, mut time) = (None, None, None, None);
+    let mut offset = 0;
+    let mut index = 0;
+    while let Some(word) = words.get(index) {
+        index += 1;
+        if word.contains(':') {
+            let parsed = NaiveTime::parse_from_str(word, "%H:%M:%S")
+                .or_else(|_| NaiveTime::parse_from_str(word, "%H:%M"))
+                .map_err(|_| error())?;
+            time = Some(parsed);
+        } else if let Ok(date) = NaiveDate::parse_from_str(word, "%Y-%m-%d") {
+            (year, month, day) = (Some(date.year()), Some(date.month()), Some(date.day()));
+        } else if let Ok(number) = word.parse::<i64>() {
+            // A number is a count of the unit following it, a day or a year
+            if let Some(seconds) = words.get(index).and_then(|unit| unit_seconds(unit)) {
+                offset += number * seconds;
+                index += 1;
+            } else if (1..=31).contains(&number) && day.is_none() {
+                day = Some(number as u32);
+            } else if (1970..=9999).contains(&number) && year.is_none() {
+                year = Some(number as i32);
+            } else {
+                return Err(error());
+            }
+        } else if let Some(number) = month_number(word) {
+            month = Some(number);
+        } else {
+            match word.as_str() {
+                "ago" | "now" | "today" => {}
+                "yesterday" => offset += 86400,
+                "noon" => time = NaiveTime::from_hms_opt(12, 0, 0),
+                "midnight" => time = NaiveTime::from_hms_opt(0, 0, 0),
+                _ => return Err(error()),
+            }
+        }
+    }
+
+    if (year, month, day, time) == (None, None, None, None) {
+        return Ok(now_timestamp() - offset);
+    }
+    // Parts missing from the date are taken from the current time
+    let now = Local::now().naive_local();
+    let date = NaiveDate::from_ymd_opt(
+        year.unwrap_or(now.year()),
+        month.unwrap_or(now.month()),
+        day.unwrap_or(now.day())
+    ).ok_or_else(error)?;
+    Ok(local_timestamp(date.and_time(time.unwrap_or(now.time())))?.0 - offset)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn date_formats() {
+        let (timestamp, tz) = parse_date("2005-04-07T15:13:13 -0700").unwrap();
+        assert_eq!(timestamp, 1112911993);
+        assert_eq!(format_date(timestamp, &tz, DateFormat::Default), "Thu Apr 7 15:13:13 2005 -0700");
+        assert_eq!(format_date(timestamp, &tz, DateFormat::Iso), "2005-04-07 15:13:13 -0700");
+        assert_eq!(format_date(timestamp, &tz, DateFormat::IsoStrict), "2005-04-07T15:13:13-07:00");
+        assert_eq!(format_date(timestamp, &tz, DateFormat::Rfc), "Thu, 7 Apr 2005 15:13:13 -0700");
+        assert_eq!(parse_date("Thu Apr 7 15:13:13 2005 -0700").unwrap().0, timestamp);
+        assert_eq!(parse_date("1112911993 +0200").unwrap(), (timestamp, "+0200".to_string()));
+        assert_eq!(parse_date("@1112911993 -0130").unwrap(), (timestamp, "-0130".to_string()));
+        assert_eq!(relative_date(0, 100), "2 minutes ago");
+        assert_eq!(relative_date(0, 400 * 86400), "1 year, 1 month ago");
+    }
+
+    #[test]
+    fn approxidate_forms() {
+        let at = |date: &str| {
+            let naive = NaiveDateTime::parse_from_str(date, "%Y-%m-%d %H:%M:%S").unwrap();
+            local_timestamp(naive).unwrap().0
+        };
+        assert_eq!(approxidate("1760000000").unwrap(), 1760000000);
+        assert_eq!(approxidate("2026-10-18.23:40:00").unwrap(), at("2026-10-18 23:40:00"));
+        assert_eq!(approxidate("Oct 18 2026 10:30").unwrap(), at("2026-10-18 10:30:00"));
+        assert_eq!(approxidate("18 October, 2026 noon").unwrap(), at("2026-10-18 12:00:00"));
+        // Without a time the date keeps the current time of day
+        let day = Local.timestamp_opt(approxidate("Oct 18 2026").unwrap(), 0).unwrap();
+        assert_eq!(day.date_naive(), NaiveDate::from_ymd_opt(2026, 10, 18).unwrap());
+        let ago = now_timestamp() - approxidate("2.weeks.ago").unwrap();
+        assert!((14 * 86400..14 * 86400 + 5).contains(&ago));
+        assert!(approxidate("Oct 42 2026").is_err());
+        assert!(approxidate("someday").is_err());
+    }
+}
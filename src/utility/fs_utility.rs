@@ -16,6 +16,30 @@ pub fn create_path_and_move_there<T: AsRef<Path>>(path: &T) -> Result<()> {
     Ok(())
 }
 
+/// Run the closure inside another directory, the current one is restored afterwards
+pub fn in_directory<T>(path: impl AsRef<Path>, f: impl FnOnce() -> Result<T>) -> Result<T> {
+    let previous = env::current_dir()?;
+    env::set_current_dir(path)?;
+    let result = f();
+    env::set_current_dir(previous)?;
+    result
+}
+
+/// Copy folder with all its contents, existing files are overwritten
+pub fn copy_dir(from: impl AsRef<Path>, to: impl AsRef<Path>) -> Result<()> {
+    fs::create_dir_all(&to)?;
+    for entry in fs::read_dir(from)? {
+        let entry = entry?;
+        let target = to.as_ref().join(entry.file_name());
+        if entry.file_type()?.is_dir() {
+            copy_dir(entry.path(), target)?;
+        } else {
+            fs::copy(entry.path(), target)?;
+        }
+    }
+    Ok(())
+}
+
 pub fn find_root_folder() -> Result<String> {
     let mut prefix_path = String::from("./");
     // Check depth 256 for a .git folder
@@ -29,6 +53,11 @@ pub fn find_root_folder() -> Result<String> {
     Err(anyhow!("Cannot find .git folder!"))
 }
 
+/// Path of a state file in `.git/<folder>` (`folder` is empty for files directly in `.git`)
+pub fn state_path(folder: &str, name: &str) -> Result<String> {
+    Ok(find_root_folder()? + ".git/" + folder + name)
+}
+
 pub fn compute_path_from_sha(sha: &str) -> Result<String> {
     let path = find_root_folder()? + ".git/objects/" + &sha[..2] + "/" + &sha[2..sha.len()];
     Ok(path)
@@ -74,4 +103,65 @@ pub fn write_data(data: Vec<u8>) -> Result<String> {
     obj.write_all(encoded_text.as_slice())?;
 
     Ok(hash)
-}
\ No newline at end of file
+}
+
+/// Read text from the file, `-` means standard input
+pub fn read_file_or_stdin(path: &str) -> Result<Vec<u8>> {
+    if path == "-" {
+        let mut text = Vec::new();
+        std::io::stdin().read_to_end(&mut text)?;
+        Ok(text)
+    } else {
+        fs::read(path).with_context(|| format!("Could not read file '{path}'"))
+    }
+}
+
+/// Write file through a lock file, so readers never see partial contents
+pub fn write_locked(path: &str, contents: impl AsRef<[u8]>) -> Result<()> {
+    LockFile::acquire(path)?.commit(contents)
+}
+
+/// Exclusive `<path>.lock` file, committing renames it over the path, dropping removes it
+pub struct LockFile {
+    path: String,
+    lock: String,
+    file: Option<fs::File>,
+}
+
+impl LockFile {
+    /// Create the lock file, fails when another process holds the lock
+    pub fn acquire(path: &str) -> Result<LockFile> {
+        let lock = format!("{path}.lock");
+        if let Some(parent) = Path::new(path).parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let file = fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&lock)
+            .map_err(|_| anyhow!("Unable to create '{lock}': File exists or is not writable"))?;
+        Ok(LockFile { path: path.to_string(), lock, file: Some(file) })
+    }
+
+    /// Write the new contents and replace the locked file with them
+    pub fn commit(mut self, contents: impl AsRef<[u8]>) -> Result<()> {
+        if let Some(mut file) = self.file.take() {
+            let written = file.write_all(contents.as_ref()).and_then(|_| {
+                fs::rename(&self.lock, &self.path)
+            });
+            if let Err(err) = written {
+                let _ = fs::remove_file(&self.lock);
+                return Err(err.into());
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Drop for LockFile {
+    fn drop(&mut self) {
+        if self.file.take().is_some() {
+            let _ = fs::remove_file(&self.lock);
+        }
+    }
+}
@@ -1,2 +1,3 @@
 pub mod fs_utility;
-pub mod other_util;
\ No newline at end of file
+pub mod other_util;
+pub mod date_util;
@@ -1,11 +1,11 @@
-use anyhow::{ anyhow, bail, Result };
-use std::time::SystemTime;
-use sha1::{ Sha1, Digest };
+use crate::config::Config;
+use crate::object::Signature;
+use crate::utility::date_util::{ local_now, parse_date };
 
-// Hardcoded constants
-const COMMITER_NAME: &str = "Petro Bondar";
-const COMMITER_EMAIL: &str = "pb@gmail.com";
-const COMMITER_AFTER_STAMP: &str = "-0700";
+use anyhow::{ bail, Result };
+use std::env;
+use std::process::Command;
+use sha1::{ Sha1, Digest };
 
 pub fn add_data_prefix(prefix: &[u8], mut text: Vec<u8>) -> Vec<u8> {
     let mut result = prefix.to_vec();
@@ -16,61 +16,103 @@ pub fn add_data_prefix(prefix: &[u8], mut text: Vec<u8>) -> Vec<u8> {
     result
 }
 
-pub fn get_time_stamp_string() -> Result<String> {
-    Ok(SystemTime::now().duration_since(SystemTime::UNIX_EPOCH)?.as_secs().to_string())
-}
+/// Identity of `author` or `committer` from `GIT_<ROLE>_*` variables,
+/// then `<role>.name`/`user.name` config (same for email), dated now unless `GIT_<ROLE>_DATE` is set
+pub fn signature(role: &str) -> Result<Signature> {
+    let config = Config::load()?;
+    let variable = |field: &str| {
+        env::var(format!("GIT_{}_{field}", role.to_uppercase())).ok()
+    };
+    let lookup = |field: &str| {
+        variable(&field.to_uppercase())
+            .or_else(|| config.get(&format!("{role}.{field}")).ok().flatten())
+            .or_else(|| config.get(&format!("user.{field}")).ok().flatten())
+    };
 
-pub fn append_committer_data(contents: &mut Vec<u8>, timestamp: &str) {
-    contents.extend(
-        format!("{COMMITER_NAME} <{COMMITER_EMAIL}> {timestamp} {COMMITER_AFTER_STAMP}\n").bytes()
-    );
+    let name = lookup("name").filter(|name| !name.is_empty());
+    let email = lookup("email").or_else(|| env::var("EMAIL").ok());
+    let (Some(name), Some(email)) = (name, email) else {
+        bail!(
+            "{}{} identity unknown, please set user.name and user.email",
+            role[..1].to_uppercase(),
+            &role[1..]
+        );
+    };
+
+    let (time, tz) = match variable("DATE") {
+        Some(date) => parse_date(&date)?,
+        None => local_now(),
+    };
+    Ok(Signature { name: name.into_bytes(), email: email.into_bytes(), time, tz })
 }
 
 pub fn get_hash_from_data(data: &[u8]) -> String {
     hex::encode(Sha1::new().chain_update(data).finalize())
 }
 
-/// Returns tuples (<file name>, <mode>, <SHA-1>) from correct tree object
-pub fn parse_tree(binary: &[u8]) -> Result<Vec<(String, String, String)>> {
-    // Convert to string and divide it into blocks
-    #[allow(unsafe_code)]
-    let buff_string = unsafe { String::from_utf8_unchecked(binary.to_vec()) };
-    let (header, mut text) = buff_string
-        .split_once('\0')
-        .ok_or_else(|| anyhow!("Cannot separate header!"))?;
-
-    // If header block is for correct tree -> parse text to find tuples (<file name>, <mode>, <SHA-1>)
-    let mut contents: Vec<(String, String, String)> = Vec::new();
-    if
-        let ("tree", _tree_size) = header
-            .split_once(' ')
-            .ok_or_else(|| anyhow!("Not a tree type!"))?
-    {
-        // Simple parse with unchecked string
-        while !text.is_empty() {
-            // Check if struct is correct and we can extract mode
-            if let Some((mode, rest)) = text.split_once(' ') {
-                // Extract filename
-                let (file_name, rest) = rest
-                    .split_once('\0')
-                    .ok_or_else(|| anyhow!("Cannot separate file name!"))?;
+/// Compute blob SHA for contents without writing an object
+pub fn hash_blob(contents: &[u8]) -> String {
+    get_hash_from_data(&add_data_prefix(b"blob", contents.to_vec()))
+}
 
-                // Extract SHA-1
-                let (sha, rem) = rest.split_at(20);
-                text = rem;
+/// Clean up commit message: strip trailing spaces and surrounding empty lines, squeeze empty lines
+pub fn cleanup_message(text: &[u8]) -> Vec<u8> {
+    let mut result: Vec<u8> = Vec::new();
+    let mut pending_empty = false;
+    for line in text.split(|c| *c == b'\n').map(<[u8]>::trim_ascii_end) {
+        if line.is_empty() {
+            pending_empty = !result.is_empty();
+            continue;
+        }
+        if pending_empty {
+            result.push(b'\n');
+            pending_empty = false;
+        }
+        result.extend(line);
+        result.push(b'\n');
+    }
+    result
+}
 
-                // Add content
-                contents.push((file_name.to_string(), mode.to_string(), hex::encode(sha)));
+/// Editor command: `GIT_EDITOR`, `core.editor`, `VISUAL`, `EDITOR` or `vi`, todo lists are
+/// edited with `GIT_SEQUENCE_EDITOR` or `sequence.editor` when set
+pub fn editor(sequence: bool) -> Result<String> {
+    let config = Config::load()?;
+    let mut choices: Vec<Option<String>> = Vec::new();
+    if sequence {
+        choices.push(env::var("GIT_SEQUENCE_EDITOR").ok());
+        choices.push(config.get("sequence.editor")?);
+    }
+    choices.push(env::var("GIT_EDITOR").ok());
+    choices.push(config.get("core.editor")?);
+    choices.push(env::var("VISUAL").ok());
+    choices.push(env::var("EDITOR").ok());
+    Ok(choices.into_iter().flatten().find(|editor| !editor.is_empty()).unwrap_or("vi".into()))
+}
 
-                // Debug log
-                // println!("{_mode} {file_name}: {}", hex::encode(_sha));
-            } else {
-                bail!("Not a tree object!");
-            }
-        }
-    } else {
-        bail!("Not a tree object!");
+/// Let the user change the file in the editor, run by the shell (`:` leaves it as it is)
+pub fn launch_editor(path: &str, sequence: bool) -> Result<()> {
+    let editor = editor(sequence)?;
+    if editor == ":" {
+        return Ok(());
+    }
+    let status = Command::new("sh")
+        .arg("-c")
+        .arg(format!("{editor} \"$@\""))
+        .arg(&editor)
+        .arg(path)
+        .status()?;
+    if !status.success() {
+        bail!("There was a problem with the editor '{editor}'.");
     }
+    Ok(())
+}
 
-    Ok(contents)
-}
\ No newline at end of file
+/// Message without comment lines, cleaned up
+pub fn strip_comments(text: &[u8]) -> Vec<u8> {
+    let lines: Vec<&[u8]> = text
+        .split(|c| *c == b'\n')
+        .filter(|line| !line.starts_with(b"#"))
+        .collect();
+    cleanup_message(&lines.join(&b'\n'))
+}
@@ -55,7 +55,21 @@ fn write_random_contents(dir: &TempDir) -> Result<Vec<TestDirEntry>, Box<dyn std
     Ok(res)
 }
 
-fn get_tree_entries(sha: &String) -> Result<Vec<(String, String)>> {
+/// Run git once with arguments in current folder and return its stdout
+fn git_stdout(args: &[&str]) -> Result<String, Box<dyn std::error::Error>> {
+    let output = Command::new("git").args(args).output()?;
+    assert!(output.status.success(), "git {args:?} failed");
+    Ok(String::from_utf8(output.stdout)?)
+}
+
+/// Run our binary once with arguments in current folder and return its stdout
+fn mygit_stdout(args: &[&str]) -> Result<String, Box<dyn std::error::Error>> {
+    let output = Command::cargo_bin(CRATE_NAME)?.args(args).output()?;
+    assert!(output.status.success(), "{args:?} failed");
+    Ok(String::from_utf8(output.stdout)?)
+}
+
+fn get_tree_entries(sha: &str) -> Result<Vec<(String, String)>> {
     let path = format!(".git/objects/{}/{}", &sha[..2], &sha[2..sha.len()]);
     let data = fs::read(path)?;
     // Decompress data and read it to string
@@ -73,7 +87,7 @@ fn get_tree_entries(sha: &String) -> Result<Vec<(String, String)>> {
     let entries: Vec<_> = text
         .split('\n')
         .map(|s| {
-            let pair = s.split_once(' ').unwrap_or_else(|| ("\0", s));
+            let pair = s.split_once(' ').unwrap_or(("\0", s));
             (pair.0.to_string(), pair.1.to_string())
         })
         .collect();
@@ -191,7 +205,7 @@ fn write_blob() -> Result<(), Box<dyn std::error::Error>> {
 
     // Check hashed object
     print!("Checking hashed data");
-    check_cmd.args(["cat-file", "-p", &EXPECTED_OUT]);
+    check_cmd.args(["cat-file", "-p", EXPECTED_OUT]);
     check_cmd.assert().success().stdout(predicate::eq(TEST_DATA));
     println!(" - OK");
 
@@ -466,4 +480,1591 @@ fn clone_test() -> Result<(), Box<dyn std::error::Error>> {
     temp_folder_2.close()?;
 
     Ok(())
-}
\ No newline at end of file
+}
+
+/// CLONE AND COMMIT
+#[serial(comm)]
+#[test]
+fn clone_commit_test() -> Result<(), Box<dyn std::error::Error>> {
+    println!("------------ CLONE AND COMMIT -------------");
+    let temp_folder = assert_fs::TempDir::new()?;
+    env::set_current_dir(temp_folder.path())?;
+
+    print!("Clonning with git and mygit");
+    git_stdout(&["clone", "-q", TEST_REPO_1, "git"])?;
+    mygit_stdout(&["clone", TEST_REPO_1, "my"])?;
+    println!(" - OK");
+
+    // Files are staged with their tree modes, nothing shows up as changed
+    print!("Validating the index of the clone");
+    env::set_current_dir(temp_folder.child("git").path())?;
+    let expected = git_stdout(&["ls-files", "-s"])?;
+    env::set_current_dir(temp_folder.child("my").path())?;
+    assert_eq!(git_stdout(&["ls-files", "-s"])?, expected);
+    assert_eq!(mygit_stdout(&["diff", "--cached"])?, "");
+    assert_eq!(git_stdout(&["status", "--short"])?, "");
+    println!(" - OK");
+
+    // The clone stages the checked out files, so the next commit keeps them
+    print!("Calling your commit after clone");
+    for side in ["git", "my"] {
+        env::set_current_dir(temp_folder.child(side).path())?;
+        fs::write("added.txt", "added\n")?;
+        git_stdout(&["add", "added.txt"])?;
+        match side {
+            "git" => git_stdout(&["commit", "-q", "-m", "added"])?,
+            _ => mygit_stdout(&["commit", "-m", "added"])?,
+        };
+    }
+    env::set_current_dir(temp_folder.child("git").path())?;
+    let expected = git_stdout(&["ls-tree", "-r", "HEAD"])?;
+    env::set_current_dir(temp_folder.child("my").path())?;
+    assert_eq!(git_stdout(&["ls-tree", "-r", "HEAD"])?, expected);
+    println!(" - OK");
+
+    env::set_current_dir("/")?;
+    temp_folder.close()?;
+
+    Ok(())
+}
+
+/// DIFF
+#[serial(comm)]
+#[test]
+fn diff_test() -> Result<(), Box<dyn std::error::Error>> {
+    println!("------------ DIFF -------------");
+    let temp_folder = assert_fs::TempDir::new()?;
+    env::set_current_dir(temp_folder.path())?;
+
+    // Init and commit initial state
+    print!("Setup repo");
+    git_stdout(&["init"])?;
+    temp_folder.child("main.c").write_str("int main() {\n  a();\n  b();\n}\nvoid f() {\n  x;\n}\n")?;
+    temp_folder.child("gone.txt").write_str("bye\n")?;
+    temp_folder.child("data.bin").write_binary(b"bin\0ary")?;
+    temp_folder.child("dir/notes").write_str("one\ntwo")?;
+    git_stdout(&["add", "."])?;
+    git_stdout(&["commit", "-m", "initial"])?;
+    println!(" - OK");
+
+    // Change working tree and stage part of it
+    print!("Change files");
+    temp_folder.child("main.c").write_str("int main() {\n  a();\n  B();\n}\nvoid f() {\n  x;\n  y;\n}\n")?;
+    fs::remove_file("gone.txt")?;
+    temp_folder.child("data.bin").write_binary(b"bin\0ary2")?;
+    temp_folder.child("dir/notes").write_str("one\ntwo\nthree\n")?;
+    temp_folder.child("staged.txt").write_str("staged\n")?;
+    git_stdout(&["add", "staged.txt", "dir/notes"])?;
+    println!(" - OK");
+
+    // Compare all modes with git
+    for args in [
+        vec!["diff"],
+        vec!["diff", "--cached"],
+        vec!["diff", "HEAD"],
+        vec!["diff", "--stat"],
+        vec!["diff", "--numstat", "HEAD"],
+        vec!["diff", "--name-status", "HEAD"],
+        vec!["diff", "-U1", "--patience"],
+        vec!["diff", "--histogram", "HEAD", "--", "dir"],
+    ] {
+        print!("Calling your {}", args.join(" "));
+        assert_eq!(mygit_stdout(&args)?, git_stdout(&args)?);
+        println!(" - OK");
+    }
+
+    // Two trees
+    print!("Comparing commits");
+    git_stdout(&["add", "-A"])?;
+    git_stdout(&["commit", "-m", "second"])?;
+    assert_eq!(mygit_stdout(&["diff", "HEAD~1", "HEAD"])?, git_stdout(&["diff", "HEAD~1", "HEAD"])?);
+    assert_eq!(mygit_stdout(&["diff", "--stat", "HEAD~1..HEAD"])?, git_stdout(&["diff", "--stat", "HEAD~1..HEAD"])?);
+    println!(" - OK");
+
+    env::set_current_dir("/")?;
+    temp_folder.close()?;
+
+    Ok(())
+}
+
+/// DIFF RANDOM
+#[serial(comm)]
+#[test]
+fn diff_random_test() -> Result<(), Box<dyn std::error::Error>> {
+    println!("------------ DIFF RANDOM -------------");
+    let temp_folder = assert_fs::TempDir::new()?;
+    env::set_current_dir(temp_folder.path())?;
+    git_stdout(&["init"])?;
+
+    // Small pool of lines (indented, braces, blanks), so changes have many ways to line up
+    let pool = ["{\n", "}\n", "\n", "a\n", "  b\n", "    c\n", "\tif x\n", "return\n", "  }\n"];
+    let mut rng = StdRng::seed_from_u64(26);
+    let random_lines = |rng: &mut StdRng, count: usize| -> Vec<&str> {
+        (0..count).map(|_| pool[rng.gen_range(0..pool.len())]).collect()
+    };
+
+    print!("Calling your diff on random changes");
+    for _ in 0..40 {
+        let count = rng.gen_range(0..25);
+        let old = random_lines(&mut rng, count);
+        let mut new = old.clone();
+        for _ in 0..rng.gen_range(1..5) {
+            let at = rng.gen_range(0..=new.len());
+            let len = rng.gen_range(1..5);
+            if rng.gen_bool(0.5) {
+                let inserted = random_lines(&mut rng, len);
+                new.splice(at..at, inserted);
+            } else {
+                new.drain(at..(at + len).min(new.len()));
+            }
+        }
+        temp_folder.child("f").write_str(&old.concat())?;
+        git_stdout(&["add", "f"])?;
+        temp_folder.child("f").write_str(&new.concat())?;
+
+        for algorithm in ["myers", "patience", "histogram"] {
+            let args = ["diff", "-U1", &format!("--diff-algorithm={algorithm}")];
+            assert_eq!(mygit_stdout(&args)?, git_stdout(&args)?, "{old:?} -> {new:?}");
+        }
+    }
+    println!(" - OK");
+
+    env::set_current_dir("/")?;
+    temp_folder.close()?;
+
+    Ok(())
+}
+
+/// DIFF-TREE
+#[serial(comm)]
+#[test]
+fn diff_tree_test() -> Result<(), Box<dyn std::error::Error>> {
+    println!("------------ DIFF-TREE -------------");
+    let temp_folder = assert_fs::TempDir::new()?;
+    env::set_current_dir(temp_folder.path())?;
+
+    // Initial commit with several files
+    print!("Setup repo");
+    git_stdout(&["init"])?;
+    let numbers = |from: usize, to: usize| -> String {
+        (from..to).map(|n| format!("line {n}\n")).collect()
+    };
+    temp_folder.child("moved.txt").write_str(&numbers(0, 40))?;
+    temp_folder.child("source.txt").write_str(&numbers(100, 140))?;
+    temp_folder.child("src/deep/file.c").write_str(&numbers(200, 240))?;
+    temp_folder.child("keep/same.txt").write_str("same\n")?;
+    git_stdout(&["add", "."])?;
+    git_stdout(&["commit", "-m", "initial"])?;
+    println!(" - OK");
+
+    // Rename, rename with edit, copy and modification
+    print!("Second commit");
+    git_stdout(&["mv", "moved.txt", "renamed.txt"])?;
+    git_stdout(&["mv", "src/deep/file.c", "src/file.c"])?;
+    temp_folder.child("src/file.c").write_str(&(numbers(200, 240) + "extra\n"))?;
+    temp_folder.child("copy.txt").write_str(&numbers(100, 140))?;
+    temp_folder.child("source.txt").write_str(&numbers(100, 141))?;
+    git_stdout(&["add", "-A"])?;
+    git_stdout(&["commit", "-m", "second"])?;
+    println!(" - OK");
+
+    for args in [
+        vec!["diff-tree", "HEAD"],
+        vec!["diff-tree", "-r", "HEAD"],
+        vec!["diff-tree", "-r", "-M", "HEAD"],
+        vec!["diff-tree", "-r", "-M90%", "--name-status", "HEAD"],
+        vec!["diff-tree", "-r", "-C", "HEAD"],
+        vec!["diff-tree", "-r", "--root", "HEAD~1"],
+        vec!["diff-tree", "-r", "-M", "HEAD~1", "HEAD", "--", "src"],
+    ] {
+        print!("Calling your {}", args.join(" "));
+        assert_eq!(mygit_stdout(&args)?, git_stdout(&args)?);
+        println!(" - OK");
+    }
+
+    env::set_current_dir("/")?;
+    temp_folder.close()?;
+
+    Ok(())
+}
+
+/// LOG
+#[serial(comm)]
+#[test]
+fn log_test() -> Result<(), Box<dyn std::error::Error>> {
+    println!("--------------- LOG ----------------");
+    let temp_folder = assert_fs::TempDir::new()?;
+    env::set_current_dir(temp_folder.path())?;
+
+    // History with a side branch merged back
+    print!("Setup repo");
+    git_stdout(&["init"])?;
+    let commit = |file: &str, text: &str, message: &str| -> Result<(), Box<dyn std::error::Error>> {
+        temp_folder.child(file).write_str(text)?;
+        git_stdout(&["add", "."])?;
+        git_stdout(&["commit", "-m", message])?;
+        Ok(())
+    };
+    commit("a.txt", "a\n", "first\n\nwith body")?;
+    commit("b.txt", "b\n", "second")?;
+    git_stdout(&["checkout", "-b", "side"])?;
+    commit("dir/c.txt", "c\n", "side work")?;
+    commit("b.txt", "b\nb\n", "side edit of b")?;
+    git_stdout(&["checkout", "-"])?;
+    commit("a.txt", "a\na\n", "third")?;
+    git_stdout(&["merge", "--no-edit", "side"])?;
+    // Branch forked before the merge, merged after another commit
+    git_stdout(&["checkout", "-b", "other", "HEAD~1"])?;
+    commit("d.txt", "d\n", "other work")?;
+    git_stdout(&["checkout", "-"])?;
+    commit("a.txt", "a\na\na\n", "fourth")?;
+    git_stdout(&["merge", "--no-edit", "other"])?;
+    println!(" - OK");
+
+    for args in [
+        vec!["log"],
+        vec!["log", "--oneline"],
+        vec!["log", "--oneline", "--graph"],
+        vec!["log", "--graph"],
+        vec!["log", "--graph", "--format=%s%n%b"],
+        vec!["log", "--topo-order", "--format=%h %p %an <%ae> %s"],
+        vec!["log", "-n", "2", "--first-parent", "--pretty=fuller"],
+        vec!["log", "--format=%H%n%b", "--grep=body"],
+        vec!["log", "--oneline", "HEAD~1..side"],
+        vec!["log", "--oneline", "--", "b.txt"],
+        vec!["log", "--oneline", "--", "dir"],
+    ] {
+        print!("Calling your {}", args.join(" "));
+        assert_eq!(mygit_stdout(&args)?, git_stdout(&args)?);
+        println!(" - OK");
+    }
+
+    env::set_current_dir("/")?;
+    temp_folder.close()?;
+
+    Ok(())
+}
+
+/// COMMIT
+#[serial(comm)]
+#[test]
+fn commit_porcelain_test() -> Result<(), Box<dyn std::error::Error>> {
+    println!("-------------- COMMIT --------------");
+    let temp_folder = assert_fs::TempDir::new()?;
+    env::set_current_dir(temp_folder.path())?;
+    git_stdout(&["init"])?;
+
+    // Root commit from staged files in nested folders
+    print!("Calling your commit (root)");
+    temp_folder.child("a.txt").write_str("a\n")?;
+    temp_folder.child("dir/sub/b.txt").write_str("b\n")?;
+    temp_folder.child("dir-c.txt").write_str("c\n")?;
+    git_stdout(&["add", "."])?;
+    let output = mygit_stdout(&["commit", "-m", "first", "-m", "body"])?;
+    assert!(output.starts_with("[master (root-commit) "));
+    assert_eq!(git_stdout(&["rev-parse", "HEAD^{tree}"])?, git_stdout(&["write-tree"])?);
+    assert_eq!(git_stdout(&["log", "--format=%B"])?, "first\n\nbody\n\n");
+    println!(" - OK");
+
+    // Nothing staged, then an explicitly empty commit
+    print!("Calling your commit (empty)");
+    mygit_stdout(&["commit", "-m", "nothing"])?;
+    assert_eq!(git_stdout(&["rev-list", "--count", "HEAD"])?, "1\n");
+    mygit_stdout(&["commit", "--allow-empty", "-m", "empty"])?;
+    assert_eq!(git_stdout(&["rev-list", "--count", "HEAD"])?, "2\n");
+    println!(" - OK");
+
+    // Amend replaces the last commit and keeps its parent
+    print!("Calling your commit --amend");
+    temp_folder.child("msg.txt").write_str("from file\n\n\n\nsecond paragraph  \n")?;
+    git_stdout(&["rm", "-q", "a.txt"])?;
+    mygit_stdout(&["commit", "--amend", "-F", "msg.txt"])?;
+    assert_eq!(git_stdout(&["rev-list", "--count", "HEAD"])?, "2\n");
+    assert_eq!(git_stdout(&["log", "-1", "--format=%B"])?, "from file\n\nsecond paragraph\n\n");
+    assert_eq!(git_stdout(&["rev-parse", "HEAD^{tree}"])?, git_stdout(&["write-tree"])?);
+    let reflog = git_stdout(&["reflog", "--format=%gs"])?;
+    assert_eq!(reflog, "commit (amend): from file\ncommit: empty\ncommit (initial): first\n");
+    Command::cargo_bin(CRATE_NAME)?
+        .args(["commit", "--allow-empty", "-m", "x", "-F", "msg.txt"])
+        .assert()
+        .stderr(predicate::str::contains("Option -m cannot be combined with -F"));
+    assert_eq!(git_stdout(&["rev-list", "--count", "HEAD"])?, "2\n");
+    println!(" - OK");
+
+    env::set_current_dir("/")?;
+    temp_folder.close()?;
+
+    Ok(())
+}
+
+/// COMMIT-TREE PARENTS
+#[serial(comm)]
+#[test]
+fn commit_tree_parents_test() -> Result<(), Box<dyn std::error::Error>> {
+    println!("------------ COMMIT-TREE PARENTS -------------");
+    let temp_folder = assert_fs::TempDir::new()?;
+    env::set_current_dir(temp_folder.path())?;
+    git_stdout(&["init"])?;
+    temp_folder.child("a.txt").write_str("a\n")?;
+    git_stdout(&["add", "."])?;
+    let tree = git_stdout(&["write-tree"])?.trim_end().to_string();
+
+    // Root commit with message from stdin
+    print!("Calling your commit-tree without parents");
+    let mut child = Command::cargo_bin(CRATE_NAME)?
+        .args(["commit-tree", &tree])
+        .stdin(std::process::Stdio::piped())
+        .stdout(std::process::Stdio::piped())
+        .spawn()?;
+    child.stdin.take().ok_or(anyhow!("No stdin"))?.write_all(b"from stdin\n")?;
+    let output = child.wait_with_output()?;
+    let root = String::from_utf8(output.stdout)?.trim_end().to_string();
+    assert_eq!(git_stdout(&["rev-list", "--parents", "-n", "1", &root])?, format!("{root}\n"));
+    assert_eq!(git_stdout(&["log", "-1", "--format=%B", &root])?, "from stdin\n\n");
+    println!(" - OK");
+
+    // Merge commit with repeated -m paragraphs, duplicate parents are dropped
+    print!("Calling your commit-tree with several parents");
+    let side = mygit_stdout(&["commit-tree", &tree, "-p", &root, "-m", "side"])?;
+    let side = side.trim_end();
+    let args = ["commit-tree", &tree, "-p", &root, "-p", side, "-p", &root, "-m", "a", "-m", "b"];
+    let merge = mygit_stdout(&args)?;
+    let merge = merge.trim_end();
+    assert_eq!(
+        git_stdout(&["rev-list", "--parents", "-n", "1", merge])?,
+        format!("{merge} {root} {side}\n")
+    );
+    assert_eq!(git_stdout(&["log", "-1", "--format=%B", merge])?, "a\n\nb\n\n");
+    println!(" - OK");
+
+    // Objects of wrong type are rejected
+    print!("Calling your commit-tree with invalid objects");
+    Command::cargo_bin(CRATE_NAME)?
+        .args(["commit-tree", &root, "-m", "x"])
+        .assert()
+        .stderr(predicate::str::contains("is not a valid 'tree' object"));
+    Command::cargo_bin(CRATE_NAME)?
+        .args(["commit-tree", &tree, "-p", &tree, "-m", "x"])
+        .assert()
+        .stderr(predicate::str::contains("is not a valid 'commit' object"));
+    Command::cargo_bin(CRATE_NAME)?
+        .args(["commit-tree", &tree, "-m", "x", "-F", "-"])
+        .assert()
+        .stderr(predicate::str::contains("Option -m cannot be combined with -F"));
+    println!(" - OK");
+
+    env::set_current_dir("/")?;
+    temp_folder.close()?;
+
+    Ok(())
+}
+
+/// IDENTITY
+#[serial(comm)]
+#[test]
+fn identity_test() -> Result<(), Box<dyn std::error::Error>> {
+    println!("------------ IDENTITY -------------");
+    let temp_folder = assert_fs::TempDir::new()?;
+    env::set_current_dir(temp_folder.path())?;
+    git_stdout(&["init"])?;
+    git_stdout(&["config", "user.name", "Config Name"])?;
+    git_stdout(&["config", "user.email", "config@example.com"])?;
+    let tree = git_stdout(&["write-tree"])?.trim_end().to_string();
+
+    // Repository config
+    print!("Calling your commit-tree with identity from config");
+    let sha = mygit_stdout(&["commit-tree", &tree, "-m", "config"])?;
+    let format = "--format=%an <%ae>|%cn <%ce>";
+    assert_eq!(
+        git_stdout(&["log", "-1", format, sha.trim_end()])?,
+        "Config Name <config@example.com>|Config Name <config@example.com>\n"
+    );
+    println!(" - OK");
+
+    // Environment overrides config, dates in several formats
+    print!("Calling your commit-tree with identity from environment");
+    let output = Command::cargo_bin(CRATE_NAME)?
+        .args(["commit-tree", &tree, "-m", "env"])
+        .env("GIT_AUTHOR_NAME", "Env Author")
+        .env("GIT_AUTHOR_DATE", "Thu, 07 Apr 2005 22:13:13 +0200")
+        .env("GIT_COMMITTER_EMAIL", "committer@example.com")
+        .env("GIT_COMMITTER_DATE", "@1112911993 -0130")
+        .output()?;
+    let sha = String::from_utf8(output.stdout)?;
+    let format = "--format=%an <%ae> %ad%n%cn <%ce> %cd";
+    assert_eq!(
+        git_stdout(&["log", "-1", format, "--date=raw", sha.trim_end()])?,
+        "Env Author <config@example.com> 1112904793 +0200\n\
+         Config Name <committer@example.com> 1112911993 -0130\n"
+    );
+    println!(" - OK");
+
+    env::set_current_dir("/")?;
+    temp_folder.close()?;
+
+    Ok(())
+}
+
+/// CONFIG
+#[serial(comm)]
+#[test]
+fn config_test() -> Result<(), Box<dyn std::error::Error>> {
+    println!("------------ CONFIG -------------");
+    let temp_folder = assert_fs::TempDir::new()?;
+    env::set_current_dir(temp_folder.path())?;
+    git_stdout(&["init"])?;
+    let config = "# keep me\n[core]\n\tbare = false ; comment\n\tflag\n[remote \"origin\"]\n\
+                  \turl = \"quoted \\\"value\\\"\"\n\tfetch = one\n\tfetch = two\n[include]\n\tpath = extra\n";
+    temp_folder.child(".git/config").write_str(config)?;
+    temp_folder.child(".git/extra").write_str("[extra]\n\tsize = 2k\n")?;
+
+    // Reading compared with git
+    print!("Calling your config (read)");
+    for args in [
+        &["--get", "core.bare"][..],
+        &["core.flag"],
+        &["--bool", "core.flag"],
+        &["--get-all", "remote.origin.fetch"],
+        &["remote.origin.url"],
+        &["--int", "extra.size"],
+        &["--list", "--show-origin"],
+        &["--local", "--list", "--show-scope"],
+    ] {
+        let args = [&["config"], args].concat();
+        assert_eq!(mygit_stdout(&args)?, git_stdout(&args)?);
+    }
+    println!(" - OK");
+
+    // Writing keeps comments and is readable by git
+    print!("Calling your config (write)");
+    mygit_stdout(&["config", "core.bare", "true"])?;
+    mygit_stdout(&["config", "--set", "remote.origin.prune", "yes"])?;
+    mygit_stdout(&["config", "new.Sub.key", " spaced "])?;
+    mygit_stdout(&["config", "--unset-all", "remote.origin.fetch"])?;
+    mygit_stdout(&["config", "--unset", "core.flag"])?;
+    assert_eq!(git_stdout(&["config", "core.bare"])?, "true\n");
+    assert_eq!(git_stdout(&["config", "remote.origin.prune"])?, "yes\n");
+    assert_eq!(git_stdout(&["config", "new.Sub.key"])?, " spaced \n");
+    Command::new("git").args(["config", "--get-all", "remote.origin.fetch"]).assert().failure();
+    let contents = fs::read_to_string(".git/config")?;
+    assert!(contents.starts_with("# keep me\n[core]\n\tbare = true\n[remote \"origin\"]\n"));
+    println!(" - OK");
+
+    env::set_current_dir("/")?;
+    temp_folder.close()?;
+
+    Ok(())
+}
+
+/// BRANCH
+#[serial(comm)]
+#[test]
+fn branch_test() -> Result<(), Box<dyn std::error::Error>> {
+    println!("------------ BRANCH -------------");
+    let temp_folder = assert_fs::TempDir::new()?;
+    env::set_current_dir(temp_folder.path())?;
+    git_stdout(&["init"])?;
+    git_stdout(&["commit", "--allow-empty", "-m", "one"])?;
+    git_stdout(&["commit", "--allow-empty", "-m", "two"])?;
+
+    // Creation and listing compared with git
+    print!("Calling your branch (create and list)");
+    mygit_stdout(&["branch", "side", "HEAD~1"])?;
+    mygit_stdout(&["branch", "feature/long-name"])?;
+    mygit_stdout(&["branch", "-u", "side", "feature/long-name"])?;
+    git_stdout(&["update-ref", "refs/remotes/origin/master", "HEAD~1"])?;
+    git_stdout(&["symbolic-ref", "refs/remotes/origin/HEAD", "refs/remotes/origin/master"])?;
+    let listings = [&["branch"][..], &["branch", "-v"], &["branch", "-vv"], &["branch", "-a", "-v"]];
+    for args in listings {
+        assert_eq!(mygit_stdout(args)?, git_stdout(args)?);
+    }
+    let reflog = fs::read_to_string(".git/logs/refs/heads/side")?;
+    assert!(reflog.ends_with("\tbranch: Created from HEAD~1\n"));
+    println!(" - OK");
+
+    // Rename moves reflog and config, delete checks merged-ness
+    print!("Calling your branch (rename and delete)");
+    mygit_stdout(&["branch", "-m", "feature/long-name", "renamed"])?;
+    assert_eq!(git_stdout(&["config", "branch.renamed.merge"])?, "refs/heads/side\n");
+    assert!(fs::read_to_string(".git/logs/refs/heads/renamed")?.contains("Branch: renamed"));
+    let output = Command::cargo_bin(CRATE_NAME)?.args(["branch", "-d", "renamed"]).output()?;
+    assert!(String::from_utf8(output.stderr)?.contains("is not fully merged"));
+    mygit_stdout(&["branch", "-D", "renamed"])?;
+    assert!(mygit_stdout(&["branch", "-d", "side"])?.starts_with("Deleted branch side (was "));
+    assert_eq!(git_stdout(&["branch"])?, "* master\n");
+    Command::new("git").args(["config", "branch.renamed.merge"]).assert().failure();
+    println!(" - OK");
+
+    env::set_current_dir("/")?;
+    temp_folder.close()?;
+
+    Ok(())
+}
+
+/// SWITCH
+#[serial(comm)]
+#[test]
+fn switch_test() -> Result<(), Box<dyn std::error::Error>> {
+    println!("------------ SWITCH -------------");
+    let temp_folder = assert_fs::TempDir::new()?;
+    env::set_current_dir(temp_folder.path())?;
+    git_stdout(&["init"])?;
+    temp_folder.child("f").write_str("a\n")?;
+    temp_folder.child("d/g").write_str("b\n")?;
+    temp_folder.child("k").write_str("keep\n")?;
+    git_stdout(&["add", "."])?;
+    git_stdout(&["commit", "-m", "one"])?;
+    git_stdout(&["checkout", "-b", "side"])?;
+    temp_folder.child("f").write_str("a2\n")?;
+    git_stdout(&["rm", "-q", "d/g"])?;
+    temp_folder.child("d2").write_str("n\n")?;
+    git_stdout(&["add", "."])?;
+    git_stdout(&["commit", "-m", "two"])?;
+    git_stdout(&["checkout", "master"])?;
+
+    // Changed files are updated, removed ones deleted, local changes carried over
+    print!("Calling your switch (clean and carried changes)");
+    temp_folder.child("k").write_str("local\n")?;
+    assert_eq!(mygit_stdout(&["switch", "side"])?, "M\tk\nSwitched to branch 'side'\n");
+    temp_folder.child("d").assert(predicate::path::missing());
+    temp_folder.child("f").assert("a2\n");
+    assert_eq!(git_stdout(&["status", "--short"])?, " M k\n");
+    let reflog = fs::read_to_string(".git/logs/HEAD")?;
+    assert!(reflog.ends_with("\tcheckout: moving from master to side\n"));
+    println!(" - OK");
+
+    // Conflicting local changes abort the switch unless forced
+    print!("Calling your switch (conflicts, -c and --detach)");
+    temp_folder.child("f").write_str("dirty\n")?;
+    let output = Command::cargo_bin(CRATE_NAME)?.args(["switch", "master"]).output()?;
+    assert!(String::from_utf8(output.stderr)?.contains("would be overwritten by checkout:\n\tf\n"));
+    assert_eq!(git_stdout(&["branch", "--show-current"])?, "side\n");
+    mygit_stdout(&["checkout", "-f", "master"])?;
+    assert_eq!(git_stdout(&["status", "--short"])?, "");
+    temp_folder.child("d/g").assert("b\n");
+    assert_eq!(mygit_stdout(&["switch", "-c", "new"])?, "Switched to a new branch 'new'\n");
+    assert!(mygit_stdout(&["checkout", "--detach", "side"])?.starts_with("HEAD is now at "));
+    assert_eq!(git_stdout(&["rev-parse", "HEAD"])?, git_stdout(&["rev-parse", "side"])?);
+    assert_eq!(git_stdout(&["status", "--short"])?, "");
+    println!(" - OK");
+
+    env::set_current_dir("/")?;
+    temp_folder.close()?;
+
+    Ok(())
+}
+
+/// MODES
+#[serial(comm)]
+#[test]
+fn modes_test() -> Result<(), Box<dyn std::error::Error>> {
+    use std::os::unix::fs::{ symlink, PermissionsExt };
+
+    println!("------------ MODES -------------");
+    let temp_folder = assert_fs::TempDir::new()?;
+    env::set_current_dir(temp_folder.path())?;
+    git_stdout(&["init"])?;
+    temp_folder.child("run.sh").write_str("echo hi\n")?;
+    fs::set_permissions("run.sh", fs::Permissions::from_mode(0o755))?;
+    temp_folder.child("target").write_str("t\n")?;
+    symlink("target", "link")?;
+
+    // Exec bit and symlink are recorded like git does
+    print!("Calling your write-tree (exec bit and symlink)");
+    git_stdout(&["add", "."])?;
+    assert_eq!(mygit_stdout(&["write-tree"])?, git_stdout(&["write-tree"])?);
+    git_stdout(&["config", "core.filemode", "false"])?;
+    fs::set_permissions("run.sh", fs::Permissions::from_mode(0o644))?;
+    assert_eq!(mygit_stdout(&["write-tree"])?, git_stdout(&["write-tree"])?);
+    git_stdout(&["config", "core.filemode", "true"])?;
+    fs::set_permissions("run.sh", fs::Permissions::from_mode(0o755))?;
+    println!(" - OK");
+
+    // Checkout recreates them from the tree
+    print!("Calling your switch (exec bit and symlink)");
+    git_stdout(&["commit", "-m", "modes"])?;
+    git_stdout(&["switch", "--orphan", "empty"])?;
+    git_stdout(&["commit", "--allow-empty", "-m", "empty"])?;
+    mygit_stdout(&["switch", "master"])?;
+    assert_eq!(fs::metadata("run.sh")?.permissions().mode() & 0o111, 0o111);
+    assert_eq!(fs::read_link("link")?, std::path::Path::new("target"));
+    assert_eq!(git_stdout(&["status", "--short"])?, "");
+    git_stdout(&["config", "core.symlinks", "false"])?;
+    mygit_stdout(&["switch", "empty"])?;
+    mygit_stdout(&["switch", "master"])?;
+    temp_folder.child("link").assert("target");
+    assert_eq!(git_stdout(&["status", "--short"])?, "");
+    println!(" - OK");
+
+    env::set_current_dir("/")?;
+    temp_folder.close()?;
+
+    Ok(())
+}
+
+/// SUBMODULE
+#[serial(comm)]
+#[test]
+fn submodule_test() -> Result<(), Box<dyn std::error::Error>> {
+    println!("------------ SUBMODULE -------------");
+    let temp_folder = assert_fs::TempDir::new()?;
+    temp_folder.child("lib/lib.txt").write_str("lib\n")?;
+    temp_folder.child("super/main.txt").write_str("main\n")?;
+    env::set_current_dir(temp_folder.child("lib").path())?;
+    git_stdout(&["init"])?;
+    git_stdout(&["add", "."])?;
+    git_stdout(&["commit", "-m", "lib"])?;
+    git_stdout(&["gc", "-q"])?;
+    env::set_current_dir(temp_folder.child("super").path())?;
+    git_stdout(&["init"])?;
+    git_stdout(&["-c", "protocol.file.allow=always", "submodule", "add", "-q", "../lib", "lib"])?;
+    git_stdout(&["add", "."])?;
+    git_stdout(&["commit", "-m", "super"])?;
+    env::set_current_dir(temp_folder.path())?;
+    git_stdout(&["clone", "-q", "super", "clone"])?;
+    env::set_current_dir(temp_folder.child("clone").path())?;
+
+    // Fresh clone has an empty folder for the submodule
+    print!("Calling your submodule (status, init, update)");
+    temp_folder.child("clone/lib").assert(predicate::path::is_dir());
+    assert_eq!(mygit_stdout(&["submodule", "status"])?, git_stdout(&["submodule", "status"])?);
+    let output = mygit_stdout(&["submodule", "init"])?;
+    assert!(output.starts_with("Submodule 'lib' ("));
+    assert!(output.ends_with("/lib) registered for path 'lib'\n"));
+    assert_eq!(git_stdout(&["config", "submodule.lib.active"])?, "true\n");
+    assert!(mygit_stdout(&["submodule", "update"])?.starts_with("Submodule path 'lib': checked out '"));
+    temp_folder.child("clone/lib/lib.txt").assert("lib\n");
+    assert_eq!(mygit_stdout(&["submodule"])?, git_stdout(&["submodule", "status"])?);
+    assert_eq!(git_stdout(&["status", "--short"])?, "");
+    assert_eq!(mygit_stdout(&["write-tree"])?, git_stdout(&["write-tree"])?);
+    println!(" - OK");
+
+    // Failed clones leave the submodule unpopulated
+    print!("Calling your submodule (failed update)");
+    env::set_current_dir(temp_folder.path())?;
+    temp_folder.child("empty/e.txt").write_str("e\n")?;
+    env::set_current_dir(temp_folder.child("empty").path())?;
+    git_stdout(&["init"])?;
+    git_stdout(&["add", "."])?;
+    git_stdout(&["commit", "-m", "empty"])?;
+    env::set_current_dir(temp_folder.path())?;
+    git_stdout(&["clone", "-q", "super", "clone2"])?;
+    env::set_current_dir(temp_folder.child("clone2").path())?;
+    let empty = temp_folder.child("empty").path().to_string_lossy().to_string();
+    git_stdout(&["config", "submodule.lib.url", &empty])?;
+    let output = Command::cargo_bin(CRATE_NAME)?.args(["submodule", "update"]).output()?;
+    assert!(String::from_utf8(output.stderr)?.contains("commit not found in the repository"));
+    temp_folder.child("clone2/lib/.git").assert(predicate::path::missing());
+    assert!(mygit_stdout(&["submodule", "status"])?.starts_with('-'));
+    println!(" - OK");
+
+    env::set_current_dir("/")?;
+    temp_folder.close()?;
+
+    Ok(())
+}
+
+/// TAG
+#[serial(comm)]
+#[test]
+fn tag_test() -> Result<(), Box<dyn std::error::Error>> {
+    println!("------------ TAG -------------");
+    let temp_folder = assert_fs::TempDir::new()?;
+    env::set_current_dir(temp_folder.path())?;
+    git_stdout(&["init"])?;
+    git_stdout(&["commit", "--allow-empty", "-m", "first", "-m", "body"])?;
+    git_stdout(&["commit", "--allow-empty", "-m", "second"])?;
+
+    // Created tags are listed the same way by git
+    print!("Calling your tag (create and list)");
+    mygit_stdout(&["tag", "light", "HEAD~1"])?;
+    mygit_stdout(&["tag", "-a", "-m", "release", "-m", "notes", "v1.0"])?;
+    mygit_stdout(&["tag", "-m", "nested", "release/candidate", "v1.0"])?;
+    assert_eq!(git_stdout(&["cat-file", "-t", "v1.0"])?, "tag\n");
+    assert_eq!(git_stdout(&["rev-parse", "light"])?, git_stdout(&["rev-parse", "HEAD~1"])?);
+    for args in [&["tag"][..], &["tag", "-n"], &["tag", "-n3"], &["tag", "-l", "v*", "r*"]] {
+        assert_eq!(mygit_stdout(args)?, git_stdout(args)?);
+    }
+    println!(" - OK");
+
+    // Peeling tags in revisions, replacing and deleting them
+    print!("Calling your tag (peel, force and delete)");
+    assert_eq!(
+        mygit_stdout(&["log", "--oneline", "release/candidate"])?,
+        git_stdout(&["log", "--oneline", "release/candidate"])?
+    );
+    let output = Command::cargo_bin(CRATE_NAME)?.args(["tag", "light"]).output()?;
+    assert!(String::from_utf8(output.stderr)?.contains("tag 'light' already exists"));
+    assert!(mygit_stdout(&["tag", "-f", "light"])?.starts_with("Updated tag 'light' (was "));
+    assert!(mygit_stdout(&["tag", "-d", "light"])?.starts_with("Deleted tag 'light' (was "));
+    assert_eq!(git_stdout(&["tag"])?, "release/candidate\nv1.0\n");
+    println!(" - OK");
+
+    env::set_current_dir("/")?;
+    temp_folder.close()?;
+
+    Ok(())
+}
+
+/// REFS
+#[serial(comm)]
+#[test]
+fn refs_test() -> Result<(), Box<dyn std::error::Error>> {
+    println!("------------ REFS -------------");
+    let temp_folder = assert_fs::TempDir::new()?;
+    env::set_current_dir(temp_folder.path())?;
+    git_stdout(&["init"])?;
+    git_stdout(&["commit", "--allow-empty", "-m", "first"])?;
+    git_stdout(&["tag", "-a", "-m", "release", "v1"])?;
+    git_stdout(&["branch", "feature/packed"])?;
+    git_stdout(&["pack-refs", "--all"])?;
+    git_stdout(&["commit", "--allow-empty", "-m", "second"])?;
+
+    // Packed refs are read together with the loose ones
+    print!("Calling your show-ref (packed refs)");
+    for args in [&["show-ref"][..], &["show-ref", "-d", "--tags"], &["show-ref", "--head", "-s"]] {
+        assert_eq!(mygit_stdout(args)?, git_stdout(args)?);
+    }
+    assert_eq!(mygit_stdout(&["branch", "-v"])?, git_stdout(&["branch", "-v"])?);
+    assert_eq!(
+        mygit_stdout(&["log", "--oneline", "feature/packed"])?,
+        git_stdout(&["log", "--oneline", "feature/packed"])?
+    );
+    println!(" - OK");
+
+    // Updates check the old value, deleting removes the packed entry
+    print!("Calling your update-ref (old value check and delete)");
+    let packed = git_stdout(&["rev-parse", "feature/packed"])?.trim().to_string();
+    let output = Command::cargo_bin(CRATE_NAME)?
+        .args(["update-ref", "refs/heads/feature/packed", "HEAD", "HEAD"])
+        .output()?;
+    assert!(String::from_utf8(output.stderr)?.contains("but expected"));
+    mygit_stdout(&["update-ref", "-m", "moved", "refs/heads/feature/packed", "HEAD", &packed])?;
+    assert_eq!(git_stdout(&["rev-parse", "feature/packed"])?, git_stdout(&["rev-parse", "HEAD"])?);
+    mygit_stdout(&["update-ref", "-d", "refs/tags/v1"])?;
+    assert_eq!(git_stdout(&["tag"])?, "");
+    assert!(!fs::read_to_string(".git/packed-refs")?.contains("refs/tags/v1"));
+    println!(" - OK");
+
+    env::set_current_dir("/")?;
+    temp_folder.close()?;
+
+    Ok(())
+}
+
+/// REFLOG
+#[serial(comm)]
+#[test]
+fn reflog_test() -> Result<(), Box<dyn std::error::Error>> {
+    println!("------------ REFLOG -------------");
+    let temp_folder = assert_fs::TempDir::new()?;
+    env::set_current_dir(temp_folder.path())?;
+    git_stdout(&["init"])?;
+
+    // Entries written by our commands are read by git
+    print!("Calling your reflog (recording and show)");
+    mygit_stdout(&["commit", "--allow-empty", "-m", "first"])?;
+    mygit_stdout(&["commit", "--allow-empty", "-m", "second"])?;
+    mygit_stdout(&["switch", "-c", "side"])?;
+    mygit_stdout(&["update-ref", "-m", "rewind", "refs/heads/side", "HEAD~1"])?;
+    for args in [&["reflog"][..], &["reflog", "show", "side"], &["reflog", "-n", "2"]] {
+        assert_eq!(mygit_stdout(args)?, git_stdout(args)?);
+    }
+    for revision in ["HEAD@{1}", "side@{1}~1", "@{0}"] {
+        let args = ["log", "--oneline", revision];
+        assert_eq!(mygit_stdout(&args)?, git_stdout(&args)?);
+    }
+    println!(" - OK");
+
+    // Dates in the forms git accepts select the value the ref had at that time
+    print!("Calling your reflog (date selectors)");
+    for revision in [
+        "HEAD@{1760000000}",
+        "HEAD@{2000000000}",
+        "side@{Oct 18 2099}",
+        "side@{2099-10-18.23:40:00}",
+        "HEAD@{1.hour.ago}",
+    ] {
+        let args = ["log", "--oneline", revision];
+        assert_eq!(mygit_stdout(&args)?, git_stdout(&args)?);
+    }
+    println!(" - OK");
+
+    // Removed entries disappear for git too
+    print!("Calling your reflog (delete and expire)");
+    mygit_stdout(&["reflog", "delete", "HEAD@{1}"])?;
+    assert_eq!(mygit_stdout(&["reflog"])?, git_stdout(&["reflog"])?);
+    assert_eq!(git_stdout(&["reflog"])?.lines().count(), 3);
+    mygit_stdout(&["reflog", "expire", "--expire=now", "--all"])?;
+    assert_eq!(git_stdout(&["reflog"])?, "");
+    println!(" - OK");
+
+    env::set_current_dir("/")?;
+    temp_folder.close()?;
+
+    Ok(())
+}
+
+/// MERGE
+#[serial(comm)]
+#[test]
+fn merge_test() -> Result<(), Box<dyn std::error::Error>> {
+    println!("------------ MERGE -------------");
+    let temp_folder = assert_fs::TempDir::new()?;
+    env::set_current_dir(temp_folder.path())?;
+    git_stdout(&["init", "-b", "master"])?;
+    temp_folder.child("f").write_str("1\n2\n3\n4\n5\n6\n7\n8\n")?;
+    temp_folder.child("g").write_str("g\n")?;
+    git_stdout(&["add", "."])?;
+    git_stdout(&["commit", "-m", "base"])?;
+    git_stdout(&["branch", "behind"])?;
+    git_stdout(&["branch", "feat"])?;
+    git_stdout(&["checkout", "-b", "side"])?;
+    temp_folder.child("f").write_str("1\nTWO\n3\n4\n5\n6\n7\nEIGHT\n")?;
+    temp_folder.child("s").write_str("s\n")?;
+    git_stdout(&["add", "."])?;
+    git_stdout(&["commit", "-m", "side"])?;
+    git_stdout(&["checkout", "master"])?;
+    temp_folder.child("f").write_str("1\nzwei\n3\n4\n5\n6\n7\n8\n")?;
+    git_stdout(&["commit", "-am", "master"])?;
+
+    // Both sides changed the same line, the rest of the file is merged
+    print!("Calling your merge (conflict and abort)");
+    let output = mygit_stdout(&["merge", "side"])?;
+    assert_eq!(
+        output,
+        "Auto-merging f\nCONFLICT (content): Merge conflict in f\n\
+         Automatic merge failed; fix conflicts and then commit the result.\n"
+    );
+    temp_folder
+        .child("f")
+        .assert("1\n<<<<<<< HEAD\nzwei\n=======\nTWO\n>>>>>>> side\n3\n4\n5\n6\n7\nEIGHT\n");
+    temp_folder.child("s").assert("s\n");
+    assert_eq!(git_stdout(&["ls-files", "-u"])?.lines().count(), 3);
+    let message = fs::read_to_string(".git/MERGE_MSG")?;
+    assert_eq!(message, "Merge branch 'side'\n\n# Conflicts:\n#\tf\n");
+    mygit_stdout(&["merge", "--abort"])?;
+    assert_eq!(git_stdout(&["status", "--short"])?, "");
+    temp_folder.child("s").assert(predicate::path::missing());
+    temp_folder.child(".git/MERGE_HEAD").assert(predicate::path::missing());
+    println!(" - OK");
+
+    // Resolved merge is committed with both parents
+    print!("Calling your merge (continue)");
+    mygit_stdout(&["merge", "side"])?;
+    temp_folder.child("f").write_str("1\nTWO\n3\n4\n5\n6\n7\nEIGHT\n")?;
+    git_stdout(&["add", "f"])?;
+    assert!(mygit_stdout(&["merge", "--continue"])?.ends_with("] Merge branch 'side'\n"));
+    let parents = git_stdout(&["log", "-1", "--format=%P"])?;
+    assert_eq!(parents.split_whitespace().count(), 2);
+    assert_eq!(git_stdout(&["log", "-1", "--format=%s"])?, "Merge branch 'side'\n");
+    assert_eq!(git_stdout(&["status", "--short"])?, "");
+    assert_eq!(mygit_stdout(&["merge", "side"])?, "Already up to date.\n");
+    println!(" - OK");
+
+    // Branch behind the merged one only moves forward, otherwise a merge commit is made
+    print!("Calling your merge (fast-forward and clean)");
+    git_stdout(&["checkout", "behind"])?;
+    assert!(mygit_stdout(&["merge", "side"])?.contains("Fast-forward\n"));
+    assert_eq!(git_stdout(&["rev-parse", "HEAD"])?, git_stdout(&["rev-parse", "side"])?);
+    git_stdout(&["checkout", "feat"])?;
+    temp_folder.child("n").write_str("n\n")?;
+    git_stdout(&["add", "n"])?;
+    git_stdout(&["commit", "-m", "feat"])?;
+    let output = mygit_stdout(&["merge", "side"])?;
+    assert!(output.contains("Merge made by the 'ort' strategy.\n"));
+    assert_eq!(git_stdout(&["log", "-1", "--format=%s"])?, "Merge branch 'side' into feat\n");
+    temp_folder.child("f").assert("1\nTWO\n3\n4\n5\n6\n7\nEIGHT\n");
+    assert_eq!(git_stdout(&["status", "--short"])?, "");
+    println!(" - OK");
+
+    // Changes follow the file renamed on the other side
+    print!("Calling your merge (rename)");
+    git_stdout(&["checkout", "-b", "renamed"])?;
+    git_stdout(&["mv", "f", "renamed_f"])?;
+    git_stdout(&["commit", "-m", "rename"])?;
+    git_stdout(&["checkout", "feat"])?;
+    temp_folder.child("f").write_str("1\nTWO\n3\n4\n5\n6\n7\nEIGHT\nnine\n")?;
+    git_stdout(&["commit", "-am", "nine"])?;
+    assert!(mygit_stdout(&["merge", "renamed"])?.contains("Merge made by the 'ort' strategy.\n"));
+    temp_folder.child("f").assert(predicate::path::missing());
+    temp_folder.child("renamed_f").assert("1\nTWO\n3\n4\n5\n6\n7\nEIGHT\nnine\n");
+    assert_eq!(git_stdout(&["status", "--short"])?, "");
+    println!(" - OK");
+
+    // A file in the way of a directory is moved aside, modes added differently conflict
+    print!("Calling your merge (file/directory and mode conflicts)");
+    use std::os::unix::fs::PermissionsExt;
+    git_stdout(&["checkout", "-b", "dirs"])?;
+    fs::remove_file("n")?;
+    temp_folder.child("n/x").write_str("x\n")?;
+    temp_folder.child("e").write_str("e\n")?;
+    fs::set_permissions("e", fs::Permissions::from_mode(0o755))?;
+    git_stdout(&["add", "-A"])?;
+    git_stdout(&["commit", "-m", "dirs"])?;
+    git_stdout(&["checkout", "feat"])?;
+    temp_folder.child("n").write_str("n\nmore\n")?;
+    temp_folder.child("e").write_str("e\n")?;
+    git_stdout(&["add", "-A"])?;
+    git_stdout(&["commit", "-m", "more"])?;
+    assert_eq!(
+        mygit_stdout(&["merge", "dirs"])?,
+        "CONFLICT (add/add): Merge conflict in e\n\
+         CONFLICT (file/directory): directory in the way of n from HEAD; moving it to n~HEAD \
+         instead.\n\
+         CONFLICT (modify/delete): n~HEAD deleted in dirs and modified in HEAD.  Version HEAD \
+         of n~HEAD left in tree.\n\
+         Automatic merge failed; fix conflicts and then commit the result.\n"
+    );
+    assert_eq!(git_stdout(&["status", "--short"])?, "AA e\nD  n\nA  n/x\nUD n~HEAD\n");
+    temp_folder.child("n~HEAD").assert("n\nmore\n");
+    temp_folder.child("n/x").assert("x\n");
+    println!(" - OK");
+
+    env::set_current_dir("/")?;
+    temp_folder.close()?;
+
+    Ok(())
+}
+
+/// MERGE-BASE AND REV-LIST
+#[serial(comm)]
+#[test]
+fn ancestry_test() -> Result<(), Box<dyn std::error::Error>> {
+    println!("------------ MERGE-BASE AND REV-LIST -------------");
+    let temp_folder = assert_fs::TempDir::new()?;
+    env::set_current_dir(temp_folder.path())?;
+    git_stdout(&["init", "-b", "main"])?;
+    mygit_stdout(&["commit", "--allow-empty", "-m", "a"])?;
+    git_stdout(&["branch", "side"])?;
+    git_stdout(&["branch", "other"])?;
+    mygit_stdout(&["commit", "--allow-empty", "-m", "b"])?;
+    git_stdout(&["checkout", "side"])?;
+    mygit_stdout(&["commit", "--allow-empty", "-m", "c"])?;
+    mygit_stdout(&["commit", "--allow-empty", "-m", "d"])?;
+    git_stdout(&["checkout", "main"])?;
+    mygit_stdout(&["merge", "-m", "merge side", "side"])?;
+    mygit_stdout(&["commit", "--allow-empty", "-m", "e"])?;
+    git_stdout(&["checkout", "side"])?;
+    mygit_stdout(&["commit", "--allow-empty", "-m", "f"])?;
+    git_stdout(&["checkout", "other"])?;
+    mygit_stdout(&["commit", "--allow-empty", "-m", "g"])?;
+    git_stdout(&["checkout", "main"])?;
+
+    // Bases of two or more commits, all of them or only the best one
+    print!("Calling your merge-base");
+    for args in [
+        &["merge-base", "main", "side"][..],
+        &["merge-base", "--all", "main", "side"],
+        &["merge-base", "--octopus", "main", "side", "other"],
+        &["merge-base", "side", "main~1", "other"],
+    ] {
+        assert_eq!(mygit_stdout(args)?, git_stdout(args)?);
+    }
+    let status = |args: &[&str]| -> Result<bool, Box<dyn std::error::Error>> {
+        Ok(Command::cargo_bin(CRATE_NAME)?.args(args).status()?.success())
+    };
+    assert!(status(&["merge-base", "--is-ancestor", "side~1", "main"])?);
+    assert!(!status(&["merge-base", "--is-ancestor", "side", "main"])?);
+    println!(" - OK");
+
+    // Ranges, symmetric differences and counts
+    print!("Calling your rev-list");
+    for args in [
+        &["rev-list", "main"][..],
+        &["rev-list", "main..side"],
+        &["rev-list", "--left-right", "main...side"],
+        &["rev-list", "--count", "--left-right", "main...other"],
+        &["rev-list", "side", "--not", "main"],
+        &["rev-list", "--ancestry-path", "side~2..main"],
+    ] {
+        assert_eq!(mygit_stdout(args)?, git_stdout(args)?);
+    }
+    println!(" - OK");
+
+    env::set_current_dir("/")?;
+    temp_folder.close()?;
+
+    Ok(())
+}
+
+/// COMMIT-GRAPH
+#[serial(comm)]
+#[test]
+fn commit_graph_test() -> Result<(), Box<dyn std::error::Error>> {
+    println!("------------ COMMIT-GRAPH -------------");
+    let temp_folder = assert_fs::TempDir::new()?;
+    env::set_current_dir(temp_folder.path())?;
+    git_stdout(&["init", "-b", "main"])?;
+    temp_folder.child("a").write_str("a\n")?;
+    temp_folder.child("dir/sub/b").write_str("b\n")?;
+    git_stdout(&["add", "."])?;
+    git_stdout(&["commit", "-m", "base"])?;
+    git_stdout(&["branch", "side"])?;
+    git_stdout(&["branch", "other"])?;
+    temp_folder.child("dir/c").write_str("c\n")?;
+    git_stdout(&["add", "."])?;
+    git_stdout(&["commit", "-m", "main"])?;
+    git_stdout(&["checkout", "side"])?;
+    temp_folder.child("dir/sub/b").write_str("side\n")?;
+    git_stdout(&["commit", "-am", "side"])?;
+    git_stdout(&["checkout", "other"])?;
+    temp_folder.child("a").write_str("other\n")?;
+    git_stdout(&["commit", "-am", "other"])?;
+    git_stdout(&["checkout", "main"])?;
+    git_stdout(&["merge", "-m", "octopus", "side", "other"])?;
+    git_stdout(&["tag", "-a", "-m", "tag", "v1", "side"])?;
+
+    // Same file as git writes without generation data
+    print!("Calling your commit-graph write");
+    let graph_file = ".git/objects/info/commit-graph";
+    git_stdout(
+        &[
+            "-c",
+            "commitGraph.generationVersion=1",
+            "commit-graph",
+            "write",
+            "--reachable",
+            "--changed-paths",
+        ]
+    )?;
+    let expected = fs::read(graph_file)?;
+    fs::remove_file(graph_file)?;
+    mygit_stdout(&["commit-graph", "write", "--reachable", "--changed-paths"])?;
+    assert_eq!(fs::read(graph_file)?, expected);
+    git_stdout(&["commit-graph", "verify"])?;
+    println!(" - OK");
+
+    // Walks read parents, generations and changed paths from the graph
+    print!("Calling your log and merge-base with commit-graph");
+    for args in [
+        &["log", "--format=%H %s", "--", "dir"][..],
+        &["log", "--format=%H %s", "--", "dir/sub/b"],
+        &["log", "--format=%H %s", "--", "a", "dir/c"],
+        &["rev-list", "main", "^side"],
+        &["merge-base", "--all", "side", "other"],
+        &["merge-base", "--octopus", "main", "side", "other"],
+    ] {
+        assert_eq!(mygit_stdout(args)?, git_stdout(args)?);
+    }
+    println!(" - OK");
+
+    // New commits go to a layer of the chain on top of the existing one
+    print!("Calling your commit-graph write --split");
+    fs::remove_file(graph_file)?;
+    mygit_stdout(&["commit-graph", "write", "--split"])?;
+    temp_folder.child("x").write_str("x\n")?;
+    git_stdout(&["add", "."])?;
+    git_stdout(&["commit", "-m", "x"])?;
+    mygit_stdout(&["commit-graph", "write", "--split"])?;
+    let chain_file = ".git/objects/info/commit-graphs/commit-graph-chain";
+    assert_eq!(fs::read_to_string(chain_file)?.lines().count(), 2);
+    git_stdout(&["commit-graph", "verify"])?;
+    let args = &["log", "--format=%H %s", "--", "x", "dir"];
+    assert_eq!(mygit_stdout(args)?, git_stdout(args)?);
+    println!(" - OK");
+
+    env::set_current_dir("/")?;
+    temp_folder.close()?;
+
+    Ok(())
+}
+
+/// REBASE
+#[serial(comm)]
+#[test]
+fn rebase_test() -> Result<(), Box<dyn std::error::Error>> {
+    println!("------------ REBASE -------------");
+    let temp_folder = assert_fs::TempDir::new()?;
+    env::set_current_dir(temp_folder.path())?;
+    git_stdout(&["init", "-b", "main"])?;
+    temp_folder.child("f").write_str("1\n2\n3\n4\n5\n6\n7\n8\n")?;
+    git_stdout(&["add", "."])?;
+    git_stdout(&["commit", "-m", "base"])?;
+    git_stdout(&["branch", "topic"])?;
+    temp_folder.child("f").write_str("1\nTWO\n3\n4\n5\n6\n7\n8\n")?;
+    git_stdout(&["commit", "-am", "m1"])?;
+    temp_folder.child("g").write_str("g\n")?;
+    git_stdout(&["add", "g"])?;
+    git_stdout(&["commit", "-m", "m2"])?;
+    git_stdout(&["checkout", "topic"])?;
+    temp_folder.child("f").write_str("1\nzwei\n3\n4\n5\n6\n7\n8\n")?;
+    git_stdout(&["commit", "-am", "t1"])?;
+    temp_folder.child("h").write_str("h\n")?;
+    git_stdout(&["add", "h"])?;
+    git_stdout(&["commit", "-m", "t2"])?;
+    temp_folder.child("g").write_str("g\n")?;
+    git_stdout(&["add", "g"])?;
+    git_stdout(&["commit", "-m", "t3"])?;
+
+    // Commits already in the upstream are left out, conflicts stop the rebase
+    print!("Calling your rebase (conflict and abort)");
+    let orig_head = git_stdout(&["rev-parse", "HEAD"])?;
+    let output = mygit_stdout(&["rebase", "main"])?;
+    assert!(output.starts_with("warning: skipped previously applied commit"));
+    assert!(output.contains("CONFLICT (content): Merge conflict in f\n"));
+    assert!(output.ends_with("t1\n"));
+    temp_folder.child(".git/rebase-merge").assert(predicate::path::is_dir());
+    assert_eq!(git_stdout(&["status", "--short"])?, "UU f\n");
+    mygit_stdout(&["rebase", "--abort"])?;
+    assert_eq!(git_stdout(&["rev-parse", "HEAD"])?, orig_head);
+    assert_eq!(git_stdout(&["branch", "--show-current"])?, "topic\n");
+    assert_eq!(git_stdout(&["status", "--short"])?, "");
+    temp_folder.child(".git/rebase-merge").assert(predicate::path::missing());
+    println!(" - OK");
+
+    // Resolved conflicts are committed by --continue
+    print!("Calling your rebase (continue)");
+    mygit_stdout(&["rebase", "main"])?;
+    temp_folder.child("f").write_str("1\nBOTH\n3\n4\n5\n6\n7\n8\n")?;
+    git_stdout(&["add", "f"])?;
+    let output = mygit_stdout(&["rebase", "--continue"])?;
+    assert!(output.ends_with("Successfully rebased and updated refs/heads/topic.\n"));
+    let subjects = git_stdout(&["log", "--format=%s"])?;
+    assert_eq!(subjects, "t2\nt1\nm2\nm1\nbase\n");
+    temp_folder.child("f").assert("1\nBOTH\n3\n4\n5\n6\n7\n8\n");
+    assert_eq!(git_stdout(&["status", "--short"])?, "");
+    let reflog = git_stdout(&["reflog", "-1", "--format=%gs"])?;
+    assert_eq!(reflog, "rebase (finish): returning to refs/heads/topic\n");
+    println!(" - OK");
+
+    // Todo list edited by the sequence editor
+    print!("Calling your rebase (interactive)");
+    for (name, contents) in [("a", "a\n"), ("b", "b\n"), ("c", "c\n")] {
+        temp_folder.child(name).write_str(contents)?;
+        git_stdout(&["add", name])?;
+        git_stdout(&["commit", "-m", name])?;
+    }
+    let output = Command::cargo_bin(CRATE_NAME)?
+        .args(["rebase", "-i", "HEAD~3"])
+        .env("GIT_SEQUENCE_EDITOR", "sed -i -e '1s/^pick/drop/' -e '3s/^pick/fixup/'")
+        .env("GIT_EDITOR", ":")
+        .output()?;
+    assert!(output.status.success());
+    assert_eq!(git_stdout(&["log", "--format=%s", "-3"])?, "b\nt2\nt1\n");
+    assert_eq!(git_stdout(&["show", "--name-only", "--format="])?, "b\nc\n");
+    temp_folder.child("a").assert(predicate::path::missing());
+    println!(" - OK");
+
+    env::set_current_dir("/")?;
+    temp_folder.close()?;
+
+    Ok(())
+}
+
+/// CHERRY-PICK AND REVERT
+#[serial(comm)]
+#[test]
+fn cherry_pick_test() -> Result<(), Box<dyn std::error::Error>> {
+    println!("------------ CHERRY-PICK AND REVERT -------------");
+    let temp_folder = assert_fs::TempDir::new()?;
+    env::set_current_dir(temp_folder.path())?;
+    git_stdout(&["init", "-b", "main"])?;
+    temp_folder.child("f").write_str("1\n2\n3\n4\n5\n6\n7\n8\n")?;
+    git_stdout(&["add", "."])?;
+    git_stdout(&["commit", "-m", "base"])?;
+    git_stdout(&["branch", "topic"])?;
+    temp_folder.child("f").write_str("1\nTWO\n3\n4\n5\n6\n7\n8\n")?;
+    git_stdout(&["commit", "-am", "m1"])?;
+    git_stdout(&["checkout", "topic"])?;
+    temp_folder.child("h").write_str("h\n")?;
+    git_stdout(&["add", "h"])?;
+    git_stdout(&["commit", "-m", "t1"])?;
+    temp_folder.child("f").write_str("1\nzwei\n3\n4\n5\n6\n7\n8\n")?;
+    git_stdout(&["commit", "-am", "t2"])?;
+    temp_folder.child("g").write_str("g\n")?;
+    git_stdout(&["add", "g"])?;
+    git_stdout(&["commit", "-m", "t3\n\nSigned-off-by: A <a@x>"])?;
+    git_stdout(&["checkout", "main"])?;
+
+    // Original author and message, -x adds the origin (as a trailer after trailers)
+    print!("Calling your cherry-pick (-x)");
+    let picked = git_stdout(&["rev-parse", "topic~2"])?;
+    let output = mygit_stdout(&["cherry-pick", "-x", "topic~2", "topic"])?;
+    assert!(output.starts_with("[main "));
+    assert!(output.contains("] t1\n"));
+    let message = git_stdout(&["log", "-1", "--format=%B", "HEAD~1"])?;
+    assert_eq!(message, format!("t1\n\n(cherry picked from commit {})\n\n", picked.trim()));
+    let message = git_stdout(&["log", "-1", "--format=%B"])?;
+    assert!(message.starts_with("t3\n\nSigned-off-by: A <a@x>\n(cherry picked from commit "));
+    let authors = git_stdout(&["log", "-1", "--format=%an %ae %at", "topic"])?;
+    assert_eq!(git_stdout(&["log", "-1", "--format=%an %ae %at"])?, authors);
+    temp_folder.child("g").assert("g\n");
+    println!(" - OK");
+
+    // Conflicts stop the pick, --continue commits the resolution with the original author
+    print!("Calling your cherry-pick (conflict and continue)");
+    let output = mygit_stdout(&["cherry-pick", "topic~1"])?;
+    assert!(output.contains("CONFLICT (content): Merge conflict in f\n"));
+    assert!(output.contains("error: could not apply "));
+    temp_folder.child(".git/CHERRY_PICK_HEAD").assert(predicate::path::exists());
+    assert_eq!(git_stdout(&["status", "--short"])?, "UU f\n");
+    temp_folder.child("f").write_str("1\nBOTH\n3\n4\n5\n6\n7\n8\n")?;
+    git_stdout(&["add", "f"])?;
+    assert!(mygit_stdout(&["cherry-pick", "--continue"])?.contains("] t2\n"));
+    temp_folder.child(".git/CHERRY_PICK_HEAD").assert(predicate::path::missing());
+    let authors = git_stdout(&["log", "-1", "--format=%an %ae %at", "topic~1"])?;
+    assert_eq!(git_stdout(&["log", "-1", "--format=%an %ae %at"])?, authors);
+    assert_eq!(git_stdout(&["log", "-1", "--format=%s"])?, "t2\n");
+    assert_eq!(git_stdout(&["status", "--short"])?, "");
+    println!(" - OK");
+
+    // Several reverts keep a todo list, --abort goes back to where they started
+    print!("Calling your revert (sequence and abort)");
+    let head = git_stdout(&["rev-parse", "HEAD"])?;
+    let output = mygit_stdout(&["revert", "--no-edit", "HEAD~2", "HEAD~4", "HEAD"])?;
+    assert!(output.contains("] Revert \"t1\"\n"));
+    assert!(output.contains("error: could not revert "));
+    let todo = fs::read_to_string(".git/sequencer/todo")?;
+    assert_eq!(todo.lines().count(), 2);
+    assert!(todo.starts_with("revert "));
+    temp_folder.child(".git/REVERT_HEAD").assert(predicate::path::exists());
+    mygit_stdout(&["revert", "--abort"])?;
+    assert_eq!(git_stdout(&["rev-parse", "HEAD"])?, head);
+    assert_eq!(git_stdout(&["status", "--short"])?, "");
+    temp_folder.child(".git/sequencer").assert(predicate::path::missing());
+    println!(" - OK");
+
+    // Revert message, -n only changes the index and the working tree
+    print!("Calling your revert (clean and -n)");
+    let reverted = git_stdout(&["rev-parse", "HEAD~1"])?;
+    let output = mygit_stdout(&["revert", "--no-edit", "HEAD~1"])?;
+    assert!(!output.contains(" Date: "));
+    let message = git_stdout(&["log", "-1", "--format=%B"])?;
+    let expected = format!("Revert \"t3\"\n\nThis reverts commit {}.\n", reverted.trim());
+    assert!(message.starts_with(&expected));
+    temp_folder.child("g").assert(predicate::path::missing());
+    let head = git_stdout(&["rev-parse", "HEAD"])?;
+    mygit_stdout(&["cherry-pick", "-n", "HEAD~2"])?;
+    assert_eq!(git_stdout(&["rev-parse", "HEAD"])?, head);
+    assert_eq!(git_stdout(&["status", "--short"])?, "A  g\n");
+    println!(" - OK");
+
+    env::set_current_dir("/")?;
+    temp_folder.close()?;
+
+    Ok(())
+}
+
+/// RESET AND RESTORE
+#[serial(comm)]
+#[test]
+fn reset_test() -> Result<(), Box<dyn std::error::Error>> {
+    println!("------------ RESET AND RESTORE -------------");
+    let temp_folder = assert_fs::TempDir::new()?;
+    env::set_current_dir(temp_folder.path())?;
+    git_stdout(&["init", "-b", "main"])?;
+    temp_folder.child("f").write_str("1\n2\n")?;
+    git_stdout(&["add", "."])?;
+    git_stdout(&["commit", "-m", "base"])?;
+    temp_folder.child("g").write_str("g\n")?;
+    git_stdout(&["add", "g"])?;
+    git_stdout(&["commit", "-m", "two"])?;
+    temp_folder.child("d/h").write_str("h\n")?;
+    git_stdout(&["add", "d"])?;
+    git_stdout(&["commit", "-m", "three"])?;
+    let three = git_stdout(&["rev-parse", "HEAD"])?;
+
+    // Soft keeps the index, mixed resets it and lists the unstaged changes
+    print!("Calling your reset (soft and mixed)");
+    temp_folder.child("f").write_str("1\n2\nx\n")?;
+    mygit_stdout(&["reset", "--soft", "HEAD~1"])?;
+    assert_eq!(git_stdout(&["status", "--short"])?, "A  d/h\n M f\n");
+    temp_folder.child(".git/ORIG_HEAD").assert(three.clone());
+    assert_eq!(mygit_stdout(&["reset", "HEAD@{1}"])?, "Unstaged changes after reset:\nM\tf\n");
+    assert_eq!(git_stdout(&["rev-parse", "HEAD"])?, three);
+    assert_eq!(git_stdout(&["status", "--short"])?, " M f\n");
+    let reflog = git_stdout(&["reflog", "-2", "--format=%gs"])?;
+    assert_eq!(reflog, "reset: moving to HEAD@{1}\nreset: moving to HEAD~1\n");
+    println!(" - OK");
+
+    // Paths are unstaged (removed from the index when missing in the commit)
+    print!("Calling your reset (paths)");
+    temp_folder.child("n").write_str("new\n")?;
+    git_stdout(&["add", "-A"])?;
+    mygit_stdout(&["reset", "--", "n"])?;
+    mygit_stdout(&["reset", "HEAD~2", "d"])?;
+    assert_eq!(git_stdout(&["status", "--short"])?, "D  d/h\nM  f\n?? d/\n?? n\n");
+    let output = Command::cargo_bin(CRATE_NAME)?.args(["reset", "--hard", "d/h"]).output()?;
+    assert!(String::from_utf8(output.stderr)?.contains("Cannot do hard reset with paths."));
+    println!(" - OK");
+
+    // Hard resets the working tree too
+    print!("Calling your reset (hard)");
+    let two = git_stdout(&["rev-parse", "--short", "HEAD~1"])?;
+    let output = mygit_stdout(&["reset", "--hard", "HEAD~1"])?;
+    assert_eq!(output, format!("HEAD is now at {} two\n", two.trim()));
+    assert_eq!(git_stdout(&["status", "--short"])?, "?? d/\n?? n\n");
+    temp_folder.child("f").assert("1\n2\n");
+    mygit_stdout(&["reset", "--hard", "ORIG_HEAD"])?;
+    assert_eq!(git_stdout(&["rev-parse", "HEAD"])?, three);
+    println!(" - OK");
+
+    // Working tree files come from the index, index entries from HEAD or the source
+    print!("Calling your restore");
+    temp_folder.child("f").write_str("z\n")?;
+    temp_folder.child("g").write_str("w\n")?;
+    git_stdout(&["add", "g"])?;
+    fs::remove_file("d/h")?;
+    mygit_stdout(&["restore", "f"])?;
+    temp_folder.child("f").assert("1\n2\n");
+    mygit_stdout(&["restore", "--staged", "g"])?;
+    assert_eq!(git_stdout(&["status", "--short"])?, " D d/h\n M g\n?? n\n");
+    mygit_stdout(&["restore", "--source=HEAD~2", "--staged", "--worktree", "d", "g"])?;
+    assert_eq!(git_stdout(&["status", "--short"])?, "D  d/h\nD  g\n?? n\n");
+    temp_folder.child("g").assert(predicate::path::missing());
+    let output = Command::cargo_bin(CRATE_NAME)?.args(["restore", "nothere"]).output()?;
+    let error = String::from_utf8(output.stderr)?;
+    assert!(error.contains("pathspec 'nothere' did not match any file(s) known to git"));
+    println!(" - OK");
+
+    env::set_current_dir("/")?;
+    temp_folder.close()?;
+
+    Ok(())
+}
+
+/// STASH
+#[serial(comm)]
+#[test]
+fn stash_test() -> Result<(), Box<dyn std::error::Error>> {
+    println!("------------ STASH -------------");
+    let temp_folder = assert_fs::TempDir::new()?;
+    env::set_current_dir(temp_folder.path())?;
+    git_stdout(&["init", "-b", "main"])?;
+    temp_folder.child("f").write_str("1\n2\n3\n4\n5\n")?;
+    git_stdout(&["add", "."])?;
+    git_stdout(&["commit", "-m", "base"])?;
+    let head = git_stdout(&["rev-parse", "--short", "HEAD"])?;
+
+    // Stash commit has HEAD, the index commit and the untracked files commit as parents
+    print!("Calling your stash (push)");
+    temp_folder.child("f").write_str("1\n2\n3\n4\n5\nx\n")?;
+    temp_folder.child("g").write_str("g\n")?;
+    git_stdout(&["add", "g"])?;
+    temp_folder.child("u").write_str("u\n")?;
+    let output = mygit_stdout(&["stash", "push", "-u"])?;
+    let title = format!("WIP on main: {} base", head.trim());
+    assert_eq!(output, format!("Saved working directory and index state {title}\n"));
+    assert_eq!(git_stdout(&["status", "--short"])?, "");
+    let index_title = format!("index on {}\n", &title[7..]);
+    assert_eq!(git_stdout(&["log", "-1", "--format=%s", "stash^2"])?, index_title);
+    assert_eq!(git_stdout(&["ls-tree", "--name-only", "stash^3"])?, "u\n");
+    assert_eq!(git_stdout(&["stash", "list"])?, format!("stash@{{0}}: {title}\n"));
+    assert_eq!(mygit_stdout(&["stash"])?, "No local changes to save\n");
+    println!(" - OK");
+
+    // Show and list read the entries back
+    print!("Calling your stash (list and show)");
+    temp_folder.child("f").write_str("w\n")?;
+    mygit_stdout(&["stash", "push", "-m", "second"])?;
+    let list = mygit_stdout(&["stash", "list"])?;
+    assert_eq!(list, format!("stash@{{0}}: On main: second\nstash@{{1}}: {title}\n"));
+    assert_eq!(mygit_stdout(&["stash", "show", "1"])?, git_stdout(&["stash", "show", "1"])?);
+    assert_eq!(mygit_stdout(&["stash", "show", "-p"])?, git_stdout(&["stash", "show", "-p"])?);
+    println!(" - OK");
+
+    // Staged changes come back staged only with --index
+    print!("Calling your stash (apply)");
+    mygit_stdout(&["stash", "apply", "--index", "stash@{1}"])?;
+    assert_eq!(git_stdout(&["status", "--short"])?, " M f\nA  g\n?? u\n");
+    git_stdout(&["reset", "--hard"])?;
+    fs::remove_file("u")?;
+    mygit_stdout(&["stash", "apply", "1"])?;
+    assert_eq!(git_stdout(&["status", "--short"])?, " M f\nA  g\n?? u\n");
+    temp_folder.child("u").assert("u\n");
+    println!(" - OK");
+
+    // Conflicting pop keeps the entry, drop removes it
+    print!("Calling your stash (pop and drop)");
+    git_stdout(&["reset", "--hard"])?;
+    temp_folder.child("f").write_str("1\n2\nC\n4\n5\n")?;
+    git_stdout(&["commit", "-am", "conflicting"])?;
+    let output = mygit_stdout(&["stash", "pop"])?;
+    assert!(output.contains("CONFLICT (content): Merge conflict in f\n"));
+    assert!(output.ends_with("The stash entry is kept in case you need it again.\n"));
+    assert_eq!(git_stdout(&["stash", "list"])?.lines().count(), 2);
+    git_stdout(&["reset", "--hard"])?;
+    let stash = git_stdout(&["rev-parse", "stash@{0}"])?;
+    let output = mygit_stdout(&["stash", "drop"])?;
+    assert_eq!(output, format!("Dropped refs/stash@{{0}} ({})\n", stash.trim()));
+    assert_eq!(git_stdout(&["stash", "list"])?, format!("stash@{{0}}: {title}\n"));
+    fs::remove_file("u")?;
+    mygit_stdout(&["stash", "pop"])?;
+    assert_eq!(git_stdout(&["status", "--short"])?, " M f\nA  g\n?? u\n");
+    assert_eq!(git_stdout(&["stash", "list"])?, "");
+    temp_folder.child(".git/refs/stash").assert(predicate::path::missing());
+    println!(" - OK");
+
+    env::set_current_dir("/")?;
+    temp_folder.close()?;
+
+    Ok(())
+}
+
+/// SHOW
+#[serial(comm)]
+#[test]
+fn show_test() -> Result<(), Box<dyn std::error::Error>> {
+    println!("------------ SHOW -------------");
+    let temp_folder = assert_fs::TempDir::new()?;
+    env::set_current_dir(temp_folder.path())?;
+    git_stdout(&["init", "-b", "main"])?;
+    temp_folder.child("f").write_str("a\n")?;
+    temp_folder.child("d/h").write_str("h\n")?;
+    git_stdout(&["add", "."])?;
+    git_stdout(&["commit", "-m", "first"])?;
+    temp_folder.child("f").write_str("a\nb\n")?;
+    git_stdout(&["mv", "d/h", "d/k"])?;
+    git_stdout(&["commit", "-am", "second\n\nbody"])?;
+    git_stdout(&["tag", "-a", "v1", "-m", "tag msg"])?;
+
+    // Commits come with their patch or the selected diff output
+    print!("Calling your show (commits)");
+    assert_eq!(mygit_stdout(&["show"])?, git_stdout(&["show"])?);
+    let args = ["show", "--stat", "HEAD~1", "HEAD"];
+    assert_eq!(mygit_stdout(&args)?, git_stdout(&args)?);
+    let args = ["show", "--format=%h %s", "--name-status"];
+    assert_eq!(mygit_stdout(&args)?, git_stdout(&args)?);
+    let args = ["show", "--oneline", "-s", "HEAD", "HEAD~1"];
+    assert_eq!(mygit_stdout(&args)?, git_stdout(&args)?);
+    println!(" - OK");
+
+    // Annotated tags show the tagged commit after their message
+    print!("Calling your show (tag)");
+    let args = ["show", "--name-only", "v1"];
+    assert_eq!(mygit_stdout(&args)?, git_stdout(&args)?);
+    println!(" - OK");
+
+    // Trees are listed, blobs printed, also by <rev>:<path> and :<path>
+    print!("Calling your show (trees and blobs)");
+    let listing = "tree HEAD^{tree}\n\nd/\nf\n\ntree HEAD:d\n\nk\n";
+    assert_eq!(mygit_stdout(&["show", "HEAD^{tree}", "HEAD:d"])?, listing);
+    assert_eq!(mygit_stdout(&["show", "HEAD:f", "HEAD~1:d/h"])?, "a\nb\nh\n");
+    assert_eq!(mygit_stdout(&["show", ":d/k"])?, "h\n");
+    let output = Command::cargo_bin(CRATE_NAME)?.args(["show", "HEAD:nope"]).output()?;
+    assert!(String::from_utf8(output.stderr)?.contains("path 'nope' does not exist in 'HEAD'"));
+    println!(" - OK");
+
+    env::set_current_dir("/")?;
+    temp_folder.close()?;
+
+    Ok(())
+}
+
+/// BLAME
+#[serial(comm)]
+#[test]
+fn blame_test() -> Result<(), Box<dyn std::error::Error>> {
+    println!("------------ BLAME -------------");
+    let temp_folder = assert_fs::TempDir::new()?;
+    env::set_current_dir(temp_folder.path())?;
+    git_stdout(&["init", "-b", "main"])?;
+    temp_folder.child("f").write_str("one\ntwo\nthree\nfour\n")?;
+    git_stdout(&["add", "."])?;
+    git_stdout(&["commit", "-m", "first"])?;
+    temp_folder.child("f").write_str("one\n2\nthree\nfour\nfive\n")?;
+    git_stdout(&["commit", "-am", "second", "--author", "Bobby <b@x>"])?;
+    git_stdout(&["mv", "f", "g"])?;
+    temp_folder.child("g").write_str("one\n2\nthree  \nfour\nfive\n")?;
+    git_stdout(&["commit", "-am", "rename"])?;
+    temp_folder.child("g").write_str("ONE\n2\nthree  \nFOUR\nfive\n")?;
+    git_stdout(&["commit", "-am", "reformat"])?;
+
+    // Lines are attributed across the rename, with the original file name
+    print!("Calling your blame");
+    assert_eq!(mygit_stdout(&["blame", "g"])?, git_stdout(&["blame", "g"])?);
+    let args = ["blame", "-L", "2,+2", "g"];
+    assert_eq!(mygit_stdout(&args)?, git_stdout(&args)?);
+    let args = ["blame", "-w", "HEAD~1", "--", "g"];
+    assert_eq!(mygit_stdout(&args)?, git_stdout(&args)?);
+    println!(" - OK");
+
+    print!("Calling your blame (porcelain)");
+    let args = ["blame", "--porcelain", "g"];
+    assert_eq!(mygit_stdout(&args)?, git_stdout(&args)?);
+    let args = ["blame", "--line-porcelain", "-L", "3,", "g"];
+    assert_eq!(mygit_stdout(&args)?, git_stdout(&args)?);
+    println!(" - OK");
+
+    // Ignored commits leave the lines to the commits they replaced
+    print!("Calling your blame (ignored revisions)");
+    let args = ["blame", "--ignore-rev", "HEAD", "g"];
+    assert_eq!(mygit_stdout(&args)?, git_stdout(&args)?);
+    let head = git_stdout(&["rev-parse", "HEAD"])?;
+    temp_folder.child(".revs").write_str(&format!("# reformat\n{head}"))?;
+    git_stdout(&["config", "blame.ignoreRevsFile", ".revs"])?;
+    assert_eq!(mygit_stdout(&["blame", "g"])?, git_stdout(&["blame", "g"])?);
+    let output = Command::cargo_bin(CRATE_NAME)?.args(["blame", "-L", "9,", "g"]).output()?;
+    assert!(String::from_utf8(output.stderr)?.contains("file g has only 5 lines"));
+    println!(" - OK");
+
+    // Names of the versions lines come from are padded to the longest one
+    print!("Calling your blame (renamed to a longer name)");
+    git_stdout(&["config", "--unset", "blame.ignoreRevsFile"])?;
+    git_stdout(&["mv", "g", "longer_name"])?;
+    temp_folder.child("longer_name").write_str("ONE\n2\nthree  \nFOUR\nfive\nsix\n")?;
+    git_stdout(&["commit", "-am", "longer"])?;
+    let args = ["blame", "longer_name"];
+    assert_eq!(mygit_stdout(&args)?, git_stdout(&args)?);
+    println!(" - OK");
+
+    env::set_current_dir("/")?;
+    temp_folder.close()?;
+
+    Ok(())
+}
+
+/// GREP
+#[serial(comm)]
+#[test]
+fn grep_test() -> Result<(), Box<dyn std::error::Error>> {
+    println!("------------ GREP -------------");
+    let temp_folder = assert_fs::TempDir::new()?;
+    env::set_current_dir(temp_folder.path())?;
+    git_stdout(&["init", "-b", "main"])?;
+    temp_folder.child("a").write_str("foo bar\nFoo+baz\nfoobar\n(x)\n-5\n")?;
+    temp_folder.child("d/b").write_str("bin\0foo\n")?;
+    temp_folder.child("d/c").write_str("nothing\nfoo\n")?;
+    git_stdout(&["add", "."])?;
+    git_stdout(&["commit", "-m", "first"])?;
+    temp_folder.child("d/c").write_str("old foo\n")?;
+    git_stdout(&["commit", "-am", "second"])?;
+    temp_folder.child("a").write_str("foo bar\nFoo+baz\nfoobar\n(x)\n-5\nextra foo\n")?;
+    git_stdout(&["add", "a"])?;
+    temp_folder.child("d/c").write_str("no match\n")?;
+
+    // Tracked files of the working tree, with the basic, extended or fixed syntax
+    print!("Calling your grep");
+    for args in [
+        &["grep", "foo"][..],
+        &["grep", "-n", "-w", "foo"],
+        &["grep", "-i", "FOO\\+"],
+        &["grep", "-E", "o+b"],
+        &["grep", "-F", "(x)"],
+        &["grep", "-e", "bar", "-e", "match"],
+        &["grep", "-n", "-e", "-5"],
+    ] {
+        assert_eq!(mygit_stdout(args)?, git_stdout(args)?);
+    }
+    println!(" - OK");
+
+    // The index and trees of commits are searched without checking them out
+    print!("Calling your grep (index and trees)");
+    for args in [
+        &["grep", "--cached", "-c", "foo"][..],
+        &["grep", "-l", "foo", "HEAD", "--", "d"],
+        &["grep", "foo", "HEAD", "HEAD~1"],
+        &["grep", "-n", "foo", "HEAD~1", "d"],
+    ] {
+        assert_eq!(mygit_stdout(args)?, git_stdout(args)?);
+    }
+    let output = Command::cargo_bin(CRATE_NAME)?.args(["grep", "--cached", "x", "HEAD"]).output()?;
+    assert!(String::from_utf8(output.stderr)?.contains("both --cached and trees are given"));
+    println!(" - OK");
+
+    env::set_current_dir("/")?;
+    temp_folder.close()?;
+
+    Ok(())
+}